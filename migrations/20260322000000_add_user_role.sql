@@ -0,0 +1,7 @@
+-- Rôle applicatif (user / admin), repris dans le JWT à la connexion
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'user'
+    CHECK (role IN ('user', 'admin'));
+
+-- is_active est désormais vérifié à la connexion : plus de valeur NULL ambiguë
+UPDATE users SET is_active = TRUE WHERE is_active IS NULL;
+ALTER TABLE users ALTER COLUMN is_active SET NOT NULL;
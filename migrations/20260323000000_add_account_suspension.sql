@@ -0,0 +1,3 @@
+-- Suspension de compte : is_active = FALSE, avec la raison et la date de suspension
+ALTER TABLE users ADD COLUMN suspended_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN suspension_reason TEXT;
@@ -0,0 +1,16 @@
+-- Historique des changements d'offre (upgrade payant ou downgrade)
+CREATE TABLE account_tier_changes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    from_tier_id UUID NOT NULL REFERENCES account_tiers(id),
+    to_tier_id UUID NOT NULL REFERENCES account_tiers(id),
+    amount_cents BIGINT NOT NULL DEFAULT 0,
+    payment_provider TEXT,
+    payment_reference TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_account_tier_changes_user_id ON account_tier_changes(user_id);
+
+-- Date du premier dépassement du quota : point de départ du délai de grâce avant lecture seule
+ALTER TABLE users ADD COLUMN over_quota_since TIMESTAMPTZ;
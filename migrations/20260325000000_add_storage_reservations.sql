@@ -0,0 +1,20 @@
+-- Réservations de quota : la taille déclarée d'un upload est réservée à l'initialisation,
+-- convertie en espace utilisé au finalize, libérée à l'abandon ou par le GC après une période d'inactivité
+CREATE TABLE storage_reservations (
+    file_id UUID PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    size_bytes BIGINT NOT NULL CHECK (size_bytes >= 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_activity_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_storage_reservations_user_id ON storage_reservations(user_id);
+CREATE INDEX idx_storage_reservations_last_activity_at ON storage_reservations(last_activity_at);
+
+-- Uploads en cours au moment de la migration : ils deviennent des réservations
+INSERT INTO storage_reservations (file_id, user_id, size_bytes)
+SELECT f.id, fa.user_id, f.size
+FROM files f
+JOIN file_access fa ON fa.file_id = f.id AND fa.access_level = 'owner'
+WHERE f.is_fully_uploaded = FALSE
+ON CONFLICT (file_id) DO NOTHING;
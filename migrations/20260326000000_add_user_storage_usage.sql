@@ -0,0 +1,26 @@
+-- Compteurs d'usage par utilisateur, maintenus dans les transactions du drive
+-- (initialize, finalize, suppression, corbeille, abandon) pour éviter de recalculer
+-- SUM(files.size) à chaque requête. Un job de réconciliation les recalcule périodiquement.
+CREATE TABLE user_storage_usage (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    used_bytes BIGINT NOT NULL DEFAULT 0,
+    reserved_bytes BIGINT NOT NULL DEFAULT 0,
+    file_count BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    reconciled_at TIMESTAMPTZ
+);
+
+-- Initialisation depuis les données existantes
+INSERT INTO user_storage_usage (user_id, used_bytes, reserved_bytes, file_count, reconciled_at)
+SELECT
+    u.id,
+    COALESCE((
+        SELECT SUM(f.size)
+        FROM file_access fa
+        JOIN files f ON f.id = fa.file_id
+        WHERE fa.user_id = u.id AND fa.access_level = 'owner' AND f.is_fully_uploaded = TRUE
+    ), 0),
+    COALESCE((SELECT SUM(r.size_bytes) FROM storage_reservations r WHERE r.user_id = u.id), 0),
+    (SELECT COUNT(*) FROM file_access fa WHERE fa.user_id = u.id AND fa.access_level = 'owner'),
+    NOW()
+FROM users u;
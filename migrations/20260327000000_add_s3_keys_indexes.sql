@@ -0,0 +1,4 @@
+-- Un chunk S3 peut être référencé par plusieurs fichiers (copies) : la suppression d'un fichier
+-- ne supprime l'objet S3 que lorsque plus aucune ligne s3_keys ne pointe vers sa clé.
+CREATE INDEX idx_s3_keys_s3_key ON s3_keys(s3_key);
+CREATE INDEX idx_s3_keys_file_id ON s3_keys(file_id);
@@ -0,0 +1,22 @@
+-- Journal des changements du drive, par utilisateur, pour la synchronisation incrémentale.
+-- Chaque utilisateur a sa propre séquence (drive_change_sequences.last_seq), incrémentée sous
+-- verrou de ligne jusqu'au commit : les entrées d'un utilisateur deviennent visibles dans l'ordre
+-- de leur numéro, un curseur ne peut donc jamais sauter une entrée validée plus tard.
+CREATE TABLE drive_change_sequences (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    last_seq BIGINT NOT NULL DEFAULT 0,
+    -- Dernier numéro supprimé par la rétention : un curseur inférieur impose une resynchronisation
+    pruned_seq BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE drive_changes (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    seq BIGINT NOT NULL,
+    item_type TEXT NOT NULL CHECK (item_type IN ('file', 'folder')),
+    item_id UUID NOT NULL,
+    change_type TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, seq)
+);
+
+CREATE INDEX idx_drive_changes_created_at ON drive_changes(created_at);
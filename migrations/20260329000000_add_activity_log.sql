@@ -0,0 +1,31 @@
+-- Journal d'activité (audit) : qui a partagé, téléchargé, renommé, révoqué quoi, et connexions.
+-- Append-only : les entrées ne sont jamais modifiées, seule la rétention supprime les plus anciennes.
+CREATE TABLE activity_log (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    action TEXT NOT NULL,
+    item_type TEXT CHECK (item_type IN ('file', 'folder')),
+    item_id UUID,
+    -- Dossier contenant l'élément au moment de l'action (fil d'activité d'un dossier partagé)
+    folder_id UUID,
+    -- Destinataire d'un partage ou d'une révocation (pas de FK : ON DELETE SET NULL serait une mise à jour)
+    target_user_id UUID,
+    ip_address TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((item_type IS NULL) = (item_id IS NULL))
+);
+
+CREATE INDEX idx_activity_log_user ON activity_log(user_id, id);
+CREATE INDEX idx_activity_log_item ON activity_log(item_id, id) WHERE item_id IS NOT NULL;
+CREATE INDEX idx_activity_log_folder ON activity_log(folder_id, id) WHERE folder_id IS NOT NULL;
+CREATE INDEX idx_activity_log_created_at ON activity_log(created_at);
+
+CREATE FUNCTION reject_activity_log_update() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'activity_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER activity_log_append_only
+    BEFORE UPDATE ON activity_log
+    FOR EACH ROW EXECUTE FUNCTION reject_activity_log_update();
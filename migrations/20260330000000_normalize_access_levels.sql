@@ -0,0 +1,19 @@
+-- Normalisation des niveaux d'accès : seuls owner / editor / viewer sont valides.
+-- L'ancien défaut 'read' (et toute valeur inconnue) devient 'viewer', le niveau le plus restrictif.
+
+UPDATE file_access SET access_level = 'viewer'
+WHERE access_level NOT IN ('owner', 'editor', 'viewer');
+
+UPDATE folder_access SET access_level = 'viewer'
+WHERE access_level NOT IN ('owner', 'editor', 'viewer');
+
+ALTER TABLE file_access ALTER COLUMN access_level SET DEFAULT 'viewer';
+ALTER TABLE folder_access ALTER COLUMN access_level SET DEFAULT 'viewer';
+
+ALTER TABLE file_access
+    ADD CONSTRAINT file_access_access_level_check
+    CHECK (access_level IN ('owner', 'editor', 'viewer'));
+
+ALTER TABLE folder_access
+    ADD CONSTRAINT folder_access_access_level_check
+    CHECK (access_level IN ('owner', 'editor', 'viewer'));
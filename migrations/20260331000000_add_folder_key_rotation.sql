@@ -0,0 +1,24 @@
+-- Rotation de la clé d'un dossier après révocation d'un accès.
+-- Chaque dossier a une version de clé courante ; chaque accès indique la version de la clé qu'il détient.
+ALTER TABLE folders ADD COLUMN key_version INTEGER NOT NULL DEFAULT 1;
+-- Renseigné par une révocation avec rotation : aucun ajout dans le dossier tant que la nouvelle clé n'est pas distribuée
+ALTER TABLE folders ADD COLUMN key_rotation_pending_since TIMESTAMPTZ;
+
+ALTER TABLE folder_access ADD COLUMN key_version INTEGER NOT NULL DEFAULT 1;
+
+-- Version de la clé du dossier parent utilisée à la création de l'élément
+ALTER TABLE folders ADD COLUMN parent_key_version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE files ADD COLUMN parent_key_version INTEGER NOT NULL DEFAULT 1;
+
+-- Anciennes clés d'un dossier, conservées pour les membres restants : les éléments créés avant
+-- une rotation restent lisibles. Les clés d'un utilisateur révoqué sont supprimées avec son accès.
+CREATE TABLE folder_key_history (
+    folder_id UUID NOT NULL REFERENCES folders(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    key_version INTEGER NOT NULL,
+    encrypted_folder_key BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (folder_id, user_id, key_version)
+);
+
+CREATE INDEX idx_folder_key_history_user_id ON folder_key_history(user_id);
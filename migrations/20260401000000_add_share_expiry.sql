@@ -0,0 +1,7 @@
+-- Partages temporaires : un accès avec expires_at est révoqué automatiquement à cette date
+-- (job drive::jobs::revoke_expired_shares), le propriétaire est notifié.
+ALTER TABLE file_access ADD COLUMN expires_at TIMESTAMPTZ;
+ALTER TABLE folder_access ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX idx_file_access_expires_at ON file_access(expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX idx_folder_access_expires_at ON folder_access(expires_at) WHERE expires_at IS NOT NULL;
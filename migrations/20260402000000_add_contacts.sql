@@ -0,0 +1,48 @@
+-- Carnet de contacts : une relation n'existe qu'après acceptation d'une invitation.
+-- La recherche de clé publique est réservée aux contacts acceptés.
+CREATE TABLE contact_invitations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    from_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    to_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (from_user_id, to_user_id),
+    CHECK (from_user_id <> to_user_id)
+);
+
+CREATE INDEX idx_contact_invitations_to_user ON contact_invitations(to_user_id);
+
+-- Relation symétrique : une ligne par sens, chaque côté garde sa propre empreinte vérifiée
+CREATE TABLE contacts (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    contact_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Empreinte de la clé publique du contact, calculée et vérifiée côté client
+    key_fingerprint TEXT,
+    fingerprint_verified_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, contact_user_id),
+    CHECK (user_id <> contact_user_id)
+);
+
+CREATE INDEX idx_contacts_contact_user ON contacts(contact_user_id);
+
+CREATE TABLE contact_groups (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+-- Un membre est toujours un contact du propriétaire du groupe : la suppression du contact
+-- le retire de tous ses groupes
+CREATE TABLE contact_group_members (
+    group_id UUID NOT NULL REFERENCES contact_groups(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL,
+    contact_user_id UUID NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (group_id, contact_user_id),
+    FOREIGN KEY (user_id, contact_user_id)
+        REFERENCES contacts(user_id, contact_user_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_contact_group_members_contact ON contact_group_members(user_id, contact_user_id);
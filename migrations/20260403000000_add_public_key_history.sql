@@ -0,0 +1,53 @@
+-- Historique append-only des clés publiques (transparence des clés) : chaque version est
+-- accompagnée d'une déclaration canonique signée côté client par la clé précédente,
+-- ce que le serveur ne peut pas falsifier en substituant une clé.
+ALTER TABLE users ADD COLUMN public_key_version INTEGER NOT NULL DEFAULT 1;
+
+CREATE TABLE public_key_history (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    public_key TEXT NOT NULL,
+    -- SHA-256 (hex) de public_key, même format que contacts.key_fingerprint
+    fingerprint TEXT NOT NULL,
+    -- Déclaration canonique signée (voir auth::services::key_statement)
+    statement TEXT NOT NULL,
+    -- Signature base64 de la déclaration par la clé précédente (par la clé elle-même pour la
+    -- première version) ; NULL pour les clés antérieures à cet historique
+    signature TEXT,
+    -- Clé privée de cette version, chiffrée par le mot de passe : les clés d'éléments
+    -- partagés avant un changement de clé restent déchiffrables par l'utilisateur
+    encrypted_private_key TEXT NOT NULL,
+    private_key_salt TEXT,
+    iv TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, version)
+);
+
+INSERT INTO public_key_history (
+    user_id, version, public_key, fingerprint, statement,
+    encrypted_private_key, private_key_salt, iv
+)
+SELECT
+    id,
+    1,
+    public_key,
+    encode(sha256(convert_to(public_key, 'UTF8')), 'hex'),
+    'gauzian-key-statement/v1' || E'\n'
+        || 'user_id:' || id || E'\n'
+        || 'version:1' || E'\n'
+        || 'fingerprint:' || encode(sha256(convert_to(public_key, 'UTF8')), 'hex') || E'\n'
+        || 'previous_fingerprint:none',
+    encrypted_private_key,
+    private_key_salt,
+    iv
+FROM users;
+
+CREATE FUNCTION reject_public_key_history_update() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'public_key_history is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER public_key_history_append_only
+    BEFORE UPDATE ON public_key_history
+    FOR EACH ROW EXECUTE FUNCTION reject_public_key_history_update();
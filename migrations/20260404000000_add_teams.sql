@@ -0,0 +1,61 @@
+-- Équipes : une paire de clés par équipe, dont la clé privée est chiffrée pour chaque membre.
+-- Un dossier est partagé une seule fois à l'équipe (clés d'éléments chiffrées avec la clé
+-- publique de l'équipe) ; ajouter ou retirer un membre ne demande que de rechiffrer la clé
+-- privée de l'équipe.
+CREATE TABLE teams (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Propriété partagée : tous les admins gèrent les membres, il en reste toujours au moins un
+CREATE TABLE team_members (
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('admin', 'member')),
+    -- Clé privée de l'équipe chiffrée avec la clé publique du membre
+    encrypted_private_key TEXT NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (team_id, user_id)
+);
+
+CREATE INDEX idx_team_members_user ON team_members(user_id);
+
+-- Accès de l'équipe, une ligne par dossier de l'arborescence partagée (comme folder_access)
+CREATE TABLE team_folder_access (
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    folder_id UUID NOT NULL REFERENCES folders(id) ON DELETE CASCADE,
+    encrypted_folder_key BYTEA NOT NULL,
+    access_level TEXT NOT NULL CHECK (access_level IN ('editor', 'viewer')),
+    key_version INTEGER NOT NULL DEFAULT 1,
+    -- Dossier partagé lui-même (ancré à la racine des membres), par opposition à ses sous-dossiers
+    is_root BOOLEAN NOT NULL DEFAULT FALSE,
+    shared_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (team_id, folder_id)
+);
+
+CREATE INDEX idx_team_folder_access_folder ON team_folder_access(folder_id);
+
+CREATE TABLE team_file_access (
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    folder_id UUID REFERENCES folders(id) ON DELETE SET NULL,
+    encrypted_file_key BYTEA NOT NULL,
+    access_level TEXT NOT NULL CHECK (access_level IN ('editor', 'viewer')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (team_id, file_id)
+);
+
+CREATE INDEX idx_team_file_access_folder ON team_file_access(folder_id);
+
+-- Accès matérialisés pour chaque membre : les contrôles d'accès et les listings existants
+-- s'appliquent sans changement. team_id identifie la clé privée à utiliser (celle de l'équipe)
+-- et NULL désigne un partage direct, prioritaire sur l'accès d'équipe.
+ALTER TABLE folder_access ADD COLUMN team_id UUID REFERENCES teams(id) ON DELETE CASCADE;
+ALTER TABLE file_access ADD COLUMN team_id UUID REFERENCES teams(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_folder_access_team ON folder_access(team_id, user_id) WHERE team_id IS NOT NULL;
+CREATE INDEX idx_file_access_team ON file_access(team_id, user_id) WHERE team_id IS NOT NULL;
@@ -0,0 +1,21 @@
+-- Auteur d'un partage direct, affiché dans la liste des partages en attente
+ALTER TABLE folder_access ADD COLUMN shared_by UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE file_access ADD COLUMN shared_by UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_folder_access_pending ON folder_access(user_id)
+    WHERE is_accepted = FALSE AND is_deleted = FALSE;
+CREATE INDEX idx_file_access_pending ON file_access(user_id)
+    WHERE is_accepted = FALSE AND is_deleted = FALSE;
+
+-- Emails de partage reçu : immédiat (par défaut, aucune ligne), résumé quotidien ou désactivé
+CREATE TABLE notification_settings (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    share_email_mode TEXT NOT NULL DEFAULT 'instant'
+        CHECK (share_email_mode IN ('instant', 'daily', 'off')),
+    -- Fin de la période couverte par le dernier résumé (passage en mode quotidien à défaut)
+    last_digest_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notification_settings_digest ON notification_settings(last_digest_at)
+    WHERE share_email_mode = 'daily';
@@ -0,0 +1,42 @@
+-- Organisation propre à chaque utilisateur : favoris et couleur sur sa ligne d'accès
+ALTER TABLE folder_access ADD COLUMN is_favorite BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE folder_access ADD COLUMN color_label TEXT
+    CHECK (color_label IN ('red', 'orange', 'yellow', 'green', 'blue', 'purple', 'grey'));
+ALTER TABLE file_access ADD COLUMN is_favorite BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE file_access ADD COLUMN color_label TEXT
+    CHECK (color_label IN ('red', 'orange', 'yellow', 'green', 'blue', 'purple', 'grey'));
+
+CREATE INDEX idx_folder_access_favorite ON folder_access(user_id) WHERE is_favorite = TRUE;
+CREATE INDEX idx_file_access_favorite ON file_access(user_id) WHERE is_favorite = TRUE;
+
+-- Tags de l'utilisateur : le nom est chiffré côté client, le jeton (HMAC du nom avec une clé
+-- de l'utilisateur) permet de filtrer par tag exact sans que le serveur connaisse le nom
+CREATE TABLE drive_tags (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token TEXT NOT NULL,
+    encrypted_name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, token)
+);
+
+CREATE TABLE file_tags (
+    user_id UUID NOT NULL,
+    token TEXT NOT NULL,
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, token, file_id),
+    FOREIGN KEY (user_id, token) REFERENCES drive_tags(user_id, token) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_file_tags_file ON file_tags(file_id);
+
+CREATE TABLE folder_tags (
+    user_id UUID NOT NULL,
+    token TEXT NOT NULL,
+    folder_id UUID NOT NULL REFERENCES folders(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, token, folder_id),
+    FOREIGN KEY (user_id, token) REFERENCES drive_tags(user_id, token) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_folder_tags_folder ON folder_tags(folder_id);
@@ -0,0 +1,27 @@
+-- Index de recherche chiffré, construit par le client et découpé en chunks.
+-- La version permet un contrôle de concurrence optimiste entre les appareils de l'utilisateur.
+CREATE TABLE search_indexes (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    version BIGINT NOT NULL,
+    chunk_count INTEGER NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE search_index_chunks (
+    user_id UUID NOT NULL REFERENCES search_indexes(user_id) ON DELETE CASCADE,
+    chunk_index INTEGER NOT NULL,
+    -- Version de l'index lors de la dernière écriture du chunk
+    version BIGINT NOT NULL,
+    encrypted_data TEXT NOT NULL,
+    PRIMARY KEY (user_id, chunk_index)
+);
+
+-- Jeton aveugle du nom (HMAC du nom normalisé avec une clé de l'utilisateur) pour la recherche
+-- exacte. Il est porté par la ligne d'accès : chaque utilisateur calcule le sien avec sa clé.
+ALTER TABLE file_access ADD COLUMN search_token TEXT;
+ALTER TABLE folder_access ADD COLUMN search_token TEXT;
+
+CREATE INDEX idx_file_access_search_token ON file_access(user_id, search_token)
+    WHERE search_token IS NOT NULL;
+CREATE INDEX idx_folder_access_search_token ON folder_access(user_id, search_token)
+    WHERE search_token IS NOT NULL;
@@ -0,0 +1,4 @@
+-- Octets de chunks reçus pour un upload en cours : bornés par la taille réservée
+-- et comparés à la taille déclarée au finalize
+ALTER TABLE storage_reservations ADD COLUMN received_bytes BIGINT NOT NULL DEFAULT 0
+    CHECK (received_bytes >= 0);
@@ -0,0 +1,3 @@
+-- Début de la purge d'un compte : posé dans la transaction qui purge le drive,
+-- il rend la suppression définitive (plus d'annulation possible)
+ALTER TABLE users ADD COLUMN purge_started_at TIMESTAMPTZ;
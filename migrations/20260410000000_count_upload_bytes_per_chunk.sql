@@ -0,0 +1,16 @@
+-- Octets reçus d'un upload : somme des tailles des chunks enregistrés, un chunk renvoyé
+-- pour le même index (retry) remplaçant le précédent au lieu de s'y ajouter
+ALTER TABLE s3_keys ADD COLUMN size_bytes BIGINT NOT NULL DEFAULT 0 CHECK (size_bytes >= 0);
+
+-- Doublons laissés par des retries : seul le dernier enregistrement de chaque index est gardé
+-- (les objets S3 des lignes supprimées restent orphelins)
+DELETE FROM s3_keys a
+USING s3_keys b
+WHERE a.file_id = b.file_id
+  AND a.index = b.index
+  AND (COALESCE(a.created_at, '-infinity'), a.id) < (COALESCE(b.created_at, '-infinity'), b.id);
+
+CREATE UNIQUE INDEX idx_s3_keys_file_id_index ON s3_keys(file_id, index);
+
+-- Remplacé par la somme des chunks (un compteur incrémenté à chaque requête comptait les retries)
+ALTER TABLE storage_reservations DROP COLUMN received_bytes;
@@ -0,0 +1,8 @@
+-- Un changement d'offre est réservé (pending) avant l'appel au prestataire de paiement :
+-- un seul changement en cours par utilisateur, pour ne jamais encaisser deux fois
+ALTER TABLE account_tier_changes
+    ADD COLUMN status TEXT NOT NULL DEFAULT 'completed'
+        CHECK (status IN ('pending', 'completed', 'failed', 'refunded', 'refund_failed'));
+
+CREATE UNIQUE INDEX idx_account_tier_changes_one_pending
+    ON account_tier_changes(user_id) WHERE status = 'pending';
@@ -0,0 +1,94 @@
+// Handlers HTTP du journal d'activité
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::repo;
+use crate::{auth::Claims, drive::repo::ItemKind, response::ApiResponse, state::AppState};
+
+/// Taille de page par défaut / maximale
+const DEFAULT_ACTIVITY_PAGE_SIZE: i64 = 50;
+const MAX_ACTIVITY_PAGE_SIZE: i64 = 200;
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    /// `next_cursor` de la page précédente
+    pub cursor: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl ActivityQuery {
+    fn page_size(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_ACTIVITY_PAGE_SIZE)
+            .clamp(1, MAX_ACTIVITY_PAGE_SIZE)
+    }
+}
+
+/// GET /activity?cursor=&limit= - Actions de l'utilisateur connecté (drive et connexions)
+pub async fn get_user_activity_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<ActivityQuery>,
+) -> Response {
+    match repo::get_user_activity(&state.db_pool, claims.id, query.cursor, query.page_size()).await
+    {
+        Ok(page) => ApiResponse::ok(page).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to retrieve user activity: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve activity").into_response()
+        }
+    }
+}
+
+/// GET /activity/files/{file_id} - Historique d'un fichier
+pub async fn get_file_activity_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<ActivityQuery>,
+) -> Response {
+    item_activity(&state, claims.id, ItemKind::File, file_id, &query).await
+}
+
+/// GET /activity/folders/{folder_id} - Historique d'un dossier et de ses éléments directs
+pub async fn get_folder_activity_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+    Query(query): Query<ActivityQuery>,
+) -> Response {
+    item_activity(&state, claims.id, ItemKind::Folder, folder_id, &query).await
+}
+
+async fn item_activity(
+    state: &AppState,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    query: &ActivityQuery,
+) -> Response {
+    match repo::get_item_activity(
+        &state.db_pool,
+        user_id,
+        kind,
+        item_id,
+        query.cursor,
+        query.page_size(),
+    )
+    .await
+    {
+        Ok(page) => ApiResponse::ok(page).into_response(),
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Item not found or access denied").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve {} activity: {:?}", kind.as_str(), e);
+            ApiResponse::internal_error("Failed to retrieve activity").into_response()
+        }
+    }
+}
@@ -0,0 +1,32 @@
+// Tâches de fond du journal d'activité
+
+use super::{repo, services};
+use crate::state::AppState;
+
+/// Nombre maximum d'entrées supprimées par requête
+const PRUNE_BATCH_SIZE: i64 = 10_000;
+
+/// Supprime les entrées plus anciennes que ACTIVITY_LOG_RETENTION_DAYS
+pub async fn prune_activity_log(state: &AppState) {
+    let retention_days = services::activity_retention_days();
+    let mut total = 0u64;
+
+    loop {
+        match repo::prune_activity_log(&state.db_pool, retention_days, PRUNE_BATCH_SIZE).await {
+            Ok(pruned) => {
+                total += pruned;
+                if pruned < PRUNE_BATCH_SIZE as u64 {
+                    break;
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to prune activity log: {}", e);
+                break;
+            }
+        }
+    }
+
+    if total > 0 {
+        tracing::info!("Pruned {} activity log entries", total);
+    }
+}
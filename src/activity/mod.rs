@@ -0,0 +1,12 @@
+// Module activity - Journal d'activité (audit) par élément et par utilisateur
+// Alimenté par les handlers drive et auth, consultable via /activity
+
+pub mod handlers;
+pub mod jobs;
+pub mod repo;
+pub mod routes;
+pub mod services;
+
+// Re-exports
+pub use routes::activity_routes;
+pub use services::{ActivityAction, ActivityEntry};
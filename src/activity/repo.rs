@@ -0,0 +1,178 @@
+// Repository - Journal d'activité (table `activity_log`, append-only)
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use super::services::ActivityEntry;
+use crate::drive::repo::ItemKind;
+
+/// Entrée du journal renvoyée par les endpoints `/activity`
+#[derive(Debug, FromRow, Serialize)]
+pub struct ActivityRecord {
+    pub id: i64,
+    pub user_id: Uuid,
+    pub action: String,
+    pub item_type: Option<String>,
+    pub item_id: Option<Uuid>,
+    pub folder_id: Option<Uuid>,
+    pub target_user_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Page du journal, de la plus récente à la plus ancienne entrée
+#[derive(Debug, Serialize)]
+pub struct ActivityPage {
+    pub entries: Vec<ActivityRecord>,
+    /// À repasser en `cursor` pour la page suivante (None : dernière page)
+    pub next_cursor: Option<i64>,
+}
+
+impl ActivityPage {
+    /// Construit une page à partir de `limit + 1` lignes lues (la ligne en trop signale une suite)
+    fn from_rows(mut entries: Vec<ActivityRecord>, limit: i64) -> Self {
+        let has_more = entries.len() as i64 > limit;
+        entries.truncate(limit as usize);
+        let next_cursor = if has_more {
+            entries.last().map(|entry| entry.id)
+        } else {
+            None
+        };
+        Self {
+            entries,
+            next_cursor,
+        }
+    }
+}
+
+/// Ajoute une entrée. Le dossier contenant l'élément est résolu ici : parent du dossier,
+/// ou dossier du fichier (vue de l'auteur de préférence, sinon celle d'un autre détenteur).
+pub async fn insert_activity(pool: &PgPool, entry: &ActivityEntry) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO activity_log (user_id, action, item_type, item_id, folder_id, target_user_id, ip_address)
+        VALUES (
+            $1, $2, $3, $4,
+            CASE $3::TEXT
+                WHEN 'file' THEN (
+                    SELECT folder_id FROM file_access
+                    WHERE file_id = $4 AND folder_id IS NOT NULL
+                    ORDER BY (user_id = $1) DESC
+                    LIMIT 1
+                )
+                WHEN 'folder' THEN (SELECT parent_folder_id FROM folders WHERE id = $4)
+            END,
+            $5, $6
+        )
+        "#,
+    )
+    .bind(entry.user_id)
+    .bind(entry.action.as_str())
+    .bind(entry.item.map(|(kind, _)| kind.as_str()))
+    .bind(entry.item.map(|(_, item_id)| item_id))
+    .bind(entry.target_user_id)
+    .bind(entry.ip_address.as_deref())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Actions effectuées par un utilisateur (connexions comprises)
+pub async fn get_user_activity(
+    pool: &PgPool,
+    user_id: Uuid,
+    cursor: Option<i64>,
+    limit: i64,
+) -> Result<ActivityPage, sqlx::Error> {
+    let entries = sqlx::query_as::<_, ActivityRecord>(
+        r#"
+        SELECT id, user_id, action, item_type, item_id, folder_id, target_user_id, ip_address, created_at
+        FROM activity_log
+        WHERE user_id = $1 AND ($2::BIGINT IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(user_id)
+    .bind(cursor)
+    .bind(limit + 1)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ActivityPage::from_rows(entries, limit))
+}
+
+/// Historique d'un élément, visible par tous ses détenteurs ayant accepté le partage.
+/// Pour un dossier, inclut les actions sur ses éléments directs ("Alice a ajouté X").
+pub async fn get_item_activity(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    cursor: Option<i64>,
+    limit: i64,
+) -> Result<ActivityPage, sqlx::Error> {
+    let access_query = match kind {
+        ItemKind::File => {
+            "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND is_accepted = TRUE)"
+        }
+        ItemKind::Folder => {
+            "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND is_accepted = TRUE)"
+        }
+    };
+    let has_access: bool = sqlx::query_scalar(access_query)
+        .bind(item_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    if !has_access {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    // Les connexions n'ont pas d'élément : l'adresse IP n'est donc jamais exposée ici
+    let entries = sqlx::query_as::<_, ActivityRecord>(
+        r#"
+        SELECT id, user_id, action, item_type, item_id, folder_id, target_user_id, ip_address, created_at
+        FROM activity_log
+        WHERE ((item_type = $1 AND item_id = $2) OR ($1 = 'folder' AND folder_id = $2))
+          AND ($3::BIGINT IS NULL OR id < $3)
+        ORDER BY id DESC
+        LIMIT $4
+        "#,
+    )
+    .bind(kind.as_str())
+    .bind(item_id)
+    .bind(cursor)
+    .bind(limit + 1)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ActivityPage::from_rows(entries, limit))
+}
+
+/// Supprime au plus `limit` entrées plus anciennes que `retention_days`
+pub async fn prune_activity_log(
+    pool: &PgPool,
+    retention_days: i64,
+    limit: i64,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM activity_log
+        WHERE id IN (
+            SELECT id FROM activity_log
+            WHERE created_at < NOW() - make_interval(days => $1::INT)
+            LIMIT $2
+        )
+        "#,
+    )
+    .bind(retention_days as i32)
+    .bind(limit)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,19 @@
+// Routes du module activity
+
+use crate::state::AppState;
+use axum::{Router, routing::get};
+
+use super::handlers;
+
+pub fn activity_routes() -> Router<AppState> {
+    Router::new()
+        .route("/activity", get(handlers::get_user_activity_handler))
+        .route(
+            "/activity/files/{file_id}",
+            get(handlers::get_file_activity_handler),
+        )
+        .route(
+            "/activity/folders/{folder_id}",
+            get(handlers::get_folder_activity_handler),
+        )
+}
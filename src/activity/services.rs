@@ -0,0 +1,118 @@
+// Services - Actions journalisées, configuration de la rétention, écriture best effort
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::repo;
+use crate::drive::repo::ItemKind;
+
+/// Action enregistrée dans le journal d'activité
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityAction {
+    Login,
+    LoginFailed,
+    Logout,
+    Uploaded,
+    Downloaded,
+    Renamed,
+    Moved,
+    Deleted,
+    Restored,
+    Shared,
+    Revoked,
+    Accepted,
+    Rejected,
+    KeyRotated,
+    ShareExpired,
+    PublicKeyChanged,
+    SharedWithTeam,
+    RevokedFromTeam,
+}
+
+impl ActivityAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActivityAction::Login => "login",
+            ActivityAction::LoginFailed => "login_failed",
+            ActivityAction::Logout => "logout",
+            ActivityAction::Uploaded => "uploaded",
+            ActivityAction::Downloaded => "downloaded",
+            ActivityAction::Renamed => "renamed",
+            ActivityAction::Moved => "moved",
+            ActivityAction::Deleted => "deleted",
+            ActivityAction::Restored => "restored",
+            ActivityAction::Shared => "shared",
+            ActivityAction::Revoked => "revoked",
+            ActivityAction::Accepted => "accepted",
+            ActivityAction::Rejected => "rejected",
+            ActivityAction::KeyRotated => "key_rotated",
+            ActivityAction::ShareExpired => "share_expired",
+            ActivityAction::PublicKeyChanged => "public_key_changed",
+            ActivityAction::SharedWithTeam => "shared_with_team",
+            ActivityAction::RevokedFromTeam => "revoked_from_team",
+        }
+    }
+}
+
+/// Entrée à journaliser : `user_id` est l'auteur de l'action (ou le compte visé pour un échec de connexion)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivityEntry {
+    pub user_id: Uuid,
+    pub action: ActivityAction,
+    pub item: Option<(ItemKind, Uuid)>,
+    pub target_user_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+}
+
+impl ActivityEntry {
+    pub fn new(user_id: Uuid, action: ActivityAction) -> Self {
+        Self {
+            user_id,
+            action,
+            item: None,
+            target_user_id: None,
+            ip_address: None,
+        }
+    }
+
+    pub fn on_item(mut self, kind: ItemKind, item_id: Uuid) -> Self {
+        self.item = Some((kind, item_id));
+        self
+    }
+
+    pub fn target_user(mut self, user_id: Uuid) -> Self {
+        self.target_user_id = Some(user_id);
+        self
+    }
+
+    pub fn from_ip(mut self, ip_address: Option<&str>) -> Self {
+        self.ip_address = ip_address.map(str::to_string);
+        self
+    }
+}
+
+/// Durée de conservation du journal d'activité (ACTIVITY_LOG_RETENTION_DAYS, 365 jours par défaut)
+pub fn activity_retention_days() -> i64 {
+    parse_activity_retention_days(std::env::var("ACTIVITY_LOG_RETENTION_DAYS").ok().as_deref())
+}
+
+/// Valeur de ACTIVITY_LOG_RETENTION_DAYS : 365 jours si absente, invalide ou nulle
+pub fn parse_activity_retention_days(value: Option<&str>) -> i64 {
+    value
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(365)
+}
+
+/// Journalise une action déjà effectuée. Best effort : un échec d'écriture est tracé
+/// mais ne fait pas échouer la requête dont l'action a déjà été validée.
+pub async fn record(pool: &PgPool, entry: ActivityEntry) {
+    if let Err(e) = repo::insert_activity(pool, &entry).await {
+        tracing::error!(
+            "Failed to record activity {} for user {}: {}",
+            entry.action.as_str(),
+            entry.user_id,
+            e
+        );
+    }
+}
@@ -0,0 +1,256 @@
+// Handlers HTTP de l'API d'administration
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        AdminClaims,
+        services::{
+            UserStatus, cache_user_status, revoke_user_sessions, send_account_reactivated_mail,
+            send_account_suspended_mail,
+        },
+    },
+    drive::repo::{get_drive_info, sync_over_quota_state},
+    response::ApiResponse,
+    state::AppState,
+};
+
+use super::repo;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+const MAX_SUSPENSION_REASON_LENGTH: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    pub search: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTierRequest {
+    pub account_tier_id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct SuspendUserRequest {
+    pub reason: String,
+}
+
+/// GET /admin/users?search=&limit=&offset= - Liste les utilisateurs avec leur usage
+pub async fn list_users_handler(
+    State(state): State<AppState>,
+    _admin: AdminClaims,
+    Query(query): Query<ListUsersQuery>,
+) -> Response {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let search = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+
+    // Usage joint dans la même requête (pas de requête par utilisateur)
+    match repo::list_users(&state.db_pool, search, limit, offset).await {
+        Ok(users) => ApiResponse::ok(users).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to list users: {}", e);
+            ApiResponse::internal_error("Failed to list users").into_response()
+        }
+    }
+}
+
+/// GET /admin/users/{user_id} - Détail d'un utilisateur avec son usage
+pub async fn get_user_handler(
+    State(state): State<AppState>,
+    _admin: AdminClaims,
+    Path(user_id): Path<Uuid>,
+) -> Response {
+    match repo::get_user_with_usage(&state.db_pool, user_id).await {
+        Ok(user) => ApiResponse::ok(user).into_response(),
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("User not found").into_response(),
+        Err(e) => {
+            tracing::error!("Failed to fetch user {}: {}", user_id, e);
+            ApiResponse::internal_error("Failed to fetch user").into_response()
+        }
+    }
+}
+
+/// PATCH /admin/users/{user_id}/tier - Change l'offre d'un utilisateur
+pub async fn update_user_tier_handler(
+    State(state): State<AppState>,
+    AdminClaims(admin): AdminClaims,
+    Path(user_id): Path<Uuid>,
+    Json(body): Json<UpdateTierRequest>,
+) -> Response {
+    match repo::set_user_tier(&state.db_pool, user_id, body.account_tier_id).await {
+        Ok(()) => {
+            tracing::info!(
+                "Admin {} changed account tier of user {} to {}",
+                admin.id,
+                user_id,
+                body.account_tier_id
+            );
+            // Un downgrade peut faire passer l'utilisateur au-dessus du quota : le délai de grâce démarre maintenant
+            match get_drive_info(&state.db_pool, user_id).await {
+                Ok(mut info) => {
+                    if let Err(e) = sync_over_quota_state(&state.db_pool, user_id, &mut info).await
+                    {
+                        tracing::error!("Failed to update over-quota state: {:?}", e);
+                    }
+                }
+                Err(e) => tracing::error!("Failed to retrieve drive info: {:?}", e),
+            }
+            ApiResponse::ok("Account tier updated").into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("User not found").into_response(),
+        Err(sqlx::Error::Protocol(msg)) => ApiResponse::bad_request(msg).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to update account tier of user {}: {}", user_id, e);
+            ApiResponse::internal_error("Failed to update account tier").into_response()
+        }
+    }
+}
+
+/// POST /admin/users/{user_id}/suspend - Suspend un compte, révoque ses sessions et prévient l'utilisateur
+pub async fn suspend_user_handler(
+    State(state): State<AppState>,
+    AdminClaims(admin): AdminClaims,
+    Path(user_id): Path<Uuid>,
+    Json(body): Json<SuspendUserRequest>,
+) -> Response {
+    if admin.id == user_id {
+        return ApiResponse::bad_request("Cannot suspend your own account").into_response();
+    }
+
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return ApiResponse::bad_request("Suspension reason is required").into_response();
+    }
+    if reason.len() > MAX_SUSPENSION_REASON_LENGTH {
+        return ApiResponse::bad_request("Suspension reason is too long").into_response();
+    }
+
+    let user = match repo::get_user(&state.db_pool, user_id).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::not_found("User not found").into_response();
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch user {}: {}", user_id, e);
+            return ApiResponse::internal_error("Failed to suspend user").into_response();
+        }
+    };
+
+    if let Err(e) = repo::suspend_user(&state.db_pool, user_id, reason).await {
+        tracing::error!("Failed to suspend user {}: {}", user_id, e);
+        return ApiResponse::internal_error("Failed to suspend user").into_response();
+    }
+
+    // Le cache est mis à jour immédiatement : l'extracteur Claims refuse aussitôt les requêtes
+    let mut redis_conn = state.redis_manager.clone();
+    if let Err(e) = cache_user_status(&mut redis_conn, user_id, UserStatus::Suspended).await {
+        tracing::error!("Failed to cache status of user {}: {}", user_id, e);
+        return ApiResponse::internal_error("User suspended but sessions could not be revoked")
+            .into_response();
+    }
+    if let Err(e) = revoke_user_sessions(&mut redis_conn, user_id).await {
+        tracing::error!("Failed to revoke sessions of user {}: {}", user_id, e);
+        return ApiResponse::internal_error("User suspended but sessions could not be revoked")
+            .into_response();
+    }
+
+    tracing::info!("Admin {} suspended user {}", admin.id, user_id);
+
+    if let Err(e) = send_account_suspended_mail(&state.mailer, &user.email, reason) {
+        tracing::warn!("Failed to notify user {} of suspension: {}", user_id, e);
+    }
+
+    ApiResponse::ok("User suspended").into_response()
+}
+
+/// POST /admin/users/{user_id}/reactivate - Lève la suspension d'un compte
+pub async fn reactivate_user_handler(
+    State(state): State<AppState>,
+    AdminClaims(admin): AdminClaims,
+    Path(user_id): Path<Uuid>,
+) -> Response {
+    let user = match repo::get_user(&state.db_pool, user_id).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::not_found("User not found").into_response();
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch user {}: {}", user_id, e);
+            return ApiResponse::internal_error("Failed to reactivate user").into_response();
+        }
+    };
+
+    if user.is_active {
+        return ApiResponse::conflict("User is not suspended").into_response();
+    }
+
+    if let Err(e) = repo::reactivate_user(&state.db_pool, user_id).await {
+        tracing::error!("Failed to reactivate user {}: {}", user_id, e);
+        return ApiResponse::internal_error("Failed to reactivate user").into_response();
+    }
+
+    let mut redis_conn = state.redis_manager.clone();
+    if let Err(e) = cache_user_status(&mut redis_conn, user_id, UserStatus::Active).await {
+        // Sans mise à jour, le statut "suspended" reste en cache jusqu'à son expiration
+        tracing::warn!("Failed to cache status of user {}: {}", user_id, e);
+    }
+
+    tracing::info!("Admin {} reactivated user {}", admin.id, user_id);
+
+    if let Err(e) = send_account_reactivated_mail(&state.mailer, &user.email) {
+        tracing::warn!("Failed to notify user {} of reactivation: {}", user_id, e);
+    }
+
+    ApiResponse::ok("User reactivated").into_response()
+}
+
+/// POST /admin/users/{user_id}/logout - Déconnecte l'utilisateur de toutes ses sessions
+pub async fn force_logout_handler(
+    State(state): State<AppState>,
+    AdminClaims(admin): AdminClaims,
+    Path(user_id): Path<Uuid>,
+) -> Response {
+    match repo::get_user(&state.db_pool, user_id).await {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::not_found("User not found").into_response();
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch user {}: {}", user_id, e);
+            return ApiResponse::internal_error("Failed to revoke sessions").into_response();
+        }
+    }
+
+    let mut redis_conn = state.redis_manager.clone();
+    match revoke_user_sessions(&mut redis_conn, user_id).await {
+        Ok(()) => {
+            tracing::info!(
+                "Admin {} revoked all sessions of user {}",
+                admin.id,
+                user_id
+            );
+            ApiResponse::ok("All sessions revoked").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to revoke sessions of user {}: {}", user_id, e);
+            ApiResponse::internal_error("Failed to revoke sessions").into_response()
+        }
+    }
+}
@@ -0,0 +1,9 @@
+// Module admin - API d'administration (gestion des comptes utilisateurs)
+// Toutes les routes exigent le rôle admin (extracteur AdminClaims)
+
+pub mod handlers;
+pub mod repo;
+pub mod routes;
+
+// Re-exports
+pub use routes::admin_routes;
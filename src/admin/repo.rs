@@ -0,0 +1,184 @@
+// Repository - Requêtes SQL de l'administration des comptes
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::drive::repo::DriveInfo;
+
+#[derive(Serialize, Debug, sqlx::FromRow)]
+pub struct AdminUser {
+    pub id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub role: String,
+    pub is_active: bool,
+    pub account_tier_id: Uuid,
+    pub account_tier: String,
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub suspension_reason: Option<String>,
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
+}
+
+const ADMIN_USER_COLUMNS: &str = r#"
+    u.id, u.email, u.username, u.role, u.is_active,
+    u.account_tier_id, at.name AS account_tier,
+    u.suspended_at, u.suspension_reason, u.deletion_scheduled_at
+"#;
+
+/// Utilisateur avec son usage du drive (mêmes valeurs que `drive::repo::get_drive_info`)
+#[derive(Serialize, sqlx::FromRow)]
+pub struct AdminUserWithUsage {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub user: AdminUser,
+    #[sqlx(flatten)]
+    pub usage: DriveInfo,
+}
+
+/// Usage lu depuis les compteurs `user_storage_usage` (jointure `s`), dans la même requête
+/// que l'utilisateur. `account_tier` est déjà lu par ADMIN_USER_COLUMNS.
+const ADMIN_USAGE_COLUMNS: &str = r#"
+    COALESCE(s.used_bytes, 0)::BIGINT AS used_space,
+    COALESCE(s.reserved_bytes, 0)::BIGINT AS reserved_space,
+    COALESCE(s.file_count, 0)::BIGINT AS file_count,
+    (
+        SELECT COUNT(*) FROM folder_access fa
+        WHERE fa.user_id = u.id AND fa.access_level = 'owner'
+    )::BIGINT AS folder_count,
+    at.storage_limit_bytes,
+    u.over_quota_since
+"#;
+
+/// Liste les utilisateurs avec leur usage (paginé, filtre optionnel sur l'email ou le nom d'utilisateur)
+pub async fn list_users(
+    pool: &PgPool,
+    search: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<AdminUserWithUsage>, sqlx::Error> {
+    sqlx::query_as::<_, AdminUserWithUsage>(&format!(
+        r#"
+        SELECT {ADMIN_USER_COLUMNS}, {ADMIN_USAGE_COLUMNS}
+        FROM users u
+        JOIN account_tiers at ON at.id = u.account_tier_id
+        LEFT JOIN user_storage_usage s ON s.user_id = u.id
+        WHERE $1::TEXT IS NULL
+           OR u.email ILIKE '%' || $1 || '%'
+           OR u.username ILIKE '%' || $1 || '%'
+        ORDER BY u.email
+        LIMIT $2 OFFSET $3
+        "#
+    ))
+    .bind(search)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
+
+/// Récupère un utilisateur par son ID
+pub async fn get_user(pool: &PgPool, user_id: Uuid) -> Result<AdminUser, sqlx::Error> {
+    sqlx::query_as::<_, AdminUser>(&format!(
+        r#"
+        SELECT {ADMIN_USER_COLUMNS}
+        FROM users u
+        JOIN account_tiers at ON at.id = u.account_tier_id
+        WHERE u.id = $1
+        "#
+    ))
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Récupère un utilisateur avec son usage
+pub async fn get_user_with_usage(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<AdminUserWithUsage, sqlx::Error> {
+    sqlx::query_as::<_, AdminUserWithUsage>(&format!(
+        r#"
+        SELECT {ADMIN_USER_COLUMNS}, {ADMIN_USAGE_COLUMNS}
+        FROM users u
+        JOIN account_tiers at ON at.id = u.account_tier_id
+        LEFT JOIN user_storage_usage s ON s.user_id = u.id
+        WHERE u.id = $1
+        "#
+    ))
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Change l'offre d'un utilisateur.
+/// Retourne Protocol si l'offre n'existe pas, RowNotFound si l'utilisateur n'existe pas.
+pub async fn set_user_tier(
+    pool: &PgPool,
+    user_id: Uuid,
+    account_tier_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let tier_exists: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM account_tiers WHERE id = $1)")
+            .bind(account_tier_id)
+            .fetch_one(pool)
+            .await?;
+
+    if !tier_exists {
+        return Err(sqlx::Error::Protocol("Unknown account tier".into()));
+    }
+
+    let result = sqlx::query("UPDATE users SET account_tier_id = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(account_tier_id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Suspend un compte (is_active = FALSE) avec sa raison.
+/// Une suspension déjà en cours garde sa date d'origine. Retourne RowNotFound si l'utilisateur n'existe pas.
+pub async fn suspend_user(pool: &PgPool, user_id: Uuid, reason: &str) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET is_active = FALSE,
+            suspended_at = COALESCE(suspended_at, NOW()),
+            suspension_reason = $2
+        WHERE id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(reason)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Lève la suspension d'un compte. Retourne RowNotFound si l'utilisateur n'existe pas.
+pub async fn reactivate_user(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET is_active = TRUE, suspended_at = NULL, suspension_reason = NULL
+        WHERE id = $1
+        "#,
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
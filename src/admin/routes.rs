@@ -0,0 +1,31 @@
+// Routes du module admin, montées sous /admin dans routes.rs principal
+
+use crate::state::AppState;
+use axum::{
+    Router,
+    routing::{get, patch, post},
+};
+
+use super::handlers;
+
+pub fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/users", get(handlers::list_users_handler))
+        .route("/users/{user_id}", get(handlers::get_user_handler))
+        .route(
+            "/users/{user_id}/tier",
+            patch(handlers::update_user_tier_handler),
+        )
+        .route(
+            "/users/{user_id}/suspend",
+            post(handlers::suspend_user_handler),
+        )
+        .route(
+            "/users/{user_id}/reactivate",
+            post(handlers::reactivate_user_handler),
+        )
+        .route(
+            "/users/{user_id}/logout",
+            post(handlers::force_logout_handler),
+        )
+}
@@ -188,3 +188,27 @@ pub async fn add_event_participant(
     .await?;
     Ok(())
 }
+
+/// Supprime toutes les données agenda d'un utilisateur (suppression de compte)
+pub async fn delete_user_agenda_data(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM agenda_event_participants WHERE participant_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Les participations des autres utilisateurs suivent par ON DELETE CASCADE
+    sqlx::query("DELETE FROM agenda_events WHERE owner_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM agenda_categories WHERE owner_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
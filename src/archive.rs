@@ -0,0 +1,370 @@
+// Écriture d'archives ZIP en streaming (sans compression, format ZIP64)
+//
+// Les données des utilisateurs sont déjà chiffrées côté client : les compresser
+// est inutile. Chaque entrée est écrite avec un "data descriptor" (CRC et tailles
+// après les données), ce qui permet de produire l'archive au fil de l'eau sans
+// connaître la taille des entrées à l'avance ni garder l'archive en mémoire.
+// Les structures ZIP64 sont toujours utilisées : pas de limite à 4 Go.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use futures::SinkExt;
+use uuid::Uuid;
+
+use crate::storage::{MultipartUpload, StorageClient, StorageError};
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x02014b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+
+const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+const VERSION_ZIP64: u16 = 45;
+/// Bit 3 : CRC et tailles dans le data descriptor, bit 11 : noms en UTF-8
+const GENERAL_PURPOSE_FLAGS: u16 = 0x0008 | 0x0800;
+const COMPRESSION_STORED: u16 = 0;
+
+// ========== CRC32 (IEEE 802.3) ==========
+
+const CRC32_TABLE: [u32; 256] = build_crc32_table();
+
+const fn build_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC32 incrémental
+#[derive(Debug, Clone)]
+pub struct Crc32(u32);
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Crc32(0xFFFF_FFFF)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.0 = CRC32_TABLE[((self.0 ^ *byte as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub fn finalize(&self) -> u32 {
+        !self.0
+    }
+}
+
+/// CRC32 d'un buffer complet
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+// ========== Writer ZIP ==========
+
+/// Convertit une date en format MS-DOS (time, date) utilisé par les en-têtes ZIP
+fn dos_datetime(datetime: DateTime<Utc>) -> (u16, u16) {
+    let year = datetime.year().clamp(1980, 2107) as u16;
+    let time = ((datetime.hour() as u16) << 11)
+        | ((datetime.minute() as u16) << 5)
+        | (datetime.second() as u16 / 2);
+    let date = ((year - 1980) << 9) | ((datetime.month() as u16) << 5) | datetime.day() as u16;
+    (time, date)
+}
+
+#[derive(Debug)]
+struct EntryRecord {
+    name: String,
+    crc32: u32,
+    size: u64,
+    header_offset: u64,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+#[derive(Debug)]
+struct OpenEntry {
+    record: EntryRecord,
+    crc: Crc32,
+}
+
+/// Writer ZIP en streaming : chaque méthode retourne les octets à écrire,
+/// dans l'ordre, sur la sortie (fichier, upload multipart, corps HTTP...).
+///
+/// Utilisation : `start_entry` → `record_data` (pour chaque bloc transmis tel quel)
+/// → `finish_entry`, puis `finish` une fois toutes les entrées écrites.
+#[derive(Debug, Default)]
+pub struct ZipStreamWriter {
+    offset: u64,
+    entries: Vec<EntryRecord>,
+    current: Option<OpenEntry>,
+}
+
+impl ZipStreamWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Nombre total d'octets produits jusqu'ici
+    pub fn bytes_written(&self) -> u64 {
+        self.offset
+    }
+
+    /// Ouvre une nouvelle entrée et retourne son en-tête local
+    pub fn start_entry(&mut self, name: &str) -> Bytes {
+        assert!(
+            self.current.is_none(),
+            "previous zip entry must be finished before starting a new one"
+        );
+
+        let (dos_time, dos_date) = dos_datetime(Utc::now());
+        let name_bytes = name.as_bytes();
+
+        let mut header = BytesMut::with_capacity(30 + name_bytes.len() + 20);
+        header.put_u32_le(LOCAL_FILE_HEADER_SIGNATURE);
+        header.put_u16_le(VERSION_ZIP64);
+        header.put_u16_le(GENERAL_PURPOSE_FLAGS);
+        header.put_u16_le(COMPRESSION_STORED);
+        header.put_u16_le(dos_time);
+        header.put_u16_le(dos_date);
+        header.put_u32_le(0); // CRC32 : dans le data descriptor
+        header.put_u32_le(0xFFFF_FFFF); // Taille compressée : voir extra ZIP64
+        header.put_u32_le(0xFFFF_FFFF); // Taille non compressée : voir extra ZIP64
+        header.put_u16_le(name_bytes.len() as u16);
+        header.put_u16_le(20);
+        header.put_slice(name_bytes);
+        header.put_u16_le(ZIP64_EXTRA_FIELD_ID);
+        header.put_u16_le(16);
+        header.put_u64_le(0);
+        header.put_u64_le(0);
+
+        self.current = Some(OpenEntry {
+            record: EntryRecord {
+                name: name.to_string(),
+                crc32: 0,
+                size: 0,
+                header_offset: self.offset,
+                dos_time,
+                dos_date,
+            },
+            crc: Crc32::new(),
+        });
+
+        self.offset += header.len() as u64;
+        header.freeze()
+    }
+
+    /// Comptabilise un bloc de données de l'entrée courante.
+    /// Le bloc doit être écrit tel quel sur la sortie par l'appelant.
+    pub fn record_data(&mut self, data: &[u8]) {
+        let entry = self
+            .current
+            .as_mut()
+            .expect("record_data called without an open zip entry");
+        entry.crc.update(data);
+        entry.record.size += data.len() as u64;
+        self.offset += data.len() as u64;
+    }
+
+    /// Ferme l'entrée courante et retourne son data descriptor
+    pub fn finish_entry(&mut self) -> Bytes {
+        let OpenEntry { mut record, crc } = self
+            .current
+            .take()
+            .expect("finish_entry called without an open zip entry");
+        record.crc32 = crc.finalize();
+
+        let mut descriptor = BytesMut::with_capacity(24);
+        descriptor.put_u32_le(DATA_DESCRIPTOR_SIGNATURE);
+        descriptor.put_u32_le(record.crc32);
+        descriptor.put_u64_le(record.size);
+        descriptor.put_u64_le(record.size);
+
+        self.entries.push(record);
+        self.offset += descriptor.len() as u64;
+        descriptor.freeze()
+    }
+
+    /// Écrit une entrée complète déjà en mémoire (petits fichiers JSON...)
+    pub fn entry(&mut self, name: &str, data: &[u8]) -> Bytes {
+        let mut out = BytesMut::new();
+        out.put(self.start_entry(name));
+        self.record_data(data);
+        out.put_slice(data);
+        out.put(self.finish_entry());
+        out.freeze()
+    }
+
+    /// Termine l'archive : répertoire central + enregistrements de fin ZIP64
+    pub fn finish(mut self) -> Bytes {
+        let mut out = BytesMut::new();
+        if self.current.is_some() {
+            // Ne devrait pas arriver : on ferme l'entrée pour garder une archive valide
+            out.put(self.finish_entry());
+        }
+
+        let central_directory_offset = self.offset;
+        let central_directory_start = out.len();
+
+        for entry in &self.entries {
+            let name_bytes = entry.name.as_bytes();
+            out.put_u32_le(CENTRAL_DIRECTORY_SIGNATURE);
+            out.put_u16_le(VERSION_ZIP64); // Version made by
+            out.put_u16_le(VERSION_ZIP64); // Version needed to extract
+            out.put_u16_le(GENERAL_PURPOSE_FLAGS);
+            out.put_u16_le(COMPRESSION_STORED);
+            out.put_u16_le(entry.dos_time);
+            out.put_u16_le(entry.dos_date);
+            out.put_u32_le(entry.crc32);
+            out.put_u32_le(0xFFFF_FFFF);
+            out.put_u32_le(0xFFFF_FFFF);
+            out.put_u16_le(name_bytes.len() as u16);
+            out.put_u16_le(28);
+            out.put_u16_le(0); // Commentaire
+            out.put_u16_le(0); // Disque de départ
+            out.put_u16_le(0); // Attributs internes
+            out.put_u32_le(0); // Attributs externes
+            out.put_u32_le(0xFFFF_FFFF); // Offset : voir extra ZIP64
+            out.put_slice(name_bytes);
+            out.put_u16_le(ZIP64_EXTRA_FIELD_ID);
+            out.put_u16_le(24);
+            out.put_u64_le(entry.size);
+            out.put_u64_le(entry.size);
+            out.put_u64_le(entry.header_offset);
+        }
+
+        let central_directory_size = (out.len() - central_directory_start) as u64;
+        let zip64_end_offset = central_directory_offset + central_directory_size;
+        let entry_count = self.entries.len() as u64;
+
+        out.put_u32_le(ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        out.put_u64_le(44); // Taille de l'enregistrement (hors 12 premiers octets)
+        out.put_u16_le(VERSION_ZIP64);
+        out.put_u16_le(VERSION_ZIP64);
+        out.put_u32_le(0);
+        out.put_u32_le(0);
+        out.put_u64_le(entry_count);
+        out.put_u64_le(entry_count);
+        out.put_u64_le(central_directory_size);
+        out.put_u64_le(central_directory_offset);
+
+        out.put_u32_le(ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIGNATURE);
+        out.put_u32_le(0);
+        out.put_u64_le(zip64_end_offset);
+        out.put_u32_le(1);
+
+        out.put_u32_le(END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        out.put_u16_le(0);
+        out.put_u16_le(0);
+        out.put_u16_le(0xFFFF);
+        out.put_u16_le(0xFFFF);
+        out.put_u32_le(0xFFFF_FFFF);
+        out.put_u32_le(0xFFFF_FFFF);
+        out.put_u16_le(0);
+
+        out.freeze()
+    }
+}
+
+// ========== Destinations ==========
+
+/// Destination des octets d'une archive produite au fil de l'eau
+pub trait ArchiveSink {
+    fn write_archive_bytes(
+        &mut self,
+        data: Bytes,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// Upload multipart vers le stockage (exports RGPD)
+impl ArchiveSink for MultipartUpload {
+    async fn write_archive_bytes(&mut self, data: Bytes) -> Result<(), String> {
+        self.write(&data).await.map_err(|e| e.to_string())
+    }
+}
+
+/// Corps de réponse HTTP en streaming (`Body::from_stream`)
+impl ArchiveSink for futures::channel::mpsc::Sender<Result<Bytes, std::io::Error>> {
+    async fn write_archive_bytes(&mut self, data: Bytes) -> Result<(), String> {
+        self.send(Ok(data))
+            .await
+            .map_err(|_| "client disconnected".to_string())
+    }
+}
+
+/// Écrit une entrée JSON complète (manifest, métadonnées...)
+pub async fn write_json_entry<S: ArchiveSink>(
+    zip: &mut ZipStreamWriter,
+    sink: &mut S,
+    name: &str,
+    value: &serde_json::Value,
+) -> Result<(), String> {
+    let data = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    sink.write_archive_bytes(zip.entry(name, &data)).await
+}
+
+/// Écrit les chunks d'un fichier tels que stockés (chiffrés), un par entrée
+/// `files/<file_id>/<index>.chunk`, et retourne leur description pour le manifest
+/// (chemin, IV, taille, empreinte). Un chunk absent du stockage est signalé
+/// `missing` au lieu d'interrompre l'archive.
+pub async fn write_file_chunks<S: ArchiveSink>(
+    zip: &mut ZipStreamWriter,
+    sink: &mut S,
+    storage: &StorageClient,
+    file_id: Uuid,
+    chunks: Vec<(String, i32)>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let mut chunks_json = Vec::with_capacity(chunks.len());
+
+    for (chunk_key, index) in chunks {
+        let (data, metadata) = match storage.download_line(&chunk_key).await {
+            Ok(result) => result,
+            Err(StorageError::NotFound) => {
+                tracing::warn!("Chunk {} of file {} missing from storage", index, file_id);
+                chunks_json.push(serde_json::json!({ "index": index, "missing": true }));
+                continue;
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let path = format!("files/{}/{}.chunk", file_id, index);
+        sink.write_archive_bytes(zip.start_entry(&path)).await?;
+        zip.record_data(&data);
+        let size = data.len();
+        sink.write_archive_bytes(data).await?;
+        sink.write_archive_bytes(zip.finish_entry()).await?;
+
+        chunks_json.push(serde_json::json!({
+            "index": index,
+            "path": path,
+            "iv": metadata.iv,
+            "size": size,
+            "sha256": metadata.data_hash,
+        }));
+    }
+
+    Ok(chunks_json)
+}
@@ -3,16 +3,31 @@
 
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::State,
     http::{HeaderMap, StatusCode},
 };
 use rand::distr::{Alphanumeric, SampleString};
 use serde::{Deserialize, Serialize};
-
-use crate::{response::ApiResponse, state::AppState};
+use uuid::Uuid;
+
+use crate::{
+    activity::{self, ActivityAction, ActivityEntry},
+    contacts,
+    notifications::services::{self as notifications, NotificationEvent},
+    response::ApiResponse,
+    state::AppState,
+};
 
 use super::{repo, services};
 
+/// Adresse IP du client transmise par le reverse proxy
+fn client_ip(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("X-Real-IP")
+        .or_else(|| headers.get("X-Forwarded-For"))
+        .and_then(|h| h.to_str().ok())
+}
+
 // ========== Validation ==========
 
 fn validate_password(password: &str) -> Result<(), &'static str> {
@@ -116,6 +131,33 @@ pub struct RegisterRequest {
     pub iv: String,
     pub encrypted_record_key: String,
     pub temp_token: String, // Token temporaire obtenu après vérification OTP
+    /// Signature de la déclaration de la première clé (voir POST /account/public-key)
+    #[serde(default)]
+    pub key_signature: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+    pub confirmation: String, // L'utilisateur doit retaper son email
+}
+
+#[derive(Serialize)]
+pub struct DeleteAccountResponse {
+    pub deletion_scheduled_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePublicKeyRequest {
+    pub password: String,
+    /// Version de la clé remplacée (409 si elle a changé entre-temps)
+    pub expected_version: i32,
+    pub public_key: String,
+    pub encrypted_private_key: String,
+    pub private_key_salt: String,
+    pub iv: String,
+    /// Signature de la déclaration de la nouvelle clé par la clé actuelle
+    pub signature: String,
 }
 
 // ========== Handlers ==========
@@ -123,6 +165,7 @@ pub struct RegisterRequest {
 /// POST /login - Authentifie un utilisateur
 pub async fn login_handler(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
 ) -> Result<ApiResponse<LoginResponse>, (StatusCode, String)> {
     let mut redis = state.redis_manager.clone();
@@ -165,6 +208,11 @@ pub async fn login_handler(
             })?;
 
         crate::metrics::track_auth_attempt("login", false);
+        activity::services::record(
+            &state.db_pool,
+            ActivityEntry::new(user.id, ActivityAction::LoginFailed).from_ip(client_ip(&headers)),
+        )
+        .await;
         return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
     }
 
@@ -178,9 +226,15 @@ pub async fn login_handler(
             )
         })?;
 
-    // 3. Créer un JWT
+    // 3. Refuser les comptes suspendus (après le mot de passe : ne révèle rien sans lui)
+    if !user.is_active {
+        crate::metrics::track_auth_attempt("login", false);
+        return Err((StatusCode::FORBIDDEN, "Account is suspended".to_string()));
+    }
+
+    // 4. Créer un JWT avec le rôle stocké en base
     let token =
-        services::create_jwt(user.id, "user", state.jwt_secret.as_bytes()).map_err(|e| {
+        services::create_jwt(user.id, &user.role, state.jwt_secret.as_bytes()).map_err(|e| {
             tracing::error!("Failed to create JWT during login: {}", e);
             crate::metrics::track_auth_attempt("login", false);
             (
@@ -190,6 +244,11 @@ pub async fn login_handler(
         })?;
 
     crate::metrics::track_auth_attempt("login", true);
+    activity::services::record(
+        &state.db_pool,
+        ActivityEntry::new(user.id, ActivityAction::Login).from_ip(client_ip(&headers)),
+    )
+    .await;
 
     Ok(ApiResponse::ok(LoginResponse {
         token: token.clone(),
@@ -219,6 +278,12 @@ pub async fn logout_handler(
             )
         })?;
 
+    activity::services::record(
+        &state.db_pool,
+        ActivityEntry::new(claims.id, ActivityAction::Logout),
+    )
+    .await;
+
     Ok(ApiResponse::ok("Logged out successfully".to_string()))
 }
 
@@ -269,22 +334,6 @@ pub async fn info_handler(
     Ok(ApiResponse::ok(user_info))
 }
 
-/// GET /contacts/get_public_key/:email - Récupère la clé publique d'un utilisateur (authentifié)
-pub async fn get_public_key_handler(
-    State(state): State<AppState>,
-    _claims: services::Claims,
-    Path(email): Path<String>,
-) -> Result<ApiResponse<serde_json::Value>, (StatusCode, String)> {
-    let (user_id, public_key) = repo::get_public_key_by_email(&state.db_pool, &email)
-        .await
-        .map_err(|_| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
-
-    Ok(ApiResponse::ok(serde_json::json!({
-        "user_id": user_id,
-        "public_key": public_key
-    })))
-}
-
 pub async fn send_otp_handler(
     State(state): State<AppState>,
     Json(payload): Json<services::SendOtpRequest>,
@@ -503,11 +552,7 @@ pub async fn finalize_registration_handler(
     }
 
     // 0. Rate limit par IP avant tout traitement coûteux (Argon2)
-    let ip = headers
-        .get("X-Real-IP")
-        .or_else(|| headers.get("X-Forwarded-For"))
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("unknown");
+    let ip = client_ip(&headers).unwrap_or("unknown");
 
     let mut redis = state.redis_manager.clone();
     if services::is_register_rate_limited(&mut redis, ip)
@@ -587,6 +632,11 @@ pub async fn finalize_registration_handler(
     if let Err(msg) = validate_password(&payload.password) {
         return Err((StatusCode::BAD_REQUEST, msg.to_string()));
     }
+    if let Some(signature) = &payload.key_signature
+        && let Err(msg) = services::validate_key_signature(signature)
+    {
+        return Err((StatusCode::BAD_REQUEST, msg.to_string()));
+    }
 
     // 1. Hash le mot de passe avec Argon2
     let password_hash = services::hash_password(&payload.password).map_err(|e| {
@@ -620,6 +670,7 @@ pub async fn finalize_registration_handler(
         iv: payload.iv,
         auth_salt,
         encrypted_record_key: payload.encrypted_record_key,
+        key_signature: payload.key_signature,
     };
 
     let user_id = repo::create_user(&state.db_pool, new_user)
@@ -706,3 +757,243 @@ pub async fn finalize_registration_handler(
         public_key,
     }))
 }
+
+/// Revérifie le mot de passe avant une opération sensible, avec la même protection
+/// anti-brute-force que le login
+async fn check_account_password(
+    state: &AppState,
+    user_id: Uuid,
+    email: &str,
+    password: &str,
+) -> Result<(), (StatusCode, String)> {
+    let mut redis = state.redis_manager.clone();
+    if services::is_rate_limited(&mut redis, email)
+        .await
+        .map_err(|e| {
+            tracing::error!("Redis error during rate limit check: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error".to_string(),
+            )
+        })?
+    {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many failed attempts. Please try again later.".to_string(),
+        ));
+    }
+
+    let user = repo::get_user_credentials_by_id(&state.db_pool, user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to fetch user credentials: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string(),
+            )
+        })?;
+
+    let salt = user.auth_salt.as_deref().unwrap_or("");
+    if !services::verify_password(password, &user.password_hash, salt) {
+        services::increment_failed_login(&mut redis, email)
+            .await
+            .map_err(|e| {
+                tracing::error!("Redis error during incrementing failed login: {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal error".to_string(),
+                )
+            })?;
+
+        return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+    }
+
+    Ok(())
+}
+
+/// POST /account/delete - Programme la suppression du compte (ré-authentification requise)
+/// Le compte et toutes ses données sont purgés à l'issue du délai de grâce.
+pub async fn request_account_deletion_handler(
+    State(state): State<AppState>,
+    claims: services::Claims,
+    Json(payload): Json<DeleteAccountRequest>,
+) -> Result<ApiResponse<DeleteAccountResponse>, (StatusCode, String)> {
+    let user_info = repo::get_user_by_id(&state.db_pool, claims.id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => (
+                StatusCode::UNAUTHORIZED,
+                "Session invalide, veuillez vous reconnecter".to_string(),
+            ),
+            e => {
+                tracing::error!("Failed to fetch user info: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                )
+            }
+        })?;
+
+    if !payload
+        .confirmation
+        .trim()
+        .eq_ignore_ascii_case(&user_info.email)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Confirmation does not match account email".to_string(),
+        ));
+    }
+
+    check_account_password(&state, claims.id, &user_info.email, &payload.password).await?;
+
+    let deletion_scheduled_at = repo::schedule_account_deletion(
+        &state.db_pool,
+        claims.id,
+        services::account_deletion_grace_days(),
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to schedule account deletion: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error".to_string(),
+        )
+    })?;
+
+    tracing::info!(
+        "Account deletion scheduled for user {} at {}",
+        claims.id,
+        deletion_scheduled_at
+    );
+
+    Ok(ApiResponse::ok(DeleteAccountResponse {
+        deletion_scheduled_at,
+    }))
+}
+
+/// POST /account/delete/cancel - Annule une suppression de compte programmée
+pub async fn cancel_account_deletion_handler(
+    State(state): State<AppState>,
+    claims: services::Claims,
+) -> Result<ApiResponse<String>, (StatusCode, String)> {
+    match repo::cancel_account_deletion(&state.db_pool, claims.id).await {
+        Ok(()) => Ok(ApiResponse::ok("Account deletion cancelled".to_string())),
+        Err(sqlx::Error::RowNotFound) => Err((
+            StatusCode::NOT_FOUND,
+            "No pending account deletion".to_string(),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to cancel account deletion: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string(),
+            ))
+        }
+    }
+}
+
+// ========== Clés publiques ==========
+
+/// GET /account/public-key - Historique de ses propres clés (avec les clés privées chiffrées
+/// des versions précédentes, pour relire les éléments partagés avant un changement de clé)
+pub async fn get_own_key_history_handler(
+    State(state): State<AppState>,
+    claims: services::Claims,
+) -> Result<ApiResponse<Vec<repo::OwnKeyVersion>>, (StatusCode, String)> {
+    let history = repo::get_own_key_history(&state.db_pool, claims.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to fetch key history: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string(),
+            )
+        })?;
+
+    Ok(ApiResponse::ok(history))
+}
+
+/// POST /account/public-key - Remplace la paire de clés (ré-authentification requise).
+/// La nouvelle version est ajoutée à l'historique et les contacts sont prévenus.
+pub async fn change_public_key_handler(
+    State(state): State<AppState>,
+    claims: services::Claims,
+    Json(payload): Json<ChangePublicKeyRequest>,
+) -> Result<ApiResponse<repo::KeyStatementRecord>, (StatusCode, String)> {
+    if payload.public_key.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            services::PUBLIC_KEY_REQUIRED.to_string(),
+        ));
+    }
+    services::validate_key_signature(&payload.signature)
+        .map_err(|msg| (StatusCode::BAD_REQUEST, msg.to_string()))?;
+
+    let user_info = repo::get_user_by_id(&state.db_pool, claims.id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => (
+                StatusCode::UNAUTHORIZED,
+                "Session invalide, veuillez vous reconnecter".to_string(),
+            ),
+            e => {
+                tracing::error!("Failed to fetch user info: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                )
+            }
+        })?;
+
+    check_account_password(&state, claims.id, &user_info.email, &payload.password).await?;
+
+    let new_key = repo::NewPublicKey {
+        public_key: payload.public_key,
+        encrypted_private_key: payload.encrypted_private_key,
+        private_key_salt: payload.private_key_salt,
+        iv: payload.iv,
+        signature: payload.signature.trim().to_string(),
+    };
+    let record =
+        match repo::change_public_key(&state.db_pool, claims.id, payload.expected_version, new_key)
+            .await
+        {
+            Ok(record) => record,
+            Err(sqlx::Error::Protocol(msg)) => return Err((StatusCode::CONFLICT, msg)),
+            Err(e) => {
+                tracing::error!("Failed to change public key: {}", e);
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                ));
+            }
+        };
+
+    activity::services::record(
+        &state.db_pool,
+        ActivityEntry::new(claims.id, ActivityAction::PublicKeyChanged),
+    )
+    .await;
+
+    // Best effort : les contacts comparent ensuite l'empreinte à celle qu'ils ont vérifiée
+    match contacts::repo::get_contact_ids(&state.db_pool, claims.id).await {
+        Ok(contact_ids) => {
+            let event = NotificationEvent::ContactKeyChanged {
+                user_id: claims.id,
+                key_version: record.version,
+                fingerprint: record.fingerprint.clone(),
+            };
+            notifications::publish(&state.redis_manager, &contact_ids, &event).await;
+        }
+        Err(e) => tracing::error!("Failed to list contacts for key change alert: {}", e),
+    }
+
+    tracing::info!(
+        "Public key of user {} changed to version {}",
+        claims.id,
+        record.version
+    );
+
+    Ok(ApiResponse::ok(record))
+}
@@ -0,0 +1,71 @@
+// Tâches de fond du module auth
+// Lancées périodiquement depuis main.rs
+
+use crate::state::AppState;
+
+use super::{repo, services};
+
+/// Nombre maximum de comptes purgés par passage
+const ACCOUNT_PURGE_BATCH_SIZE: i64 = 20;
+
+/// Purge définitivement les comptes dont le délai de grâce est écoulé.
+/// Ordre : révocation des sessions, drive (S3 + DB), archives d'export, agenda, équipes, puis la ligne `users`.
+/// En cas d'échec, le compte reste programmé et sera retenté au prochain passage.
+pub async fn purge_scheduled_accounts(state: &AppState) {
+    let user_ids =
+        match repo::get_accounts_due_for_deletion(&state.db_pool, ACCOUNT_PURGE_BATCH_SIZE).await {
+            Ok(ids) => ids,
+            Err(e) => {
+                tracing::error!("Failed to list accounts due for deletion: {}", e);
+                return;
+            }
+        };
+
+    for user_id in user_ids {
+        let mut redis_conn = state.redis_manager.clone();
+        if let Err(e) = services::revoke_user_sessions(&mut redis_conn, user_id).await {
+            tracing::error!("Failed to revoke sessions of user {}: {}", user_id, e);
+            continue;
+        }
+
+        // La purge du drive rend la suppression définitive : une annulation arrivée après le
+        // listage l'interrompt avant toute suppression
+        match crate::drive::repo::purge_user_drive(&state.db_pool, &state.storage_client, user_id)
+            .await
+        {
+            Ok(()) => {}
+            Err(sqlx::Error::RowNotFound) => {
+                tracing::info!(
+                    "Deletion of account {} was cancelled, skipping purge",
+                    user_id
+                );
+                continue;
+            }
+            Err(e) => {
+                tracing::error!("Failed to purge drive of user {}: {}", user_id, e);
+                continue;
+            }
+        }
+
+        if let Err(e) = crate::export::jobs::delete_user_exports(state, user_id).await {
+            tracing::error!("Failed to purge data exports of user {}: {}", user_id, e);
+            continue;
+        }
+
+        if let Err(e) = crate::agenda::repo::delete_user_agenda_data(&state.db_pool, user_id).await
+        {
+            tracing::error!("Failed to purge agenda of user {}: {}", user_id, e);
+            continue;
+        }
+
+        if let Err(e) = crate::teams::repo::remove_user_from_teams(&state.db_pool, user_id).await {
+            tracing::error!("Failed to remove user {} from teams: {}", user_id, e);
+            continue;
+        }
+
+        match repo::delete_user(&state.db_pool, user_id).await {
+            Ok(()) => tracing::info!("Account {} permanently deleted", user_id),
+            Err(e) => tracing::error!("Failed to delete user {}: {}", user_id, e),
+        }
+    }
+}
@@ -1,10 +1,11 @@
 // Module auth - Gestion de l'authentification et des utilisateurs
 
 pub mod handlers;
+pub mod jobs;
 pub mod repo;
 pub mod routes;
 pub mod services;
 
 // Re-exports pour faciliter l'usage depuis d'autres modules
 pub use routes::auth_routes;
-pub use services::Claims;
+pub use services::{AdminClaims, Claims};
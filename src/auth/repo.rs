@@ -1,10 +1,13 @@
 // Repository - Accès aux données utilisateurs (queries SQL)
 // Toutes les interactions avec la table `users`
 
+use chrono::{DateTime, Utc};
 use serde::Serialize;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 
+use super::services;
+
 // ========== Types de données ==========
 
 #[derive(sqlx::FromRow, Debug)]
@@ -17,6 +20,8 @@ pub struct User {
     pub private_key_salt: String,
     pub iv: String,
     pub public_key: String,
+    pub role: String,
+    pub is_active: bool,
 }
 
 #[derive(Debug)]
@@ -31,6 +36,8 @@ pub struct NewUser {
     pub iv: String,
     pub auth_salt: Option<String>,
     pub encrypted_record_key: String,
+    /// Signature de la déclaration de la première clé (par la clé elle-même)
+    pub key_signature: Option<String>,
 }
 
 #[derive(Serialize, Debug, sqlx::FromRow)]
@@ -42,35 +49,97 @@ pub struct UserInfo {
     pub private_key_salt: String,
     pub iv: String,
     pub public_key: String,
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
+}
+
+/// Version publique de l'historique des clés (table `public_key_history`)
+#[derive(Serialize, Debug, sqlx::FromRow)]
+pub struct KeyStatementRecord {
+    pub version: i32,
+    pub public_key: String,
+    pub fingerprint: String,
+    pub statement: String,
+    pub signature: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Version de clé vue par son propriétaire : inclut la clé privée chiffrée par le mot de passe
+#[derive(Serialize, Debug, sqlx::FromRow)]
+pub struct OwnKeyVersion {
+    pub version: i32,
+    pub public_key: String,
+    pub fingerprint: String,
+    pub statement: String,
+    pub signature: Option<String>,
+    pub encrypted_private_key: String,
+    pub private_key_salt: Option<String>,
+    pub iv: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Nouvelle paire de clés envoyée par le client lors d'un changement de clé
+#[derive(Debug)]
+pub struct NewPublicKey {
+    pub public_key: String,
+    pub encrypted_private_key: String,
+    pub private_key_salt: String,
+    pub iv: String,
+    /// Signature de la déclaration par la clé précédente
+    pub signature: String,
 }
 
 // ========== Queries ==========
 
-/// Crée un nouvel utilisateur
+/// Crée un nouvel utilisateur et la première entrée de son historique de clés
 pub async fn create_user(pool: &PgPool, new_user: NewUser) -> Result<Uuid, sqlx::Error> {
-    let user_id = sqlx::query_scalar::<_, Uuid>(
+    let user_id = Uuid::new_v4();
+    let fingerprint = services::public_key_fingerprint(&new_user.public_key);
+    let statement = services::key_statement(user_id, 1, &fingerprint, None);
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
         r#"
         INSERT INTO users (
             id, username, password_hash, encrypted_private_key, public_key,
             email, encrypted_settings, private_key_salt, iv, auth_salt, encrypted_record_key, account_tier_id
         ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, (SELECT id FROM account_tiers WHERE name = 'free'))
-        RETURNING id
         "#,
     )
-    .bind(Uuid::new_v4())
+    .bind(user_id)
     .bind(new_user.username)
     .bind(new_user.password_hash)
-    .bind(new_user.encrypted_private_key)
-    .bind(new_user.public_key)
+    .bind(&new_user.encrypted_private_key)
+    .bind(&new_user.public_key)
     .bind(new_user.email)
     .bind(new_user.encrypted_settings)
-    .bind(new_user.private_key_salt)
-    .bind(new_user.iv)
+    .bind(&new_user.private_key_salt)
+    .bind(&new_user.iv)
     .bind(new_user.auth_salt)
     .bind(new_user.encrypted_record_key)
-    .fetch_one(pool)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO public_key_history (
+            user_id, version, public_key, fingerprint, statement, signature,
+            encrypted_private_key, private_key_salt, iv
+        ) VALUES ($1, 1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(user_id)
+    .bind(new_user.public_key)
+    .bind(fingerprint)
+    .bind(statement)
+    .bind(new_user.key_signature)
+    .bind(new_user.encrypted_private_key)
+    .bind(new_user.private_key_salt)
+    .bind(new_user.iv)
+    .execute(&mut *tx)
     .await?;
 
+    tx.commit().await?;
     Ok(user_id)
 }
 
@@ -79,7 +148,7 @@ pub async fn get_user_by_email(pool: &PgPool, email: &str) -> Result<User, sqlx:
     sqlx::query_as::<_, User>(
         r#"
         SELECT id, username, password_hash, auth_salt, encrypted_private_key,
-               private_key_salt, iv, public_key
+               private_key_salt, iv, public_key, role, is_active
         FROM users
         WHERE email = $1
         "#,
@@ -94,7 +163,7 @@ pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<UserInfo, sq
     sqlx::query_as::<_, UserInfo>(
         r#"
         SELECT id, username, email, encrypted_private_key,
-               private_key_salt, iv, public_key
+               private_key_salt, iv, public_key, deletion_scheduled_at
         FROM users
         WHERE id = $1
         "#,
@@ -104,34 +173,256 @@ pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<UserInfo, sq
     .await
 }
 
-/// Récupère la clé publique d'un utilisateur par son email (pour le partage)
-pub async fn get_public_key_by_email(
-    pool: &PgPool,
-    email: &str,
-) -> Result<(Uuid, String), sqlx::Error> {
-    sqlx::query_as::<_, (Uuid, String)>(
+pub async fn check_email_exists(pool: &PgPool, email: &str) -> Result<bool, sqlx::Error> {
+    let count: (i64,) = sqlx::query_as(
         r#"
-        SELECT id, public_key
+        SELECT COUNT(*)
         FROM users
         WHERE email = $1
         "#,
     )
     .bind(email)
     .fetch_one(pool)
+    .await?;
+
+    Ok(count.0 > 0)
+}
+
+/// Récupère un utilisateur (avec son hash de mot de passe) par son ID, pour ré-authentification
+pub async fn get_user_credentials_by_id(pool: &PgPool, user_id: Uuid) -> Result<User, sqlx::Error> {
+    sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, username, password_hash, auth_salt, encrypted_private_key,
+               private_key_salt, iv, public_key, role, is_active
+        FROM users
+        WHERE id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
     .await
 }
 
-pub async fn check_email_exists(pool: &PgPool, email: &str) -> Result<bool, sqlx::Error> {
-    let count: (i64,) = sqlx::query_as(
+/// Récupère le rôle et l'état d'activation d'un utilisateur
+pub async fn get_user_role(pool: &PgPool, user_id: Uuid) -> Result<(String, bool), sqlx::Error> {
+    sqlx::query_as::<_, (String, bool)>(
         r#"
-        SELECT COUNT(*)
+        SELECT role, is_active
         FROM users
-        WHERE email = $1
+        WHERE id = $1
         "#,
     )
-    .bind(email)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+// ========== Suppression de compte ==========
+
+/// Programme la suppression définitive du compte après `grace_days` jours.
+/// Une demande déjà en cours n'est pas repoussée.
+pub async fn schedule_account_deletion(
+    pool: &PgPool,
+    user_id: Uuid,
+    grace_days: i64,
+) -> Result<DateTime<Utc>, sqlx::Error> {
+    sqlx::query_scalar::<_, DateTime<Utc>>(
+        r#"
+        UPDATE users
+        SET deletion_requested_at = COALESCE(deletion_requested_at, NOW()),
+            deletion_scheduled_at = COALESCE(deletion_scheduled_at, NOW() + make_interval(days => $2::INT))
+        WHERE id = $1
+        RETURNING deletion_scheduled_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(grace_days as i32)
     .fetch_one(pool)
+    .await
+}
+
+/// Annule une suppression programmée, tant que le délai de grâce n'est pas écoulé.
+/// Retourne RowNotFound si aucune suppression n'est annulable.
+pub async fn cancel_account_deletion(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET deletion_requested_at = NULL, deletion_scheduled_at = NULL
+        WHERE id = $1 AND deletion_scheduled_at > NOW() AND purge_started_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .execute(pool)
     .await?;
 
-    Ok(count.0 > 0)
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Marque le début de la purge d'un compte dont le délai de grâce est écoulé, dans la
+/// transaction appelante. La ligne `users` reste verrouillée jusqu'à la fin de cette transaction :
+/// une annulation concurrente attend, puis échoue sur `purge_started_at`.
+/// Retourne false si la suppression n'est plus programmée.
+pub async fn claim_account_purge(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let claimed = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        UPDATE users
+        SET purge_started_at = COALESCE(purge_started_at, NOW())
+        WHERE id = $1 AND deletion_scheduled_at IS NOT NULL AND deletion_scheduled_at <= NOW()
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(claimed.is_some())
+}
+
+/// Liste les comptes dont le délai de grâce est écoulé
+pub async fn get_accounts_due_for_deletion(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT id
+        FROM users
+        WHERE deletion_scheduled_at IS NOT NULL AND deletion_scheduled_at <= NOW()
+        ORDER BY deletion_scheduled_at
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Supprime définitivement la ligne `users` (les données drive/agenda doivent déjà être purgées)
+pub async fn delete_user(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        DELETE FROM users
+        WHERE id = $1 AND deletion_scheduled_at IS NOT NULL AND deletion_scheduled_at <= NOW()
+        "#,
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// ========== Historique des clés publiques ==========
+
+/// Historique public des clés d'un utilisateur, de la plus ancienne à la plus récente
+pub async fn get_public_key_history(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<KeyStatementRecord>, sqlx::Error> {
+    sqlx::query_as::<_, KeyStatementRecord>(
+        r#"
+        SELECT version, public_key, fingerprint, statement, signature, created_at
+        FROM public_key_history
+        WHERE user_id = $1
+        ORDER BY version
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Historique complet de ses propres clés (clés privées chiffrées des versions précédentes)
+pub async fn get_own_key_history(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<OwnKeyVersion>, sqlx::Error> {
+    sqlx::query_as::<_, OwnKeyVersion>(
+        r#"
+        SELECT version, public_key, fingerprint, statement, signature,
+               encrypted_private_key, private_key_salt, iv, created_at
+        FROM public_key_history
+        WHERE user_id = $1
+        ORDER BY version
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Remplace la paire de clés de l'utilisateur et ajoute la version à l'historique.
+/// `expected_version` doit être la version courante (Protocol(PUBLIC_KEY_VERSION_CONFLICT) sinon).
+pub async fn change_public_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    expected_version: i32,
+    new_key: NewPublicKey,
+) -> Result<KeyStatementRecord, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let (current_version, current_key): (i32, String) =
+        sqlx::query_as("SELECT public_key_version, public_key FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    if current_version != expected_version {
+        return Err(sqlx::Error::Protocol(
+            services::PUBLIC_KEY_VERSION_CONFLICT.into(),
+        ));
+    }
+
+    let version = current_version + 1;
+    let previous_fingerprint = services::public_key_fingerprint(&current_key);
+    let fingerprint = services::public_key_fingerprint(&new_key.public_key);
+    let statement =
+        services::key_statement(user_id, version, &fingerprint, Some(&previous_fingerprint));
+
+    sqlx::query(
+        r#"
+        UPDATE users
+        SET public_key = $2, encrypted_private_key = $3, private_key_salt = $4, iv = $5,
+            public_key_version = $6
+        WHERE id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(&new_key.public_key)
+    .bind(&new_key.encrypted_private_key)
+    .bind(&new_key.private_key_salt)
+    .bind(&new_key.iv)
+    .bind(version)
+    .execute(&mut *tx)
+    .await?;
+
+    let record = sqlx::query_as::<_, KeyStatementRecord>(
+        r#"
+        INSERT INTO public_key_history (
+            user_id, version, public_key, fingerprint, statement, signature,
+            encrypted_private_key, private_key_salt, iv
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING version, public_key, fingerprint, statement, signature, created_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(version)
+    .bind(new_key.public_key)
+    .bind(fingerprint)
+    .bind(statement)
+    .bind(new_key.signature)
+    .bind(new_key.encrypted_private_key)
+    .bind(new_key.private_key_salt)
+    .bind(new_key.iv)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(record)
 }
@@ -19,15 +19,23 @@ pub fn auth_routes() -> Router<AppState> {
         .route("/logout", post(handlers::logout_handler))
         .route("/autologin", get(handlers::auto_login_handler))
         .route("/info", get(handlers::info_handler))
-        .route(
-            "/contacts/get_public_key/{email}",
-            get(handlers::get_public_key_handler),
-        )
         .route("/register/send-otp", post(handlers::send_otp_handler))
         .route("/register/verify-otp", post(handlers::verify_otp_handler))
         .route(
             "/register/finalize",
             post(handlers::finalize_registration_handler),
         )
+        .route(
+            "/account/delete",
+            post(handlers::request_account_deletion_handler),
+        )
+        .route(
+            "/account/delete/cancel",
+            post(handlers::cancel_account_deletion_handler),
+        )
+        .route(
+            "/account/public-key",
+            get(handlers::get_own_key_history_handler).post(handlers::change_public_key_handler),
+        )
     // .route("/recovery", post(handlers::recovery_handler))
 }
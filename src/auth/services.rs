@@ -16,13 +16,12 @@ use chrono::{Duration, Utc};
 use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Validation, decode, encode};
 use rand::RngCore;
 use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 use crate::state::AppState;
 
-use lettre::message::Mailbox;
-use lettre::message::{MultiPart, SinglePart, header::ContentType};
-use lettre::{Message, SmtpTransport, Transport};
+use lettre::SmtpTransport;
 
 // ========== Erreurs ==========
 
@@ -42,7 +41,24 @@ pub struct Claims {
     pub role: String,
     pub exp: usize,
     pub jti: String, // JWT ID pour la révocation
+    #[serde(default)]
+    pub iat: usize, // Date d'émission, pour la révocation de toutes les sessions d'un utilisateur
+    #[serde(default)]
+    pub iat_ms: i64, // Date d'émission en millisecondes (0 pour les tokens émis avant ce champ)
 }
+/// Rôle donnant accès à l'API d'administration
+pub const ADMIN_ROLE: &str = "admin";
+
+impl Claims {
+    pub fn is_admin(&self) -> bool {
+        self.role == ADMIN_ROLE
+    }
+}
+
+/// Claims d'un administrateur : extracteur qui rejette (403) les autres utilisateurs
+#[derive(Debug, Clone)]
+pub struct AdminClaims(pub Claims);
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
 
 pub struct TempClaims {
@@ -68,7 +84,8 @@ pub fn create_jwt(
     role: &str,
     secret: &[u8],
 ) -> Result<String, jsonwebtoken::errors::Error> {
-    let expiration = Utc::now()
+    let now = Utc::now();
+    let expiration = now
         .checked_add_signed(Duration::days(10))
         .expect("valid timestamp")
         .timestamp() as usize;
@@ -78,6 +95,8 @@ pub fn create_jwt(
         role: role.to_string(),
         exp: expiration,
         jti: Uuid::new_v4().to_string(),
+        iat: now.timestamp() as usize,
+        iat_ms: now.timestamp_millis(),
     };
 
     let key = EncodingKey::from_secret(secret);
@@ -146,6 +165,281 @@ pub async fn blacklist_token(
     result
 }
 
+// ========== Révocation de toutes les sessions d'un utilisateur ==========
+
+/// Durée de vie maximale d'un JWT : au-delà, le marqueur de révocation est inutile
+const SESSION_REVOCATION_TTL_SECONDS: u64 = 10 * 24 * 3600;
+
+fn sessions_revoked_key(user_id: Uuid) -> String {
+    format!("sessions_revoked:{user_id}")
+}
+
+/// Marqueurs de révocation écrits avant le passage aux millisecondes : valeurs en secondes
+const LEGACY_REVOCATION_SECONDS_MAX: i64 = 100_000_000_000;
+
+/// Révoque tous les JWT émis pour un utilisateur jusqu'à maintenant, à la milliseconde près
+/// (suppression de compte, déconnexion forcée...)
+pub async fn revoke_user_sessions(
+    manager: &mut redis::aio::ConnectionManager,
+    user_id: Uuid,
+) -> Result<(), redis::RedisError> {
+    let result = manager
+        .set_ex(
+            sessions_revoked_key(user_id),
+            Utc::now().timestamp_millis(),
+            SESSION_REVOCATION_TTL_SECONDS,
+        )
+        .await;
+
+    crate::metrics::track_redis_operation("set", result.is_ok());
+    result
+}
+
+/// Vérifie si le token a été émis avant une révocation globale des sessions.
+/// FAIL-CLOSED: si Redis est indisponible, on bloque l'accès par sécurité.
+async fn is_session_revoked(
+    manager: &mut redis::aio::ConnectionManager,
+    claims: &Claims,
+) -> Result<bool, AuthError> {
+    let revoked_at: Option<i64> =
+        manager
+            .get(sessions_revoked_key(claims.id))
+            .await
+            .map_err(|e| {
+                tracing::error!("Redis query failed (fail-closed): {}", e);
+                crate::metrics::track_redis_operation("get", false);
+                AuthError(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Authentication service temporarily unavailable".into(),
+                )
+            })?;
+
+    crate::metrics::track_redis_operation("get", true);
+    Ok(revoked_at.is_some_and(|ts| issued_before_revocation(claims, ts)))
+}
+
+/// Le token a-t-il été émis avant (ou à la milliseconde de) la révocation `revoked_at` ?
+/// Un login qui suit la révocation dans la même seconde reste valide. Les tokens sans `iat_ms` et
+/// les marqueurs en secondes (écrits avant le passage aux millisecondes) gardent la comparaison
+/// à la seconde.
+pub fn issued_before_revocation(claims: &Claims, revoked_at: i64) -> bool {
+    let revoked_at_ms = if revoked_at < LEGACY_REVOCATION_SECONDS_MAX {
+        revoked_at * 1000 + 999
+    } else {
+        revoked_at
+    };
+    let issued_at_ms = if claims.iat_ms > 0 {
+        claims.iat_ms
+    } else {
+        claims.iat as i64 * 1000
+    };
+    issued_at_ms <= revoked_at_ms
+}
+
+// ========== Statut du compte (suspension) ==========
+
+/// Durée de cache du statut d'un compte. Le cache est réécrit à chaque suspension/réactivation :
+/// le TTL ne sert que de filet de sécurité.
+const USER_STATUS_CACHE_TTL_SECONDS: u64 = 5 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserStatus {
+    Active,
+    Suspended,
+}
+
+impl UserStatus {
+    pub fn from_is_active(is_active: bool) -> Self {
+        if is_active {
+            UserStatus::Active
+        } else {
+            UserStatus::Suspended
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "active" => Some(UserStatus::Active),
+            "suspended" => Some(UserStatus::Suspended),
+            _ => None,
+        }
+    }
+}
+
+fn user_status_key(user_id: Uuid) -> String {
+    format!("user_status:{user_id}")
+}
+
+/// Met en cache le statut d'un compte (à appeler après chaque changement en base)
+pub async fn cache_user_status(
+    manager: &mut redis::aio::ConnectionManager,
+    user_id: Uuid,
+    status: UserStatus,
+) -> Result<(), redis::RedisError> {
+    let result = manager
+        .set_ex(
+            user_status_key(user_id),
+            status.as_str(),
+            USER_STATUS_CACHE_TTL_SECONDS,
+        )
+        .await;
+
+    crate::metrics::track_redis_operation("set", result.is_ok());
+    result
+}
+
+/// Statut du compte : lu dans Redis, et depuis Postgres (puis mis en cache) en cas d'absence.
+/// FAIL-CLOSED: si Redis est indisponible, on bloque l'accès par sécurité.
+async fn get_user_status(state: &AppState, user_id: Uuid) -> Result<UserStatus, AuthError> {
+    let mut redis_conn = state.redis_manager.clone();
+    let cached: Option<String> = redis_conn
+        .get(user_status_key(user_id))
+        .await
+        .map_err(|e| {
+            tracing::error!("Redis query failed (fail-closed): {}", e);
+            crate::metrics::track_redis_operation("get", false);
+            AuthError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Authentication service temporarily unavailable".into(),
+            )
+        })?;
+    crate::metrics::track_redis_operation("get", true);
+
+    if let Some(status) = cached.as_deref().and_then(UserStatus::parse) {
+        return Ok(status);
+    }
+
+    let (_, is_active) = super::repo::get_user_role(&state.db_pool, user_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AuthError(StatusCode::UNAUTHORIZED, "Invalid token".into()),
+            e => {
+                tracing::error!("Failed to fetch user status: {}", e);
+                AuthError(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".into(),
+                )
+            }
+        })?;
+
+    let status = UserStatus::from_is_active(is_active);
+    if let Err(e) = cache_user_status(&mut redis_conn, user_id, status).await {
+        tracing::warn!("Failed to cache status of user {}: {}", user_id, e);
+    }
+    Ok(status)
+}
+
+/// Notifie l'utilisateur de la suspension de son compte
+pub fn send_account_suspended_mail(
+    mailer: &SmtpTransport,
+    email: &str,
+    reason: &str,
+) -> Result<(), String> {
+    let plain_body = format!(
+        "Bonjour,\n\nVotre compte Gauzian a été suspendu pour la raison suivante :\n\n{}\n\nVous ne pouvez plus vous connecter tant que la suspension est active. Pour toute question, répondez à cet email.\n\nL'équipe Gauzian",
+        reason
+    );
+
+    let html_body = crate::mail::html_layout(
+        "Votre compte Gauzian a été suspendu",
+        &format!(
+            "<p style=\"margin:0 0 12px 0;font-size:15px;line-height:1.6;\">Bonjour,</p>
+            <p style=\"margin:0 0 20px 0;font-size:15px;line-height:1.6;\">Votre compte Gauzian a été suspendu pour la raison suivante :</p>
+            <p style=\"margin:0 0 20px 0;padding:12px 16px;border-radius:10px;background:#f3f4f6;font-size:15px;line-height:1.6;\">{}</p>
+            <p style=\"margin:0 0 20px 0;font-size:14px;line-height:1.6;color:#4b5563;\">Vous ne pouvez plus vous connecter tant que la suspension est active. Pour toute question, répondez à cet email.</p>",
+            crate::mail::escape_html(reason)
+        ),
+    );
+
+    crate::mail::send_mail(
+        mailer,
+        email,
+        "Votre compte Gauzian a été suspendu",
+        plain_body,
+        html_body,
+    )
+}
+
+/// Notifie l'utilisateur de la réactivation de son compte
+pub fn send_account_reactivated_mail(mailer: &SmtpTransport, email: &str) -> Result<(), String> {
+    let plain_body = "Bonjour,\n\nVotre compte Gauzian a été réactivé : vous pouvez de nouveau vous connecter.\n\nL'équipe Gauzian".to_string();
+
+    let html_body = crate::mail::html_layout(
+        "Votre compte Gauzian a été réactivé",
+        "<p style=\"margin:0 0 12px 0;font-size:15px;line-height:1.6;\">Bonjour,</p>
+            <p style=\"margin:0 0 20px 0;font-size:15px;line-height:1.6;\">Votre compte Gauzian a été réactivé : vous pouvez de nouveau vous connecter.</p>",
+    );
+
+    crate::mail::send_mail(
+        mailer,
+        email,
+        "Votre compte Gauzian a été réactivé",
+        plain_body,
+        html_body,
+    )
+}
+
+// ========== Suppression de compte ==========
+
+/// Délai de grâce avant la purge définitive d'un compte (ACCOUNT_DELETION_GRACE_DAYS, 30 jours par défaut)
+pub fn account_deletion_grace_days() -> i64 {
+    std::env::var("ACCOUNT_DELETION_GRACE_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days >= 0)
+        .unwrap_or(30)
+}
+
+// ========== Transparence des clés publiques ==========
+
+/// En-tête (et version du format) des déclarations de clé signées
+pub const KEY_STATEMENT_HEADER: &str = "gauzian-key-statement/v1";
+
+pub const INVALID_KEY_SIGNATURE: &str = "signature must be a non-empty base64 string";
+pub const PUBLIC_KEY_REQUIRED: &str = "public_key is required";
+pub const PUBLIC_KEY_VERSION_CONFLICT: &str = "Public key was changed concurrently";
+
+/// Empreinte d'une clé publique : SHA-256 (hex minuscule) de la clé telle que stockée,
+/// même format que les empreintes vérifiées par les contacts
+pub fn public_key_fingerprint(public_key: &str) -> String {
+    format!("{:x}", Sha256::digest(public_key.as_bytes()))
+}
+
+/// Déclaration canonique d'une version de clé, signée côté client par la clé précédente
+/// (par la nouvelle clé pour la première version). Le serveur ne détient aucune clé privée :
+/// il ne peut pas produire de déclaration valide pour une clé substituée, les clients
+/// vérifient la chaîne de signatures.
+pub fn key_statement(
+    user_id: Uuid,
+    version: i32,
+    fingerprint: &str,
+    previous_fingerprint: Option<&str>,
+) -> String {
+    format!(
+        "{}\nuser_id:{}\nversion:{}\nfingerprint:{}\nprevious_fingerprint:{}",
+        KEY_STATEMENT_HEADER,
+        user_id,
+        version,
+        fingerprint,
+        previous_fingerprint.unwrap_or("none")
+    )
+}
+
+/// Signature d'une déclaration : base64 non vide (vérifiée cryptographiquement par les clients)
+pub fn validate_key_signature(signature: &str) -> Result<(), &'static str> {
+    match general_purpose::STANDARD.decode(signature.trim()) {
+        Ok(bytes) if !bytes.is_empty() => Ok(()),
+        _ => Err(INVALID_KEY_SIGNATURE),
+    }
+}
+
 // ========== Rate Limiting (Anti-Brute-Force) ==========
 
 const MAX_LOGIN_ATTEMPTS: u32 = 5;
@@ -259,16 +553,90 @@ impl FromRequestParts<AppState> for Claims {
             AuthError(StatusCode::UNAUTHORIZED, "Invalid token".into())
         })?;
 
-        // FAIL-CLOSED: si Redis est down, on refuse l'accès
-        let mut redis_conn = state.redis_manager.clone();
-        if is_token_blacklisted(&mut redis_conn, &claims.jti).await? {
+        validate_session(state, &claims).await?;
+        Ok(claims)
+    }
+}
+
+/// Vérifie qu'un token décodé est toujours valide : non expiré, non révoqué (logout, révocation
+/// de toutes les sessions) et compte non suspendu. Sert aussi aux connexions longues (flux SSE)
+/// pour revérifier la session après l'ouverture.
+pub async fn validate_session(state: &AppState, claims: &Claims) -> Result<(), AuthError> {
+    if (claims.exp as i64) < Utc::now().timestamp() {
+        return Err(AuthError(
+            StatusCode::UNAUTHORIZED,
+            "Token has expired".into(),
+        ));
+    }
+
+    // FAIL-CLOSED: si Redis est down, on refuse l'accès
+    let mut redis_conn = state.redis_manager.clone();
+    if is_token_blacklisted(&mut redis_conn, &claims.jti).await? {
+        return Err(AuthError(
+            StatusCode::UNAUTHORIZED,
+            "Token has been revoked".into(),
+        ));
+    }
+
+    if is_session_revoked(&mut redis_conn, claims).await? {
+        return Err(AuthError(
+            StatusCode::UNAUTHORIZED,
+            "Session has been revoked".into(),
+        ));
+    }
+
+    if get_user_status(state, claims.id).await? == UserStatus::Suspended {
+        return Err(AuthError(
+            StatusCode::FORBIDDEN,
+            "Account is suspended".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+// ========== Extracteur Axum pour AdminClaims ==========
+
+impl FromRequestParts<AppState> for AdminClaims {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+
+        if !claims.is_admin() {
             return Err(AuthError(
-                StatusCode::UNAUTHORIZED,
-                "Token has been revoked".into(),
+                StatusCode::FORBIDDEN,
+                "Admin access required".into(),
             ));
         }
 
-        Ok(claims)
+        // Le rôle du JWT peut dater de 10 jours : on le revérifie en base
+        let (role, is_active) = super::repo::get_user_role(&state.db_pool, claims.id)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => {
+                    AuthError(StatusCode::UNAUTHORIZED, "Invalid token".into())
+                }
+                e => {
+                    tracing::error!("Failed to fetch user role: {}", e);
+                    AuthError(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Internal server error".into(),
+                    )
+                }
+            })?;
+
+        if role != ADMIN_ROLE || !is_active {
+            return Err(AuthError(
+                StatusCode::FORBIDDEN,
+                "Admin access required".into(),
+            ));
+        }
+
+        Ok(AdminClaims(claims))
     }
 }
 
@@ -319,70 +687,26 @@ pub async fn send_otp(email: &str, otp: &str, mailer: &SmtpTransport) -> Result<
         otp
     );
 
-    let html_body = format!(
-                "<!doctype html>
-                <html lang=\"fr\">
-                    <body style=\"margin:0;padding:0;background:#f6f8fb;font-family:Arial,sans-serif;color:#1f2937;\">
-                        <table role=\"presentation\" width=\"100%\" cellspacing=\"0\" cellpadding=\"0\" style=\"padding:24px 12px;\">
-                            <tr>
-                                <td align=\"center\">
-                                    <table role=\"presentation\" width=\"100%\" cellspacing=\"0\" cellpadding=\"0\" style=\"max-width:560px;background:#ffffff;border-radius:12px;border:1px solid #e5e7eb;padding:24px;\">
-                                        <tr>
-                                            <td>
-                                                <h1 style=\"margin:0 0 16px 0;font-size:20px;line-height:1.3;color:#111827;\">Votre code OTP Gauzian</h1>
-                                                <p style=\"margin:0 0 12px 0;font-size:15px;line-height:1.6;\">Bonjour,</p>
-                                                <p style=\"margin:0 0 20px 0;font-size:15px;line-height:1.6;\">Voici votre code OTP pour finaliser votre inscription :</p>
-                                                <p style=\"margin:0 0 20px 0;text-align:center;\">
-                                                    <span style=\"display:inline-block;padding:12px 20px;border-radius:10px;background:#111827;color:#ffffff;font-size:28px;letter-spacing:4px;font-weight:700;\">{}</span>
-                                                </p>
-                                                <p style=\"margin:0 0 20px 0;font-size:14px;line-height:1.6;color:#4b5563;\">Ce code est valide pendant <strong>10 minutes</strong>.</p>
-                                                <p style=\"margin:0;font-size:14px;line-height:1.6;color:#4b5563;\">Merci,<br>L'équipe Gauzian</p>
-                                            </td>
-                                        </tr>
-                                    </table>
-                                </td>
-                            </tr>
-                        </table>
-                    </body>
-                </html>",
-                otp
-        );
-
-    let message = Message::builder()
-        .from(
-            "GAUZIAN <gauzian@pupin.fr>"
-                .parse::<Mailbox>()
-                .map_err(|e| e.to_string())?,
-        )
-        .to(format!("Destinataire <{}>", email)
-            .parse::<Mailbox>()
-            .map_err(|e| e.to_string())?)
-        .subject("Votre code OTP pour Gauzian")
-        .multipart(
-            MultiPart::alternative()
-                .singlepart(
-                    SinglePart::builder()
-                        .header(ContentType::TEXT_PLAIN)
-                        .body(plain_body),
-                )
-                .singlepart(
-                    SinglePart::builder()
-                        .header(ContentType::TEXT_HTML)
-                        .body(html_body),
-                ),
-        )
-        .map_err(|e| e.to_string())?;
+    let html_body = crate::mail::html_layout(
+        "Votre code OTP Gauzian",
+        &format!(
+            "<p style=\"margin:0 0 12px 0;font-size:15px;line-height:1.6;\">Bonjour,</p>
+            <p style=\"margin:0 0 20px 0;font-size:15px;line-height:1.6;\">Voici votre code OTP pour finaliser votre inscription :</p>
+            <p style=\"margin:0 0 20px 0;text-align:center;\">
+                <span style=\"display:inline-block;padding:12px 20px;border-radius:10px;background:#111827;color:#ffffff;font-size:28px;letter-spacing:4px;font-weight:700;\">{}</span>
+            </p>
+            <p style=\"margin:0 0 20px 0;font-size:14px;line-height:1.6;color:#4b5563;\">Ce code est valide pendant <strong>10 minutes</strong>.</p>",
+            otp
+        ),
+    );
 
-    match mailer.send(&message) {
-        Ok(_) => {
-            tracing::info!("Email sent successfully to {}", email);
-            Ok(())
-        }
-        Err(e) => {
-            tracing::error!("Failed to send email to {}: {}", email, e);
-            Err(e.to_string())
-        }
-    }
+    crate::mail::send_mail(
+        mailer,
+        email,
+        "Votre code OTP pour Gauzian",
+        plain_body,
+        html_body,
+    )
 }
 // store dans le redis l'OTP avec une TTL de 10 minutes
 pub async fn store_otp(
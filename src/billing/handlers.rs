@@ -0,0 +1,249 @@
+// Handlers HTTP des offres de stockage (liste, changement d'offre)
+
+use axum::{
+    Json,
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    auth::Claims,
+    drive::{self, handlers::DriveInfoResponse},
+    response::ApiResponse,
+    state::AppState,
+};
+
+use super::{
+    repo::{self, AbandonedTierChange},
+    services::{self, PaymentOutcome, PaymentRequest},
+};
+
+#[derive(Deserialize)]
+pub struct ChangeTierRequest {
+    pub account_tier_id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct TiersResponse {
+    pub tiers: Vec<repo::AccountTier>,
+    pub current_tier_id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct ChangeTierResponse {
+    pub account_tier: repo::AccountTier,
+    pub amount_charged_cents: i64,
+    pub drive_info: DriveInfoResponse,
+}
+
+/// GET /account/tiers - Liste les offres et l'offre actuelle
+pub async fn list_tiers_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    let tiers = match repo::list_tiers(&state.db_pool).await {
+        Ok(tiers) => tiers,
+        Err(e) => {
+            tracing::error!("Failed to list account tiers: {}", e);
+            return ApiResponse::internal_error("Failed to list account tiers").into_response();
+        }
+    };
+
+    match repo::get_user_tier(&state.db_pool, claims.id).await {
+        Ok(current) => ApiResponse::ok(TiersResponse {
+            tiers,
+            current_tier_id: current.id,
+        })
+        .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to fetch account tier of user {}: {}", claims.id, e);
+            ApiResponse::internal_error("Failed to list account tiers").into_response()
+        }
+    }
+}
+
+/// POST /account/tier - Change d'offre (paiement pour un upgrade, immédiat pour un downgrade).
+/// Un downgrade sous l'usage actuel ouvre le délai de grâce avant la lecture seule.
+pub async fn change_tier_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(body): Json<ChangeTierRequest>,
+) -> Response {
+    let current = match repo::get_user_tier(&state.db_pool, claims.id).await {
+        Ok(tier) => tier,
+        Err(e) => {
+            tracing::error!("Failed to fetch account tier of user {}: {}", claims.id, e);
+            return ApiResponse::internal_error("Failed to change account tier").into_response();
+        }
+    };
+
+    let target = match repo::get_tier(&state.db_pool, body.account_tier_id).await {
+        Ok(tier) => tier,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::not_found("Account tier not found").into_response();
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch account tier: {}", e);
+            return ApiResponse::internal_error("Failed to change account tier").into_response();
+        }
+    };
+
+    if current.id == target.id {
+        return ApiResponse::bad_request("Already on this account tier").into_response();
+    }
+
+    let amount_cents = services::tier_change_amount(&current, &target);
+
+    // Réservé avant le paiement : une requête concurrente est refusée sans être facturée
+    let change_id = match repo::begin_tier_change(
+        &state.db_pool,
+        claims.id,
+        current.id,
+        target.id,
+        amount_cents,
+    )
+    .await
+    {
+        Ok(id) => id,
+        Err(sqlx::Error::Protocol(msg)) => return ApiResponse::conflict(msg).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to start account tier change: {}", e);
+            return ApiResponse::internal_error("Failed to change account tier").into_response();
+        }
+    };
+
+    let mut payment_reference = None;
+
+    if amount_cents > 0 {
+        let request = PaymentRequest {
+            user_id: claims.id,
+            tier_name: target.name.clone(),
+            amount_cents,
+        };
+
+        let response = match state.payment_provider.charge(&request).await {
+            Ok(PaymentOutcome::Paid { reference }) => {
+                payment_reference = Some(reference);
+                None
+            }
+            Ok(PaymentOutcome::Declined { reason }) => {
+                tracing::info!("Payment declined for user {}: {}", claims.id, reason);
+                Some(
+                    ApiResponse::payment_required(format!("Payment declined: {reason}"))
+                        .into_response(),
+                )
+            }
+            Err(e) => {
+                tracing::error!("Payment provider error: {}", e);
+                Some(
+                    ApiResponse::service_unavailable("Payment provider unavailable")
+                        .into_response(),
+                )
+            }
+        };
+        if let Some(response) = response {
+            abandon_tier_change(&state, change_id, AbandonedTierChange::Failed, None).await;
+            return response;
+        }
+    }
+
+    let provider_name = payment_reference
+        .as_ref()
+        .map(|_| state.payment_provider.name());
+
+    if let Err(e) = repo::complete_tier_change(
+        &state.db_pool,
+        change_id,
+        provider_name,
+        payment_reference.as_deref(),
+    )
+    .await
+    {
+        let outcome = match &payment_reference {
+            Some(reference) => {
+                // Paiement encaissé mais offre non appliquée : remboursement
+                match state.payment_provider.refund(reference, amount_cents).await {
+                    Ok(()) => AbandonedTierChange::Refunded,
+                    Err(refund_error) => {
+                        tracing::error!(
+                            "User {} was charged ({}, reference {}) but tier change {} failed ({}) and refund failed: {}",
+                            claims.id,
+                            provider_name.unwrap_or_default(),
+                            reference,
+                            change_id,
+                            e,
+                            refund_error
+                        );
+                        AbandonedTierChange::RefundFailed
+                    }
+                }
+            }
+            None => AbandonedTierChange::Failed,
+        };
+        abandon_tier_change(&state, change_id, outcome, payment_reference.as_deref()).await;
+        return match e {
+            sqlx::Error::Protocol(msg) => ApiResponse::conflict(msg).into_response(),
+            e => {
+                tracing::error!("Failed to change account tier: {}", e);
+                ApiResponse::internal_error("Failed to change account tier").into_response()
+            }
+        };
+    }
+
+    tracing::info!(
+        "User {} changed account tier from {} to {}",
+        claims.id,
+        current.name,
+        target.name
+    );
+
+    // Un downgrade peut faire passer l'utilisateur au-dessus du quota : le délai de grâce démarre maintenant
+    let mut info = match drive::repo::get_drive_info(&state.db_pool, claims.id).await {
+        Ok(info) => info,
+        Err(e) => {
+            tracing::error!("Failed to retrieve drive info: {:?}", e);
+            return ApiResponse::internal_error("Failed to retrieve drive info").into_response();
+        }
+    };
+    if let Err(e) = drive::repo::sync_over_quota_state(&state.db_pool, claims.id, &mut info).await {
+        tracing::error!("Failed to update over-quota state: {:?}", e);
+    }
+
+    let quota = drive::services::quota_state(
+        info.over_quota_since,
+        drive::services::over_quota_grace_days(),
+        chrono::Utc::now(),
+    );
+
+    ApiResponse::ok(ChangeTierResponse {
+        account_tier: target,
+        amount_charged_cents: amount_cents,
+        drive_info: DriveInfoResponse { info, quota },
+    })
+    .into_response()
+}
+
+/// Clôt un changement d'offre non appliqué ; un échec est journalisé (la réservation expire d'elle-même)
+async fn abandon_tier_change(
+    state: &AppState,
+    change_id: Uuid,
+    outcome: AbandonedTierChange,
+    payment_reference: Option<&str>,
+) {
+    let provider_name = payment_reference.map(|_| state.payment_provider.name());
+    if let Err(e) = repo::abandon_tier_change(
+        &state.db_pool,
+        change_id,
+        outcome,
+        provider_name,
+        payment_reference,
+    )
+    .await
+    {
+        tracing::error!(
+            "Failed to record tier change {} as {}: {}",
+            change_id,
+            outcome.as_str(),
+            e
+        );
+    }
+}
@@ -0,0 +1,9 @@
+// Module billing - Offres de stockage, changement d'offre et paiement
+
+pub mod handlers;
+pub mod repo;
+pub mod routes;
+pub mod services;
+
+// Re-exports
+pub use routes::billing_routes;
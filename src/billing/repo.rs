@@ -0,0 +1,227 @@
+// Repository - Offres (account_tiers) et historique des changements d'offre
+
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct AccountTier {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub storage_limit_bytes: i64,
+    pub price_cents: i64,
+}
+
+const ACCOUNT_TIER_COLUMNS: &str = r#"
+    at.id, at.name, at.description, at.storage_limit_bytes,
+    (at.price * 100)::BIGINT AS price_cents
+"#;
+
+/// Liste les offres, de la plus petite à la plus grande
+pub async fn list_tiers(pool: &PgPool) -> Result<Vec<AccountTier>, sqlx::Error> {
+    sqlx::query_as::<_, AccountTier>(&format!(
+        r#"
+        SELECT {ACCOUNT_TIER_COLUMNS}
+        FROM account_tiers at
+        ORDER BY at.storage_limit_bytes, at.price
+        "#
+    ))
+    .fetch_all(pool)
+    .await
+}
+
+/// Récupère une offre par son ID
+pub async fn get_tier(pool: &PgPool, tier_id: Uuid) -> Result<AccountTier, sqlx::Error> {
+    sqlx::query_as::<_, AccountTier>(&format!(
+        r#"
+        SELECT {ACCOUNT_TIER_COLUMNS}
+        FROM account_tiers at
+        WHERE at.id = $1
+        "#
+    ))
+    .bind(tier_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Récupère l'offre actuelle d'un utilisateur
+pub async fn get_user_tier(pool: &PgPool, user_id: Uuid) -> Result<AccountTier, sqlx::Error> {
+    sqlx::query_as::<_, AccountTier>(&format!(
+        r#"
+        SELECT {ACCOUNT_TIER_COLUMNS}
+        FROM users u
+        JOIN account_tiers at ON at.id = u.account_tier_id
+        WHERE u.id = $1
+        "#
+    ))
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Erreur (`sqlx::Error::Protocol`) : l'offre a changé entre la lecture et le changement
+pub const TIER_CHANGED_CONCURRENTLY: &str = "Account tier was changed concurrently";
+
+/// Erreur (`sqlx::Error::Protocol`) : un autre changement d'offre de l'utilisateur est en cours
+pub const TIER_CHANGE_IN_PROGRESS: &str = "An account tier change is already in progress";
+
+/// Au-delà, un changement resté `pending` (arrêt du serveur pendant le paiement) ne bloque plus l'utilisateur
+const PENDING_TIER_CHANGE_TTL_MINUTES: i32 = 15;
+
+/// Réserve un changement d'offre (`pending`) avant l'appel au prestataire de paiement : un seul
+/// changement en cours par utilisateur (index unique partiel), deux requêtes concurrentes ne
+/// peuvent donc pas être facturées toutes les deux. Échoue (Protocol) si l'offre a changé
+/// entre-temps ou si un changement est déjà en cours. Retourne l'ID du changement.
+pub async fn begin_tier_change(
+    pool: &PgPool,
+    user_id: Uuid,
+    from_tier_id: Uuid,
+    to_tier_id: Uuid,
+    amount_cents: i64,
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let current: Uuid =
+        sqlx::query_scalar("SELECT account_tier_id FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+    if current != from_tier_id {
+        return Err(sqlx::Error::Protocol(TIER_CHANGED_CONCURRENTLY.into()));
+    }
+
+    let expired: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        UPDATE account_tier_changes
+        SET status = 'failed'
+        WHERE user_id = $1 AND status = 'pending'
+          AND created_at < NOW() - make_interval(mins => $2)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(PENDING_TIER_CHANGE_TTL_MINUTES)
+    .fetch_all(&mut *tx)
+    .await?;
+    for change_id in expired {
+        tracing::warn!(
+            "Tier change {} of user {} stayed pending and was abandoned, check the payment provider",
+            change_id,
+            user_id
+        );
+    }
+
+    let change_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO account_tier_changes (user_id, from_tier_id, to_tier_id, amount_cents, status)
+        VALUES ($1, $2, $3, $4, 'pending')
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(from_tier_id)
+    .bind(to_tier_id)
+    .bind(amount_cents)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+            sqlx::Error::Protocol(TIER_CHANGE_IN_PROGRESS.into())
+        }
+        e => e,
+    })?;
+
+    tx.commit().await?;
+    Ok(change_id)
+}
+
+/// Applique un changement d'offre réservé et le marque `completed` avec la référence du paiement.
+/// Échoue (Protocol) si l'offre a changé entre-temps (ex: modification par un administrateur).
+pub async fn complete_tier_change(
+    pool: &PgPool,
+    change_id: Uuid,
+    payment_provider: Option<&str>,
+    payment_reference: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE users u
+        SET account_tier_id = c.to_tier_id
+        FROM account_tier_changes c
+        WHERE c.id = $1 AND c.status = 'pending'
+          AND u.id = c.user_id AND u.account_tier_id = c.from_tier_id
+        "#,
+    )
+    .bind(change_id)
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::Protocol(TIER_CHANGED_CONCURRENTLY.into()));
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE account_tier_changes
+        SET status = 'completed', payment_provider = $2, payment_reference = $3
+        WHERE id = $1
+        "#,
+    )
+    .bind(change_id)
+    .bind(payment_provider)
+    .bind(payment_reference)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Issue d'un changement d'offre réservé mais non appliqué
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbandonedTierChange {
+    /// Aucun paiement encaissé (refus, prestataire indisponible)
+    Failed,
+    /// Paiement encaissé puis remboursé
+    Refunded,
+    /// Paiement encaissé mais remboursement en échec : à régulariser depuis l'historique
+    RefundFailed,
+}
+
+impl AbandonedTierChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbandonedTierChange::Failed => "failed",
+            AbandonedTierChange::Refunded => "refunded",
+            AbandonedTierChange::RefundFailed => "refund_failed",
+        }
+    }
+}
+
+/// Clôt un changement d'offre réservé sans l'appliquer. La référence du paiement éventuel est
+/// conservée dans l'historique pour le suivi des remboursements.
+pub async fn abandon_tier_change(
+    pool: &PgPool,
+    change_id: Uuid,
+    outcome: AbandonedTierChange,
+    payment_provider: Option<&str>,
+    payment_reference: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE account_tier_changes
+        SET status = $2, payment_provider = $3, payment_reference = $4
+        WHERE id = $1 AND status = 'pending'
+        "#,
+    )
+    .bind(change_id)
+    .bind(outcome.as_str())
+    .bind(payment_provider)
+    .bind(payment_reference)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,15 @@
+// Routes du module billing
+
+use crate::state::AppState;
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use super::handlers;
+
+pub fn billing_routes() -> Router<AppState> {
+    Router::new()
+        .route("/account/tiers", get(handlers::list_tiers_handler))
+        .route("/account/tier", post(handlers::change_tier_handler))
+}
@@ -0,0 +1,159 @@
+// Services - Paiement des changements d'offre
+// Le prestataire de paiement est abstrait derrière le trait PaymentProvider (choisi via PAYMENT_PROVIDER)
+
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::repo::AccountTier;
+
+// ========== Prestataire de paiement ==========
+
+/// Paiement demandé pour un changement d'offre
+#[derive(Debug, Clone)]
+pub struct PaymentRequest {
+    pub user_id: Uuid,
+    pub tier_name: String,
+    pub amount_cents: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    /// Paiement accepté, `reference` identifie la transaction chez le prestataire
+    Paid { reference: String },
+    /// Paiement refusé (carte refusée, fonds insuffisants...)
+    Declined { reason: String },
+}
+
+/// Le prestataire n'a pas pu traiter la demande (indisponible, non configuré...)
+#[derive(Debug, Clone)]
+pub struct PaymentError(pub String);
+
+impl std::fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Point d'extension pour brancher un prestataire de paiement réel.
+/// BoxFuture plutôt qu'un `async fn` : le trait doit rester utilisable en `Arc<dyn PaymentProvider>`.
+pub trait PaymentProvider: Send + Sync {
+    /// Nom du prestataire, enregistré avec chaque changement d'offre
+    fn name(&self) -> &'static str;
+
+    fn charge<'a>(
+        &'a self,
+        request: &'a PaymentRequest,
+    ) -> BoxFuture<'a, Result<PaymentOutcome, PaymentError>>;
+
+    /// Rembourse un paiement encaissé (`reference` retournée par `charge`) dont le changement
+    /// d'offre n'a pas pu être appliqué
+    fn refund<'a>(
+        &'a self,
+        reference: &'a str,
+        amount_cents: i64,
+    ) -> BoxFuture<'a, Result<(), PaymentError>>;
+}
+
+/// Prestataire factice (dev/tests) : accepte tout, ou refuse tout si construit avec `declining`
+pub struct FakePaymentProvider {
+    decline_reason: Option<String>,
+}
+
+impl FakePaymentProvider {
+    pub fn approving() -> Self {
+        Self {
+            decline_reason: None,
+        }
+    }
+
+    pub fn declining(reason: impl Into<String>) -> Self {
+        Self {
+            decline_reason: Some(reason.into()),
+        }
+    }
+}
+
+impl PaymentProvider for FakePaymentProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn charge<'a>(
+        &'a self,
+        _request: &'a PaymentRequest,
+    ) -> BoxFuture<'a, Result<PaymentOutcome, PaymentError>> {
+        Box::pin(async move {
+            Ok(match &self.decline_reason {
+                Some(reason) => PaymentOutcome::Declined {
+                    reason: reason.clone(),
+                },
+                None => PaymentOutcome::Paid {
+                    reference: format!("fake_{}", Uuid::new_v4()),
+                },
+            })
+        })
+    }
+
+    fn refund<'a>(
+        &'a self,
+        _reference: &'a str,
+        _amount_cents: i64,
+    ) -> BoxFuture<'a, Result<(), PaymentError>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Aucun prestataire configuré : les offres payantes sont indisponibles (les downgrades restent possibles)
+pub struct DisabledPaymentProvider;
+
+impl PaymentProvider for DisabledPaymentProvider {
+    fn name(&self) -> &'static str {
+        "disabled"
+    }
+
+    fn charge<'a>(
+        &'a self,
+        _request: &'a PaymentRequest,
+    ) -> BoxFuture<'a, Result<PaymentOutcome, PaymentError>> {
+        Box::pin(async { Err(PaymentError("No payment provider configured".to_string())) })
+    }
+
+    fn refund<'a>(
+        &'a self,
+        _reference: &'a str,
+        _amount_cents: i64,
+    ) -> BoxFuture<'a, Result<(), PaymentError>> {
+        Box::pin(async { Err(PaymentError("No payment provider configured".to_string())) })
+    }
+}
+
+/// Construit le prestataire à partir de PAYMENT_PROVIDER (`fake`, sinon désactivé)
+pub fn payment_provider_from_env() -> Arc<dyn PaymentProvider> {
+    match std::env::var("PAYMENT_PROVIDER").as_deref() {
+        Ok("fake") => {
+            tracing::warn!("Using fake payment provider: tier upgrades are free");
+            Arc::new(FakePaymentProvider::approving())
+        }
+        Ok(other) if !other.is_empty() => {
+            tracing::error!(
+                "Unknown PAYMENT_PROVIDER '{}', paid tiers are disabled",
+                other
+            );
+            Arc::new(DisabledPaymentProvider)
+        }
+        _ => Arc::new(DisabledPaymentProvider),
+    }
+}
+
+// ========== Changement d'offre ==========
+
+/// Montant à facturer pour passer de `current` à `target` : le prix de la nouvelle offre
+/// pour un upgrade, rien pour un downgrade ou une offre au même prix
+pub fn tier_change_amount(current: &AccountTier, target: &AccountTier) -> i64 {
+    if target.price_cents > current.price_cents {
+        target.price_cents
+    } else {
+        0
+    }
+}
@@ -0,0 +1,350 @@
+// Handlers HTTP du carnet de contacts
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{repo, services};
+use crate::{
+    auth::Claims,
+    notifications::services::{self as notifications, NotificationEvent},
+    response::ApiResponse,
+    state::AppState,
+};
+
+/// Réponse identique que l'adresse soit inscrite ou non
+const INVITATION_SENT: &str = "Invitation sent if the address belongs to a registered user";
+const CONTACT_NOT_FOUND: &str = "Contact not found";
+const GROUP_NOT_FOUND: &str = "Group not found";
+const GROUP_NAME_TAKEN: &str = "A group with this name already exists";
+
+#[derive(Deserialize)]
+pub struct InviteContactRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ContactsQuery {
+    /// Filtre sur le nom d'utilisateur ou l'email
+    pub search: Option<String>,
+    pub group_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyFingerprintRequest {
+    pub fingerprint: String,
+}
+
+#[derive(Deserialize)]
+pub struct GroupRequest {
+    pub name: String,
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505"))
+}
+
+// ========== Invitations ==========
+
+/// POST /contacts/invitations - Invite un utilisateur par email
+pub async fn invite_contact_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(body): Json<InviteContactRequest>,
+) -> Response {
+    let email = services::normalize_email(&body.email);
+    if email.is_empty() {
+        return ApiResponse::bad_request("Email is required").into_response();
+    }
+
+    match repo::create_invitation(&state.db_pool, claims.id, &email).await {
+        Ok(Some((invitation_id, to_user_id))) => {
+            let event = NotificationEvent::ContactInvitation {
+                invitation_id,
+                from_user_id: claims.id,
+            };
+            notifications::publish(&state.redis_manager, &[to_user_id], &event).await;
+            ApiResponse::ok(INVITATION_SENT).into_response()
+        }
+        Ok(None) => ApiResponse::ok(INVITATION_SENT).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to create contact invitation: {:?}", e);
+            ApiResponse::internal_error("Failed to send invitation").into_response()
+        }
+    }
+}
+
+/// GET /contacts/invitations - Invitations reçues et envoyées en attente
+pub async fn list_invitations_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::list_invitations(&state.db_pool, claims.id).await {
+        Ok(invitations) => ApiResponse::ok(invitations).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to list contact invitations: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve invitations").into_response()
+        }
+    }
+}
+
+/// POST /contacts/invitations/{invitation_id}/accept - Accepte une invitation reçue
+pub async fn accept_invitation_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(invitation_id): Path<Uuid>,
+) -> Response {
+    match repo::accept_invitation(&state.db_pool, claims.id, invitation_id).await {
+        Ok(from_user_id) => {
+            let event = NotificationEvent::ContactAccepted { user_id: claims.id };
+            notifications::publish(&state.redis_manager, &[from_user_id], &event).await;
+            ApiResponse::ok(serde_json::json!({ "user_id": from_user_id })).into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Invitation not found").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to accept contact invitation: {:?}", e);
+            ApiResponse::internal_error("Failed to accept invitation").into_response()
+        }
+    }
+}
+
+/// DELETE /contacts/invitations/{invitation_id} - Refuse (destinataire) ou annule (auteur)
+pub async fn delete_invitation_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(invitation_id): Path<Uuid>,
+) -> Response {
+    match repo::delete_invitation(&state.db_pool, claims.id, invitation_id).await {
+        Ok(()) => ApiResponse::ok("Invitation deleted").into_response(),
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Invitation not found").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to delete contact invitation: {:?}", e);
+            ApiResponse::internal_error("Failed to delete invitation").into_response()
+        }
+    }
+}
+
+// ========== Contacts ==========
+
+/// GET /contacts?search=&group_id= - Contacts acceptés
+pub async fn list_contacts_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<ContactsQuery>,
+) -> Response {
+    let pattern = query
+        .search
+        .as_deref()
+        .filter(|search| !search.trim().is_empty())
+        .map(services::search_pattern);
+
+    match repo::list_contacts(
+        &state.db_pool,
+        claims.id,
+        pattern.as_deref(),
+        query.group_id,
+    )
+    .await
+    {
+        Ok(contacts) => ApiResponse::ok(contacts).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to list contacts: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve contacts").into_response()
+        }
+    }
+}
+
+/// DELETE /contacts/{contact_user_id} - Retire le contact des deux carnets
+pub async fn remove_contact_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(contact_user_id): Path<Uuid>,
+) -> Response {
+    match repo::remove_contact(&state.db_pool, claims.id, contact_user_id).await {
+        Ok(()) => ApiResponse::ok("Contact removed").into_response(),
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found(CONTACT_NOT_FOUND).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to remove contact: {:?}", e);
+            ApiResponse::internal_error("Failed to remove contact").into_response()
+        }
+    }
+}
+
+/// GET /contacts/{contact_user_id}/public_key - Clé publique et empreinte vérifiée d'un contact
+pub async fn get_contact_key_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(contact_user_id): Path<Uuid>,
+) -> Response {
+    match repo::get_contact_key(&state.db_pool, claims.id, contact_user_id).await {
+        Ok(key) => ApiResponse::ok(key).into_response(),
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found(CONTACT_NOT_FOUND).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to retrieve contact public key: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve public key").into_response()
+        }
+    }
+}
+
+/// GET /contacts/get_public_key/{email} - Clé publique d'un contact accepté, par email.
+/// 404 identique pour une adresse inconnue ou qui n'est pas un contact.
+pub async fn get_public_key_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(email): Path<String>,
+) -> Response {
+    let email = services::normalize_email(&email);
+    match repo::get_contact_key_by_email(&state.db_pool, claims.id, &email).await {
+        Ok(key) => ApiResponse::ok(key).into_response(),
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found(CONTACT_NOT_FOUND).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to retrieve contact public key: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve public key").into_response()
+        }
+    }
+}
+
+/// PUT /contacts/{contact_user_id}/fingerprint - Enregistre l'empreinte de clé vérifiée
+pub async fn verify_fingerprint_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(contact_user_id): Path<Uuid>,
+    Json(body): Json<VerifyFingerprintRequest>,
+) -> Response {
+    let fingerprint = match services::normalize_fingerprint(&body.fingerprint) {
+        Ok(fingerprint) => fingerprint,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
+    match repo::set_key_fingerprint(&state.db_pool, claims.id, contact_user_id, &fingerprint).await
+    {
+        Ok(()) => {
+            ApiResponse::ok(serde_json::json!({ "key_fingerprint": fingerprint })).into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found(CONTACT_NOT_FOUND).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to store contact fingerprint: {:?}", e);
+            ApiResponse::internal_error("Failed to store fingerprint").into_response()
+        }
+    }
+}
+
+// ========== Groupes ==========
+
+/// GET /contacts/groups - Groupes de l'utilisateur
+pub async fn list_groups_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::list_groups(&state.db_pool, claims.id).await {
+        Ok(groups) => ApiResponse::ok(groups).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to list contact groups: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve groups").into_response()
+        }
+    }
+}
+
+/// POST /contacts/groups - Crée un groupe
+pub async fn create_group_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(body): Json<GroupRequest>,
+) -> Response {
+    let name = match services::normalize_group_name(&body.name) {
+        Ok(name) => name,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
+    match repo::create_group(&state.db_pool, claims.id, &name).await {
+        Ok(group_id) => {
+            ApiResponse::ok(serde_json::json!({ "group_id": group_id })).into_response()
+        }
+        Err(e) if is_unique_violation(&e) => {
+            ApiResponse::conflict(GROUP_NAME_TAKEN).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to create contact group: {:?}", e);
+            ApiResponse::internal_error("Failed to create group").into_response()
+        }
+    }
+}
+
+/// PATCH /contacts/groups/{group_id} - Renomme un groupe
+pub async fn rename_group_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+    Json(body): Json<GroupRequest>,
+) -> Response {
+    let name = match services::normalize_group_name(&body.name) {
+        Ok(name) => name,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
+    match repo::rename_group(&state.db_pool, claims.id, group_id, &name).await {
+        Ok(()) => ApiResponse::ok("Group renamed").into_response(),
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found(GROUP_NOT_FOUND).into_response(),
+        Err(e) if is_unique_violation(&e) => {
+            ApiResponse::conflict(GROUP_NAME_TAKEN).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to rename contact group: {:?}", e);
+            ApiResponse::internal_error("Failed to rename group").into_response()
+        }
+    }
+}
+
+/// DELETE /contacts/groups/{group_id} - Supprime un groupe (les contacts sont conservés)
+pub async fn delete_group_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+) -> Response {
+    match repo::delete_group(&state.db_pool, claims.id, group_id).await {
+        Ok(()) => ApiResponse::ok("Group deleted").into_response(),
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found(GROUP_NOT_FOUND).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to delete contact group: {:?}", e);
+            ApiResponse::internal_error("Failed to delete group").into_response()
+        }
+    }
+}
+
+/// PUT /contacts/groups/{group_id}/members/{contact_user_id} - Ajoute un contact au groupe
+pub async fn add_group_member_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((group_id, contact_user_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+    match repo::add_group_member(&state.db_pool, claims.id, group_id, contact_user_id).await {
+        Ok(()) => ApiResponse::ok("Contact added to group").into_response(),
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Group or contact not found").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to add contact to group: {:?}", e);
+            ApiResponse::internal_error("Failed to update group").into_response()
+        }
+    }
+}
+
+/// DELETE /contacts/groups/{group_id}/members/{contact_user_id} - Retire un contact du groupe
+pub async fn remove_group_member_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((group_id, contact_user_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+    match repo::remove_group_member(&state.db_pool, claims.id, group_id, contact_user_id).await {
+        Ok(()) => ApiResponse::ok("Contact removed from group").into_response(),
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Group member not found").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to remove contact from group: {:?}", e);
+            ApiResponse::internal_error("Failed to update group").into_response()
+        }
+    }
+}
@@ -0,0 +1,10 @@
+// Module contacts - Carnet d'adresses par invitation mutuelle
+// Seuls les contacts acceptés peuvent obtenir la clé publique d'un utilisateur
+
+pub mod handlers;
+pub mod repo;
+pub mod routes;
+pub mod services;
+
+// Re-exports
+pub use routes::contacts_routes;
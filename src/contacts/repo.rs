@@ -0,0 +1,459 @@
+// Repository - Carnet de contacts (tables `contact_invitations`, `contacts`, `contact_groups`)
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::auth::repo::{self as auth_repo, KeyStatementRecord};
+
+/// Invitation en attente, vue depuis l'un des deux côtés (`user_id` : l'autre partie)
+#[derive(Debug, FromRow, Serialize)]
+pub struct InvitationRecord {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Invitations {
+    pub received: Vec<InvitationRecord>,
+    pub sent: Vec<InvitationRecord>,
+}
+
+/// Contact accepté, avec l'empreinte de clé vérifiée par l'utilisateur
+#[derive(Debug, FromRow, Serialize)]
+pub struct ContactRecord {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub key_fingerprint: Option<String>,
+    pub fingerprint_verified_at: Option<DateTime<Utc>>,
+    pub group_ids: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Clé publique d'un contact et dernière empreinte vérifiée : le client compare l'empreinte
+/// de la clé reçue à celle enregistrée pour détecter une substitution, et vérifie la chaîne
+/// de déclarations signées de l'historique
+#[derive(Debug, FromRow, Serialize)]
+pub struct ContactKey {
+    pub user_id: Uuid,
+    pub public_key: String,
+    pub key_version: i32,
+    pub key_fingerprint: Option<String>,
+    pub fingerprint_verified_at: Option<DateTime<Utc>>,
+    #[sqlx(skip)]
+    pub history: Vec<KeyStatementRecord>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct ContactGroup {
+    pub id: Uuid,
+    pub name: String,
+    pub member_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+// ========== Invitations ==========
+
+/// Crée une invitation vers le compte `email`. Retourne None sans rien créer si l'email est
+/// inconnu, désigne l'appelant, un contact existant ou une invitation déjà envoyée : la
+/// réponse de l'API ne doit pas révéler si l'adresse est inscrite.
+pub async fn create_invitation(
+    pool: &PgPool,
+    from_user_id: Uuid,
+    email: &str,
+) -> Result<Option<(Uuid, Uuid)>, sqlx::Error> {
+    sqlx::query_as::<_, (Uuid, Uuid)>(
+        r#"
+        INSERT INTO contact_invitations (from_user_id, to_user_id)
+        SELECT $1, u.id
+        FROM users u
+        WHERE u.email = $2
+          AND u.id <> $1
+          AND NOT EXISTS (
+              SELECT 1 FROM contacts c WHERE c.user_id = $1 AND c.contact_user_id = u.id
+          )
+        ON CONFLICT (from_user_id, to_user_id) DO NOTHING
+        RETURNING id, to_user_id
+        "#,
+    )
+    .bind(from_user_id)
+    .bind(email)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Invitations reçues et envoyées encore en attente
+pub async fn list_invitations(pool: &PgPool, user_id: Uuid) -> Result<Invitations, sqlx::Error> {
+    let received = sqlx::query_as::<_, InvitationRecord>(
+        r#"
+        SELECT i.id, u.id AS user_id, u.username, u.email, i.created_at
+        FROM contact_invitations i
+        JOIN users u ON u.id = i.from_user_id
+        WHERE i.to_user_id = $1
+        ORDER BY i.created_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let sent = sqlx::query_as::<_, InvitationRecord>(
+        r#"
+        SELECT i.id, u.id AS user_id, u.username, u.email, i.created_at
+        FROM contact_invitations i
+        JOIN users u ON u.id = i.to_user_id
+        WHERE i.from_user_id = $1
+        ORDER BY i.created_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Invitations { received, sent })
+}
+
+/// Accepte une invitation reçue : crée la relation dans les deux sens et supprime l'invitation
+/// (ainsi que l'invitation croisée éventuelle). Retourne l'auteur de l'invitation.
+pub async fn accept_invitation(
+    pool: &PgPool,
+    user_id: Uuid,
+    invitation_id: Uuid,
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let from_user_id: Uuid = sqlx::query_scalar(
+        r#"
+        DELETE FROM contact_invitations
+        WHERE id = $1 AND to_user_id = $2
+        RETURNING from_user_id
+        "#,
+    )
+    .bind(invitation_id)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM contact_invitations WHERE from_user_id = $1 AND to_user_id = $2")
+        .bind(user_id)
+        .bind(from_user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO contacts (user_id, contact_user_id)
+        VALUES ($1, $2), ($2, $1)
+        ON CONFLICT (user_id, contact_user_id) DO NOTHING
+        "#,
+    )
+    .bind(user_id)
+    .bind(from_user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(from_user_id)
+}
+
+/// Refuse une invitation reçue ou annule une invitation envoyée
+pub async fn delete_invitation(
+    pool: &PgPool,
+    user_id: Uuid,
+    invitation_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM contact_invitations
+        WHERE id = $1 AND (from_user_id = $2 OR to_user_id = $2)
+        "#,
+    )
+    .bind(invitation_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+// ========== Contacts ==========
+
+/// Contacts acceptés, filtrés par motif ILIKE (nom d'utilisateur ou email) et/ou par groupe
+pub async fn list_contacts(
+    pool: &PgPool,
+    user_id: Uuid,
+    search_pattern: Option<&str>,
+    group_id: Option<Uuid>,
+) -> Result<Vec<ContactRecord>, sqlx::Error> {
+    sqlx::query_as::<_, ContactRecord>(
+        r#"
+        SELECT
+            u.id AS user_id,
+            u.username,
+            u.email,
+            c.key_fingerprint,
+            c.fingerprint_verified_at,
+            ARRAY(
+                SELECT m.group_id FROM contact_group_members m
+                WHERE m.user_id = c.user_id AND m.contact_user_id = c.contact_user_id
+                ORDER BY m.added_at
+            ) AS group_ids,
+            c.created_at
+        FROM contacts c
+        JOIN users u ON u.id = c.contact_user_id
+        WHERE c.user_id = $1
+          AND ($2::TEXT IS NULL OR u.username ILIKE $2 OR u.email ILIKE $2)
+          AND ($3::UUID IS NULL OR EXISTS (
+              SELECT 1 FROM contact_group_members m
+              WHERE m.group_id = $3 AND m.user_id = c.user_id AND m.contact_user_id = c.contact_user_id
+          ))
+        ORDER BY u.username
+        "#,
+    )
+    .bind(user_id)
+    .bind(search_pattern)
+    .bind(group_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Utilisateurs ayant `user_id` dans leurs contacts (destinataires des alertes de changement de clé)
+pub async fn get_contact_ids(pool: &PgPool, user_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar("SELECT user_id FROM contacts WHERE contact_user_id = $1")
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// Supprime la relation dans les deux sens (et donc les appartenances aux groupes)
+pub async fn remove_contact(
+    pool: &PgPool,
+    user_id: Uuid,
+    contact_user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM contacts
+        WHERE (user_id = $1 AND contact_user_id = $2)
+           OR (user_id = $2 AND contact_user_id = $1)
+        "#,
+    )
+    .bind(user_id)
+    .bind(contact_user_id)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Clé publique d'un contact accepté et son historique (RowNotFound pour tout autre utilisateur)
+pub async fn get_contact_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    contact_user_id: Uuid,
+) -> Result<ContactKey, sqlx::Error> {
+    let mut key = sqlx::query_as::<_, ContactKey>(
+        r#"
+        SELECT u.id AS user_id, u.public_key, u.public_key_version AS key_version,
+               c.key_fingerprint, c.fingerprint_verified_at
+        FROM contacts c
+        JOIN users u ON u.id = c.contact_user_id
+        WHERE c.user_id = $1 AND c.contact_user_id = $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(contact_user_id)
+    .fetch_one(pool)
+    .await?;
+
+    key.history = auth_repo::get_public_key_history(pool, key.user_id).await?;
+    Ok(key)
+}
+
+/// Clé publique d'un contact accepté désigné par son email
+pub async fn get_contact_key_by_email(
+    pool: &PgPool,
+    user_id: Uuid,
+    email: &str,
+) -> Result<ContactKey, sqlx::Error> {
+    let mut key = sqlx::query_as::<_, ContactKey>(
+        r#"
+        SELECT u.id AS user_id, u.public_key, u.public_key_version AS key_version,
+               c.key_fingerprint, c.fingerprint_verified_at
+        FROM contacts c
+        JOIN users u ON u.id = c.contact_user_id
+        WHERE c.user_id = $1 AND u.email = $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(email)
+    .fetch_one(pool)
+    .await?;
+
+    key.history = auth_repo::get_public_key_history(pool, key.user_id).await?;
+    Ok(key)
+}
+
+/// Enregistre l'empreinte de clé vérifiée par l'utilisateur pour ce contact
+pub async fn set_key_fingerprint(
+    pool: &PgPool,
+    user_id: Uuid,
+    contact_user_id: Uuid,
+    fingerprint: &str,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE contacts
+        SET key_fingerprint = $3, fingerprint_verified_at = NOW()
+        WHERE user_id = $1 AND contact_user_id = $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(contact_user_id)
+    .bind(fingerprint)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+// ========== Groupes ==========
+
+pub async fn list_groups(pool: &PgPool, user_id: Uuid) -> Result<Vec<ContactGroup>, sqlx::Error> {
+    sqlx::query_as::<_, ContactGroup>(
+        r#"
+        SELECT
+            g.id,
+            g.name,
+            (SELECT COUNT(*) FROM contact_group_members m WHERE m.group_id = g.id) AS member_count,
+            g.created_at
+        FROM contact_groups g
+        WHERE g.user_id = $1
+        ORDER BY g.name
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Crée un groupe vide (violation d'unicité si le nom est déjà pris)
+pub async fn create_group(pool: &PgPool, user_id: Uuid, name: &str) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar("INSERT INTO contact_groups (user_id, name) VALUES ($1, $2) RETURNING id")
+        .bind(user_id)
+        .bind(name)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn rename_group(
+    pool: &PgPool,
+    user_id: Uuid,
+    group_id: Uuid,
+    name: &str,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query("UPDATE contact_groups SET name = $3 WHERE id = $1 AND user_id = $2")
+        .bind(group_id)
+        .bind(user_id)
+        .bind(name)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+pub async fn delete_group(pool: &PgPool, user_id: Uuid, group_id: Uuid) -> Result<(), sqlx::Error> {
+    let result = sqlx::query("DELETE FROM contact_groups WHERE id = $1 AND user_id = $2")
+        .bind(group_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Ajoute un contact accepté à un groupe de l'utilisateur (idempotent).
+/// RowNotFound si le groupe n'appartient pas à l'utilisateur ou si ce n'est pas un contact.
+pub async fn add_group_member(
+    pool: &PgPool,
+    user_id: Uuid,
+    group_id: Uuid,
+    contact_user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let found: Option<bool> = sqlx::query_scalar(
+        r#"
+        SELECT TRUE
+        FROM contact_groups g
+        JOIN contacts c ON c.user_id = g.user_id AND c.contact_user_id = $3
+        WHERE g.id = $1 AND g.user_id = $2
+        "#,
+    )
+    .bind(group_id)
+    .bind(user_id)
+    .bind(contact_user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    if found.is_none() {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO contact_group_members (group_id, user_id, contact_user_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (group_id, contact_user_id) DO NOTHING
+        "#,
+    )
+    .bind(group_id)
+    .bind(user_id)
+    .bind(contact_user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_group_member(
+    pool: &PgPool,
+    user_id: Uuid,
+    group_id: Uuid,
+    contact_user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM contact_group_members
+        WHERE group_id = $1 AND user_id = $2 AND contact_user_id = $3
+        "#,
+    )
+    .bind(group_id)
+    .bind(user_id)
+    .bind(contact_user_id)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
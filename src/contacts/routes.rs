@@ -0,0 +1,54 @@
+// Routes du module contacts
+
+use crate::state::AppState;
+use axum::{
+    Router,
+    routing::{delete, get, patch, post, put},
+};
+
+use super::handlers;
+
+pub fn contacts_routes() -> Router<AppState> {
+    Router::new()
+        .route("/contacts", get(handlers::list_contacts_handler))
+        .route(
+            "/contacts/invitations",
+            get(handlers::list_invitations_handler).post(handlers::invite_contact_handler),
+        )
+        .route(
+            "/contacts/invitations/{invitation_id}",
+            delete(handlers::delete_invitation_handler),
+        )
+        .route(
+            "/contacts/invitations/{invitation_id}/accept",
+            post(handlers::accept_invitation_handler),
+        )
+        .route(
+            "/contacts/get_public_key/{email}",
+            get(handlers::get_public_key_handler),
+        )
+        .route(
+            "/contacts/groups",
+            get(handlers::list_groups_handler).post(handlers::create_group_handler),
+        )
+        .route(
+            "/contacts/groups/{group_id}",
+            patch(handlers::rename_group_handler).delete(handlers::delete_group_handler),
+        )
+        .route(
+            "/contacts/groups/{group_id}/members/{contact_user_id}",
+            put(handlers::add_group_member_handler).delete(handlers::remove_group_member_handler),
+        )
+        .route(
+            "/contacts/{contact_user_id}",
+            delete(handlers::remove_contact_handler),
+        )
+        .route(
+            "/contacts/{contact_user_id}/public_key",
+            get(handlers::get_contact_key_handler),
+        )
+        .route(
+            "/contacts/{contact_user_id}/fingerprint",
+            put(handlers::verify_fingerprint_handler),
+        )
+}
@@ -0,0 +1,51 @@
+// Services - Validation des entrées du carnet de contacts
+
+/// Longueur maximale d'un nom de groupe (en caractères)
+pub const MAX_GROUP_NAME_LENGTH: usize = 100;
+
+/// Erreurs de validation renvoyées en 400
+pub const INVALID_GROUP_NAME: &str = "Group name must be between 1 and 100 characters";
+pub const INVALID_FINGERPRINT: &str = "Fingerprint must be a hex-encoded SHA-256 digest";
+
+/// Nom de groupe sans espaces superflus, refusé s'il est vide ou trop long
+pub fn normalize_group_name(name: &str) -> Result<String, &'static str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_GROUP_NAME_LENGTH {
+        return Err(INVALID_GROUP_NAME);
+    }
+    Ok(name.to_string())
+}
+
+/// Empreinte SHA-256 d'une clé publique, calculée par le client : 64 caractères hexadécimaux,
+/// séparateurs `:` et espaces tolérés. Stockée en minuscules sans séparateur pour que le client
+/// puisse la comparer à l'empreinte de la clé renvoyée par le serveur.
+pub fn normalize_fingerprint(fingerprint: &str) -> Result<String, &'static str> {
+    let hex: String = fingerprint
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(INVALID_FINGERPRINT);
+    }
+    Ok(hex)
+}
+
+/// Motif ILIKE « contient » pour la recherche : `%`, `_` et `\` saisis sont pris littéralement
+pub fn search_pattern(search: &str) -> String {
+    let mut pattern = String::with_capacity(search.len() + 2);
+    pattern.push('%');
+    for c in search.trim().chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Email tel que stocké à l'inscription
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
@@ -1,17 +1,29 @@
-use axum::extract::{Json, Multipart, Path, State};
+use axum::extract::{Json, Multipart, Path, Query, State};
 use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use tracing::{info, instrument};
 use uuid::Uuid;
 
+use super::repo::ItemKind;
+use super::services::{AccessLevel, ShareGrant};
 use super::{repo, services};
-use crate::{auth::Claims, response::ApiResponse, state::AppState};
+use crate::{
+    activity::{self, ActivityAction, ActivityEntry},
+    archive::{self, ArchiveSink, ZipStreamWriter},
+    auth::Claims,
+    notifications::services::{self as notifications, NotificationEvent},
+    response::ApiResponse,
+    state::AppState,
+    storage::{ChunkSpooler, SpooledChunk, StorageError},
+    upload_limiter::{UPLOAD_RETRY_AFTER_SECS, UploadLimitError},
+};
 use base64::Engine;
 use sqlx;
 
 use axum::body::{Body, Bytes};
 use axum::http::header;
-use futures::stream::StreamExt;
+use futures::{SinkExt, stream::StreamExt};
 
 fn sanitize_content_disposition_filename(raw: &str) -> String {
     let sanitized = raw
@@ -26,6 +38,115 @@ fn sanitize_content_disposition_filename(raw: &str) -> String {
     }
 }
 
+/// Refuse les modifications quand le drive est en lecture seule (quota dépassé au-delà du délai de grâce).
+/// La suppression reste toujours possible pour permettre de repasser sous le quota.
+async fn ensure_drive_writable(state: &AppState, user_id: Uuid) -> Result<(), Response> {
+    let mut drive_info = repo::get_drive_info(&state.db_pool, user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to retrieve drive info: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve drive info").into_response()
+        })?;
+
+    if let Err(e) = repo::sync_over_quota_state(&state.db_pool, user_id, &mut drive_info).await {
+        tracing::error!("Failed to update over-quota state: {:?}", e);
+        return Err(ApiResponse::internal_error("Failed to retrieve drive info").into_response());
+    }
+
+    match services::quota_state(
+        drive_info.over_quota_since,
+        services::over_quota_grace_days(),
+        chrono::Utc::now(),
+    ) {
+        services::QuotaState::ReadOnly { .. } => Err(ApiResponse::insufficient_storage(
+            "Storage quota exceeded: drive is read-only until usage is reduced",
+        )
+        .into_response()),
+        _ => Ok(()),
+    }
+}
+
+/// Journalise un partage et prévient son destinataire (notification temps réel et email selon
+/// ses préférences, best effort)
+async fn record_share(
+    state: &AppState,
+    from_user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    recipient_id: Uuid,
+) {
+    let entry = ActivityEntry::new(from_user_id, ActivityAction::Shared)
+        .on_item(kind, item_id)
+        .target_user(recipient_id);
+    activity::services::record(&state.db_pool, entry).await;
+
+    let event = NotificationEvent::ShareReceived {
+        item_type: kind.as_str().to_string(),
+        item_id,
+        from_user_id,
+    };
+    notifications::publish(&state.redis_manager, &[recipient_id], &event).await;
+    notifications::email_share_received(state, recipient_id, from_user_id, kind.as_str()).await;
+}
+
+/// Journalise une action de l'utilisateur sur un élément (après succès, best effort)
+async fn log_item_activity(
+    state: &AppState,
+    user_id: Uuid,
+    action: ActivityAction,
+    kind: ItemKind,
+    item_id: Uuid,
+) {
+    activity::services::record(
+        &state.db_pool,
+        ActivityEntry::new(user_id, action).on_item(kind, item_id),
+    )
+    .await;
+}
+
+/// Prévient les collaborateurs d'un dossier partagé qu'un élément y est apparu
+/// (`file_added` / `folder_added`, après le commit, best effort)
+async fn notify_item_added(state: &AppState, user_id: Uuid, kind: ItemKind, item_id: Uuid) {
+    let recipients =
+        match repo::get_added_item_recipients(&state.db_pool, user_id, kind, item_id).await {
+            Ok(recipients) => recipients,
+            Err(e) => {
+                tracing::error!(
+                    "Failed to load recipients of added {} {}: {:?}",
+                    kind.as_str(),
+                    item_id,
+                    e
+                );
+                return;
+            }
+        };
+
+    let event = match kind {
+        ItemKind::File => NotificationEvent::FileAdded {
+            file_id: item_id,
+            added_by: user_id,
+        },
+        ItemKind::Folder => NotificationEvent::FolderAdded {
+            folder_id: item_id,
+            added_by: user_id,
+        },
+    };
+    notifications::publish(&state.redis_manager, &recipients, &event).await;
+}
+
+/// Droits d'un partage, refusés si l'expiration demandée n'est pas dans le futur
+fn share_grant(
+    access_level: AccessLevel,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<ShareGrant, &'static str> {
+    let grant = ShareGrant {
+        access_level,
+        expires_at,
+    };
+    grant.validate(Utc::now())?;
+    Ok(grant)
+}
+
 // ========== Request/Response Structures ==========
 
 #[derive(Deserialize)]
@@ -35,6 +156,40 @@ pub struct InitializeFileRequest {
     mime_type: String,
     folder_id: String,
     encrypted_file_key: String,
+    /// Clés re-chiffrées pour chaque autre membre du dossier partagé
+    #[serde(default)]
+    member_keys: Vec<MemberKey>,
+    /// Clés chiffrées pour chaque équipe à laquelle le dossier est partagé
+    #[serde(default)]
+    team_keys: Vec<TeamKey>,
+}
+
+/// Clé d'un élément chiffrée pour un membre du dossier partagé de destination
+#[derive(Deserialize)]
+pub struct MemberKey {
+    pub user_id: Uuid,
+    pub encrypted_key: String,
+}
+
+fn member_keys_to_tuples(member_keys: Vec<MemberKey>) -> Vec<(Uuid, String)> {
+    member_keys
+        .into_iter()
+        .map(|k| (k.user_id, k.encrypted_key))
+        .collect()
+}
+
+/// Clé d'un élément chiffrée avec la clé publique d'une équipe
+#[derive(Deserialize)]
+pub struct TeamKey {
+    pub team_id: Uuid,
+    pub encrypted_key: String,
+}
+
+fn team_keys_to_tuples(team_keys: Vec<TeamKey>) -> Vec<(Uuid, String)> {
+    team_keys
+        .into_iter()
+        .map(|k| (k.team_id, k.encrypted_key))
+        .collect()
 }
 
 // ========== Handlers ==========
@@ -51,18 +206,11 @@ pub async fn initialize_file_handler(
         }
     };
 
-    let drive_info = match repo::get_drive_info(&state.db_pool, claims.id).await {
-        Ok(info) => info,
-        Err(e) => {
-            tracing::error!("Failed to retrieve drive info: {:?}", e);
-            return ApiResponse::internal_error("Failed to retrieve drive info").into_response();
-        }
-    };
-
-    if drive_info.used_space + body.size > drive_info.storage_limit_bytes {
-        return ApiResponse::insufficient_storage("Insufficient storage space").into_response();
+    if body.size < 0 {
+        return ApiResponse::bad_request("File size must be non-negative").into_response();
     }
 
+    // Le quota est vérifié et réservé atomiquement avec la création du fichier
     let file_id = match repo::initialize_file_in_db(
         &state.db_pool,
         claims.id,
@@ -70,7 +218,11 @@ pub async fn initialize_file_handler(
         &body.encrypted_metadata,
         &body.mime_type,
         folder_id,
-        &body.encrypted_file_key,
+        repo::ItemKeys {
+            encrypted_key: &body.encrypted_file_key,
+            member_keys: &member_keys_to_tuples(body.member_keys),
+            team_keys: &team_keys_to_tuples(body.team_keys),
+        },
     )
     .await
     {
@@ -78,6 +230,15 @@ pub async fn initialize_file_handler(
         Err(sqlx::Error::RowNotFound) => {
             return ApiResponse::not_found("Folder not found").into_response();
         }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_ROTATION_PENDING => {
+            return ApiResponse::conflict(msg).into_response();
+        }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::INSUFFICIENT_STORAGE => {
+            return ApiResponse::insufficient_storage(msg).into_response();
+        }
+        Err(sqlx::Error::Protocol(msg)) => {
+            return ApiResponse::bad_request(&msg).into_response();
+        }
         Err(e) => {
             tracing::error!("Failed to initialize file in DB: {:?}", e);
             return ApiResponse::internal_error("Failed to initialize file").into_response();
@@ -96,6 +257,82 @@ pub async fn protected_handler(claims: Claims) -> ApiResponse<String> {
     ))
 }
 
+/// 503 + Retry-After quand aucun slot d'upload ne s'est libéré dans le délai d'attente
+fn upload_busy_response(user_id: Uuid, error: UploadLimitError) -> Response {
+    tracing::warn!("Upload rejected for user {}: {:?}", user_id, error);
+    let message = match error {
+        UploadLimitError::UserLimit => "Too many concurrent uploads, please retry",
+        UploadLimitError::ServerBusy => "Server busy, please retry",
+    };
+
+    let mut response = ApiResponse::service_unavailable(message).into_response();
+    response.headers_mut().insert(
+        header::RETRY_AFTER,
+        header::HeaderValue::from(UPLOAD_RETRY_AFTER_SECS),
+    );
+    response
+}
+
+/// Réponse d'erreur d'un contrôle de réservation : 409 sans upload en cours,
+/// 507 au-delà de la taille déclarée à l'initialisation
+fn chunk_reservation_error(e: sqlx::Error, context: &str) -> Response {
+    match e {
+        sqlx::Error::Protocol(msg) if msg == repo::UPLOAD_NOT_IN_PROGRESS => {
+            ApiResponse::conflict(msg).into_response()
+        }
+        sqlx::Error::Protocol(msg) if msg == repo::UPLOAD_EXCEEDS_RESERVATION => {
+            ApiResponse::insufficient_storage(msg).into_response()
+        }
+        e => {
+            tracing::error!("{}: {:?}", context, e);
+            ApiResponse::internal_error(context).into_response()
+        }
+    }
+}
+
+/// Vérifie qu'un chunk tient dans la taille déclarée de l'upload avant son envoi au stockage
+async fn check_chunk_reservation(
+    state: &AppState,
+    file_id: Uuid,
+    index: i32,
+    len: i64,
+) -> Result<(), Response> {
+    match repo::check_chunk_reservation(&state.db_pool, file_id, index, len).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ApiResponse::conflict(repo::UPLOAD_NOT_IN_PROGRESS).into_response()),
+        Err(e) => Err(chunk_reservation_error(
+            e,
+            "Failed to verify storage reservation",
+        )),
+    }
+}
+
+/// Enregistre un chunk stocké, puis supprime l'objet du chunk qu'il remplace (retry).
+/// Si l'enregistrement est refusé, l'objet tout juste stocké est supprimé.
+async fn record_stored_chunk(
+    state: &AppState,
+    file_id: Uuid,
+    index: i32,
+    s3_key: &str,
+    len: i64,
+) -> Result<Uuid, Response> {
+    match repo::save_chunk_metadata(&state.db_pool, file_id, index, s3_key, len).await {
+        Ok(saved) => {
+            if let Some(replaced) = saved.replaced_s3_key {
+                repo::delete_storage_objects(&state.storage_client, &[replaced]).await;
+            }
+            Ok(saved.id)
+        }
+        Err(e) => {
+            repo::delete_storage_objects(&state.storage_client, &[s3_key.to_string()]).await;
+            Err(chunk_reservation_error(
+                e,
+                "Failed to record chunk metadata",
+            ))
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct UploadChunkRequest {
     file_id: Uuid,
@@ -109,17 +346,15 @@ pub async fn upload_chunk_handler(
     claims: Claims,
     Json(body): Json<UploadChunkRequest>,
 ) -> Response {
-    // Acquérir un permit pour limiter les uploads concurrents
-    let _permit = match state.upload_semaphore.try_acquire() {
+    // Attendre un slot d'upload (limite globale et par utilisateur)
+    let _permit = match state.upload_limiter.acquire(claims.id).await {
         Ok(permit) => permit,
-        Err(_) => {
-            tracing::warn!("Upload rejected: too many concurrent uploads");
-            return ApiResponse::bad_request("Server busy, please retry").into_response();
-        }
+        Err(e) => return upload_busy_response(claims.id, e),
     };
 
     // Vérifier que l'utilisateur a le droit d'uploader sur ce fichier
-    let has_access = match repo::user_is_file_owner(&state.db_pool, claims.id, body.file_id).await {
+    let has_access = match repo::user_can_upload_file(&state.db_pool, claims.id, body.file_id).await
+    {
         Ok(exists) => exists,
         Err(e) => {
             tracing::error!("Failed to check file access: {:?}", e);
@@ -139,6 +374,13 @@ pub async fn upload_chunk_handler(
         }
     };
 
+    let chunk_len = chunk_data.len() as i64;
+    if let Err(response) =
+        check_chunk_reservation(&state, body.file_id, body.index, chunk_len).await
+    {
+        return response;
+    }
+
     let storage_client = &state.storage_client;
 
     // Mesurer la durée d'upload du chunk vers S3
@@ -160,19 +402,17 @@ pub async fn upload_chunk_handler(
         }
     };
 
-    let s3_record_id = match repo::save_chunk_metadata(
-        &state.db_pool,
+    let s3_record_id = match record_stored_chunk(
+        &state,
         body.file_id,
         body.index,
         &meta_data_s3.s3_id,
+        chunk_len,
     )
     .await
     {
         Ok(id) => id,
-        Err(e) => {
-            tracing::error!("Failed to insert S3 key into database: {:?}", e);
-            return ApiResponse::internal_error("Failed to record chunk metadata").into_response();
-        }
+        Err(response) => return response,
     };
 
     ApiResponse::ok(serde_json::json!({
@@ -190,15 +430,12 @@ pub async fn upload_chunk_restful_handler(
     Path(file_id): Path<Uuid>,
     mut multipart: Multipart,
 ) -> Response {
-    let _permit = match state.upload_semaphore.try_acquire() {
+    let _permit = match state.upload_limiter.acquire(claims.id).await {
         Ok(permit) => permit,
-        Err(_) => {
-            tracing::warn!("Upload rejected: too many concurrent uploads");
-            return ApiResponse::bad_request("Server busy, please retry").into_response();
-        }
+        Err(e) => return upload_busy_response(claims.id, e),
     };
 
-    let has_access = match repo::user_is_file_owner(&state.db_pool, claims.id, file_id).await {
+    let has_access = match repo::user_can_upload_file(&state.db_pool, claims.id, file_id).await {
         Ok(exists) => exists,
         Err(e) => {
             tracing::error!("Failed to check file access: {:?}", e);
@@ -210,19 +447,22 @@ pub async fn upload_chunk_restful_handler(
         return ApiResponse::not_found("File not found or access denied").into_response();
     }
 
-    let drive_info = match repo::get_drive_info(&state.db_pool, claims.id).await {
-        Ok(info) => info,
+    // Le quota a été réservé à l'initialisation : un chunk n'est accepté que pour un upload en cours.
+    // Chaque chunk marque l'activité de la réservation (les longs uploads ne sont pas collectés).
+    match repo::touch_storage_reservation(&state.db_pool, file_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiResponse::conflict(repo::UPLOAD_NOT_IN_PROGRESS).into_response();
+        }
         Err(e) => {
-            tracing::error!("Failed to retrieve drive info: {:?}", e);
-            return ApiResponse::internal_error("Failed to retrieve drive info").into_response();
+            tracing::error!("Failed to touch storage reservation: {:?}", e);
+            return ApiResponse::internal_error("Failed to verify storage reservation")
+                .into_response();
         }
-    };
-
-    if drive_info.used_space >= drive_info.storage_limit_bytes {
-        return ApiResponse::insufficient_storage("Insufficient storage space").into_response();
     }
 
-    let mut chunk_bytes: Option<Bytes> = None;
+    let max_chunk_size = services::max_chunk_size_bytes();
+    let mut chunk: Option<SpooledChunk> = None;
     let mut chunk_index: Option<i32> = None;
     let mut iv: Option<String> = None;
 
@@ -235,26 +475,51 @@ pub async fn upload_chunk_restful_handler(
             }
         };
 
-        let Some(field) = next_field else {
+        let Some(mut field) = next_field else {
             break;
         };
 
         let field_name = field.name().unwrap_or_default().to_string();
         match field_name.as_str() {
             "chunk" => {
-                let data = match field.bytes().await {
-                    Ok(bytes) => bytes,
-                    Err(e) => {
-                        tracing::error!("Failed to read chunk bytes: {:?}", e);
-                        return ApiResponse::bad_request("Invalid chunk data").into_response();
+                // Le chunk est haché et spoolé au fil de la réception, sans être chargé en entier en mémoire
+                let mut spooler = ChunkSpooler::new(max_chunk_size);
+                loop {
+                    let data = match field.chunk().await {
+                        Ok(Some(data)) => data,
+                        Ok(None) => break,
+                        Err(e) => {
+                            tracing::error!("Failed to read chunk bytes: {:?}", e);
+                            return ApiResponse::bad_request("Invalid chunk data").into_response();
+                        }
+                    };
+
+                    match spooler.write(&data).await {
+                        Ok(()) => {}
+                        Err(StorageError::TooLarge) => {
+                            return ApiResponse::payload_too_large("Chunk too large")
+                                .into_response();
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to spool chunk: {:?}", e);
+                            return ApiResponse::internal_error("Failed to receive chunk")
+                                .into_response();
+                        }
                     }
-                };
+                }
 
-                if data.is_empty() {
+                if spooler.is_empty() {
                     return ApiResponse::bad_request("Empty chunk body").into_response();
                 }
 
-                chunk_bytes = Some(data);
+                match spooler.finish().await {
+                    Ok(spooled) => chunk = Some(spooled),
+                    Err(e) => {
+                        tracing::error!("Failed to spool chunk: {:?}", e);
+                        return ApiResponse::internal_error("Failed to receive chunk")
+                            .into_response();
+                    }
+                }
             }
             "chunk_index" | "index" => {
                 let value = match field.text().await {
@@ -291,7 +556,7 @@ pub async fn upload_chunk_restful_handler(
         }
     }
 
-    let Some(body) = chunk_bytes else {
+    let Some(chunk) = chunk else {
         return ApiResponse::bad_request("Missing chunk file").into_response();
     };
     let Some(index) = chunk_index else {
@@ -301,14 +566,16 @@ pub async fn upload_chunk_restful_handler(
         return ApiResponse::bad_request("Missing IV").into_response();
     };
 
-    if drive_info.used_space + body.len() as i64 > drive_info.storage_limit_bytes {
-        return ApiResponse::insufficient_storage("Insufficient storage space").into_response();
+    // Les chunks reçus ne peuvent pas dépasser la taille déclarée
+    let chunk_len = chunk.len() as i64;
+    if let Err(response) = check_chunk_reservation(&state, file_id, index, chunk_len).await {
+        return response;
     }
 
     let upload_start = std::time::Instant::now();
     let meta_data_s3 = match state
         .storage_client
-        .upload_line(body, index.to_string(), iv)
+        .upload_chunk(&chunk, index.to_string(), iv)
         .await
     {
         Ok(meta) => {
@@ -324,20 +591,11 @@ pub async fn upload_chunk_restful_handler(
         }
     };
 
-    let s3_record_id = match repo::save_chunk_metadata(
-        &state.db_pool,
-        file_id,
-        index,
-        &meta_data_s3.s3_id,
-    )
-    .await
-    {
-        Ok(id) => id,
-        Err(e) => {
-            tracing::error!("Failed to insert S3 key into database: {:?}", e);
-            return ApiResponse::internal_error("Failed to record chunk metadata").into_response();
-        }
-    };
+    let s3_record_id =
+        match record_stored_chunk(&state, file_id, index, &meta_data_s3.s3_id, chunk_len).await {
+            Ok(id) => id,
+            Err(response) => return response,
+        };
 
     ApiResponse::ok(serde_json::json!({
         "s3_record_id": s3_record_id,
@@ -355,12 +613,20 @@ pub struct CreateFolderRequest {
     encrypted_metadata: String,
     parent_folder_id: String,
     encrypted_folder_key: String,
+    #[serde(default)]
+    member_keys: Vec<MemberKey>,
+    #[serde(default)]
+    team_keys: Vec<TeamKey>,
 }
 pub async fn create_folder_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(body): Json<CreateFolderRequest>,
 ) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     let parent_folder_id = match services::parse_uuid_or_error(&body.parent_folder_id) {
         Ok(id) => id,
         Err(err) => {
@@ -373,17 +639,32 @@ pub async fn create_folder_handler(
         claims.id,
         &body.encrypted_metadata,
         parent_folder_id,
-        &body.encrypted_folder_key,
+        repo::ItemKeys {
+            encrypted_key: &body.encrypted_folder_key,
+            member_keys: &member_keys_to_tuples(body.member_keys),
+            team_keys: &team_keys_to_tuples(body.team_keys),
+        },
     )
     .await
     {
         Ok(id) => id,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::not_found("Parent folder not found or access denied")
+                .into_response();
+        }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_ROTATION_PENDING => {
+            return ApiResponse::conflict(msg).into_response();
+        }
+        Err(sqlx::Error::Protocol(msg)) => {
+            return ApiResponse::bad_request(&msg).into_response();
+        }
         Err(e) => {
             tracing::error!("Failed to create folder in DB: {:?}", e);
             return ApiResponse::internal_error("Failed to create folder").into_response();
         }
     };
 
+    notify_item_added(&state, claims.id, ItemKind::Folder, folder_id).await;
     ApiResponse::ok(serde_json::json!({ "folder_id": folder_id })).into_response()
 }
 
@@ -392,6 +673,10 @@ pub async fn get_file_folder_handler(
     claims: Claims,
     Path(parent_id): Path<String>,
 ) -> Response {
+    if parent_id.eq_ignore_ascii_case("favorites") {
+        return labelled_view_response(&state, claims.id, repo::LabelView::Favorites).await;
+    }
+
     let is_corbeille = parent_id.eq_ignore_ascii_case("corbeille");
     let is_shared_with_me = parent_id.eq_ignore_ascii_case("shared-with-me")
         || parent_id.eq_ignore_ascii_case("shared_with_me");
@@ -643,7 +928,17 @@ pub async fn delete_file_handler(
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("File deleted successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Deleted,
+                ItemKind::File,
+                body.file_id,
+            )
+            .await;
+            ApiResponse::ok("File deleted successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("File not found").into_response(),
         Err(e) => {
             tracing::error!("Failed to delete file: {:?}", e);
@@ -663,7 +958,17 @@ pub async fn delete_folder_handler(
 ) -> Response {
     // transfer-tracking removed: deletions no longer blocked by Redis
     match repo::delete_folder(&state.db_pool, claims.id, body.folder_id).await {
-        Ok(_) => ApiResponse::ok("Folder deleted successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Deleted,
+                ItemKind::Folder,
+                body.folder_id,
+            )
+            .await;
+            ApiResponse::ok("Folder deleted successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("Folder not found").into_response(),
         Err(e) => {
             tracing::error!("Failed to delete folder: {:?}", e);
@@ -690,7 +995,17 @@ pub async fn rename_file_handler(
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("File renamed successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Renamed,
+                ItemKind::File,
+                body.file_id,
+            )
+            .await;
+            ApiResponse::ok("File renamed successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("File not found").into_response(),
         Err(e) => {
             tracing::error!("Failed to rename file: {:?}", e);
@@ -727,7 +1042,17 @@ pub async fn rename_folder_handler(
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("Folder renamed successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Renamed,
+                ItemKind::Folder,
+                body.folder_id,
+            )
+            .await;
+            ApiResponse::ok("Folder renamed successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("Folder not found").into_response(),
         Err(e) => {
             tracing::error!("Failed to rename folder: {:?}", e);
@@ -761,10 +1086,24 @@ pub async fn move_file_handler(
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("File moved successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Moved,
+                ItemKind::File,
+                body.file_id,
+            )
+            .await;
+            notify_item_added(&state, claims.id, ItemKind::File, body.file_id).await;
+            ApiResponse::ok("File moved successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File or target folder not found").into_response()
         }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_ROTATION_PENDING => {
+            ApiResponse::conflict(msg).into_response()
+        }
         Err(e) => {
             tracing::error!("Failed to move file: {:?}", e);
             ApiResponse::internal_error("Failed to move file").into_response()
@@ -797,10 +1136,24 @@ pub async fn move_folder_handler(
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("Folder moved successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Moved,
+                ItemKind::Folder,
+                body.folder_id,
+            )
+            .await;
+            notify_item_added(&state, claims.id, ItemKind::Folder, body.folder_id).await;
+            ApiResponse::ok("Folder moved successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder or target folder not found").into_response()
         }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_ROTATION_PENDING => {
+            ApiResponse::conflict(msg).into_response()
+        }
         Err(e) => {
             tracing::error!("Failed to move folder: {:?}", e);
             ApiResponse::internal_error("Failed to move folder").into_response()
@@ -821,7 +1174,18 @@ pub async fn get_file_info_handler(
     };
 
     match repo::get_file_info(&state.db_pool, claims.id, file_id).await {
-        Ok(file_info) => ApiResponse::ok(file_info).into_response(),
+        Ok(file_info) => {
+            // Manifeste des chunks : point d'entrée du téléchargement côté client
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Downloaded,
+                ItemKind::File,
+                file_id,
+            )
+            .await;
+            ApiResponse::ok(file_info).into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File not found or access denied").into_response()
         }
@@ -867,6 +1231,14 @@ pub async fn download_file_handler(
 
     // Track successful download (file exists and chunks are valid)
     crate::metrics::track_file_download(true);
+    log_item_activity(
+        &state,
+        claims.id,
+        ActivityAction::Downloaded,
+        ItemKind::File,
+        file_id,
+    )
+    .await;
     // Créer un stream qui télécharge et envoie chaque chunk
     // buffer_unordered(2) limite le nombre de chunks chargés en avance
     let stream = futures::stream::iter(chunks)
@@ -986,6 +1358,103 @@ pub async fn download_chunk_binary_handler(
     }
 }
 
+/// Version du format de l'archive d'un dossier (à incrémenter si la structure change)
+const FOLDER_ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// Blocs de l'archive en attente d'envoi au client (borne la mémoire si le client lit lentement)
+const FOLDER_ARCHIVE_CHANNEL_CAPACITY: usize = 4;
+
+type ArchiveSender = futures::channel::mpsc::Sender<Result<Bytes, std::io::Error>>;
+
+/// GET /drive/folders/{folder_id}/archive - Télécharge un dossier et son contenu en ZIP.
+/// Les chunks sont transmis chiffrés, avec un manifest des métadonnées et clés chiffrées :
+/// le client déchiffre l'arborescence hors ligne. L'archive est produite au fil de l'eau.
+pub async fn download_folder_archive_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+) -> Response {
+    let (folders, files) = match repo::get_folder_archive_tree(&state.db_pool, claims.id, folder_id)
+        .await
+    {
+        Ok(tree) => tree,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::not_found("Folder not found or access denied").into_response();
+        }
+        Err(e) => {
+            tracing::error!("Failed to load folder archive tree: {:?}", e);
+            return ApiResponse::internal_error("Failed to build folder archive").into_response();
+        }
+    };
+
+    log_item_activity(
+        &state,
+        claims.id,
+        ActivityAction::Downloaded,
+        ItemKind::Folder,
+        folder_id,
+    )
+    .await;
+
+    let (mut sender, receiver) = futures::channel::mpsc::channel(FOLDER_ARCHIVE_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        if let Err(e) = write_folder_archive(&state, folder_id, folders, files, &mut sender).await {
+            // Erreur transmise au corps : la réponse est interrompue, le client voit une archive tronquée
+            tracing::error!("Failed to stream archive of folder {}: {}", folder_id, e);
+            let _ = sender.send(Err(std::io::Error::other(e))).await;
+        }
+    });
+
+    axum::response::Response::builder()
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.zip\"", folder_id),
+        )
+        .body(Body::from_stream(receiver))
+        .unwrap()
+}
+
+/// Écrit les chunks de chaque fichier (`files/<file_id>/<index>.chunk`) puis `manifest.json`
+async fn write_folder_archive(
+    state: &AppState,
+    folder_id: Uuid,
+    folders: Vec<serde_json::Value>,
+    files: Vec<repo::ArchiveFile>,
+    sender: &mut ArchiveSender,
+) -> Result<(), String> {
+    let mut zip = ZipStreamWriter::new();
+    let mut files_json = Vec::with_capacity(files.len());
+
+    for file in &files {
+        let chunks = repo::get_file_chunk_keys(&state.db_pool, file.file_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        let chunks_json = archive::write_file_chunks(
+            &mut zip,
+            sender,
+            &state.storage_client,
+            file.file_id,
+            chunks,
+        )
+        .await?;
+
+        let mut file_json = file.to_json();
+        file_json["chunks"] = serde_json::Value::Array(chunks_json);
+        files_json.push(file_json);
+    }
+
+    let manifest = serde_json::json!({
+        "format_version": FOLDER_ARCHIVE_FORMAT_VERSION,
+        "root_folder_id": folder_id,
+        "created_at": chrono::Utc::now(),
+        "folders": folders,
+        "files": files_json,
+    });
+    archive::write_json_entry(&mut zip, sender, "manifest.json", &manifest).await?;
+    sender.write_archive_bytes(zip.finish()).await
+}
+
 pub async fn get_folder_contents_handler(
     State(state): State<AppState>,
     claims: Claims,
@@ -1063,11 +1532,23 @@ pub async fn finalize_upload_handler(
                         .unwrap_or(0);
 
                     crate::metrics::track_file_upload(true, file_size as u64);
+                    log_item_activity(
+                        &state,
+                        claims.id,
+                        ActivityAction::Uploaded,
+                        ItemKind::File,
+                        file_id,
+                    )
+                    .await;
+                    notify_item_added(&state, claims.id, ItemKind::File, file_id).await;
                     ApiResponse::ok("File upload finalized successfully").into_response()
                 }
                 Err(sqlx::Error::RowNotFound) => {
                     ApiResponse::not_found("File not found or access denied").into_response()
                 }
+                Err(sqlx::Error::Protocol(msg)) if msg == repo::UPLOAD_NOT_IN_PROGRESS => {
+                    ApiResponse::conflict(msg).into_response()
+                }
                 Err(e) => {
                     tracing::error!("Failed to finalize file upload: {:?}", e);
                     crate::metrics::track_file_upload(false, 0);
@@ -1095,8 +1576,22 @@ pub async fn restore_file_handler(
     claims: Claims,
     Json(req): Json<RestoreFileRequest>,
 ) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     match repo::restore_file_from_corbeille(&state.db_pool, claims.id, req.file_id).await {
-        Ok(_) => ApiResponse::ok("File restored successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Restored,
+                ItemKind::File,
+                req.file_id,
+            )
+            .await;
+            ApiResponse::ok("File restored successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File not found in corbeille").into_response()
         }
@@ -1112,8 +1607,22 @@ pub async fn restore_folder_handler(
     claims: Claims,
     Json(req): Json<RestoreFolderRequest>,
 ) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     match repo::restore_folder_from_corbeille(&state.db_pool, claims.id, req.folder_id).await {
-        Ok(_) => ApiResponse::ok("Folder restored successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Restored,
+                ItemKind::Folder,
+                req.folder_id,
+            )
+            .await;
+            ApiResponse::ok("Folder restored successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder not found in corbeille").into_response()
         }
@@ -1139,7 +1648,10 @@ pub struct ShareFolderRequest {
     pub folder_id: Uuid,
     pub contact_id: Uuid,
     pub encrypted_item_key: String,
-    pub access_level: String,
+    pub access_level: AccessLevel,
+    /// Partage temporaire : révoqué automatiquement à cette date
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize)]
@@ -1147,7 +1659,10 @@ pub struct ShareFileRequest {
     pub file_id: Uuid,
     pub contact_id: Uuid,
     pub encrypted_item_key: String,
-    pub access_level: String,
+    pub access_level: AccessLevel,
+    /// Partage temporaire : révoqué automatiquement à cette date
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize)]
@@ -1166,7 +1681,10 @@ pub struct FileKeyBatch {
 pub struct ShareFolderBatchRequest {
     pub folder_id: Uuid,
     pub contact_id: Uuid,
-    pub access_level: String,
+    pub access_level: AccessLevel,
+    /// Partage temporaire : révoqué automatiquement à cette date
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
     pub folder_keys: Vec<FolderKeyBatch>, // Toutes les clés des sous-dossiers rechiffrées
     pub file_keys: Vec<FileKeyBatch>,     // Toutes les clés des fichiers rechiffrées
 }
@@ -1176,17 +1694,32 @@ pub async fn share_folder_handler(
     claims: Claims,
     Json(body): Json<ShareFolderRequest>,
 ) -> Response {
+    let grant = match share_grant(body.access_level, body.expires_at) {
+        Ok(grant) => grant,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
     match repo::share_folder_with_contact(
         &state.db_pool,
         claims.id,
         body.folder_id,
         body.contact_id,
         &body.encrypted_item_key,
-        &body.access_level,
+        grant,
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("Folder shared successfully").into_response(),
+        Ok(_) => {
+            record_share(
+                &state,
+                claims.id,
+                ItemKind::Folder,
+                body.folder_id,
+                body.contact_id,
+            )
+            .await;
+            ApiResponse::ok("Folder shared successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder or contact not found").into_response()
         }
@@ -1203,17 +1736,32 @@ pub async fn share_file_handler(
     claims: Claims,
     Json(body): Json<ShareFileRequest>,
 ) -> Response {
+    let grant = match share_grant(body.access_level, body.expires_at) {
+        Ok(grant) => grant,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
     match repo::share_file_with_contact(
         &state.db_pool,
         claims.id,
         body.file_id,
         body.contact_id,
         &body.encrypted_item_key,
-        &body.access_level,
+        grant,
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("File shared successfully").into_response(),
+        Ok(_) => {
+            record_share(
+                &state,
+                claims.id,
+                ItemKind::File,
+                body.file_id,
+                body.contact_id,
+            )
+            .await;
+            ApiResponse::ok("File shared successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File or contact not found").into_response()
         }
@@ -1230,6 +1778,15 @@ pub async fn share_folder_batch_handler(
     claims: Claims,
     Json(body): Json<ShareFolderBatchRequest>,
 ) -> Response {
+    let grant = match share_grant(body.access_level, body.expires_at) {
+        Ok(grant) => grant,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     // Convertir les vecs en format attendu par repo::share_folder_batch
     let folder_keys: Vec<(Uuid, String)> = body
         .folder_keys
@@ -1248,13 +1805,23 @@ pub async fn share_folder_batch_handler(
         claims.id,
         body.folder_id,
         body.contact_id,
-        &body.access_level,
+        grant,
         folder_keys,
         file_keys,
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("Folder and contents shared successfully").into_response(),
+        Ok(_) => {
+            record_share(
+                &state,
+                claims.id,
+                ItemKind::Folder,
+                body.folder_id,
+                body.contact_id,
+            )
+            .await;
+            ApiResponse::ok("Folder and contents shared successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder or contact not found").into_response()
         }
@@ -1266,29 +1833,6 @@ pub async fn share_folder_batch_handler(
     }
 }
 
-pub async fn get_public_key_handler_by_email(
-    State(state): State<AppState>,
-    _claims: Claims,
-    Path(email): Path<String>,
-) -> Response {
-    let user_info = match crate::auth::repo::get_user_by_email(&state.db_pool, &email).await {
-        Ok(user) => user,
-        Err(sqlx::Error::RowNotFound) => {
-            return ApiResponse::not_found("User not found").into_response();
-        }
-        Err(e) => {
-            tracing::error!("Failed to retrieve user info: {:?}", e);
-            return ApiResponse::internal_error("Failed to retrieve user info").into_response();
-        }
-    };
-    ApiResponse::ok(serde_json::json!({
-        "id": user_info.id,
-        "username": user_info.username,
-        "public_key": user_info.public_key,
-    }))
-    .into_response()
-}
-
 /// Récupère la liste des utilisateurs ayant accès à un dossier (pour le partage dynamique)
 pub async fn get_folder_shared_users_handler(
     State(state): State<AppState>,
@@ -1304,22 +1848,36 @@ pub async fn get_folder_shared_users_handler(
 
     match repo::get_folder_shared_users(&state.db_pool, claims.id, folder_id).await {
         Ok(users) => {
+            // Équipes du dossier : les éléments ajoutés doivent aussi être chiffrés pour elles
+            let shared_teams =
+                match repo::get_folder_shared_teams(&state.db_pool, claims.id, folder_id).await {
+                    Ok(teams) => teams,
+                    Err(e) => {
+                        tracing::error!("Failed to get folder shared teams: {:?}", e);
+                        return ApiResponse::internal_error("Failed to get folder shared users")
+                            .into_response();
+                    }
+                };
+
             // Récupérer les clés publiques pour chaque utilisateur
             let mut users_with_keys = Vec::new();
-            for (user_id, access_level) in users {
+            for shared in users {
                 if let Ok(user_info) =
-                    crate::auth::repo::get_user_by_id(&state.db_pool, user_id).await
+                    crate::auth::repo::get_user_by_id(&state.db_pool, shared.user_id).await
                 {
                     users_with_keys.push(serde_json::json!({
-                        "user_id": user_id,
-                        "access_level": access_level,
+                        "user_id": shared.user_id,
+                        "access_level": shared.access_level,
+                        "expires_at": shared.expires_at,
+                        "team_id": shared.team_id,
                         "public_key": user_info.public_key,
                         "username": user_info.username,
                     }));
                 }
             }
             ApiResponse::ok(serde_json::json!({
-                "shared_users": users_with_keys
+                "shared_users": users_with_keys,
+                "shared_teams": shared_teams,
             }))
             .into_response()
         }
@@ -1350,14 +1908,16 @@ pub async fn get_file_info_item_handler(
         Ok(users) => {
             let mut shared_users_list: Vec<serde_json::Value> = Vec::new();
 
-            for (user_id, access_level) in users {
+            for shared in users {
                 if let Ok(user_info) =
-                    crate::auth::repo::get_user_by_id(&state.db_pool, user_id).await
+                    crate::auth::repo::get_user_by_id(&state.db_pool, shared.user_id).await
                 {
                     // construire la liste enrichie ici
                     shared_users_list.push(serde_json::json!({
-                        "user_id": user_id,
-                        "permission": access_level,
+                        "user_id": shared.user_id,
+                        "permission": shared.access_level,
+                        "expires_at": shared.expires_at,
+                        "team_id": shared.team_id,
                         "public_key": user_info.public_key,
                         "username": user_info.username,
                     }));
@@ -1396,14 +1956,16 @@ pub async fn get_folder_info_item_handler(
         Ok(users) => {
             let mut shared_users_list: Vec<serde_json::Value> = Vec::new();
 
-            for (user_id, access_level) in users {
+            for shared in users {
                 if let Ok(user_info) =
-                    crate::auth::repo::get_user_by_id(&state.db_pool, user_id).await
+                    crate::auth::repo::get_user_by_id(&state.db_pool, shared.user_id).await
                 {
                     // construire la liste enrichie ici
                     shared_users_list.push(serde_json::json!({
-                        "user_id": user_id,
-                        "permission": access_level,
+                        "user_id": shared.user_id,
+                        "permission": shared.access_level,
+                        "expires_at": shared.expires_at,
+                        "team_id": shared.team_id,
                         "public_key": user_info.public_key,
                         "username": user_info.username,
                     }));
@@ -1434,7 +1996,7 @@ pub struct PropagateFileAccessRequest {
 pub struct UserKey {
     pub user_id: Uuid,
     pub encrypted_key: String,
-    pub access_level: String,
+    pub access_level: AccessLevel,
 }
 
 /// Propage les permissions d'un fichier nouvellement créé
@@ -1443,17 +2005,34 @@ pub async fn propagate_file_access_handler(
     claims: Claims,
     Json(body): Json<PropagateFileAccessRequest>,
 ) -> Response {
-    let user_keys: Vec<(Uuid, String, String)> = body
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
+    let user_keys: Vec<(Uuid, String, AccessLevel)> = body
         .user_keys
         .into_iter()
         .map(|uk| (uk.user_id, uk.encrypted_key, uk.access_level))
         .collect();
+    let recipients: Vec<Uuid> = user_keys
+        .iter()
+        .map(|(user_id, _, _)| *user_id)
+        .filter(|user_id| *user_id != claims.id)
+        .collect();
 
     match repo::propagate_file_access(&state.db_pool, claims.id, body.file_id, user_keys).await {
-        Ok(_) => ApiResponse::ok("File access propagated successfully").into_response(),
+        Ok(_) => {
+            let event = NotificationEvent::FileAdded {
+                file_id: body.file_id,
+                added_by: claims.id,
+            };
+            notifications::publish(&state.redis_manager, &recipients, &event).await;
+            ApiResponse::ok("File access propagated successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File not found or access denied").into_response()
         }
+        Err(sqlx::Error::Protocol(msg)) => ApiResponse::bad_request(&msg).into_response(),
         Err(e) => {
             tracing::error!("Failed to propagate file access: {:?}", e);
             ApiResponse::internal_error("Failed to propagate file access").into_response()
@@ -1473,18 +2052,35 @@ pub async fn propagate_folder_access_handler(
     claims: Claims,
     Json(body): Json<PropagateFolderAccessRequest>,
 ) -> Response {
-    let user_keys: Vec<(Uuid, String, String)> = body
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
+    let user_keys: Vec<(Uuid, String, AccessLevel)> = body
         .user_keys
         .into_iter()
         .map(|uk| (uk.user_id, uk.encrypted_key, uk.access_level))
         .collect();
+    let recipients: Vec<Uuid> = user_keys
+        .iter()
+        .map(|(user_id, _, _)| *user_id)
+        .filter(|user_id| *user_id != claims.id)
+        .collect();
 
     match repo::propagate_folder_access(&state.db_pool, claims.id, body.folder_id, user_keys).await
     {
-        Ok(_) => ApiResponse::ok("Folder access propagated successfully").into_response(),
+        Ok(_) => {
+            let event = NotificationEvent::FolderAdded {
+                folder_id: body.folder_id,
+                added_by: claims.id,
+            };
+            notifications::publish(&state.redis_manager, &recipients, &event).await;
+            ApiResponse::ok("Folder access propagated successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder not found or access denied").into_response()
         }
+        Err(sqlx::Error::Protocol(msg)) => ApiResponse::bad_request(&msg).into_response(),
         Err(e) => {
             tracing::error!("Failed to propagate folder access: {:?}", e);
             ApiResponse::internal_error("Failed to propagate folder access").into_response()
@@ -1536,6 +2132,9 @@ pub struct RevokeAccessRequest {
     item_type: String,
     item_id: String,
     contact_id: String,
+    /// Dossier uniquement : passe le dossier en attente de rotation de clé
+    #[serde(default)]
+    rotate: bool,
 }
 
 pub async fn revoke_access_handler(
@@ -1556,13 +2155,26 @@ pub async fn revoke_access_handler(
         }
     };
 
-    let result = match body.item_type.as_str() {
-        "file" => {
-            repo::revoke_file_access(&state.db_pool, claims.id, item_uuid, contact_uuid).await
-        }
-        "folder" => {
-            repo::revoke_folder_access(&state.db_pool, claims.id, item_uuid, contact_uuid).await
+    let (kind, result) = match body.item_type.as_str() {
+        "file" if body.rotate => {
+            return ApiResponse::bad_request("Key rotation is only available for folders")
+                .into_response();
         }
+        "file" => (
+            ItemKind::File,
+            repo::revoke_file_access(&state.db_pool, claims.id, item_uuid, contact_uuid).await,
+        ),
+        "folder" => (
+            ItemKind::Folder,
+            repo::revoke_folder_access(
+                &state.db_pool,
+                claims.id,
+                item_uuid,
+                contact_uuid,
+                body.rotate,
+            )
+            .await,
+        ),
         _ => {
             return ApiResponse::bad_request("Invalid item_type (expected 'file' or 'folder')")
                 .into_response();
@@ -1570,7 +2182,13 @@ pub async fn revoke_access_handler(
     };
 
     match result {
-        Ok(_) => ApiResponse::ok("Access revoked successfully").into_response(),
+        Ok(_) => {
+            let entry = ActivityEntry::new(claims.id, ActivityAction::Revoked)
+                .on_item(kind, item_uuid)
+                .target_user(contact_uuid);
+            activity::services::record(&state.db_pool, entry).await;
+            ApiResponse::ok("Access revoked successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Item or contact not found").into_response()
         }
@@ -1590,7 +2208,17 @@ pub async fn delete_file_restful_handler(
     Path(file_id): Path<Uuid>,
 ) -> Response {
     match repo::delete_file(&state.db_pool, &state.storage_client, claims.id, file_id).await {
-        Ok(_) => ApiResponse::ok("File deleted successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Deleted,
+                ItemKind::File,
+                file_id,
+            )
+            .await;
+            ApiResponse::ok("File deleted successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("File not found").into_response(),
         Err(e) => {
             tracing::error!("Failed to delete file: {:?}", e);
@@ -1606,7 +2234,17 @@ pub async fn delete_folder_restful_handler(
     Path(folder_id): Path<Uuid>,
 ) -> Response {
     match repo::delete_folder(&state.db_pool, claims.id, folder_id).await {
-        Ok(_) => ApiResponse::ok("Folder deleted successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Deleted,
+                ItemKind::Folder,
+                folder_id,
+            )
+            .await;
+            ApiResponse::ok("Folder deleted successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("Folder not found").into_response(),
         Err(e) => {
             tracing::error!("Failed to delete folder: {:?}", e);
@@ -1622,7 +2260,10 @@ pub struct ShareFileRestfulRequest {
     pub recipient_user_id: Uuid,
     #[serde(rename = "encrypted_item_key")]
     pub encrypted_file_key: String,
-    pub access_level: String,
+    pub access_level: AccessLevel,
+    /// Partage temporaire : révoqué automatiquement à cette date
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 pub async fn share_file_restful_handler(
@@ -1631,17 +2272,36 @@ pub async fn share_file_restful_handler(
     Path(file_id): Path<Uuid>,
     Json(body): Json<ShareFileRestfulRequest>,
 ) -> Response {
+    let grant = match share_grant(body.access_level, body.expires_at) {
+        Ok(grant) => grant,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     match repo::share_file_with_contact(
         &state.db_pool,
         claims.id,
         file_id,
         body.recipient_user_id,
         &body.encrypted_file_key,
-        &body.access_level,
+        grant,
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("File shared successfully").into_response(),
+        Ok(_) => {
+            record_share(
+                &state,
+                claims.id,
+                ItemKind::File,
+                file_id,
+                body.recipient_user_id,
+            )
+            .await;
+            ApiResponse::ok("File shared successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File or contact not found").into_response()
         }
@@ -1660,7 +2320,10 @@ pub struct ShareFolderRestfulRequest {
     pub recipient_user_id: Uuid,
     #[serde(rename = "encrypted_item_key")]
     pub encrypted_folder_key: String,
-    pub access_level: String,
+    pub access_level: AccessLevel,
+    /// Partage temporaire : révoqué automatiquement à cette date
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 pub async fn share_folder_restful_handler(
@@ -1669,17 +2332,32 @@ pub async fn share_folder_restful_handler(
     Path(folder_id): Path<Uuid>,
     Json(body): Json<ShareFolderRestfulRequest>,
 ) -> Response {
+    let grant = match share_grant(body.access_level, body.expires_at) {
+        Ok(grant) => grant,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
     match repo::share_folder_with_contact(
         &state.db_pool,
         claims.id,
         folder_id,
         body.recipient_user_id,
         &body.encrypted_folder_key,
-        &body.access_level,
+        grant,
     )
     .await
     {
-        Ok(_) => ApiResponse::ok("Folder shared successfully").into_response(),
+        Ok(_) => {
+            record_share(
+                &state,
+                claims.id,
+                ItemKind::Folder,
+                folder_id,
+                body.recipient_user_id,
+            )
+            .await;
+            ApiResponse::ok("Folder shared successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder or contact not found").into_response()
         }
@@ -1698,7 +2376,16 @@ pub async fn revoke_file_access_handler(
     Path((file_id, user_id)): Path<(Uuid, Uuid)>,
 ) -> Response {
     match repo::revoke_file_access(&state.db_pool, claims.id, file_id, user_id).await {
-        Ok(_) => ApiResponse::ok("File access revoked successfully").into_response(),
+        Ok(_) => {
+            activity::services::record(
+                &state.db_pool,
+                ActivityEntry::new(claims.id, ActivityAction::Revoked)
+                    .on_item(ItemKind::File, file_id)
+                    .target_user(user_id),
+            )
+            .await;
+            ApiResponse::ok("File access revoked successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File or user not found").into_response()
         }
@@ -1709,14 +2396,33 @@ pub async fn revoke_file_access_handler(
     }
 }
 
-/// DELETE /folders/{folder_id}/share/{user_id} - Révoquer accès dossier (RESTful)
+#[derive(Deserialize)]
+pub struct RevokeFolderAccessQuery {
+    #[serde(default)]
+    pub rotate: bool,
+}
+
+/// DELETE /folders/{folder_id}/share/{user_id}?rotate=true - Révoquer accès dossier (RESTful),
+/// avec rotation de clé optionnelle
 pub async fn revoke_folder_access_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path((folder_id, user_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<RevokeFolderAccessQuery>,
 ) -> Response {
-    match repo::revoke_folder_access(&state.db_pool, claims.id, folder_id, user_id).await {
-        Ok(_) => ApiResponse::ok("Folder access revoked successfully").into_response(),
+    match repo::revoke_folder_access(&state.db_pool, claims.id, folder_id, user_id, query.rotate)
+        .await
+    {
+        Ok(_) => {
+            activity::services::record(
+                &state.db_pool,
+                ActivityEntry::new(claims.id, ActivityAction::Revoked)
+                    .on_item(ItemKind::Folder, folder_id)
+                    .target_user(user_id),
+            )
+            .await;
+            ApiResponse::ok("Folder access revoked successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder or user not found").into_response()
         }
@@ -1727,32 +2433,270 @@ pub async fn revoke_folder_access_handler(
     }
 }
 
-// ========== PATCH Handlers for Rename ==========
+// ========== Partages d'équipe ==========
 
 #[derive(Deserialize)]
-pub struct RenameItemRequest {
-    pub new_encrypted_metadata: String,
+pub struct ShareFolderWithTeamRequest {
+    pub access_level: AccessLevel,
+    /// Clés du dossier et de ses sous-dossiers, chiffrées avec la clé publique de l'équipe
+    pub folder_keys: Vec<FolderKeyBatch>,
+    /// Clés des fichiers de l'arborescence, chiffrées avec la clé publique de l'équipe
+    pub file_keys: Vec<FileKeyBatch>,
 }
 
-/// PATCH /files/{file_id} - Renommer fichier
-pub async fn rename_file_restful_handler(
+/// POST /folders/{folder_id}/teams/{team_id} - Partager un dossier et son contenu avec une équipe
+pub async fn share_folder_with_team_handler(
     State(state): State<AppState>,
     claims: Claims,
-    Path(file_id): Path<Uuid>,
-    Json(body): Json<RenameItemRequest>,
+    Path((folder_id, team_id)): Path<(Uuid, Uuid)>,
+    Json(body): Json<ShareFolderWithTeamRequest>,
 ) -> Response {
-    match repo::rename_file(
-        &state.db_pool,
-        claims.id,
-        file_id,
-        &body.new_encrypted_metadata,
-    )
-    .await
-    {
-        Ok(_) => ApiResponse::ok("File renamed successfully").into_response(),
-        Err(sqlx::Error::RowNotFound) => {
-            ApiResponse::not_found("File not found or access denied").into_response()
-        }
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
+    let folder_keys: Vec<(Uuid, String)> = body
+        .folder_keys
+        .into_iter()
+        .map(|fk| (fk.folder_id, fk.encrypted_folder_key))
+        .collect();
+
+    let file_keys: Vec<(Uuid, String)> = body
+        .file_keys
+        .into_iter()
+        .map(|fk| (fk.file_id, fk.encrypted_file_key))
+        .collect();
+
+    match repo::share_folder_with_team(
+        &state.db_pool,
+        claims.id,
+        folder_id,
+        team_id,
+        body.access_level,
+        folder_keys,
+        file_keys,
+    )
+    .await
+    {
+        Ok(()) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::SharedWithTeam,
+                ItemKind::Folder,
+                folder_id,
+            )
+            .await;
+
+            if let Ok(member_ids) =
+                crate::teams::repo::get_member_ids(&state.db_pool, team_id).await
+            {
+                let recipients: Vec<Uuid> = member_ids
+                    .into_iter()
+                    .filter(|id| *id != claims.id)
+                    .collect();
+                let event = NotificationEvent::TeamFolderShared {
+                    team_id,
+                    folder_id,
+                    from_user_id: claims.id,
+                };
+                notifications::publish(&state.redis_manager, &recipients, &event).await;
+            }
+
+            ApiResponse::ok("Folder and contents shared with team").into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Folder or team not found").into_response()
+        }
+        Err(sqlx::Error::Protocol(msg)) => ApiResponse::bad_request(&msg).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to share folder with team: {:?}", e);
+            ApiResponse::internal_error("Failed to share folder with team").into_response()
+        }
+    }
+}
+
+/// DELETE /folders/{folder_id}/teams/{team_id}?rotate=true - Révoquer l'accès d'une équipe,
+/// avec rotation de clé optionnelle
+pub async fn revoke_team_folder_access_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((folder_id, team_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<RevokeFolderAccessQuery>,
+) -> Response {
+    match repo::revoke_team_folder_access(
+        &state.db_pool,
+        claims.id,
+        folder_id,
+        team_id,
+        query.rotate,
+    )
+    .await
+    {
+        Ok(()) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::RevokedFromTeam,
+                ItemKind::Folder,
+                folder_id,
+            )
+            .await;
+            ApiResponse::ok("Team folder access revoked successfully").into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Folder not found or access denied").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to revoke team folder access: {:?}", e);
+            ApiResponse::internal_error("Failed to revoke team folder access").into_response()
+        }
+    }
+}
+
+// ========== Rotation des clés de dossier ==========
+
+/// GET /folders/{folder_id}/keys - Clés du dossier détenues par l'utilisateur (courante et anciennes)
+pub async fn get_folder_keys_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+) -> Response {
+    match repo::get_folder_keys(&state.db_pool, claims.id, folder_id).await {
+        Ok(keys) => ApiResponse::ok(keys).into_response(),
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Folder not found or access denied").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to get folder keys {}: {:?}", folder_id, e);
+            ApiResponse::internal_error("Failed to get folder keys").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RotateFolderKeyRequest {
+    /// Version courante connue du client (celle qu'il remplace)
+    pub key_version: i32,
+    /// Nouvelle clé chiffrée pour chaque membre du dossier, appelant compris
+    pub keys: Vec<MemberKey>,
+    /// Nouvelle clé chiffrée pour chaque équipe à laquelle le dossier est partagé
+    #[serde(default)]
+    pub team_keys: Vec<TeamKey>,
+    /// Nouvelles clés des sous-dossiers, requises pour chaque sous-dossier en attente de rotation
+    #[serde(default)]
+    pub subfolders: Vec<SubfolderKeyRotation>,
+}
+
+#[derive(Deserialize)]
+pub struct SubfolderKeyRotation {
+    pub folder_id: Uuid,
+    pub key_version: i32,
+    pub keys: Vec<MemberKey>,
+    #[serde(default)]
+    pub team_keys: Vec<TeamKey>,
+}
+
+/// POST /folders/{folder_id}/rotate-key - Distribuer une nouvelle version de la clé du dossier
+/// et de ses sous-dossiers
+pub async fn rotate_folder_key_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+    Json(body): Json<RotateFolderKeyRequest>,
+) -> Response {
+    let root = repo::FolderKeyRotation {
+        folder_id,
+        expected_version: body.key_version,
+        new_keys: member_keys_to_tuples(body.keys),
+        team_keys: team_keys_to_tuples(body.team_keys),
+    };
+    let rotations: Vec<repo::FolderKeyRotation> = std::iter::once(root)
+        .chain(
+            body.subfolders
+                .into_iter()
+                .map(|sub| repo::FolderKeyRotation {
+                    folder_id: sub.folder_id,
+                    expected_version: sub.key_version,
+                    new_keys: member_keys_to_tuples(sub.keys),
+                    team_keys: team_keys_to_tuples(sub.team_keys),
+                }),
+        )
+        .collect();
+    match repo::rotate_folder_key(&state.db_pool, claims.id, folder_id, &rotations).await {
+        Ok(mut rotated) => {
+            for folder in &rotated {
+                log_item_activity(
+                    &state,
+                    claims.id,
+                    ActivityAction::KeyRotated,
+                    ItemKind::Folder,
+                    folder.folder_id,
+                )
+                .await;
+            }
+            // Le dossier visé est toujours le premier de la liste
+            let root = rotated.remove(0);
+            ApiResponse::ok(serde_json::json!({
+                "key_version": root.key_version,
+                "subfolders": rotated,
+            }))
+            .into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Folder not found or access denied").into_response()
+        }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_VERSION_CONFLICT => {
+            ApiResponse::conflict(msg).into_response()
+        }
+        Err(sqlx::Error::Protocol(msg)) => ApiResponse::bad_request(&msg).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to rotate folder key {}: {:?}", folder_id, e);
+            ApiResponse::internal_error("Failed to rotate folder key").into_response()
+        }
+    }
+}
+
+// ========== PATCH Handlers for Rename ==========
+
+#[derive(Deserialize)]
+pub struct RenameItemRequest {
+    pub new_encrypted_metadata: String,
+}
+
+/// PATCH /files/{file_id} - Renommer fichier
+pub async fn rename_file_restful_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<Uuid>,
+    Json(body): Json<RenameItemRequest>,
+) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
+    match repo::rename_file(
+        &state.db_pool,
+        claims.id,
+        file_id,
+        &body.new_encrypted_metadata,
+    )
+    .await
+    {
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Renamed,
+                ItemKind::File,
+                file_id,
+            )
+            .await;
+            ApiResponse::ok("File renamed successfully").into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("File not found or access denied").into_response()
+        }
         Err(e) => {
             tracing::error!("Failed to rename file {}: {:?}", file_id, e);
             ApiResponse::internal_error("Failed to rename file").into_response()
@@ -1760,30 +2704,692 @@ pub async fn rename_file_restful_handler(
     }
 }
 
-/// PATCH /folders/{folder_id} - Renommer dossier
-pub async fn rename_folder_restful_handler(
+/// PATCH /folders/{folder_id} - Renommer dossier
+pub async fn rename_folder_restful_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+    Json(body): Json<RenameItemRequest>,
+) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
+    match repo::rename_folder(
+        &state.db_pool,
+        claims.id,
+        folder_id,
+        &body.new_encrypted_metadata,
+    )
+    .await
+    {
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Renamed,
+                ItemKind::Folder,
+                folder_id,
+            )
+            .await;
+            ApiResponse::ok("Folder renamed successfully").into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Folder not found or access denied").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to rename folder {}: {:?}", folder_id, e);
+            ApiResponse::internal_error("Failed to rename folder").into_response()
+        }
+    }
+}
+
+// ========== Favoris, couleurs et tags ==========
+
+#[derive(Deserialize)]
+pub struct ColorLabelRequest {
+    /// Couleur parmi `services::COLOR_LABELS`, null pour la retirer
+    pub color_label: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TagRequest {
+    /// Nom du tag chiffré côté client
+    pub encrypted_name: String,
+}
+
+fn label_error(e: sqlx::Error, context: &str) -> Response {
+    match e {
+        sqlx::Error::RowNotFound => ApiResponse::not_found("Item not found").into_response(),
+        sqlx::Error::Protocol(msg) if msg == repo::TAG_NOT_FOUND => {
+            ApiResponse::not_found(msg).into_response()
+        }
+        e => {
+            tracing::error!("{}: {:?}", context, e);
+            ApiResponse::internal_error(context).into_response()
+        }
+    }
+}
+
+/// Vue transversale (favoris, tag) au format de `get_file_folder`
+async fn labelled_view_response(
+    state: &AppState,
+    user_id: Uuid,
+    view: repo::LabelView<'_>,
+) -> Response {
+    let files_and_folders = match repo::get_labelled_items(&state.db_pool, user_id, view).await {
+        Ok(list) => list,
+        Err(e) => return label_error(e, "Failed to retrieve labelled items"),
+    };
+
+    let drive_info = match repo::get_drive_info(&state.db_pool, user_id).await {
+        Ok(info) => info,
+        Err(e) => {
+            tracing::error!("Failed to retrieve drive info: {:?}", e);
+            return ApiResponse::internal_error("Failed to retrieve drive info").into_response();
+        }
+    };
+
+    ApiResponse::ok(serde_json::json!({
+        "files_and_folders": files_and_folders,
+        "drive_info": {
+            "used_space": drive_info.used_space,
+            "file_count": drive_info.file_count,
+            "folder_count": drive_info.folder_count,
+            "storage_limit_bytes": drive_info.storage_limit_bytes,
+            "account_tier": drive_info.account_tier,
+        },
+        "full_path": [],
+    }))
+    .into_response()
+}
+
+async fn set_favorite(
+    state: &AppState,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    is_favorite: bool,
+) -> Response {
+    match repo::set_favorite(&state.db_pool, user_id, kind, item_id, is_favorite).await {
+        Ok(()) => {
+            ApiResponse::ok(serde_json::json!({ "is_favorite": is_favorite })).into_response()
+        }
+        Err(e) => label_error(e, "Failed to update favorite"),
+    }
+}
+
+/// PUT /drive/files/{file_id}/favorite - Ajoute le fichier aux favoris de l'utilisateur
+pub async fn favorite_file_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<Uuid>,
+) -> Response {
+    set_favorite(&state, claims.id, ItemKind::File, file_id, true).await
+}
+
+/// DELETE /drive/files/{file_id}/favorite
+pub async fn unfavorite_file_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<Uuid>,
+) -> Response {
+    set_favorite(&state, claims.id, ItemKind::File, file_id, false).await
+}
+
+/// PUT /drive/folders/{folder_id}/favorite - Ajoute le dossier aux favoris de l'utilisateur
+pub async fn favorite_folder_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+) -> Response {
+    set_favorite(&state, claims.id, ItemKind::Folder, folder_id, true).await
+}
+
+/// DELETE /drive/folders/{folder_id}/favorite
+pub async fn unfavorite_folder_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+) -> Response {
+    set_favorite(&state, claims.id, ItemKind::Folder, folder_id, false).await
+}
+
+async fn set_color_label(
+    state: &AppState,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    color_label: Option<String>,
+) -> Response {
+    if color_label
+        .as_deref()
+        .is_some_and(|label| !services::is_valid_color_label(label))
+    {
+        return ApiResponse::bad_request(services::INVALID_COLOR_LABEL).into_response();
+    }
+
+    match repo::set_color_label(
+        &state.db_pool,
+        user_id,
+        kind,
+        item_id,
+        color_label.as_deref(),
+    )
+    .await
+    {
+        Ok(()) => {
+            ApiResponse::ok(serde_json::json!({ "color_label": color_label })).into_response()
+        }
+        Err(e) => label_error(e, "Failed to update color label"),
+    }
+}
+
+/// PUT /drive/files/{file_id}/color - Couleur du fichier pour l'utilisateur
+pub async fn set_file_color_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<Uuid>,
+    Json(body): Json<ColorLabelRequest>,
+) -> Response {
+    set_color_label(&state, claims.id, ItemKind::File, file_id, body.color_label).await
+}
+
+/// PUT /drive/folders/{folder_id}/color - Couleur du dossier pour l'utilisateur
+pub async fn set_folder_color_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+    Json(body): Json<ColorLabelRequest>,
+) -> Response {
+    set_color_label(
+        &state,
+        claims.id,
+        ItemKind::Folder,
+        folder_id,
+        body.color_label,
+    )
+    .await
+}
+
+/// GET /drive/tags - Tags de l'utilisateur (noms chiffrés) et nombre d'éléments
+pub async fn list_tags_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::list_tags(&state.db_pool, claims.id).await {
+        Ok(tags) => ApiResponse::ok(serde_json::json!({ "tags": tags })).into_response(),
+        Err(e) => label_error(e, "Failed to retrieve tags"),
+    }
+}
+
+/// PUT /drive/tags/{token} - Crée un tag ou remplace son nom chiffré
+pub async fn upsert_tag_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(token): Path<String>,
+    Json(body): Json<TagRequest>,
+) -> Response {
+    if !services::is_valid_tag_token(&token) {
+        return ApiResponse::bad_request(services::INVALID_TAG_TOKEN).into_response();
+    }
+    if !services::is_valid_encrypted_tag_name(&body.encrypted_name) {
+        return ApiResponse::bad_request(services::INVALID_TAG_NAME).into_response();
+    }
+
+    match repo::upsert_tag(&state.db_pool, claims.id, &token, &body.encrypted_name).await {
+        Ok(()) => ApiResponse::ok("Tag saved").into_response(),
+        Err(e) => label_error(e, "Failed to save tag"),
+    }
+}
+
+/// DELETE /drive/tags/{token} - Supprime le tag et le retire de tous les éléments
+pub async fn delete_tag_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(token): Path<String>,
+) -> Response {
+    match repo::delete_tag(&state.db_pool, claims.id, &token).await {
+        Ok(()) => ApiResponse::ok("Tag deleted").into_response(),
+        Err(e) => label_error(e, "Failed to delete tag"),
+    }
+}
+
+/// GET /drive/tags/{token}/items - Fichiers et dossiers portant le tag
+pub async fn get_tag_items_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(token): Path<String>,
+) -> Response {
+    labelled_view_response(&state, claims.id, repo::LabelView::Tag(&token)).await
+}
+
+async fn set_item_tag(
+    state: &AppState,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    token: &str,
+    tagged: bool,
+) -> Response {
+    let result = if tagged {
+        repo::tag_item(&state.db_pool, user_id, kind, item_id, token).await
+    } else {
+        repo::untag_item(&state.db_pool, user_id, kind, item_id, token).await
+    };
+    match result {
+        Ok(()) => ApiResponse::ok(if tagged { "Tag added" } else { "Tag removed" }).into_response(),
+        Err(e) => label_error(e, "Failed to update item tags"),
+    }
+}
+
+/// PUT /drive/files/{file_id}/tags/{token} - Ajoute un tag existant au fichier
+pub async fn tag_file_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((file_id, token)): Path<(Uuid, String)>,
+) -> Response {
+    set_item_tag(&state, claims.id, ItemKind::File, file_id, &token, true).await
+}
+
+/// DELETE /drive/files/{file_id}/tags/{token}
+pub async fn untag_file_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((file_id, token)): Path<(Uuid, String)>,
+) -> Response {
+    set_item_tag(&state, claims.id, ItemKind::File, file_id, &token, false).await
+}
+
+/// PUT /drive/folders/{folder_id}/tags/{token} - Ajoute un tag existant au dossier
+pub async fn tag_folder_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((folder_id, token)): Path<(Uuid, String)>,
+) -> Response {
+    set_item_tag(&state, claims.id, ItemKind::Folder, folder_id, &token, true).await
+}
+
+/// DELETE /drive/folders/{folder_id}/tags/{token}
+pub async fn untag_folder_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((folder_id, token)): Path<(Uuid, String)>,
+) -> Response {
+    set_item_tag(
+        &state,
+        claims.id,
+        ItemKind::Folder,
+        folder_id,
+        &token,
+        false,
+    )
+    .await
+}
+
+// ========== Recherche ==========
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    /// Jeton aveugle du nom recherché, calculé par le client
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct SearchTokenRequest {
+    /// null pour retirer l'élément de la recherche
+    pub search_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateSearchIndexRequest {
+    /// Version de l'index sur laquelle le client a construit sa mise à jour (0 : aucun index)
+    pub base_version: i64,
+    pub chunk_count: i32,
+    /// Chunks modifiés seulement ; les autres chunks sous `chunk_count` sont conservés
+    #[serde(default)]
+    pub chunks: Vec<services::SearchIndexChunkUpload>,
+}
+
+fn search_index_error(e: sqlx::Error, context: &str) -> Response {
+    match e {
+        sqlx::Error::Protocol(msg) if msg == repo::SEARCH_INDEX_VERSION_CONFLICT => {
+            ApiResponse::conflict(msg).into_response()
+        }
+        sqlx::Error::Protocol(msg) if msg == repo::SEARCH_INDEX_INCOMPLETE => {
+            ApiResponse::bad_request(msg).into_response()
+        }
+        e => {
+            tracing::error!("{}: {:?}", context, e);
+            ApiResponse::internal_error(context).into_response()
+        }
+    }
+}
+
+/// GET /drive/search?token= - Éléments accessibles dont le nom correspond au jeton
+pub async fn search_handler(
     State(state): State<AppState>,
     claims: Claims,
-    Path(folder_id): Path<Uuid>,
-    Json(body): Json<RenameItemRequest>,
+    Query(query): Query<SearchQuery>,
 ) -> Response {
-    match repo::rename_folder(
+    if !services::is_valid_search_token(&query.token) {
+        return ApiResponse::bad_request(services::INVALID_SEARCH_TOKEN).into_response();
+    }
+
+    match repo::search_by_token(&state.db_pool, claims.id, &query.token).await {
+        Ok(results) => ApiResponse::ok(results).into_response(),
+        Err(e) => search_index_error(e, "Failed to search drive"),
+    }
+}
+
+async fn set_search_token(
+    state: &AppState,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    search_token: Option<String>,
+) -> Response {
+    if search_token
+        .as_deref()
+        .is_some_and(|token| !services::is_valid_search_token(token))
+    {
+        return ApiResponse::bad_request(services::INVALID_SEARCH_TOKEN).into_response();
+    }
+
+    match repo::set_search_token(
         &state.db_pool,
+        user_id,
+        kind,
+        item_id,
+        search_token.as_deref(),
+    )
+    .await
+    {
+        Ok(()) => {
+            ApiResponse::ok(serde_json::json!({ "search_token": search_token })).into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => ApiResponse::not_found("Item not found").into_response(),
+        Err(e) => search_index_error(e, "Failed to update search token"),
+    }
+}
+
+/// PUT /drive/files/{file_id}/search-token - Jeton de recherche du nom du fichier
+pub async fn set_file_search_token_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<Uuid>,
+    Json(body): Json<SearchTokenRequest>,
+) -> Response {
+    set_search_token(
+        &state,
+        claims.id,
+        ItemKind::File,
+        file_id,
+        body.search_token,
+    )
+    .await
+}
+
+/// PUT /drive/folders/{folder_id}/search-token - Jeton de recherche du nom du dossier
+pub async fn set_folder_search_token_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+    Json(body): Json<SearchTokenRequest>,
+) -> Response {
+    set_search_token(
+        &state,
         claims.id,
+        ItemKind::Folder,
         folder_id,
-        &body.new_encrypted_metadata,
+        body.search_token,
     )
     .await
-    {
-        Ok(_) => ApiResponse::ok("Folder renamed successfully").into_response(),
+}
+
+/// GET /drive/search-index - Version de l'index et version de chaque chunk
+pub async fn get_search_index_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::get_search_index(&state.db_pool, claims.id).await {
+        Ok(index) => ApiResponse::ok(index).into_response(),
+        Err(e) => search_index_error(e, "Failed to retrieve search index"),
+    }
+}
+
+/// GET /drive/search-index/chunks/{chunk_index} - Contenu chiffré d'un chunk
+pub async fn get_search_index_chunk_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(chunk_index): Path<i32>,
+) -> Response {
+    match repo::get_search_index_chunk(&state.db_pool, claims.id, chunk_index).await {
+        Ok(chunk) => ApiResponse::ok(chunk).into_response(),
         Err(sqlx::Error::RowNotFound) => {
-            ApiResponse::not_found("Folder not found or access denied").into_response()
+            ApiResponse::not_found("Search index chunk not found").into_response()
         }
+        Err(e) => search_index_error(e, "Failed to retrieve search index chunk"),
+    }
+}
+
+/// PUT /drive/search-index - Met à jour l'index si sa version est toujours `base_version`
+/// (409 sinon : le client recharge l'index, fusionne et renvoie)
+pub async fn update_search_index_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(body): Json<UpdateSearchIndexRequest>,
+) -> Response {
+    if let Err(msg) = services::validate_search_index_update(body.chunk_count, &body.chunks) {
+        return ApiResponse::bad_request(msg).into_response();
+    }
+
+    match repo::update_search_index(
+        &state.db_pool,
+        claims.id,
+        body.base_version,
+        body.chunk_count,
+        &body.chunks,
+    )
+    .await
+    {
+        Ok(version) => ApiResponse::ok(serde_json::json!({
+            "version": version,
+            "chunk_count": body.chunk_count,
+        }))
+        .into_response(),
+        Err(e) => search_index_error(e, "Failed to update search index"),
+    }
+}
+
+/// DELETE /drive/search-index - Vide l'index (à reconstruire par le client)
+pub async fn delete_search_index_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Response {
+    match repo::delete_search_index(&state.db_pool, claims.id).await {
+        Ok(()) => ApiResponse::ok("Search index cleared").into_response(),
+        Err(e) => search_index_error(e, "Failed to delete search index"),
+    }
+}
+
+// ========== Synchronisation ==========
+
+/// Taille de page par défaut / maximale du journal des changements
+const DEFAULT_CHANGES_PAGE_SIZE: i64 = 500;
+const MAX_CHANGES_PAGE_SIZE: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct DriveChangesQuery {
+    pub cursor: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// GET /drive/changes?cursor=&limit= - Changements du drive depuis `cursor`.
+/// Sans curseur (ou curseur expiré), `reset_required` demande au client une resynchronisation
+/// complète, puis de reprendre à partir du `cursor` renvoyé.
+pub async fn get_drive_changes_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<DriveChangesQuery>,
+) -> Response {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_CHANGES_PAGE_SIZE)
+        .clamp(1, MAX_CHANGES_PAGE_SIZE);
+
+    match repo::get_drive_changes(&state.db_pool, claims.id, query.cursor, limit).await {
+        Ok(page) => ApiResponse::ok(page).into_response(),
         Err(e) => {
-            tracing::error!("Failed to rename folder {}: {:?}", folder_id, e);
-            ApiResponse::internal_error("Failed to rename folder").into_response()
+            tracing::error!("Failed to retrieve drive changes: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve drive changes").into_response()
+        }
+    }
+}
+
+// ========== Opérations groupées ==========
+
+/// Nombre maximum d'opérations par lot
+const MAX_BATCH_OPERATIONS: usize = 500;
+
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    pub operations: Vec<repo::BatchOperation>,
+    /// Tout ou rien : le premier échec annule le lot entier
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct BatchResponse {
+    pub results: Vec<BatchItemResult>,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Message d'erreur d'une opération de lot (mêmes messages que les endpoints unitaires)
+fn batch_error_message(operation: &repo::BatchOperation, error: sqlx::Error) -> String {
+    use repo::BatchOperation as Op;
+
+    match (operation, error) {
+        (_, sqlx::Error::Protocol(msg)) => msg,
+        (Op::MoveFile { .. }, sqlx::Error::RowNotFound) => "File or target folder not found".into(),
+        (Op::MoveFolder { .. }, sqlx::Error::RowNotFound) => {
+            "Folder or target folder not found".into()
+        }
+        (Op::DeleteFile { .. }, sqlx::Error::RowNotFound) => "File not found".into(),
+        (Op::DeleteFolder { .. }, sqlx::Error::RowNotFound) => "Folder not found".into(),
+        (Op::RestoreFile { .. }, sqlx::Error::RowNotFound) => "File not found in corbeille".into(),
+        (Op::RestoreFolder { .. }, sqlx::Error::RowNotFound) => {
+            "Folder not found in corbeille".into()
+        }
+        (_, e) => {
+            tracing::error!("Batch operation {:?} failed: {:?}", operation, e);
+            "Operation failed".into()
+        }
+    }
+}
+
+/// Action journalisée pour une opération de lot réussie
+fn batch_activity(operation: &repo::BatchOperation) -> (ActivityAction, ItemKind, Uuid) {
+    use repo::BatchOperation as Op;
+
+    match *operation {
+        Op::MoveFile { file_id, .. } => (ActivityAction::Moved, ItemKind::File, file_id),
+        Op::MoveFolder { folder_id, .. } => (ActivityAction::Moved, ItemKind::Folder, folder_id),
+        Op::DeleteFile { file_id } => (ActivityAction::Deleted, ItemKind::File, file_id),
+        Op::DeleteFolder { folder_id } => (ActivityAction::Deleted, ItemKind::Folder, folder_id),
+        Op::RestoreFile { file_id } => (ActivityAction::Restored, ItemKind::File, file_id),
+        Op::RestoreFolder { folder_id } => (ActivityAction::Restored, ItemKind::Folder, folder_id),
+    }
+}
+
+/// POST /drive/batch - Déplace, supprime ou restaure plusieurs éléments en une requête.
+/// Retourne un résultat par opération ; en mode `atomic`, un échec annule tout le lot (409).
+pub async fn batch_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(body): Json<BatchRequest>,
+) -> Response {
+    if body.operations.is_empty() {
+        return ApiResponse::bad_request("No operations provided").into_response();
+    }
+    if body.operations.len() > MAX_BATCH_OPERATIONS {
+        return ApiResponse::bad_request(format!(
+            "Too many operations (max {})",
+            MAX_BATCH_OPERATIONS
+        ))
+        .into_response();
+    }
+
+    if body
+        .operations
+        .iter()
+        .any(repo::BatchOperation::requires_writable_drive)
+        && let Err(response) = ensure_drive_writable(&state, claims.id).await
+    {
+        return response;
+    }
+
+    let outcomes = match repo::execute_batch(
+        &state.db_pool,
+        &state.storage_client,
+        claims.id,
+        &body.operations,
+        body.atomic,
+    )
+    .await
+    {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            tracing::error!("Failed to execute batch: {:?}", e);
+            return ApiResponse::internal_error("Failed to execute batch").into_response();
+        }
+    };
+
+    let results: Vec<BatchItemResult> = outcomes
+        .into_iter()
+        .zip(&body.operations)
+        .enumerate()
+        .map(|(index, (outcome, operation))| BatchItemResult {
+            index,
+            success: outcome.is_ok(),
+            error: outcome.err().map(|e| batch_error_message(operation, e)),
+        })
+        .collect();
+
+    if body.atomic
+        && let Some(failure) = results.iter().find(|result| !result.success)
+    {
+        return ApiResponse::conflict(format!(
+            "Batch rolled back: operation {} failed: {}",
+            failure.index,
+            failure.error.as_deref().unwrap_or_default()
+        ))
+        .into_response();
+    }
+
+    // Lot commité : mêmes journalisation et notifications que les endpoints unitaires
+    for (result, operation) in results.iter().zip(&body.operations) {
+        if result.success {
+            let (action, kind, item_id) = batch_activity(operation);
+            let entry = ActivityEntry::new(claims.id, action).on_item(kind, item_id);
+            activity::services::record(&state.db_pool, entry).await;
+            if action == ActivityAction::Moved {
+                notify_item_added(&state, claims.id, kind, item_id).await;
+            }
         }
     }
+
+    let failed = results.iter().filter(|result| !result.success).count();
+    ApiResponse::ok(BatchResponse {
+        succeeded: results.len() - failed,
+        failed,
+        results,
+    })
+    .into_response()
 }
 
 // ========== PATCH Handlers for Move ==========
@@ -1800,11 +3406,29 @@ pub async fn move_file_restful_handler(
     Path(file_id): Path<Uuid>,
     Json(body): Json<MoveItemRequest>,
 ) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     match repo::move_file(&state.db_pool, claims.id, file_id, body.target_folder_id).await {
-        Ok(_) => ApiResponse::ok("File moved successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Moved,
+                ItemKind::File,
+                file_id,
+            )
+            .await;
+            notify_item_added(&state, claims.id, ItemKind::File, file_id).await;
+            ApiResponse::ok("File moved successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File or target folder not found").into_response()
         }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_ROTATION_PENDING => {
+            ApiResponse::conflict(msg).into_response()
+        }
         Err(e) => {
             tracing::error!("Failed to move file {}: {:?}", file_id, e);
             ApiResponse::internal_error("Failed to move file").into_response()
@@ -1819,11 +3443,29 @@ pub async fn move_folder_restful_handler(
     Path(folder_id): Path<Uuid>,
     Json(body): Json<MoveItemRequest>,
 ) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     match repo::move_folder(&state.db_pool, claims.id, folder_id, body.target_folder_id).await {
-        Ok(_) => ApiResponse::ok("Folder moved successfully").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Moved,
+                ItemKind::Folder,
+                folder_id,
+            )
+            .await;
+            notify_item_added(&state, claims.id, ItemKind::Folder, folder_id).await;
+            ApiResponse::ok("Folder moved successfully").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder or target folder not found").into_response()
         }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_ROTATION_PENDING => {
+            ApiResponse::conflict(msg).into_response()
+        }
         Err(sqlx::Error::Protocol(msg)) => ApiResponse::bad_request(&msg).into_response(),
         Err(e) => {
             tracing::error!("Failed to move folder {}: {:?}", folder_id, e);
@@ -1832,6 +3474,144 @@ pub async fn move_folder_restful_handler(
     }
 }
 
+// ========== Copie ==========
+
+#[derive(Deserialize)]
+pub struct CopyFileRequest {
+    pub target_folder_id: Option<Uuid>,
+    pub encrypted_metadata: String,
+    pub encrypted_file_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct CopiedFolderKeys {
+    pub folder_id: Uuid,
+    pub encrypted_metadata: String,
+    pub encrypted_folder_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct CopiedFileKeys {
+    pub file_id: Uuid,
+    pub encrypted_metadata: String,
+    pub encrypted_file_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct CopyFolderRequest {
+    pub target_folder_id: Option<Uuid>,
+    pub encrypted_metadata: String,
+    pub encrypted_folder_key: String,
+    #[serde(default)]
+    pub folders: Vec<CopiedFolderKeys>, // Tous les sous-dossiers, rechiffrés pour la copie
+    #[serde(default)]
+    pub files: Vec<CopiedFileKeys>, // Tous les fichiers, rechiffrés pour la copie
+}
+
+/// POST /files/{file_id}/copy - Copier un fichier (chunks S3 partagés avec la source)
+pub async fn copy_file_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<Uuid>,
+    Json(body): Json<CopyFileRequest>,
+) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
+    let copy = repo::CopiedItem {
+        source_id: file_id,
+        encrypted_metadata: body.encrypted_metadata,
+        encrypted_key: body.encrypted_file_key,
+    };
+
+    match repo::copy_file(&state.db_pool, claims.id, body.target_folder_id, &copy).await {
+        Ok(new_file_id) => {
+            notify_item_added(&state, claims.id, ItemKind::File, new_file_id).await;
+            ApiResponse::ok(serde_json::json!({ "file_id": new_file_id })).into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("File or target folder not found").into_response()
+        }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_ROTATION_PENDING => {
+            ApiResponse::conflict(msg).into_response()
+        }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::INSUFFICIENT_STORAGE => {
+            ApiResponse::insufficient_storage(msg).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to copy file {}: {:?}", file_id, e);
+            ApiResponse::internal_error("Failed to copy file").into_response()
+        }
+    }
+}
+
+/// POST /folders/{folder_id}/copy - Copier un dossier et son contenu
+pub async fn copy_folder_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(folder_id): Path<Uuid>,
+    Json(body): Json<CopyFolderRequest>,
+) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
+    let root = repo::CopiedItem {
+        source_id: folder_id,
+        encrypted_metadata: body.encrypted_metadata,
+        encrypted_key: body.encrypted_folder_key,
+    };
+    let folders: Vec<repo::CopiedItem> = body
+        .folders
+        .into_iter()
+        .map(|f| repo::CopiedItem {
+            source_id: f.folder_id,
+            encrypted_metadata: f.encrypted_metadata,
+            encrypted_key: f.encrypted_folder_key,
+        })
+        .collect();
+    let files: Vec<repo::CopiedItem> = body
+        .files
+        .into_iter()
+        .map(|f| repo::CopiedItem {
+            source_id: f.file_id,
+            encrypted_metadata: f.encrypted_metadata,
+            encrypted_key: f.encrypted_file_key,
+        })
+        .collect();
+
+    match repo::copy_folder(
+        &state.db_pool,
+        claims.id,
+        body.target_folder_id,
+        &root,
+        &folders,
+        &files,
+    )
+    .await
+    {
+        Ok(new_folder_id) => {
+            notify_item_added(&state, claims.id, ItemKind::Folder, new_folder_id).await;
+            ApiResponse::ok(serde_json::json!({ "folder_id": new_folder_id })).into_response()
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            ApiResponse::not_found("Folder or target folder not found").into_response()
+        }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::KEY_ROTATION_PENDING => {
+            ApiResponse::conflict(msg).into_response()
+        }
+        Err(sqlx::Error::Protocol(msg)) if msg == repo::INSUFFICIENT_STORAGE => {
+            ApiResponse::insufficient_storage(msg).into_response()
+        }
+        Err(sqlx::Error::Protocol(msg)) => ApiResponse::bad_request(&msg).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to copy folder {}: {:?}", folder_id, e);
+            ApiResponse::internal_error("Failed to copy folder").into_response()
+        }
+    }
+}
+
 /// GET /files - Liste tous les fichiers accessibles par l'utilisateur
 pub async fn list_files_handler(State(state): State<AppState>, claims: Claims) -> Response {
     match repo::get_files_list(&state.db_pool, claims.id).await {
@@ -1843,19 +3623,47 @@ pub async fn list_files_handler(State(state): State<AppState>, claims: Claims) -
     }
 }
 
+/// GET /shares/pending - Partages en attente d'acceptation (auteur, type, niveau, date)
+pub async fn get_pending_shares_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::get_pending_shares(&state.db_pool, claims.id, None).await {
+        Ok(shares) => ApiResponse::ok(serde_json::json!({
+            "shares": shares.iter().map(|share| share.to_json()).collect::<Vec<_>>(),
+        }))
+        .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to list pending shares: {:?}", e);
+            ApiResponse::internal_error("Failed to list pending shares").into_response()
+        }
+    }
+}
+
 /// Accepter un fichier partagé (le marque comme accepté → apparaît dans le drive principal)
 pub async fn accept_shared_file_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(file_id): Path<String>,
 ) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     let file_id = match Uuid::parse_str(&file_id) {
         Ok(id) => id,
         Err(_) => return ApiResponse::bad_request("Invalid file_id").into_response(),
     };
 
     match repo::accept_shared_file(&state.db_pool, claims.id, file_id).await {
-        Ok(_) => ApiResponse::ok("File accepted").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Accepted,
+                ItemKind::File,
+                file_id,
+            )
+            .await;
+            ApiResponse::ok("File accepted").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File not found or not shared with you").into_response()
         }
@@ -1872,13 +3680,27 @@ pub async fn accept_shared_folder_handler(
     claims: Claims,
     Path(folder_id): Path<String>,
 ) -> Response {
+    if let Err(response) = ensure_drive_writable(&state, claims.id).await {
+        return response;
+    }
+
     let folder_id = match Uuid::parse_str(&folder_id) {
         Ok(id) => id,
         Err(_) => return ApiResponse::bad_request("Invalid folder_id").into_response(),
     };
 
     match repo::accept_shared_folder(&state.db_pool, claims.id, folder_id).await {
-        Ok(_) => ApiResponse::ok("Folder accepted").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Accepted,
+                ItemKind::Folder,
+                folder_id,
+            )
+            .await;
+            ApiResponse::ok("Folder accepted").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder not found or not shared with you").into_response()
         }
@@ -1901,7 +3723,17 @@ pub async fn reject_shared_file_handler(
 
     // refuser l'accès au fichier partagé (revient au meme que de suppprimer le fichier du drive de l'utilisateur)
     match repo::reject_shared_file(&state.db_pool, claims.id, file_id).await {
-        Ok(_) => ApiResponse::ok("File rejected").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Rejected,
+                ItemKind::File,
+                file_id,
+            )
+            .await;
+            ApiResponse::ok("File rejected").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("File not found or not shared with you").into_response()
         }
@@ -1923,7 +3755,17 @@ pub async fn reject_shared_folder_handler(
     };
 
     match repo::reject_shared_folder(&state.db_pool, claims.id, folder_id).await {
-        Ok(_) => ApiResponse::ok("Folder rejected").into_response(),
+        Ok(_) => {
+            log_item_activity(
+                &state,
+                claims.id,
+                ActivityAction::Rejected,
+                ItemKind::Folder,
+                folder_id,
+            )
+            .await;
+            ApiResponse::ok("Folder rejected").into_response()
+        }
         Err(sqlx::Error::RowNotFound) => {
             ApiResponse::not_found("Folder not found or not shared with you").into_response()
         }
@@ -1934,15 +3776,35 @@ pub async fn reject_shared_folder_handler(
     }
 }
 
+#[derive(serde::Serialize)]
+pub struct DriveInfoResponse {
+    #[serde(flatten)]
+    pub info: repo::DriveInfo,
+    #[serde(flatten)]
+    pub quota: services::QuotaState,
+}
+
 pub async fn get_drive_info_handler(State(state): State<AppState>, claims: Claims) -> Response {
-    match repo::get_drive_info(&state.db_pool, claims.id).await {
-        Ok(info) => ApiResponse::ok(info).into_response(),
+    let mut info = match repo::get_drive_info(&state.db_pool, claims.id).await {
+        Ok(info) => info,
         Err(sqlx::Error::RowNotFound) => {
-            ApiResponse::not_found("Drive info not found").into_response()
+            return ApiResponse::not_found("Drive info not found").into_response();
         }
         Err(e) => {
             tracing::error!("Failed to retrieve drive info: {:?}", e);
-            ApiResponse::internal_error("Failed to retrieve drive info").into_response()
+            return ApiResponse::internal_error("Failed to retrieve drive info").into_response();
         }
+    };
+
+    if let Err(e) = repo::sync_over_quota_state(&state.db_pool, claims.id, &mut info).await {
+        tracing::error!("Failed to update over-quota state: {:?}", e);
+        return ApiResponse::internal_error("Failed to retrieve drive info").into_response();
     }
+
+    let quota = services::quota_state(
+        info.over_quota_since,
+        services::over_quota_grace_days(),
+        chrono::Utc::now(),
+    );
+    ApiResponse::ok(DriveInfoResponse { info, quota }).into_response()
 }
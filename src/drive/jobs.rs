@@ -0,0 +1,225 @@
+// Tâches de fond du module drive
+// Lancées périodiquement depuis main.rs
+
+use crate::{
+    activity::{self, ActivityAction, ActivityEntry},
+    metrics,
+    notifications::services::{self as notifications, NotificationEvent},
+    state::AppState,
+};
+
+use super::{
+    repo::{self, ExpiredShare, ItemKind},
+    services,
+};
+
+/// Nombre maximum de réservations libérées par passage
+const RESERVATION_GC_BATCH_SIZE: i64 = 100;
+
+/// Nombre d'utilisateurs réconciliés par page
+const USAGE_RECONCILIATION_BATCH_SIZE: i64 = 500;
+
+/// Nombre d'entrées du journal des changements supprimées par requête
+const CHANGES_PRUNE_BATCH_SIZE: i64 = 10_000;
+
+/// Nombre maximum de partages expirés révoqués par passage (par type d'élément)
+const EXPIRED_SHARES_BATCH_SIZE: i64 = 100;
+
+/// Libère les réservations de quota des uploads abandonnés (aucun chunk depuis
+/// UPLOAD_RESERVATION_TTL_HOURS) : le fichier incomplet et ses chunks sont supprimés.
+pub async fn release_expired_reservations(state: &AppState) {
+    let expired = match repo::get_expired_reservations(
+        &state.db_pool,
+        services::upload_reservation_ttl_hours(),
+        RESERVATION_GC_BATCH_SIZE,
+    )
+    .await
+    {
+        Ok(expired) => expired,
+        Err(e) => {
+            tracing::error!("Failed to list expired storage reservations: {}", e);
+            return;
+        }
+    };
+
+    for (file_id, user_id) in expired {
+        match repo::abort_file_upload(&state.db_pool, &state.storage_client, user_id, file_id).await
+        {
+            // Fichier supprimé : la réservation suit (ON DELETE CASCADE)
+            Ok(()) | Err(sqlx::Error::RowNotFound) => {}
+            Err(e) => {
+                tracing::error!("Failed to abort abandoned upload {}: {}", file_id, e);
+                continue;
+            }
+        }
+
+        // Si le fichier subsiste (accès d'autres utilisateurs), la réservation est libérée quand même
+        if let Err(e) = repo::release_storage_reservation(&state.db_pool, file_id).await {
+            tracing::error!("Failed to release storage reservation {}: {}", file_id, e);
+            continue;
+        }
+
+        tracing::info!(
+            "Released storage reservation of abandoned upload {}",
+            file_id
+        );
+    }
+}
+
+/// Recalcule les compteurs d'usage de tous les utilisateurs depuis les données réelles,
+/// corrige les écarts et les expose dans les métriques (un écart signale un chemin
+/// qui modifie les fichiers sans mettre à jour les compteurs).
+pub async fn reconcile_storage_usage(state: &AppState) {
+    let mut after = None;
+    let mut drifted_users = 0i64;
+    let mut drift_bytes = 0i64;
+
+    loop {
+        let user_ids =
+            match repo::list_user_ids_after(&state.db_pool, after, USAGE_RECONCILIATION_BATCH_SIZE)
+                .await
+            {
+                Ok(user_ids) => user_ids,
+                Err(e) => {
+                    tracing::error!("Failed to list users for usage reconciliation: {}", e);
+                    return;
+                }
+            };
+
+        let Some(last) = user_ids.last() else {
+            break;
+        };
+        after = Some(*last);
+
+        for user_id in user_ids {
+            let (stored, actual) =
+                match repo::reconcile_storage_usage(&state.db_pool, user_id).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        tracing::error!("Failed to reconcile storage usage of {}: {}", user_id, e);
+                        continue;
+                    }
+                };
+
+            let drift = services::storage_usage_drift(&stored, &actual);
+            if drift != repo::StorageUsage::default() {
+                tracing::warn!(
+                    "Storage usage drift for user {}: used {:+}, reserved {:+}, files {:+}",
+                    user_id,
+                    drift.used_bytes,
+                    drift.reserved_bytes,
+                    drift.file_count
+                );
+                drifted_users += 1;
+                drift_bytes += drift.used_bytes.abs() + drift.reserved_bytes.abs();
+            }
+        }
+    }
+
+    metrics::track_storage_usage_reconciliation(drifted_users, drift_bytes);
+    tracing::info!(
+        "Storage usage reconciliation done: {} user(s) corrected",
+        drifted_users
+    );
+}
+
+/// Supprime les entrées du journal des changements plus anciennes que
+/// DRIVE_CHANGES_RETENTION_DAYS (les curseurs correspondants deviennent expirés).
+pub async fn prune_drive_changes(state: &AppState) {
+    let retention_days = services::drive_changes_retention_days();
+    let mut total = 0u64;
+
+    loop {
+        match repo::prune_drive_changes(&state.db_pool, retention_days, CHANGES_PRUNE_BATCH_SIZE)
+            .await
+        {
+            Ok(pruned) => {
+                total += pruned;
+                if pruned < CHANGES_PRUNE_BATCH_SIZE as u64 {
+                    break;
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to prune drive changes: {}", e);
+                break;
+            }
+        }
+    }
+
+    if total > 0 {
+        tracing::info!("Pruned {} drive change(s)", total);
+    }
+}
+
+/// Révoque les partages temporaires arrivés à expiration, avec la logique de révocation
+/// habituelle, au nom du propriétaire de l'élément, qui est notifié.
+/// Les dossiers passent en premier : leur révocation emporte les fichiers qu'ils contiennent.
+pub async fn revoke_expired_shares(state: &AppState) {
+    for kind in [ItemKind::Folder, ItemKind::File] {
+        let expired = match kind {
+            ItemKind::Folder => {
+                repo::get_expired_folder_shares(&state.db_pool, EXPIRED_SHARES_BATCH_SIZE).await
+            }
+            ItemKind::File => {
+                repo::get_expired_file_shares(&state.db_pool, EXPIRED_SHARES_BATCH_SIZE).await
+            }
+        };
+        let expired = match expired {
+            Ok(expired) => expired,
+            Err(e) => {
+                tracing::error!("Failed to list expired {} shares: {}", kind.as_str(), e);
+                continue;
+            }
+        };
+
+        for share in expired {
+            revoke_expired_share(state, kind, share).await;
+        }
+    }
+}
+
+async fn revoke_expired_share(state: &AppState, kind: ItemKind, share: ExpiredShare) {
+    let ExpiredShare {
+        item_id,
+        user_id,
+        owner_id,
+    } = share;
+
+    let result = match kind {
+        ItemKind::Folder => {
+            repo::revoke_folder_access(&state.db_pool, owner_id, item_id, user_id, false).await
+        }
+        ItemKind::File => {
+            repo::revoke_file_access(&state.db_pool, owner_id, item_id, user_id).await
+        }
+    };
+    if let Err(e) = result {
+        tracing::error!(
+            "Failed to revoke expired {} share {} for user {}: {}",
+            kind.as_str(),
+            item_id,
+            user_id,
+            e
+        );
+        return;
+    }
+
+    let entry = ActivityEntry::new(owner_id, ActivityAction::ShareExpired)
+        .on_item(kind, item_id)
+        .target_user(user_id);
+    activity::services::record(&state.db_pool, entry).await;
+
+    let event = NotificationEvent::ShareExpired {
+        item_type: kind.as_str().to_string(),
+        item_id,
+        user_id,
+    };
+    notifications::publish(&state.redis_manager, &[owner_id], &event).await;
+
+    tracing::info!(
+        "Revoked expired {} share {} for user {}",
+        kind.as_str(),
+        item_id,
+        user_id
+    );
+}
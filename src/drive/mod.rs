@@ -1,6 +1,7 @@
 // Module drive - Gestion des fichiers et dossiers E2EE
 
 pub mod handlers;
+pub mod jobs;
 pub mod repo;
 pub mod routes;
 pub mod services;
@@ -1,11 +1,17 @@
 // Repository - Accès aux données du drive
 // Queries SQL pour files, folders, file_access, folder_access
 
+use std::collections::HashMap;
+
 use base64::Engine;
 use serde_json::json;
-use sqlx::{FromRow, PgPool};
+use sqlx::{FromRow, PgConnection, PgPool};
 use uuid::Uuid;
 
+use super::services::{
+    self, AccessLevel, Permission, SearchIndexChunkUpload, ShareGrant, SharedUploadOwner,
+};
+
 // ========== Helper Functions ==========
 
 fn bytes_to_text_or_b64(bytes: &[u8]) -> String {
@@ -23,6 +29,318 @@ fn bytes_to_text_or_b64(bytes: &[u8]) -> String {
     }
 }
 
+// ========== Permissions ==========
+
+/// Niveau d'accès de `user_id` sur un élément (None : aucun accès)
+pub async fn item_access_level(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+) -> Result<Option<AccessLevel>, sqlx::Error> {
+    let query = match kind {
+        ItemKind::File => {
+            "SELECT access_level FROM file_access WHERE file_id = $1 AND user_id = $2"
+        }
+        ItemKind::Folder => {
+            "SELECT access_level FROM folder_access WHERE folder_id = $1 AND user_id = $2"
+        }
+    };
+    sqlx::query_scalar(query)
+        .bind(item_id)
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await
+}
+
+/// Vérifie `permission` selon la matrice des niveaux d'accès et retourne le niveau de l'utilisateur.
+/// RowNotFound sans accès ou sans la permission : l'existence de l'élément n'est pas révélée.
+pub async fn ensure_permission(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    permission: Permission,
+) -> Result<AccessLevel, sqlx::Error> {
+    match item_access_level(conn, user_id, kind, item_id).await? {
+        Some(level) if level.can(permission) => Ok(level),
+        _ => Err(sqlx::Error::RowNotFound),
+    }
+}
+
+/// Vérifie que `user_id` peut ajouter un élément dans `folder_id` (upload, création, déplacement,
+/// copie) : partage accepté, dossier hors corbeille, permission Upload et aucune rotation de clé en attente.
+pub async fn ensure_can_add_to_folder(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let access: Option<(AccessLevel, bool)> = sqlx::query_as(
+        r#"
+        SELECT fa.access_level, f.key_rotation_pending_since IS NOT NULL
+        FROM folder_access fa
+        JOIN folders f ON f.id = fa.folder_id
+        WHERE fa.folder_id = $1 AND fa.user_id = $2 AND fa.is_deleted = FALSE AND fa.is_accepted = TRUE
+        "#,
+    )
+    .bind(folder_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match access {
+        Some((level, _)) if !level.can(Permission::Upload) => Err(sqlx::Error::RowNotFound),
+        // Les nouveaux éléments doivent utiliser la nouvelle clé du dossier
+        Some((_, true)) => Err(sqlx::Error::Protocol(KEY_ROTATION_PENDING.into())),
+        Some(_) => Ok(()),
+        None => Err(sqlx::Error::RowNotFound),
+    }
+}
+
+/// Erreur (`sqlx::Error::Protocol`) quand le niveau demandé dépasse ce que l'utilisateur peut accorder
+pub const ACCESS_LEVEL_NOT_GRANTABLE: &str = "You cannot grant this access level";
+
+/// Vérifie que `user_id` peut partager l'élément au niveau `granted` : partage par le propriétaire,
+/// repartage en lecture seule par un éditeur, jamais la propriété.
+async fn ensure_can_grant(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    granted: AccessLevel,
+) -> Result<(), sqlx::Error> {
+    let level = ensure_permission(conn, user_id, kind, item_id, Permission::Reshare).await?;
+    if !level.can_grant(granted) {
+        return Err(sqlx::Error::Protocol(ACCESS_LEVEL_NOT_GRANTABLE.into()));
+    }
+    Ok(())
+}
+
+// ========== Ajouts dans un dossier partagé ==========
+
+/// Erreur (`sqlx::Error::Protocol`) quand les clés fournies ne couvrent pas exactement les membres du dossier
+pub const MEMBER_KEYS_MISMATCH: &str = "Encrypted keys must be provided for every folder member";
+
+/// Erreur (`sqlx::Error::Protocol`) quand les clés fournies ne couvrent pas exactement les équipes du dossier
+pub const TEAM_KEYS_MISMATCH: &str =
+    "Encrypted keys must be provided for every team the folder is shared with";
+
+/// Clés d'un élément ajouté : celle de l'uploader, celles des autres membres du dossier partagé
+/// et celles des équipes auxquelles le dossier est partagé (chiffrées avec la clé publique de l'équipe)
+#[derive(Debug, Clone, Copy)]
+pub struct ItemKeys<'a> {
+    pub encrypted_key: &'a str,
+    pub member_keys: &'a [(Uuid, String)],
+    pub team_keys: &'a [(Uuid, String)],
+}
+
+/// Accès à créer pour un élément ajouté dans un dossier
+#[derive(Debug)]
+struct AddedItemAccess {
+    user_id: Uuid,
+    access_level: AccessLevel,
+    is_accepted: bool,
+    encrypted_key: String,
+}
+
+/// Accès d'équipe à créer pour un élément ajouté dans un dossier partagé à l'équipe
+#[derive(Debug)]
+struct AddedTeamAccess {
+    team_id: Uuid,
+    access_level: AccessLevel,
+    encrypted_key: String,
+}
+
+/// Propriétaire et accès d'un élément ajouté dans un dossier
+#[derive(Debug)]
+struct AddedItemPlan {
+    owner_id: Uuid,
+    accesses: Vec<AddedItemAccess>,
+    team_accesses: Vec<AddedTeamAccess>,
+}
+
+/// Détermine le propriétaire et les accès d'un élément que `uploader_id` ajoute dans `folder_id`.
+/// Dans un dossier partagé, chaque membre reçoit un accès avec la clé re-chiffrée fournie par le
+/// client (`member_keys`) et son niveau sur le dossier ; chaque équipe reçoit de même un accès
+/// (`team_keys`), matérialisé ensuite pour ses membres. L'élément ajouté par un éditeur appartient
+/// au propriétaire du dossier, ou à l'éditeur selon SHARED_UPLOAD_OWNER.
+/// Le propriétaire du dossier peut omettre les clés et propager l'accès ensuite.
+async fn plan_added_item_access(
+    conn: &mut PgConnection,
+    uploader_id: Uuid,
+    folder_id: Option<Uuid>,
+    keys: ItemKeys<'_>,
+) -> Result<AddedItemPlan, sqlx::Error> {
+    let ItemKeys {
+        encrypted_key: uploader_key,
+        member_keys,
+        team_keys,
+    } = keys;
+    let owner_only = || AddedItemPlan {
+        owner_id: uploader_id,
+        accesses: vec![AddedItemAccess {
+            user_id: uploader_id,
+            access_level: AccessLevel::Owner,
+            is_accepted: true,
+            encrypted_key: uploader_key.to_string(),
+        }],
+        team_accesses: Vec::new(),
+    };
+
+    let Some(folder_id) = folder_id else {
+        if !member_keys.is_empty() {
+            return Err(sqlx::Error::Protocol(MEMBER_KEYS_MISMATCH.into()));
+        }
+        if !team_keys.is_empty() {
+            return Err(sqlx::Error::Protocol(TEAM_KEYS_MISMATCH.into()));
+        }
+        return Ok(owner_only());
+    };
+
+    ensure_can_add_to_folder(conn, uploader_id, folder_id).await?;
+
+    // Les accès matérialisés d'une équipe sont couverts par la clé de l'équipe
+    let members: HashMap<Uuid, (AccessLevel, bool, bool)> =
+        sqlx::query_as::<_, (Uuid, AccessLevel, bool, bool)>(
+            "SELECT user_id, access_level, is_accepted, team_id IS NOT NULL FROM folder_access WHERE folder_id = $1 AND is_deleted = FALSE",
+        )
+        .bind(folder_id)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(user_id, level, is_accepted, via_team)| (user_id, (level, is_accepted, via_team)))
+        .collect();
+
+    let teams: HashMap<Uuid, AccessLevel> = sqlx::query_as::<_, (Uuid, AccessLevel)>(
+        "SELECT team_id, access_level FROM team_folder_access WHERE folder_id = $1",
+    )
+    .bind(folder_id)
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .collect();
+
+    let (uploader_level, _, uploader_via_team) = members
+        .get(&uploader_id)
+        .copied()
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    if uploader_level == AccessLevel::Owner && member_keys.is_empty() && team_keys.is_empty() {
+        return Ok(owner_only());
+    }
+
+    let member_ids: Vec<Uuid> = members
+        .iter()
+        .filter(|(id, (_, _, via_team))| **id != uploader_id && !via_team)
+        .map(|(id, _)| *id)
+        .collect();
+    let provided: Vec<Uuid> = member_keys.iter().map(|(id, _)| *id).collect();
+    if !services::keys_cover_members(&member_ids, &provided) {
+        return Err(sqlx::Error::Protocol(MEMBER_KEYS_MISMATCH.into()));
+    }
+
+    let team_ids: Vec<Uuid> = teams.keys().copied().collect();
+    let provided: Vec<Uuid> = team_keys.iter().map(|(id, _)| *id).collect();
+    if !services::keys_cover_members(&team_ids, &provided) {
+        return Err(sqlx::Error::Protocol(TEAM_KEYS_MISMATCH.into()));
+    }
+
+    let folder_owner = members
+        .iter()
+        .find(|(_, (level, _, _))| *level == AccessLevel::Owner)
+        .map(|(id, _)| *id);
+    let item_owner = match (
+        uploader_level,
+        folder_owner,
+        services::shared_upload_owner(),
+    ) {
+        (AccessLevel::Owner, _, _) => uploader_id,
+        (_, Some(folder_owner), SharedUploadOwner::FolderOwner) => folder_owner,
+        _ => uploader_id,
+    };
+
+    // Un membre d'équipe qui n'est pas propriétaire de l'élément y accède par l'équipe :
+    // l'accès disparaît avec son départ de l'équipe
+    let mut accesses = Vec::new();
+    if !uploader_via_team || item_owner == uploader_id {
+        accesses.push(AddedItemAccess {
+            user_id: uploader_id,
+            access_level: services::added_item_access_level(
+                uploader_level,
+                item_owner == uploader_id,
+            ),
+            is_accepted: true,
+            encrypted_key: uploader_key.to_string(),
+        });
+    }
+    for (user_id, encrypted_key) in member_keys {
+        let (level, is_accepted, _) = members[user_id];
+        accesses.push(AddedItemAccess {
+            user_id: *user_id,
+            access_level: services::added_item_access_level(level, item_owner == *user_id),
+            is_accepted,
+            encrypted_key: encrypted_key.clone(),
+        });
+    }
+
+    let team_accesses = team_keys
+        .iter()
+        .map(|(team_id, encrypted_key)| AddedTeamAccess {
+            team_id: *team_id,
+            access_level: teams[team_id],
+            encrypted_key: encrypted_key.clone(),
+        })
+        .collect();
+
+    Ok(AddedItemPlan {
+        owner_id: item_owner,
+        accesses,
+        team_accesses,
+    })
+}
+
+/// Collaborateurs (hors `user_id`) qui voient apparaître un élément dans un dossier partagé :
+/// accès accepté et actif à l'élément et au dossier qui le contient pour `user_id`.
+/// Destinataires des notifications `file_added` / `folder_added`.
+pub async fn get_added_item_recipients(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let query = match kind {
+        // L'emplacement d'un fichier est propre à chaque utilisateur (file_access.folder_id)
+        ItemKind::File => {
+            r#"
+            SELECT fa.user_id
+            FROM file_access mine
+            JOIN folder_access pa ON pa.folder_id = mine.folder_id
+            JOIN file_access fa ON fa.file_id = mine.file_id AND fa.user_id = pa.user_id
+            WHERE mine.file_id = $1 AND mine.user_id = $2 AND mine.is_deleted = FALSE
+              AND fa.user_id <> $2 AND fa.is_deleted = FALSE AND fa.is_accepted = TRUE
+              AND pa.is_deleted = FALSE AND pa.is_accepted = TRUE
+            "#
+        }
+        ItemKind::Folder => {
+            r#"
+            SELECT fa.user_id
+            FROM folders f
+            JOIN folder_access pa ON pa.folder_id = f.parent_folder_id
+            JOIN folder_access fa ON fa.folder_id = f.id AND fa.user_id = pa.user_id
+            WHERE f.id = $1
+              AND fa.user_id <> $2 AND fa.is_deleted = FALSE AND fa.is_accepted = TRUE
+              AND pa.is_deleted = FALSE AND pa.is_accepted = TRUE
+            "#
+        }
+    };
+
+    sqlx::query_scalar::<_, Uuid>(query)
+        .bind(item_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+}
+
 // ========== Queries ==========
 
 /// Vérifier l'existence d'un dossier et l'accès de l'utilisateur
@@ -41,21 +359,15 @@ pub async fn user_has_folder_access(
     Ok(has_access)
 }
 
-/// Vérifier si un utilisateur est owner d'un fichier
-pub async fn user_is_file_owner(
+/// Vérifier si un utilisateur peut envoyer les chunks d'un fichier (owner ou editor)
+pub async fn user_can_upload_file(
     pool: &PgPool,
     user_id: Uuid,
     file_id: Uuid,
 ) -> Result<bool, sqlx::Error> {
-    let is_owner = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND access_level = 'owner')",
-    )
-    .bind(file_id)
-    .bind(user_id)
-    .fetch_one(pool)
-    .await?;
-
-    Ok(is_owner)
+    let mut conn = pool.acquire().await?;
+    let level = item_access_level(&mut conn, user_id, ItemKind::File, file_id).await?;
+    Ok(level.is_some_and(|level| level.can(Permission::Upload)))
 }
 
 /// Vérifier si un utilisateur a accès à un chunk S3
@@ -100,62 +412,71 @@ pub async fn health_check_db(pool: &PgPool) -> Result<(), sqlx::Error> {
 #[derive(sqlx::FromRow, serde::Serialize)]
 pub struct DriveInfo {
     pub used_space: i64,
+    /// Espace réservé par les uploads en cours (non finalisés)
+    pub reserved_space: i64,
     pub file_count: i64,
     pub folder_count: i64,
     pub storage_limit_bytes: i64,
     pub account_tier: String,
+    /// Date du premier dépassement du quota (NULL si sous le quota)
+    pub over_quota_since: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-/// Récupérer les informations du drive (espace utilisé, nombre de fichiers et dossiers)
+/// Récupérer les informations du drive (espace utilisé, réservé, nombre de fichiers et dossiers).
+/// `used_space` ne compte que les fichiers finalisés : les uploads en cours sont dans `reserved_space`.
+/// Espace et nombre de fichiers sont lus depuis les compteurs `user_storage_usage`.
 pub async fn get_drive_info(pool: &PgPool, user_id: Uuid) -> Result<DriveInfo, sqlx::Error> {
-    let (used_space, file_count, folder_count, storage_limit_bytes, account_tier) =
-        sqlx::query_as::<_, (i64, i64, i64, i64, String)>(
-            "
-        WITH file_stats AS (
-            SELECT 
-                u.id as user_id,
-                COALESCE(SUM(f.size), 0) as used_space,
-                COUNT(f.id) as file_count
-            FROM users u
-            JOIN file_access fa2 ON fa2.user_id = u.id
-            JOIN files f ON f.id = fa2.file_id
-            WHERE u.id = $1 
-            AND fa2.access_level = 'owner'
-            GROUP BY u.id
-        ),
-        folder_stats AS (
-            SELECT 
-                u.id as user_id,
-                COUNT(fa.id) as folder_count
-            FROM users u
-            JOIN folder_access fa ON fa.user_id = u.id
-            WHERE u.id = $1 
-            AND fa.access_level = 'owner'
-            GROUP BY u.id
-        )
+    sqlx::query_as::<_, DriveInfo>(
+        "
         SELECT 
-            COALESCE(fs.used_space, 0)::BIGINT as used_space,
-            COALESCE(fs.file_count, 0)::BIGINT as file_count,
-            COALESCE(fld.folder_count, 0)::BIGINT as folder_count,
+            COALESCE(s.used_bytes, 0)::BIGINT as used_space,
+            COALESCE(s.reserved_bytes, 0)::BIGINT as reserved_space,
+            COALESCE(s.file_count, 0)::BIGINT as file_count,
+            (
+                SELECT COUNT(fa.id)
+                FROM folder_access fa
+                WHERE fa.user_id = u.id AND fa.access_level = 'owner'
+            )::BIGINT as folder_count,
             COALESCE(at.storage_limit_bytes, 2::BIGINT * 1024 * 1024 * 1024) as storage_limit_bytes,
-            COALESCE(at.name, 'free') as account_tier
+            COALESCE(at.name, 'free') as account_tier,
+            users.over_quota_since
         FROM (SELECT $1::uuid as id) u
-        LEFT JOIN file_stats fs ON fs.user_id = u.id
-        LEFT JOIN folder_stats fld ON fld.user_id = u.id
+        LEFT JOIN user_storage_usage s ON s.user_id = u.id
         LEFT JOIN users ON users.id = u.id
         LEFT JOIN account_tiers at ON at.id = users.account_tier_id;
         ",
-        )
-        .bind(user_id)
-        .fetch_one(pool)
-        .await?;
-    Ok(DriveInfo {
-        used_space,
-        file_count,
-        folder_count,
-        storage_limit_bytes,
-        account_tier,
-    })
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Aligne `users.over_quota_since` sur l'usage actuel : posé au premier dépassement,
+/// effacé dès le retour sous le quota. N'écrit en base que si l'état change.
+pub async fn sync_over_quota_state(
+    pool: &PgPool,
+    user_id: Uuid,
+    info: &mut DriveInfo,
+) -> Result<(), sqlx::Error> {
+    let over_quota = info.used_space > info.storage_limit_bytes;
+    if over_quota == info.over_quota_since.is_some() {
+        return Ok(());
+    }
+
+    info.over_quota_since = sqlx::query_scalar(
+        r#"
+        UPDATE users
+        SET over_quota_since = CASE WHEN $2 THEN COALESCE(over_quota_since, NOW()) ELSE NULL END
+        WHERE id = $1
+        RETURNING over_quota_since
+        "#,
+    )
+    .bind(user_id)
+    .bind(over_quota)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(())
 }
 
 /// Récupérer la liste des fichiers et dossiers dans un dossier parent
@@ -169,12 +490,20 @@ pub async fn get_files_and_folders_list(
         folder_id: Uuid,
         encrypted_metadata: Vec<u8>,
         encrypted_folder_key: Vec<u8>,
+        team_id: Option<Uuid>,
         created_at: Option<String>,
         updated_at: Option<String>,
         is_root: bool,
         parent_folder_id: Option<Uuid>,
         file_type: String,
         folder_size: Option<i64>,
+        key_version: i32,
+        parent_key_version: i32,
+        key_rotation_pending: bool,
+        is_favorite: bool,
+        color_label: Option<String>,
+        search_token: Option<String>,
+        tags: Vec<String>,
     }
 
     #[derive(Debug, sqlx::FromRow)]
@@ -186,9 +515,15 @@ pub async fn get_files_and_folders_list(
         mime_type: String,
         created_at: Option<String>,
         updated_at: Option<String>,
-        access_level: String,
+        access_level: AccessLevel,
         encrypted_file_key: Vec<u8>,
+        team_id: Option<Uuid>,
         file_type: String,
+        parent_key_version: i32,
+        is_favorite: bool,
+        color_label: Option<String>,
+        search_token: Option<String>,
+        tags: Vec<String>,
     }
 
     let folders: Vec<FolderRow> = sqlx::query_as::<_, FolderRow>(
@@ -197,6 +532,7 @@ pub async fn get_files_and_folders_list(
             f.id as folder_id,
             f.encrypted_metadata,
             fa.encrypted_folder_key,
+            fa.team_id,
             f.created_at::text as created_at,
             f.updated_at::text as updated_at,
             f.is_root,
@@ -205,7 +541,18 @@ pub async fn get_files_and_folders_list(
             (select COALESCE(SUM(fi.size),0) from files fi
                 join file_access fia on fia.file_id = fi.id
                 where fia.folder_id = f.id and fia.user_id = $1 and fia.is_deleted is false and fi.is_fully_uploaded = true
-            )::BIGINT as folder_size
+            )::BIGINT as folder_size,
+            fa.key_version,
+            f.parent_key_version,
+            f.key_rotation_pending_since is not null as key_rotation_pending,
+            fa.is_favorite,
+            fa.color_label,
+            fa.search_token,
+            array(
+                select ft.token from folder_tags ft
+                where ft.user_id = $1 and ft.folder_id = f.id
+                order by ft.token
+            ) as tags
         from folder_access fa
         join folders f on f.id = fa.folder_id
                 where fa.user_id = $1
@@ -234,7 +581,17 @@ pub async fn get_files_and_folders_list(
             f.updated_at::text as updated_at,
             fa2.access_level,
             fa2.encrypted_file_key,
-            'file'::text as file_type
+            fa2.team_id,
+            'file'::text as file_type,
+            f.parent_key_version,
+            fa2.is_favorite,
+            fa2.color_label,
+            fa2.search_token,
+            array(
+                select ft.token from file_tags ft
+                where ft.user_id = $1 and ft.file_id = f.id
+                order by ft.token
+            ) as tags
         from file_access fa2
         join files f on f.id = fa2.file_id
                 where fa2.user_id = $1
@@ -259,11 +616,19 @@ pub async fn get_files_and_folders_list(
                 "encrypted_metadata": bytes_to_text_or_b64(&row.encrypted_metadata),
                 "parent_folder_id": row.parent_folder_id,
                 "encrypted_folder_key": bytes_to_text_or_b64(&row.encrypted_folder_key),
+                "team_id": row.team_id,
                 "created_at": row.created_at,
                 "updated_at": row.updated_at,
                 "is_root": row.is_root,
                 "type": row.file_type,
                 "folder_size": row.folder_size,
+                "key_version": row.key_version,
+                "parent_key_version": row.parent_key_version,
+                "key_rotation_pending": row.key_rotation_pending,
+                "is_favorite": row.is_favorite,
+                "color_label": row.color_label,
+                "search_token": row.search_token,
+                "tags": row.tags,
             })
         }).collect::<Vec<_>>(),
         "files": files.iter().map(|row| {
@@ -277,7 +642,13 @@ pub async fn get_files_and_folders_list(
                 "updated_at": row.updated_at,
                 "access_level": row.access_level,
                 "encrypted_file_key": bytes_to_text_or_b64(&row.encrypted_file_key),
+                "team_id": row.team_id,
+                "parent_key_version": row.parent_key_version,
                 "type": row.file_type,
+                "is_favorite": row.is_favorite,
+                "color_label": row.color_label,
+                "search_token": row.search_token,
+                "tags": row.tags,
             })
         }).collect::<Vec<_>>(),
     }))
@@ -305,29 +676,21 @@ pub async fn initialize_file_in_db(
     encrypted_metadata: &str,
     mime_type: &str,
     folder_id: Option<Uuid>,
-    encrypted_file_key: &str,
+    keys: ItemKeys<'_>,
 ) -> Result<Uuid, sqlx::Error> {
     let mut tx = db_pool.begin().await?;
 
-    if let Some(folder_id) = folder_id {
-        let has_access = sqlx::query_scalar::<_, bool>(
-            "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND is_deleted = FALSE AND is_accepted = TRUE)",
-        )
-        .bind(folder_id)
-        .bind(user_id)
-        .fetch_one(&mut *tx)
-        .await?;
-
-        if !has_access {
-            return Err(sqlx::Error::RowNotFound);
-        }
-    }
+    let AddedItemPlan {
+        owner_id,
+        accesses,
+        team_accesses,
+    } = plan_added_item_access(&mut tx, user_id, folder_id, keys).await?;
 
     let file_id = Uuid::new_v4();
     let rec = sqlx::query_scalar::<_, Uuid>(
         "
-        INSERT INTO files (id, size, encrypted_metadata, mime_type, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        INSERT INTO files (id, size, encrypted_metadata, mime_type, parent_key_version, created_at)
+        VALUES ($1, $2, $3, $4, COALESCE((SELECT key_version FROM folders WHERE id = $5), 1), NOW())
         RETURNING id
         ",
     )
@@ -335,1781 +698,4694 @@ pub async fn initialize_file_in_db(
     .bind(size)
     .bind(encrypted_metadata.as_bytes())
     .bind(mime_type)
+    .bind(folder_id)
     .fetch_one(&mut *tx)
     .await?;
 
-    let file_access_id = Uuid::new_v4();
-    sqlx::query(
-        "
-        INSERT INTO file_access (id, file_id, user_id, folder_id, access_level, created_at, encrypted_file_key, is_accepted)
-        VALUES ($1, $2, $3, $4, 'owner', NOW(), $5, TRUE)
-        ",
-    )
-    .bind(file_access_id)
-    .bind(file_id)
-    .bind(user_id)
-    .bind(folder_id)
-    .bind(encrypted_file_key.as_bytes())
-    .execute(&mut *tx)
-    .await?;
+    for access in &accesses {
+        sqlx::query(
+            "
+            INSERT INTO file_access (id, file_id, user_id, folder_id, access_level, created_at, encrypted_file_key, is_accepted)
+            VALUES ($1, $2, $3, $4, $5, NOW(), $6, $7)
+            ",
+        )
+        .bind(Uuid::new_v4())
+        .bind(file_id)
+        .bind(access.user_id)
+        .bind(folder_id)
+        .bind(access.access_level)
+        .bind(access.encrypted_key.as_bytes())
+        .bind(access.is_accepted)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for access in &team_accesses {
+        sqlx::query(
+            "
+            INSERT INTO team_file_access (team_id, file_id, folder_id, encrypted_file_key, access_level)
+            VALUES ($1, $2, $3, $4, $5)
+            ",
+        )
+        .bind(access.team_id)
+        .bind(file_id)
+        .bind(folder_id)
+        .bind(access.encrypted_key.as_bytes())
+        .bind(access.access_level)
+        .execute(&mut *tx)
+        .await?;
+    }
+    if !team_accesses.is_empty() {
+        materialize_team_access(&mut tx, None, None, Some(file_id)).await?;
+    }
+
+    // Le quota est celui du propriétaire de l'élément
+    reserve_storage(&mut tx, owner_id, file_id, size).await?;
+    adjust_storage_usage(&mut tx, owner_id, 0, 0, 1).await?;
 
     tx.commit().await?;
     Ok(rec)
 }
 
-/// Enregistrer les metadatas d'un chunk S3 dans la table s3_keys
-pub async fn save_chunk_metadata(
-    db_pool: &PgPool,
-    file_id: Uuid,
-    index: i32,
-    s3_key: &str,
-) -> Result<Uuid, sqlx::Error> {
-    let id = Uuid::new_v4();
+// ========== Favoris, couleurs et tags ==========
 
-    sqlx::query(
-        r#"
-        INSERT INTO s3_keys (id, s3_key, file_id, index, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, NOW(), NOW())
-        "#,
-    )
-    .bind(id)
-    .bind(s3_key)
-    .bind(file_id)
-    .bind(index)
-    .execute(db_pool)
-    .await?;
+/// Erreur (`sqlx::Error::Protocol`) quand le tag n'existe pas pour l'utilisateur
+pub const TAG_NOT_FOUND: &str = "Tag not found";
 
-    Ok(id)
+/// Marque ou retire un élément des favoris de l'utilisateur (RowNotFound sans accès)
+pub async fn set_favorite(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    is_favorite: bool,
+) -> Result<(), sqlx::Error> {
+    let query = match kind {
+        ItemKind::File => {
+            "UPDATE file_access SET is_favorite = $3 WHERE file_id = $1 AND user_id = $2 AND is_deleted = FALSE"
+        }
+        ItemKind::Folder => {
+            "UPDATE folder_access SET is_favorite = $3 WHERE folder_id = $1 AND user_id = $2 AND is_deleted = FALSE"
+        }
+    };
+    let result = sqlx::query(query)
+        .bind(item_id)
+        .bind(user_id)
+        .bind(is_favorite)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
 }
 
-/// Créer un dossier dans la base de données
-pub async fn create_folder_in_db(
-    db_pool: &PgPool,
+/// Change la couleur d'un élément pour l'utilisateur (None : aucune couleur)
+pub async fn set_color_label(
+    pool: &PgPool,
     user_id: Uuid,
-    encrypted_metadata: &str,
-    parent_folder_id: Option<Uuid>,
-    encrypted_folder_key: &str,
-) -> Result<Uuid, sqlx::Error> {
-    let folder_id = Uuid::new_v4();
-    let rec = sqlx::query_scalar::<_, Uuid>(
+    kind: ItemKind,
+    item_id: Uuid,
+    color_label: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let query = match kind {
+        ItemKind::File => {
+            "UPDATE file_access SET color_label = $3 WHERE file_id = $1 AND user_id = $2 AND is_deleted = FALSE"
+        }
+        ItemKind::Folder => {
+            "UPDATE folder_access SET color_label = $3 WHERE folder_id = $1 AND user_id = $2 AND is_deleted = FALSE"
+        }
+    };
+    let result = sqlx::query(query)
+        .bind(item_id)
+        .bind(user_id)
+        .bind(color_label)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Tag de l'utilisateur : nom chiffré côté client et jeton de filtrage
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct DriveTag {
+    pub token: String,
+    pub encrypted_name: String,
+    /// Éléments encore accessibles portant ce tag
+    pub item_count: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub async fn list_tags(pool: &PgPool, user_id: Uuid) -> Result<Vec<DriveTag>, sqlx::Error> {
+    sqlx::query_as::<_, DriveTag>(
         "
-        INSERT INTO folders (id, encrypted_metadata, parent_folder_id, is_root, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        RETURNING id
+        SELECT
+            t.token,
+            t.encrypted_name,
+            (
+                (SELECT COUNT(*) FROM file_tags ft
+                 JOIN file_access fa ON fa.file_id = ft.file_id AND fa.user_id = ft.user_id
+                 WHERE ft.user_id = t.user_id AND ft.token = t.token AND fa.is_deleted = FALSE)
+                +
+                (SELECT COUNT(*) FROM folder_tags ft
+                 JOIN folder_access fa ON fa.folder_id = ft.folder_id AND fa.user_id = ft.user_id
+                 WHERE ft.user_id = t.user_id AND ft.token = t.token AND fa.is_deleted = FALSE)
+            ) AS item_count,
+            t.created_at
+        FROM drive_tags t
+        WHERE t.user_id = $1
+        ORDER BY t.created_at
         ",
     )
-    .bind(folder_id)
-    .bind(encrypted_metadata.as_bytes())
-    .bind(parent_folder_id)
-    .bind(parent_folder_id.is_none())
-    .fetch_one(db_pool)
-    .await?;
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
 
-    let folder_access_id = Uuid::new_v4();
-    // is_root_anchor = TRUE uniquement pour les dossiers à la racine (parent_folder_id IS NULL)
-    // Pour les sous-dossiers, is_root_anchor = FALSE afin de ne pas les remonter dans la vue racine
-    let is_root_anchor = parent_folder_id.is_none();
+/// Crée un tag ou remplace son nom chiffré
+pub async fn upsert_tag(
+    pool: &PgPool,
+    user_id: Uuid,
+    token: &str,
+    encrypted_name: &str,
+) -> Result<(), sqlx::Error> {
     sqlx::query(
         "
-        INSERT INTO folder_access (id, folder_id, user_id, access_level, created_at, encrypted_folder_key, is_accepted, is_root_anchor)
-        VALUES ($1, $2, $3, 'owner', NOW(), $4, TRUE, $5)
-        "
+        INSERT INTO drive_tags (user_id, token, encrypted_name)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, token) DO UPDATE SET encrypted_name = EXCLUDED.encrypted_name
+        ",
     )
-    .bind(folder_access_id)
-    .bind(folder_id)
     .bind(user_id)
-    .bind(encrypted_folder_key.as_bytes())
-    .bind(is_root_anchor)
-    .execute(db_pool)
+    .bind(token)
+    .bind(encrypted_name)
+    .execute(pool)
     .await?;
-
-    Ok(rec)
+    Ok(())
 }
 
-#[derive(FromRow)]
-struct FolderPathRow {
-    path_index: i32,
-    id: Uuid,
-    encrypted_metadata: Vec<u8>,
-    encrypted_folder_key: Option<Vec<u8>>,
+/// Supprime un tag et le retire de tous les éléments (ON DELETE CASCADE)
+pub async fn delete_tag(pool: &PgPool, user_id: Uuid, token: &str) -> Result<(), sqlx::Error> {
+    let result = sqlx::query("DELETE FROM drive_tags WHERE user_id = $1 AND token = $2")
+        .bind(user_id)
+        .bind(token)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::Protocol(TAG_NOT_FOUND.into()));
+    }
+    Ok(())
 }
 
-/// Récupérer le chemin complet d'un dossier
-pub async fn get_full_path(
-    db_pool: &PgPool,
+/// Ajoute un tag existant à un élément accessible (idempotent)
+pub async fn tag_item(
+    pool: &PgPool,
     user_id: Uuid,
-    start_folder_id: Option<Uuid>,
-) -> Result<Vec<serde_json::Value>, sqlx::Error> {
-    let Some(folder_id) = start_folder_id else {
-        return Ok(Vec::new());
-    };
-
-    let rows = sqlx::query_as::<_, FolderPathRow>(
-        "
-        WITH RECURSIVE breadcrumb AS (
-            SELECT
-                f.id,
-                f.parent_folder_id,
-                f.encrypted_metadata,
-                0 AS path_index
-            FROM
-                folders f
-            WHERE
-                f.id = $1
-
-            UNION ALL
+    kind: ItemKind,
+    item_id: Uuid,
+    token: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
 
-            SELECT
-                parent.id,
-                parent.parent_folder_id,
-                parent.encrypted_metadata,
-                child.path_index + 1
-            FROM
-                folders parent
-            INNER JOIN
-                breadcrumb child ON parent.id = child.parent_folder_id
-        )
-        SELECT
-            b.path_index,
-            b.id,
-            b.encrypted_metadata,
-            fa.encrypted_folder_key
-        FROM
-            breadcrumb b
-        LEFT JOIN
-            folder_access fa ON b.id = fa.folder_id AND fa.user_id = $2
-        ORDER BY
-            b.path_index DESC;
-        ",
+    let tag_exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM drive_tags WHERE user_id = $1 AND token = $2)",
     )
-    .bind(folder_id)
     .bind(user_id)
-    .fetch_all(db_pool)
+    .bind(token)
+    .fetch_one(&mut *tx)
     .await?;
+    if !tag_exists {
+        return Err(sqlx::Error::Protocol(TAG_NOT_FOUND.into()));
+    }
 
-    let path: Vec<serde_json::Value> = rows
-        .into_iter()
-        .map(|row| {
-            let metadata_str = bytes_to_text_or_b64(&row.encrypted_metadata);
-            let key_str = row
-                .encrypted_folder_key
-                .as_ref()
-                .map(|k| bytes_to_text_or_b64(k));
-
-            json!({
-                "folder_id": row.id,
-                "path_index": row.path_index,
-                "encrypted_metadata": metadata_str,
-                "encrypted_folder_key": key_str
-            })
-        })
-        .collect();
+    // Comme pour les favoris, un élément à la corbeille ne peut pas être tagué
+    let query = match kind {
+        ItemKind::File => {
+            "
+            INSERT INTO file_tags (user_id, token, file_id)
+            SELECT $1, $2, $3
+            WHERE EXISTS (
+                SELECT 1 FROM file_access
+                WHERE file_id = $3 AND user_id = $1 AND is_deleted = FALSE
+            )
+            ON CONFLICT DO NOTHING
+            "
+        }
+        ItemKind::Folder => {
+            "
+            INSERT INTO folder_tags (user_id, token, folder_id)
+            SELECT $1, $2, $3
+            WHERE EXISTS (
+                SELECT 1 FROM folder_access
+                WHERE folder_id = $3 AND user_id = $1 AND is_deleted = FALSE
+            )
+            ON CONFLICT DO NOTHING
+            "
+        }
+    };
+    let inserted = sqlx::query(query)
+        .bind(user_id)
+        .bind(token)
+        .bind(item_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if inserted == 0 {
+        // Déjà tagué, ou élément inaccessible
+        let tagged_query = match kind {
+            ItemKind::File => {
+                "SELECT EXISTS(SELECT 1 FROM file_tags WHERE user_id = $1 AND token = $2 AND file_id = $3)"
+            }
+            ItemKind::Folder => {
+                "SELECT EXISTS(SELECT 1 FROM folder_tags WHERE user_id = $1 AND token = $2 AND folder_id = $3)"
+            }
+        };
+        let already_tagged = sqlx::query_scalar::<_, bool>(tagged_query)
+            .bind(user_id)
+            .bind(token)
+            .bind(item_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if !already_tagged {
+            return Err(sqlx::Error::RowNotFound);
+        }
+    }
 
-    Ok(path)
+    tx.commit().await?;
+    Ok(())
 }
 
-/// Annuler un upload de fichier (supprimer le fichier et ses chunks)
-pub async fn abort_file_upload(
-    db_pool: &PgPool,
-    storage_client: &crate::storage::StorageClient,
+/// Retire un tag d'un élément (idempotent)
+pub async fn untag_item(
+    pool: &PgPool,
     user_id: Uuid,
-    file_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    token: &str,
 ) -> Result<(), sqlx::Error> {
-    let mut tx = db_pool.begin().await?;
+    let query = match kind {
+        ItemKind::File => {
+            "DELETE FROM file_tags WHERE user_id = $1 AND token = $2 AND file_id = $3"
+        }
+        ItemKind::Folder => {
+            "DELETE FROM folder_tags WHERE user_id = $1 AND token = $2 AND folder_id = $3"
+        }
+    };
+    sqlx::query(query)
+        .bind(user_id)
+        .bind(token)
+        .bind(item_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
 
-    let access_users: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
-        "SELECT user_id FROM file_access WHERE file_id = $1 FOR UPDATE",
-    )
-    .bind(file_id)
-    .fetch_all(&mut *tx)
-    .await?;
+/// Vue transversale du drive de l'utilisateur
+#[derive(Debug, Clone, Copy)]
+pub enum LabelView<'a> {
+    Favorites,
+    /// Éléments portant le tag (jeton exact)
+    Tag(&'a str),
+}
 
-    if !access_users.contains(&user_id) {
-        return Err(sqlx::Error::RowNotFound);
+/// Fichiers et dossiers accessibles de la vue, quel que soit leur dossier parent
+/// (même format que `get_files_and_folders_list`)
+pub async fn get_labelled_items(
+    pool: &PgPool,
+    user_id: Uuid,
+    view: LabelView<'_>,
+) -> Result<serde_json::Value, sqlx::Error> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct FolderRow {
+        folder_id: Uuid,
+        encrypted_metadata: Vec<u8>,
+        encrypted_folder_key: Vec<u8>,
+        team_id: Option<Uuid>,
+        created_at: Option<String>,
+        updated_at: Option<String>,
+        is_root: bool,
+        parent_folder_id: Option<Uuid>,
+        key_version: i32,
+        parent_key_version: i32,
+        is_favorite: bool,
+        color_label: Option<String>,
+        tags: Vec<String>,
     }
 
-    let other_users_still_have_access = access_users.iter().any(|u| *u != user_id);
-    if other_users_still_have_access {
-        sqlx::query("DELETE FROM file_access WHERE file_id = $1 AND user_id = $2")
-            .bind(file_id)
-            .bind(user_id)
-            .execute(&mut *tx)
-            .await?;
-
-        tx.commit().await?;
-        return Ok(());
+    #[derive(Debug, sqlx::FromRow)]
+    struct FileRow {
+        folder_id: Option<Uuid>,
+        file_id: Uuid,
+        encrypted_metadata: Vec<u8>,
+        file_size: i64,
+        mime_type: String,
+        created_at: Option<String>,
+        updated_at: Option<String>,
+        access_level: AccessLevel,
+        encrypted_file_key: Vec<u8>,
+        team_id: Option<Uuid>,
+        parent_key_version: i32,
+        is_favorite: bool,
+        color_label: Option<String>,
+        tags: Vec<String>,
     }
 
-    let s3_keys: Vec<String> =
-        sqlx::query_scalar::<_, String>("SELECT s3_key FROM s3_keys WHERE file_id = $1")
-            .bind(file_id)
-            .fetch_all(&mut *tx)
-            .await?;
+    let tag = match view {
+        LabelView::Favorites => None,
+        LabelView::Tag(token) => Some(token),
+    };
 
-    for s3_key in s3_keys.iter() {
-        storage_client
-            .delete_line(s3_key)
-            .await
-            .map_err(|e| sqlx::Error::Protocol(format!("Failed to delete from storage: {}", e)))?;
-    }
+    let folders: Vec<FolderRow> = sqlx::query_as::<_, FolderRow>(
+        r#"
+        SELECT
+            f.id AS folder_id,
+            f.encrypted_metadata,
+            fa.encrypted_folder_key,
+            fa.team_id,
+            f.created_at::text AS created_at,
+            f.updated_at::text AS updated_at,
+            f.is_root,
+            f.parent_folder_id,
+            fa.key_version,
+            f.parent_key_version,
+            fa.is_favorite,
+            fa.color_label,
+            ARRAY(
+                SELECT ft.token FROM folder_tags ft
+                WHERE ft.user_id = $1 AND ft.folder_id = f.id
+                ORDER BY ft.token
+            ) AS tags
+        FROM folder_access fa
+        JOIN folders f ON f.id = fa.folder_id
+        WHERE fa.user_id = $1
+            AND fa.is_deleted = FALSE
+            AND fa.is_accepted = TRUE
+            AND f.is_deleted = FALSE
+            AND CASE
+                WHEN $2::text IS NULL THEN fa.is_favorite
+                ELSE EXISTS (
+                    SELECT 1 FROM folder_tags ft
+                    WHERE ft.user_id = $1 AND ft.folder_id = f.id AND ft.token = $2
+                )
+            END
+        ORDER BY f.updated_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(tag)
+    .fetch_all(pool)
+    .await?;
 
-    sqlx::query("DELETE FROM s3_keys WHERE file_id = $1")
-        .bind(file_id)
-        .execute(&mut *tx)
-        .await?;
+    let files: Vec<FileRow> = sqlx::query_as::<_, FileRow>(
+        r#"
+        SELECT
+            fa.folder_id,
+            fa.file_id,
+            f.encrypted_metadata,
+            f.size AS file_size,
+            f.mime_type,
+            f.created_at::text AS created_at,
+            f.updated_at::text AS updated_at,
+            fa.access_level,
+            fa.encrypted_file_key,
+            fa.team_id,
+            f.parent_key_version,
+            fa.is_favorite,
+            fa.color_label,
+            ARRAY(
+                SELECT ft.token FROM file_tags ft
+                WHERE ft.user_id = $1 AND ft.file_id = f.id
+                ORDER BY ft.token
+            ) AS tags
+        FROM file_access fa
+        JOIN files f ON f.id = fa.file_id
+        WHERE fa.user_id = $1
+            AND fa.is_deleted = FALSE
+            AND fa.is_accepted = TRUE
+            AND f.is_deleted = FALSE
+            AND f.is_fully_uploaded = TRUE
+            AND CASE
+                WHEN $2::text IS NULL THEN fa.is_favorite
+                ELSE EXISTS (
+                    SELECT 1 FROM file_tags ft
+                    WHERE ft.user_id = $1 AND ft.file_id = f.id AND ft.token = $2
+                )
+            END
+        ORDER BY f.updated_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(tag)
+    .fetch_all(pool)
+    .await?;
 
-    sqlx::query("DELETE FROM file_access WHERE file_id = $1")
-        .bind(file_id)
-        .execute(&mut *tx)
-        .await?;
+    Ok(json!({
+        "folders": folders.iter().map(|row| {
+            json!({
+                "folder_id": row.folder_id,
+                "encrypted_metadata": bytes_to_text_or_b64(&row.encrypted_metadata),
+                "parent_folder_id": row.parent_folder_id,
+                "encrypted_folder_key": bytes_to_text_or_b64(&row.encrypted_folder_key),
+                "team_id": row.team_id,
+                "created_at": row.created_at,
+                "updated_at": row.updated_at,
+                "is_root": row.is_root,
+                "type": "folder",
+                "key_version": row.key_version,
+                "parent_key_version": row.parent_key_version,
+                "is_favorite": row.is_favorite,
+                "color_label": row.color_label,
+                "tags": row.tags,
+            })
+        }).collect::<Vec<_>>(),
+        "files": files.iter().map(|row| {
+            json!({
+                "folder_id": row.folder_id,
+                "file_id": row.file_id,
+                "encrypted_metadata": bytes_to_text_or_b64(&row.encrypted_metadata),
+                "file_size": row.file_size,
+                "mime_type": row.mime_type,
+                "created_at": row.created_at,
+                "updated_at": row.updated_at,
+                "access_level": row.access_level,
+                "encrypted_file_key": bytes_to_text_or_b64(&row.encrypted_file_key),
+                "team_id": row.team_id,
+                "parent_key_version": row.parent_key_version,
+                "type": "file",
+                "is_favorite": row.is_favorite,
+                "color_label": row.color_label,
+                "tags": row.tags,
+            })
+        }).collect::<Vec<_>>(),
+    }))
+}
 
-    sqlx::query("DELETE FROM files WHERE id = $1")
-        .bind(file_id)
-        .execute(&mut *tx)
-        .await?;
+// ========== Index de recherche ==========
 
-    tx.commit().await?;
-    Ok(())
+/// Erreur (`sqlx::Error::Protocol`) quand l'index a changé depuis la version lue par le client
+pub const SEARCH_INDEX_VERSION_CONFLICT: &str = "Search index was modified, reload it and retry";
+/// Erreur (`sqlx::Error::Protocol`) quand un chunk manque après la mise à jour
+pub const SEARCH_INDEX_INCOMPLETE: &str = "Search index update leaves missing chunks";
+
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct SearchIndexChunkInfo {
+    pub chunk_index: i32,
+    pub version: i64,
+    pub size: i32,
 }
 
-/// Supprimer un fichier (soft delete pour owner, hard delete pour non-owner)
-pub async fn delete_file(
-    db_pool: &PgPool,
-    storage_client: &crate::storage::StorageClient,
-    user_id: Uuid,
-    file_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    let mut tx = db_pool.begin().await?;
+/// État de l'index : version 0 et aucun chunk tant que le client n'en a pas envoyé
+#[derive(Debug, serde::Serialize)]
+pub struct SearchIndexInfo {
+    pub version: i64,
+    pub chunk_count: i32,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub chunks: Vec<SearchIndexChunkInfo>,
+}
 
-    let user_access: Option<(String, bool)> = sqlx::query_as(
-        "SELECT access_level, is_deleted FROM file_access WHERE file_id = $1 AND user_id = $2 FOR UPDATE",
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct SearchIndexChunk {
+    pub chunk_index: i32,
+    pub version: i64,
+    pub encrypted_data: String,
+}
+
+pub async fn get_search_index(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<SearchIndexInfo, sqlx::Error> {
+    let index = sqlx::query_as::<_, (i64, i32, chrono::DateTime<chrono::Utc>)>(
+        "SELECT version, chunk_count, updated_at FROM search_indexes WHERE user_id = $1",
     )
-    .bind(file_id)
     .bind(user_id)
-    .fetch_optional(&mut *tx)
+    .fetch_optional(pool)
     .await?;
 
-    let (access_level, is_deleted) = match user_access {
-        Some((level, deleted)) => (level, deleted),
-        None => return Err(sqlx::Error::RowNotFound),
+    let Some((version, chunk_count, updated_at)) = index else {
+        return Ok(SearchIndexInfo {
+            version: 0,
+            chunk_count: 0,
+            updated_at: None,
+            chunks: Vec::new(),
+        });
     };
 
-    let is_owner = access_level == "owner";
-
-    if is_owner {
-        if is_deleted {
-            let s3_keys: Vec<String> =
-                sqlx::query_scalar::<_, String>("SELECT s3_key FROM s3_keys WHERE file_id = $1")
-                    .bind(file_id)
-                    .fetch_all(&mut *tx)
-                    .await?;
-
-            for s3_key in s3_keys.iter() {
-                storage_client.delete_line(s3_key).await.map_err(|e| {
-                    sqlx::Error::Protocol(format!("Failed to delete from storage: {}", e))
-                })?;
-            }
-
-            sqlx::query("DELETE FROM s3_keys WHERE file_id = $1")
-                .bind(file_id)
-                .execute(&mut *tx)
-                .await?;
-
-            sqlx::query("DELETE FROM file_access WHERE file_id = $1")
-                .bind(file_id)
-                .execute(&mut *tx)
-                .await?;
-
-            sqlx::query("DELETE FROM files WHERE id = $1")
-                .bind(file_id)
-                .execute(&mut *tx)
-                .await?;
-        } else {
-            sqlx::query(
-                "UPDATE file_access
-                 SET is_deleted = TRUE, updated_at = NOW()
-                 WHERE file_id = $1 AND user_id = $2",
-            )
-            .bind(file_id)
-            .bind(user_id)
-            .execute(&mut *tx)
-            .await?;
-
-            sqlx::query(
-                "DELETE FROM file_access
-                 WHERE file_id = $1 AND user_id != $2",
-            )
-            .bind(file_id)
-            .bind(user_id)
-            .execute(&mut *tx)
-            .await?;
+    let chunks = sqlx::query_as::<_, SearchIndexChunkInfo>(
+        "
+        SELECT chunk_index, version, LENGTH(encrypted_data) AS size
+        FROM search_index_chunks
+        WHERE user_id = $1
+        ORDER BY chunk_index
+        ",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
 
-            sqlx::query(
-                "UPDATE files
-                 SET is_deleted = TRUE, updated_at = NOW()
-                 WHERE id = $1",
-            )
-            .bind(file_id)
-            .execute(&mut *tx)
-            .await?;
-        }
-    } else {
-        sqlx::query(
-            "DELETE FROM file_access
-             WHERE file_id = $1 AND user_id = $2",
-        )
-        .bind(file_id)
-        .bind(user_id)
-        .execute(&mut *tx)
-        .await?;
-    }
+    Ok(SearchIndexInfo {
+        version,
+        chunk_count,
+        updated_at: Some(updated_at),
+        chunks,
+    })
+}
 
-    tx.commit().await?;
-    Ok(())
+pub async fn get_search_index_chunk(
+    pool: &PgPool,
+    user_id: Uuid,
+    chunk_index: i32,
+) -> Result<SearchIndexChunk, sqlx::Error> {
+    sqlx::query_as::<_, SearchIndexChunk>(
+        "
+        SELECT chunk_index, version, encrypted_data
+        FROM search_index_chunks
+        WHERE user_id = $1 AND chunk_index = $2
+        ",
+    )
+    .bind(user_id)
+    .bind(chunk_index)
+    .fetch_one(pool)
+    .await
 }
 
-/// Supprimer un dossier et son contenu (soft delete pour owner, hard delete pour non-owner)
-pub async fn delete_folder(
-    db_pool: &PgPool,
+/// Remplace les chunks envoyés et tronque l'index à `chunk_count` chunks, si l'index est
+/// toujours à `base_version`. Retourne la nouvelle version.
+pub async fn update_search_index(
+    pool: &PgPool,
     user_id: Uuid,
-    folder_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    let mut tx = db_pool.begin().await?;
+    base_version: i64,
+    chunk_count: i32,
+    chunks: &[SearchIndexChunkUpload],
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
 
-    let user_access_level: Option<String> = sqlx::query_scalar(
-        "SELECT access_level FROM folder_access WHERE folder_id = $1 AND user_id = $2 FOR UPDATE",
+    // Ligne créée à la version 0 au premier envoi, puis verrouillée : deux appareils qui
+    // envoient en même temps sont sérialisés et le second reçoit un conflit
+    sqlx::query(
+        "
+        INSERT INTO search_indexes (user_id, version, chunk_count)
+        VALUES ($1, 0, 0)
+        ON CONFLICT (user_id) DO NOTHING
+        ",
     )
-    .bind(folder_id)
     .bind(user_id)
-    .fetch_optional(&mut *tx)
+    .execute(&mut *tx)
     .await?;
 
-    let access_level = match user_access_level {
-        Some(level) => level,
-        None => return Err(sqlx::Error::RowNotFound),
-    };
-
-    let is_owner = access_level == "owner";
+    let current_version = sqlx::query_scalar::<_, i64>(
+        "SELECT version FROM search_indexes WHERE user_id = $1 FOR UPDATE",
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if current_version != base_version {
+        return Err(sqlx::Error::Protocol(SEARCH_INDEX_VERSION_CONFLICT.into()));
+    }
+    let new_version = current_version + 1;
 
-    if is_owner {
+    for chunk in chunks {
         sqlx::query(
             "
-            WITH RECURSIVE folder_tree AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id FROM folders f
-                JOIN folder_tree ft ON f.parent_folder_id = ft.id
-            )
-            UPDATE folder_access fa
-            SET is_deleted = TRUE, updated_at = NOW()
-            FROM folder_tree ft
-            WHERE fa.folder_id = ft.id AND fa.user_id = $2
+            INSERT INTO search_index_chunks (user_id, chunk_index, version, encrypted_data)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, chunk_index) DO UPDATE
+            SET version = EXCLUDED.version, encrypted_data = EXCLUDED.encrypted_data
             ",
         )
-        .bind(folder_id)
         .bind(user_id)
+        .bind(chunk.chunk_index)
+        .bind(new_version)
+        .bind(&chunk.encrypted_data)
         .execute(&mut *tx)
         .await?;
+    }
 
-        sqlx::query(
-            "
-            WITH RECURSIVE folder_tree AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id FROM folders f
-                JOIN folder_tree ft ON f.parent_folder_id = ft.id
-            )
-            UPDATE file_access fa
-            SET is_deleted = TRUE, updated_at = NOW()
-            FROM folder_tree ft
-            WHERE fa.folder_id = ft.id AND fa.user_id = $2
-            ",
-        )
-        .bind(folder_id)
+    sqlx::query("DELETE FROM search_index_chunks WHERE user_id = $1 AND chunk_index >= $2")
         .bind(user_id)
+        .bind(chunk_count)
         .execute(&mut *tx)
         .await?;
 
-        sqlx::query(
-            "
-            WITH RECURSIVE folder_tree AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id FROM folders f
-                JOIN folder_tree ft ON f.parent_folder_id = ft.id
-            )
-            DELETE FROM folder_access fa
-            USING folder_tree ft
-            WHERE fa.folder_id = ft.id AND fa.user_id != $2
-            ",
-        )
-        .bind(folder_id)
-        .bind(user_id)
-        .execute(&mut *tx)
-        .await?;
+    let stored =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM search_index_chunks WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+    if stored != i64::from(chunk_count) {
+        return Err(sqlx::Error::Protocol(SEARCH_INDEX_INCOMPLETE.into()));
+    }
 
-        sqlx::query(
-            "
-            WITH RECURSIVE folder_tree AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id FROM folders f
-                JOIN folder_tree ft ON f.parent_folder_id = ft.id
-            )
-            DELETE FROM file_access fa
-            USING folder_tree ft
-            WHERE fa.folder_id = ft.id AND fa.user_id != $2
-            ",
-        )
-        .bind(folder_id)
-        .bind(user_id)
-        .execute(&mut *tx)
-        .await?;
+    sqlx::query(
+        "
+        UPDATE search_indexes
+        SET version = $2, chunk_count = $3, updated_at = NOW()
+        WHERE user_id = $1
+        ",
+    )
+    .bind(user_id)
+    .bind(new_version)
+    .bind(chunk_count)
+    .execute(&mut *tx)
+    .await?;
 
-        sqlx::query(
-            "
-            WITH RECURSIVE folder_tree AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id FROM folders f
-                JOIN folder_tree ft ON f.parent_folder_id = ft.id
-            )
-            UPDATE files f
-            SET is_deleted = TRUE, updated_at = NOW()
-            WHERE f.id IN (
-                SELECT fa.file_id FROM file_access fa
-                JOIN folder_tree ft ON fa.folder_id = ft.id
-            )
-            ",
-        )
-        .bind(folder_id)
-        .execute(&mut *tx)
-        .await?;
-    } else {
-        sqlx::query(
-            "
-            WITH RECURSIVE folder_tree AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id FROM folders f
-                JOIN folder_tree ft ON f.parent_folder_id = ft.id
-            )
-            DELETE FROM folder_access fa
-            USING folder_tree ft
-            WHERE fa.folder_id = ft.id AND fa.user_id = $2
-            ",
-        )
-        .bind(folder_id)
+    tx.commit().await?;
+    Ok(new_version)
+}
+
+/// Vide l'index (le client le reconstruit, par exemple après un changement de clé). La version
+/// continue d'augmenter pour que les mises à jour préparées sur l'ancien index soient refusées.
+pub async fn delete_search_index(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM search_index_chunks WHERE user_id = $1")
         .bind(user_id)
         .execute(&mut *tx)
         .await?;
 
-        sqlx::query(
-            "
-            WITH RECURSIVE folder_tree AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id FROM folders f
-                JOIN folder_tree ft ON f.parent_folder_id = ft.id
-            )
-            DELETE FROM file_access fa
-            USING folder_tree ft
-            WHERE fa.folder_id = ft.id AND fa.user_id = $2
-            ",
-        )
-        .bind(folder_id)
+    sqlx::query(
+        "
+        UPDATE search_indexes
+        SET version = version + 1, chunk_count = 0, updated_at = NOW()
+        WHERE user_id = $1
+        ",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Change le jeton de recherche du nom pour l'utilisateur (None : retiré de la recherche)
+pub async fn set_search_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    search_token: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let query = match kind {
+        ItemKind::File => {
+            "UPDATE file_access SET search_token = $3 WHERE file_id = $1 AND user_id = $2 AND is_deleted = FALSE"
+        }
+        ItemKind::Folder => {
+            "UPDATE folder_access SET search_token = $3 WHERE folder_id = $1 AND user_id = $2 AND is_deleted = FALSE"
+        }
+    };
+    let result = sqlx::query(query)
+        .bind(item_id)
         .bind(user_id)
-        .execute(&mut *tx)
+        .bind(search_token)
+        .execute(pool)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Identifiants des éléments accessibles dont le nom correspond au jeton
+pub async fn search_by_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    search_token: &str,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let folders = sqlx::query_as::<_, (Uuid, Option<Uuid>)>(
+        "
+        SELECT f.id, f.parent_folder_id
+        FROM folder_access fa
+        JOIN folders f ON f.id = fa.folder_id
+        WHERE fa.user_id = $1
+            AND fa.search_token = $2
+            AND fa.is_deleted = FALSE
+            AND fa.is_accepted = TRUE
+            AND f.is_deleted = FALSE
+        ORDER BY f.updated_at DESC
+        ",
+    )
+    .bind(user_id)
+    .bind(search_token)
+    .fetch_all(pool)
+    .await?;
+
+    let files = sqlx::query_as::<_, (Uuid, Option<Uuid>)>(
+        "
+        SELECT f.id, fa.folder_id
+        FROM file_access fa
+        JOIN files f ON f.id = fa.file_id
+        WHERE fa.user_id = $1
+            AND fa.search_token = $2
+            AND fa.is_deleted = FALSE
+            AND fa.is_accepted = TRUE
+            AND f.is_deleted = FALSE
+            AND f.is_fully_uploaded = TRUE
+        ORDER BY f.updated_at DESC
+        ",
+    )
+    .bind(user_id)
+    .bind(search_token)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(serde_json::json!({
+        "folders": folders.iter().map(|(folder_id, parent_folder_id)| {
+            serde_json::json!({ "folder_id": folder_id, "parent_folder_id": parent_folder_id })
+        }).collect::<Vec<_>>(),
+        "files": files.iter().map(|(file_id, folder_id)| {
+            serde_json::json!({ "file_id": file_id, "folder_id": folder_id })
+        }).collect::<Vec<_>>(),
+    }))
+}
+
+// ========== Réservations de quota ==========
+
+/// Erreur (`sqlx::Error::Protocol`) renvoyée quand une réservation dépasse le quota
+pub const INSUFFICIENT_STORAGE: &str = "Insufficient storage space";
+/// Erreur (`sqlx::Error::Protocol`) quand un chunk dépasserait la taille déclarée à l'initialisation
+pub const UPLOAD_EXCEEDS_RESERVATION: &str = "Upload exceeds the declared file size";
+/// Erreur (`sqlx::Error::Protocol`) quand le fichier n'a pas d'upload en cours
+pub const UPLOAD_NOT_IN_PROGRESS: &str = "Upload is not in progress for this file";
+
+/// Réserve `size` octets de quota pour l'upload `file_id`, dans la transaction appelante.
+/// La ligne `user_storage_usage` est verrouillée : les réservations concurrentes d'un même
+/// utilisateur sont sérialisées, ce qui empêche des initialisations parallèles de dépasser le quota.
+pub async fn reserve_storage(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user_id: Uuid,
+    file_id: Uuid,
+    size: i64,
+) -> Result<(), sqlx::Error> {
+    check_storage_quota(tx, user_id, size).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO storage_reservations (file_id, user_id, size_bytes)
+        VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(file_id)
+    .bind(user_id)
+    .bind(size)
+    .execute(&mut **tx)
+    .await?;
+
+    adjust_storage_usage(tx, user_id, 0, size, 0).await
+}
+
+/// Vérifie que `size` octets supplémentaires tiennent dans le quota de `user_id`
+/// (usage + réservations). La ligne `user_storage_usage` reste verrouillée jusqu'à la fin
+/// de la transaction appelante.
+async fn check_storage_quota(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    size: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO user_storage_usage (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING",
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+
+    let (committed, storage_limit_bytes): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT s.used_bytes + s.reserved_bytes, at.storage_limit_bytes
+        FROM user_storage_usage s
+        JOIN users u ON u.id = s.user_id
+        JOIN account_tiers at ON at.id = u.account_tier_id
+        WHERE s.user_id = $1
+        FOR UPDATE OF s
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if committed + size > storage_limit_bytes {
+        return Err(sqlx::Error::Protocol(INSUFFICIENT_STORAGE.into()));
+    }
+    Ok(())
+}
+
+/// Marque l'activité d'un upload en cours (repousse sa collecte par le GC).
+/// Retourne false si le fichier n'a pas de réservation (upload finalisé, abandonné ou inconnu).
+pub async fn touch_storage_reservation(pool: &PgPool, file_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result =
+        sqlx::query("UPDATE storage_reservations SET last_activity_at = NOW() WHERE file_id = $1")
+            .bind(file_id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Vérifie, dans la transaction appelante, qu'un chunk de `len` octets à la position `index`
+/// tient dans la taille déclarée de l'upload (marge de chiffrement comprise) et marque l'activité
+/// de la réservation, verrouillée jusqu'à la fin de la transaction. Un chunk déjà reçu pour `index`
+/// sera remplacé : sa taille n'est pas comptée. Retourne false si le fichier n'a pas d'upload en cours.
+async fn chunk_fits_reservation(
+    conn: &mut PgConnection,
+    file_id: Uuid,
+    index: i32,
+    len: i64,
+) -> Result<bool, sqlx::Error> {
+    let reserved: Option<i64> = sqlx::query_scalar(
+        "SELECT size_bytes FROM storage_reservations WHERE file_id = $1 FOR UPDATE",
+    )
+    .bind(file_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(reserved) = reserved else {
+        return Ok(false);
+    };
+
+    let other_chunks: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(size_bytes), 0)::BIGINT FROM s3_keys WHERE file_id = $1 AND index <> $2",
+    )
+    .bind(file_id)
+    .bind(index)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if other_chunks + len > services::upload_size_allowance(reserved) {
+        return Err(sqlx::Error::Protocol(UPLOAD_EXCEEDS_RESERVATION.into()));
+    }
+
+    sqlx::query("UPDATE storage_reservations SET last_activity_at = NOW() WHERE file_id = $1")
+        .bind(file_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(true)
+}
+
+/// Vérifie qu'un chunk tient dans la taille déclarée avant son envoi au stockage
+/// (voir `chunk_fits_reservation` ; le contrôle est refait à l'enregistrement du chunk).
+pub async fn check_chunk_reservation(
+    pool: &PgPool,
+    file_id: Uuid,
+    index: i32,
+    len: i64,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let fits = chunk_fits_reservation(&mut tx, file_id, index, len).await?;
+    tx.commit().await?;
+    Ok(fits)
+}
+
+/// Liste les réservations inactives depuis `ttl_hours` (uploads abandonnés) : (file_id, user_id)
+pub async fn get_expired_reservations(
+    pool: &PgPool,
+    ttl_hours: i64,
+    limit: i64,
+) -> Result<Vec<(Uuid, Uuid)>, sqlx::Error> {
+    sqlx::query_as::<_, (Uuid, Uuid)>(
+        r#"
+        SELECT file_id, user_id
+        FROM storage_reservations
+        WHERE last_activity_at <= NOW() - make_interval(hours => $1::INT)
+        ORDER BY last_activity_at
+        LIMIT $2
+        "#,
+    )
+    .bind(ttl_hours as i32)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Libère une réservation (sans toucher au fichier)
+pub async fn release_storage_reservation(pool: &PgPool, file_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    delete_storage_reservation(&mut tx, file_id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Supprime la réservation de `file_id` (s'il y en a une) et la retire des compteurs
+async fn delete_storage_reservation(
+    conn: &mut PgConnection,
+    file_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let reservation: Option<(Uuid, i64)> = sqlx::query_as(
+        "DELETE FROM storage_reservations WHERE file_id = $1 RETURNING user_id, size_bytes",
+    )
+    .bind(file_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some((user_id, size_bytes)) = reservation {
+        adjust_storage_usage(conn, user_id, 0, -size_bytes, 0).await?;
+    }
+    Ok(())
+}
+
+// ========== Compteurs d'usage ==========
+
+/// Compteurs d'usage d'un utilisateur (table `user_storage_usage` ou recalcul depuis les données)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromRow)]
+pub struct StorageUsage {
+    pub used_bytes: i64,
+    pub reserved_bytes: i64,
+    pub file_count: i64,
+}
+
+/// Applique des deltas aux compteurs d'usage de `user_id` (ligne créée au besoin).
+/// Toujours appelé dans la transaction qui modifie les fichiers ou réservations concernés.
+async fn adjust_storage_usage(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    used_delta: i64,
+    reserved_delta: i64,
+    file_count_delta: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_storage_usage (user_id, used_bytes, reserved_bytes, file_count)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id) DO UPDATE SET
+            used_bytes = user_storage_usage.used_bytes + EXCLUDED.used_bytes,
+            reserved_bytes = user_storage_usage.reserved_bytes + EXCLUDED.reserved_bytes,
+            file_count = user_storage_usage.file_count + EXCLUDED.file_count,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(user_id)
+    .bind(used_delta)
+    .bind(reserved_delta)
+    .bind(file_count_delta)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Retire des compteurs un fichier sur le point d'être supprimé définitivement
+/// (espace utilisé et nombre de fichiers de l'owner, réservation éventuelle).
+/// À appeler dans la transaction de suppression, avant le `DELETE FROM files`.
+async fn release_file_usage(conn: &mut PgConnection, file_id: Uuid) -> Result<(), sqlx::Error> {
+    let owner: Option<(Uuid, i64, bool)> = sqlx::query_as(
+        r#"
+        SELECT fa.user_id, f.size, f.is_fully_uploaded
+        FROM files f
+        JOIN file_access fa ON fa.file_id = f.id AND fa.access_level = 'owner'
+        WHERE f.id = $1
+        LIMIT 1
+        "#,
+    )
+    .bind(file_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some((owner_id, size, is_fully_uploaded)) = owner {
+        let used_delta = if is_fully_uploaded { -size } else { 0 };
+        adjust_storage_usage(&mut *conn, owner_id, used_delta, 0, -1).await?;
+    }
+
+    delete_storage_reservation(conn, file_id).await
+}
+
+/// Liste des utilisateurs par ordre d'id, à partir de `after` (pagination de la réconciliation)
+pub async fn list_user_ids_after(
+    pool: &PgPool,
+    after: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM users WHERE ($1::uuid IS NULL OR id > $1) ORDER BY id LIMIT $2",
+    )
+    .bind(after)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Recalcule les compteurs d'usage de `user_id` depuis les données et les corrige.
+/// Retourne (compteurs avant correction, valeurs recalculées).
+/// La ligne des compteurs est verrouillée pendant le recalcul : une transaction du drive
+/// concurrente applique son delta avant ou après, jamais pendant.
+pub async fn reconcile_storage_usage(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<(StorageUsage, StorageUsage), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO user_storage_usage (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let stored = sqlx::query_as::<_, StorageUsage>(
+        r#"
+        SELECT used_bytes, reserved_bytes, file_count
+        FROM user_storage_usage
+        WHERE user_id = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let actual = sqlx::query_as::<_, StorageUsage>(
+        r#"
+        SELECT
+            COALESCE((
+                SELECT SUM(f.size)
+                FROM file_access fa
+                JOIN files f ON f.id = fa.file_id
+                WHERE fa.user_id = $1 AND fa.access_level = 'owner' AND f.is_fully_uploaded = TRUE
+            ), 0)::BIGINT as used_bytes,
+            COALESCE((
+                SELECT SUM(size_bytes) FROM storage_reservations WHERE user_id = $1
+            ), 0)::BIGINT as reserved_bytes,
+            (
+                SELECT COUNT(*) FROM file_access WHERE user_id = $1 AND access_level = 'owner'
+            )::BIGINT as file_count
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE user_storage_usage
+        SET used_bytes = $2,
+            reserved_bytes = $3,
+            file_count = $4,
+            updated_at = CASE WHEN used_bytes = $2 AND reserved_bytes = $3 AND file_count = $4
+                              THEN updated_at ELSE NOW() END,
+            reconciled_at = NOW()
+        WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(actual.used_bytes)
+    .bind(actual.reserved_bytes)
+    .bind(actual.file_count)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((stored, actual))
+}
+
+/// Chunk enregistré par `save_chunk_metadata`
+#[derive(Debug)]
+pub struct SavedChunk {
+    pub id: Uuid,
+    /// Objet S3 du chunk remplacé (retry du même index), à supprimer après le commit
+    pub replaced_s3_key: Option<String>,
+}
+
+/// Enregistrer les metadatas d'un chunk S3 dans la table s3_keys.
+/// Les octets reçus sont recomptés sous le verrou de la réservation : `UPLOAD_EXCEEDS_RESERVATION`
+/// au-delà de la taille déclarée, `UPLOAD_NOT_IN_PROGRESS` si l'upload a été finalisé ou abandonné
+/// entre-temps. Un chunk renvoyé pour le même index remplace le précédent.
+pub async fn save_chunk_metadata(
+    db_pool: &PgPool,
+    file_id: Uuid,
+    index: i32,
+    s3_key: &str,
+    size: i64,
+) -> Result<SavedChunk, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    if !chunk_fits_reservation(&mut tx, file_id, index, size).await? {
+        return Err(sqlx::Error::Protocol(UPLOAD_NOT_IN_PROGRESS.into()));
+    }
+
+    let previous_s3_key: Option<String> =
+        sqlx::query_scalar("SELECT s3_key FROM s3_keys WHERE file_id = $1 AND index = $2")
+            .bind(file_id)
+            .bind(index)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO s3_keys (id, s3_key, file_id, index, size_bytes, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, NOW(), NOW())
+        ON CONFLICT (file_id, index)
+        DO UPDATE SET s3_key = EXCLUDED.s3_key, size_bytes = EXCLUDED.size_bytes, updated_at = NOW()
+        RETURNING id
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(s3_key)
+    .bind(file_id)
+    .bind(index)
+    .bind(size)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // Un objet encore référencé (copie) est conservé
+    let replaced_s3_key = match previous_s3_key {
+        Some(previous) => {
+            let still_referenced: bool =
+                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM s3_keys WHERE s3_key = $1)")
+                    .bind(&previous)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            (!still_referenced).then_some(previous)
+        }
+        None => None,
+    };
+
+    tx.commit().await?;
+    Ok(SavedChunk {
+        id,
+        replaced_s3_key,
+    })
+}
+
+/// Créer un dossier dans la base de données
+pub async fn create_folder_in_db(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    encrypted_metadata: &str,
+    parent_folder_id: Option<Uuid>,
+    keys: ItemKeys<'_>,
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let AddedItemPlan {
+        owner_id,
+        accesses,
+        team_accesses,
+    } = plan_added_item_access(&mut tx, user_id, parent_folder_id, keys).await?;
+
+    let owner_key = accesses
+        .iter()
+        .find(|a| a.user_id == owner_id)
+        .map_or(keys.encrypted_key, |a| a.encrypted_key.as_str());
+    let folder_id = insert_folder_tx(
+        &mut tx,
+        owner_id,
+        encrypted_metadata,
+        parent_folder_id,
+        owner_key,
+    )
+    .await?;
+
+    // Accès des autres membres du dossier parent (jamais ancrés à la racine)
+    for access in accesses.iter().filter(|a| a.user_id != owner_id) {
+        sqlx::query(
+            "
+            INSERT INTO folder_access (id, folder_id, user_id, access_level, created_at, encrypted_folder_key, is_accepted, is_root_anchor)
+            VALUES ($1, $2, $3, $4, NOW(), $5, $6, FALSE)
+            ",
+        )
+        .bind(Uuid::new_v4())
+        .bind(folder_id)
+        .bind(access.user_id)
+        .bind(access.access_level)
+        .bind(access.encrypted_key.as_bytes())
+        .bind(access.is_accepted)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for access in &team_accesses {
+        sqlx::query(
+            "
+            INSERT INTO team_folder_access (team_id, folder_id, encrypted_folder_key, access_level, key_version, is_root, shared_by)
+            VALUES ($1, $2, $3, $4, (SELECT key_version FROM folders WHERE id = $2), FALSE, $5)
+            ",
+        )
+        .bind(access.team_id)
+        .bind(folder_id)
+        .bind(access.encrypted_key.as_bytes())
+        .bind(access.access_level)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    if !team_accesses.is_empty() {
+        materialize_team_access(&mut tx, None, None, Some(folder_id)).await?;
+    }
+
+    record_change_for_holders(&mut tx, ItemKind::Folder, folder_id, ChangeKind::Created).await?;
+    tx.commit().await?;
+    Ok(folder_id)
+}
+
+/// Créer un dossier (et l'accès owner de `user_id`), dans la transaction appelante
+async fn insert_folder_tx(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    encrypted_metadata: &str,
+    parent_folder_id: Option<Uuid>,
+    encrypted_folder_key: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let folder_id = Uuid::new_v4();
+    let rec = sqlx::query_scalar::<_, Uuid>(
+        "
+        INSERT INTO folders (id, encrypted_metadata, parent_folder_id, is_root, parent_key_version, created_at)
+        VALUES ($1, $2, $3, $4, COALESCE((SELECT key_version FROM folders WHERE id = $3), 1), NOW())
+        RETURNING id
+        ",
+    )
+    .bind(folder_id)
+    .bind(encrypted_metadata.as_bytes())
+    .bind(parent_folder_id)
+    .bind(parent_folder_id.is_none())
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let folder_access_id = Uuid::new_v4();
+    // is_root_anchor = TRUE uniquement pour les dossiers à la racine (parent_folder_id IS NULL)
+    // Pour les sous-dossiers, is_root_anchor = FALSE afin de ne pas les remonter dans la vue racine
+    let is_root_anchor = parent_folder_id.is_none();
+    sqlx::query(
+        "
+        INSERT INTO folder_access (id, folder_id, user_id, access_level, created_at, encrypted_folder_key, is_accepted, is_root_anchor)
+        VALUES ($1, $2, $3, 'owner', NOW(), $4, TRUE, $5)
+        "
+    )
+    .bind(folder_access_id)
+    .bind(folder_id)
+    .bind(user_id)
+    .bind(encrypted_folder_key.as_bytes())
+    .bind(is_root_anchor)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(rec)
+}
+
+#[derive(FromRow)]
+struct FolderPathRow {
+    path_index: i32,
+    id: Uuid,
+    encrypted_metadata: Vec<u8>,
+    encrypted_folder_key: Option<Vec<u8>>,
+    team_id: Option<Uuid>,
+}
+
+/// Récupérer le chemin complet d'un dossier
+pub async fn get_full_path(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    start_folder_id: Option<Uuid>,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let Some(folder_id) = start_folder_id else {
+        return Ok(Vec::new());
+    };
+
+    let rows = sqlx::query_as::<_, FolderPathRow>(
+        "
+        WITH RECURSIVE breadcrumb AS (
+            SELECT
+                f.id,
+                f.parent_folder_id,
+                f.encrypted_metadata,
+                0 AS path_index
+            FROM
+                folders f
+            WHERE
+                f.id = $1
+
+            UNION ALL
+
+            SELECT
+                parent.id,
+                parent.parent_folder_id,
+                parent.encrypted_metadata,
+                child.path_index + 1
+            FROM
+                folders parent
+            INNER JOIN
+                breadcrumb child ON parent.id = child.parent_folder_id
+        )
+        SELECT
+            b.path_index,
+            b.id,
+            b.encrypted_metadata,
+            fa.encrypted_folder_key,
+            fa.team_id
+        FROM
+            breadcrumb b
+        LEFT JOIN
+            folder_access fa ON b.id = fa.folder_id AND fa.user_id = $2
+        ORDER BY
+            b.path_index DESC;
+        ",
+    )
+    .bind(folder_id)
+    .bind(user_id)
+    .fetch_all(db_pool)
+    .await?;
+
+    let path: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            let metadata_str = bytes_to_text_or_b64(&row.encrypted_metadata);
+            let key_str = row
+                .encrypted_folder_key
+                .as_ref()
+                .map(|k| bytes_to_text_or_b64(k));
+
+            json!({
+                "folder_id": row.id,
+                "path_index": row.path_index,
+                "encrypted_metadata": metadata_str,
+                "encrypted_folder_key": key_str,
+                "team_id": row.team_id
+            })
+        })
+        .collect();
+
+    Ok(path)
+}
+
+/// Annuler un upload de fichier (supprimer le fichier et ses chunks)
+pub async fn abort_file_upload(
+    db_pool: &PgPool,
+    storage_client: &crate::storage::StorageClient,
+    user_id: Uuid,
+    file_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let access_users: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
+        "SELECT user_id FROM file_access WHERE file_id = $1 FOR UPDATE",
+    )
+    .bind(file_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if !access_users.contains(&user_id) {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    // Un upload non finalisé (dont les membres d'un dossier partagé ont déjà un accès)
+    // est supprimé pour tous par un utilisateur pouvant uploader
+    let is_fully_uploaded: bool =
+        sqlx::query_scalar("SELECT is_fully_uploaded FROM files WHERE id = $1")
+            .bind(file_id)
+            .fetch_one(&mut *tx)
+            .await?;
+    let can_upload = item_access_level(&mut tx, user_id, ItemKind::File, file_id)
+        .await?
+        .is_some_and(|level| level.can(Permission::Upload));
+
+    let other_users_still_have_access = access_users.iter().any(|u| *u != user_id);
+    if other_users_still_have_access && (is_fully_uploaded || !can_upload) {
+        sqlx::query("DELETE FROM file_access WHERE file_id = $1 AND user_id = $2")
+            .bind(file_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        return Ok(());
+    }
+
+    release_file_usage(&mut tx, file_id).await?;
+    let s3_keys = detach_file_chunks(&mut tx, &[file_id]).await?;
+
+    sqlx::query("DELETE FROM file_access WHERE file_id = $1")
+        .bind(file_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM files WHERE id = $1")
+        .bind(file_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    delete_storage_objects(storage_client, &s3_keys).await;
+    Ok(())
+}
+
+/// Supprimer un fichier (soft delete pour owner, hard delete pour non-owner)
+pub async fn delete_file(
+    db_pool: &PgPool,
+    storage_client: &crate::storage::StorageClient,
+    user_id: Uuid,
+    file_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let s3_keys = delete_file_tx(&mut tx, user_id, file_id).await?;
+    tx.commit().await?;
+
+    delete_storage_objects(storage_client, &s3_keys).await;
+    Ok(())
+}
+
+/// Supprimer un fichier, dans la transaction appelante.
+/// Retourne les clés S3 à supprimer une fois la transaction validée (suppression définitive).
+pub async fn delete_file_tx(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    file_id: Uuid,
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut s3_keys = Vec::new();
+
+    let user_access: Option<(AccessLevel, bool)> = sqlx::query_as(
+        "SELECT access_level, is_deleted FROM file_access WHERE file_id = $1 AND user_id = $2 FOR UPDATE",
+    )
+    .bind(file_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let (access_level, is_deleted) = match user_access {
+        Some((level, deleted)) => (level, deleted),
+        None => return Err(sqlx::Error::RowNotFound),
+    };
+
+    let is_owner = access_level.can(Permission::Delete);
+
+    // Mise à la corbeille pour l'owner ; accès retiré (ou suppression définitive) sinon
+    let changes: Vec<ChangeEntry> = item_holders(&mut *conn, ItemKind::File, file_id)
+        .await?
+        .into_iter()
+        .filter(|holder| is_owner || *holder == user_id)
+        .map(|holder| ChangeEntry {
+            user_id: holder,
+            kind: ItemKind::File,
+            item_id: file_id,
+            change: if is_owner && holder == user_id && !is_deleted {
+                ChangeKind::Deleted
+            } else {
+                ChangeKind::Purged
+            },
+        })
+        .collect();
+
+    if is_owner {
+        if is_deleted {
+            release_file_usage(&mut *conn, file_id).await?;
+            s3_keys = detach_file_chunks(&mut *conn, &[file_id]).await?;
+
+            sqlx::query("DELETE FROM file_access WHERE file_id = $1")
+                .bind(file_id)
+                .execute(&mut *conn)
+                .await?;
+
+            sqlx::query("DELETE FROM files WHERE id = $1")
+                .bind(file_id)
+                .execute(&mut *conn)
+                .await?;
+        } else {
+            sqlx::query(
+                "UPDATE file_access
+                 SET is_deleted = TRUE, updated_at = NOW()
+                 WHERE file_id = $1 AND user_id = $2",
+            )
+            .bind(file_id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                "DELETE FROM file_access
+                 WHERE file_id = $1 AND user_id != $2",
+            )
+            .bind(file_id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                "UPDATE files
+                 SET is_deleted = TRUE, updated_at = NOW()
+                 WHERE id = $1",
+            )
+            .bind(file_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+    } else {
+        sqlx::query(
+            "DELETE FROM file_access
+             WHERE file_id = $1 AND user_id = $2",
+        )
+        .bind(file_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    append_changes(conn, &changes).await?;
+
+    Ok(s3_keys)
+}
+
+/// Supprime des chunks du stockage après validation de la transaction qui les a détachés.
+/// Un échec laisse un objet orphelin dans S3 (journalisé) mais ne remet pas en cause la suppression.
+pub async fn delete_storage_objects(
+    storage_client: &crate::storage::StorageClient,
+    s3_keys: &[String],
+) {
+    for s3_key in s3_keys {
+        if let Err(e) = storage_client.delete_line(s3_key).await {
+            tracing::error!(
+                "Failed to delete orphaned chunk {} from storage: {}",
+                s3_key,
+                e
+            );
+        }
+    }
+}
+
+/// Détache les chunks des fichiers `file_ids` (lignes `s3_keys`) avant leur suppression définitive.
+/// Un chunk peut être référencé par plusieurs fichiers (copies) : seules les clés S3 qui ne sont plus
+/// référencées par aucun fichier sont retournées, à supprimer une fois la transaction validée.
+/// Toutes les lignes partageant ces clés sont verrouillées : deux suppressions concurrentes de copies
+/// d'un même fichier ne peuvent pas conclure chacune que l'autre garde une référence.
+async fn detach_file_chunks(
+    conn: &mut PgConnection,
+    file_ids: &[Uuid],
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query(
+        r#"
+        SELECT 1
+        FROM s3_keys
+        WHERE s3_key IN (SELECT s3_key FROM s3_keys WHERE file_id = ANY($1))
+        ORDER BY id
+        FOR UPDATE
+        "#,
+    )
+    .bind(file_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    // Le SELECT principal voit s3_keys avant le DELETE : les lignes détachées sont exclues par file_id
+    sqlx::query_scalar::<_, String>(
+        r#"
+        WITH detached AS (
+            DELETE FROM s3_keys WHERE file_id = ANY($1) RETURNING s3_key
+        )
+        SELECT DISTINCT d.s3_key
+        FROM detached d
+        WHERE NOT EXISTS (
+            SELECT 1 FROM s3_keys sk
+            WHERE sk.s3_key = d.s3_key AND sk.file_id <> ALL($1)
+        )
+        "#,
+    )
+    .bind(file_ids)
+    .fetch_all(&mut *conn)
+    .await
+}
+
+/// Supprimer un dossier et son contenu (soft delete pour owner, hard delete pour non-owner)
+pub async fn delete_folder(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    delete_folder_tx(&mut tx, user_id, folder_id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Supprimer un dossier, dans la transaction appelante
+pub async fn delete_folder_tx(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let user_access_level: Option<AccessLevel> = sqlx::query_scalar(
+        "SELECT access_level FROM folder_access WHERE folder_id = $1 AND user_id = $2 FOR UPDATE",
+    )
+    .bind(folder_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let access_level = match user_access_level {
+        Some(level) => level,
+        None => return Err(sqlx::Error::RowNotFound),
+    };
+
+    let is_owner = access_level.can(Permission::Delete);
+
+    // Owner : dossier à la corbeille, accès des autres utilisateurs retirés sur toute l'arborescence
+    let mut changes = if is_owner {
+        folder_tree_foreign_removals(&mut *conn, folder_id, user_id).await?
+    } else {
+        Vec::new()
+    };
+    changes.push(ChangeEntry {
+        user_id,
+        kind: ItemKind::Folder,
+        item_id: folder_id,
+        change: if is_owner {
+            ChangeKind::Deleted
+        } else {
+            ChangeKind::Purged
+        },
+    });
+
+    if is_owner {
+        sqlx::query(
+            "
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id FROM folders f
+                JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            )
+            UPDATE folder_access fa
+            SET is_deleted = TRUE, updated_at = NOW()
+            FROM folder_tree ft
+            WHERE fa.folder_id = ft.id AND fa.user_id = $2
+            ",
+        )
+        .bind(folder_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            "
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id FROM folders f
+                JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            )
+            UPDATE file_access fa
+            SET is_deleted = TRUE, updated_at = NOW()
+            FROM folder_tree ft
+            WHERE fa.folder_id = ft.id AND fa.user_id = $2
+            ",
+        )
+        .bind(folder_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            "
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id FROM folders f
+                JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            )
+            DELETE FROM folder_access fa
+            USING folder_tree ft
+            WHERE fa.folder_id = ft.id AND fa.user_id != $2
+            ",
+        )
+        .bind(folder_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            "
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id FROM folders f
+                JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            )
+            DELETE FROM file_access fa
+            USING folder_tree ft
+            WHERE fa.folder_id = ft.id AND fa.user_id != $2
+            ",
+        )
+        .bind(folder_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            "
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id FROM folders f
+                JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            )
+            UPDATE files f
+            SET is_deleted = TRUE, updated_at = NOW()
+            WHERE f.id IN (
+                SELECT fa.file_id FROM file_access fa
+                JOIN folder_tree ft ON fa.folder_id = ft.id
+            )
+            ",
+        )
+        .bind(folder_id)
+        .execute(&mut *conn)
+        .await?;
+    } else {
+        sqlx::query(
+            "
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id FROM folders f
+                JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            )
+            DELETE FROM folder_access fa
+            USING folder_tree ft
+            WHERE fa.folder_id = ft.id AND fa.user_id = $2
+            ",
+        )
+        .bind(folder_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            "
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id FROM folders f
+                JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            )
+            DELETE FROM file_access fa
+            USING folder_tree ft
+            WHERE fa.folder_id = ft.id AND fa.user_id = $2
+            ",
+        )
+        .bind(folder_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    append_changes(conn, &changes).await
+}
+
+/// Renommer un fichier (modifier encrypted_metadata)
+pub async fn rename_file(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    file_id: Uuid,
+    new_encrypted_metadata: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    ensure_permission(
+        &mut tx,
+        user_id,
+        ItemKind::File,
+        file_id,
+        Permission::Rename,
+    )
+    .await?;
+
+    sqlx::query("UPDATE files SET encrypted_metadata = $1, updated_at = NOW() WHERE id = $2")
+        .bind(new_encrypted_metadata.as_bytes())
+        .bind(file_id)
+        .execute(&mut *tx)
+        .await?;
+
+    record_change_for_holders(&mut tx, ItemKind::File, file_id, ChangeKind::Renamed).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Renommer un dossier (modifier encrypted_metadata)
+pub async fn rename_folder(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+    new_encrypted_metadata: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    ensure_permission(
+        &mut tx,
+        user_id,
+        ItemKind::Folder,
+        folder_id,
+        Permission::Rename,
+    )
+    .await?;
+
+    sqlx::query("UPDATE folders SET encrypted_metadata = $1, updated_at = NOW() WHERE id = $2")
+        .bind(new_encrypted_metadata.as_bytes())
+        .bind(folder_id)
+        .execute(&mut *tx)
+        .await?;
+
+    record_change_for_holders(&mut tx, ItemKind::Folder, folder_id, ChangeKind::Renamed).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Déplacer un fichier vers un autre dossier
+pub async fn move_file(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    file_id: Uuid,
+    new_folder_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let mut conn = db_pool.acquire().await?;
+    move_file_tx(&mut conn, user_id, file_id, new_folder_id).await
+}
+
+/// Déplacer un fichier, dans la transaction appelante
+pub async fn move_file_tx(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    file_id: Uuid,
+    new_folder_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    ensure_permission(conn, user_id, ItemKind::File, file_id, Permission::Move).await?;
+
+    if let Some(folder_id) = new_folder_id {
+        ensure_can_add_to_folder(conn, user_id, folder_id).await?;
+    }
+
+    sqlx::query("UPDATE file_access SET folder_id = $1 WHERE file_id = $2 AND user_id = $3")
+        .bind(new_folder_id)
+        .bind(file_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    // L'emplacement d'un fichier est propre à chaque utilisateur (file_access.folder_id)
+    record_change(conn, user_id, ItemKind::File, file_id, ChangeKind::Moved).await
+}
+
+/// Déplacer un dossier vers un autre parent
+pub async fn move_folder(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+    new_parent_folder_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let mut conn = db_pool.acquire().await?;
+    move_folder_tx(&mut conn, user_id, folder_id, new_parent_folder_id).await
+}
+
+/// Déplacer un dossier, dans la transaction appelante
+pub async fn move_folder_tx(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    folder_id: Uuid,
+    new_parent_folder_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    ensure_permission(conn, user_id, ItemKind::Folder, folder_id, Permission::Move).await?;
+
+    if let Some(parent_folder_id) = new_parent_folder_id {
+        ensure_can_add_to_folder(conn, user_id, parent_folder_id).await?;
+
+        let would_create_cycle = sqlx::query_scalar::<_, bool>(
+            r#"
+            WITH RECURSIVE descendants AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id FROM folders f
+                JOIN descendants d ON f.parent_folder_id = d.id
+            )
+            SELECT EXISTS(SELECT 1 FROM descendants WHERE id = $2)
+            "#,
+        )
+        .bind(folder_id)
+        .bind(parent_folder_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if would_create_cycle {
+            return Err(sqlx::Error::Protocol(
+                "Cannot move folder into its own descendant".into(),
+            ));
+        }
+    }
+
+    sqlx::query("UPDATE folders SET parent_folder_id = $1, is_root = $2 WHERE id = $3")
+        .bind(new_parent_folder_id)
+        .bind(new_parent_folder_id.is_none())
+        .bind(folder_id)
+        .execute(&mut *conn)
+        .await?;
+
+    // Le parent d'un dossier est commun à tous ceux qui y ont accès
+    record_change_for_holders(conn, ItemKind::Folder, folder_id, ChangeKind::Moved).await
+}
+
+// ========== Copie ==========
+
+/// Erreur (`sqlx::Error::Protocol`) renvoyée quand les clés fournies ne couvrent pas exactement
+/// les sous-dossiers et fichiers du dossier copié
+pub const COPY_KEYS_MISMATCH: &str =
+    "Copy must provide new keys for every subfolder and file of the source folder";
+
+/// Élément à copier, avec les nouvelles métadonnées et la nouvelle clé chiffrées par le client
+#[derive(Debug, Clone)]
+pub struct CopiedItem {
+    pub source_id: Uuid,
+    pub encrypted_metadata: String,
+    pub encrypted_key: String,
+}
+
+/// Vérifie que l'utilisateur peut copier vers `target_folder_id` (None = racine)
+async fn ensure_copy_target(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    target_folder_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    match target_folder_id {
+        Some(folder_id) => ensure_can_add_to_folder(conn, user_id, folder_id).await,
+        None => Ok(()),
+    }
+}
+
+/// Crée la copie d'un fichier finalisé : nouvelle ligne `files`, accès owner de `user_id` et
+/// lignes `s3_keys` pointant vers les chunks de la source (aucun octet n'est dupliqué dans S3).
+async fn insert_file_copy_tx(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    folder_id: Option<Uuid>,
+    copy: &CopiedItem,
+) -> Result<Uuid, sqlx::Error> {
+    let file_id = Uuid::new_v4();
+    sqlx::query(
+        r#"
+        INSERT INTO files (id, size, encrypted_metadata, mime_type, is_fully_uploaded, parent_key_version, created_at)
+        SELECT $1, size, $2, mime_type, TRUE,
+               COALESCE((SELECT key_version FROM folders WHERE id = $4), 1), NOW()
+        FROM files
+        WHERE id = $3
+        "#,
+    )
+    .bind(file_id)
+    .bind(copy.encrypted_metadata.as_bytes())
+    .bind(copy.source_id)
+    .bind(folder_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "
+        INSERT INTO file_access (id, file_id, user_id, folder_id, access_level, created_at, encrypted_file_key, is_accepted)
+        VALUES ($1, $2, $3, $4, 'owner', NOW(), $5, TRUE)
+        ",
+    )
+    .bind(Uuid::new_v4())
+    .bind(file_id)
+    .bind(user_id)
+    .bind(folder_id)
+    .bind(copy.encrypted_key.as_bytes())
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO s3_keys (id, s3_key, file_id, index, size_bytes, created_at, updated_at)
+        SELECT gen_random_uuid(), s3_key, $1, index, size_bytes, NOW(), NOW()
+        FROM s3_keys
+        WHERE file_id = $2
+        "#,
+    )
+    .bind(file_id)
+    .bind(copy.source_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(file_id)
+}
+
+/// Copier un fichier vers `target_folder_id` (None = racine).
+/// La copie appartient à `user_id` et compte dans son quota ; ses chunks sont partagés avec la source.
+/// `RowNotFound` si le fichier (finalisé) ou le dossier cible n'est pas accessible.
+pub async fn copy_file(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    target_folder_id: Option<Uuid>,
+    copy: &CopiedItem,
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    ensure_copy_target(&mut tx, user_id, target_folder_id).await?;
+
+    // Verrou partagé sur l'accès source : une suppression concurrente attend la fin de la copie
+    let size: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT f.size
+        FROM file_access fa
+        JOIN files f ON f.id = fa.file_id
+        WHERE fa.file_id = $1
+          AND fa.user_id = $2
+          AND fa.is_deleted = FALSE
+          AND fa.is_accepted = TRUE
+          AND f.is_fully_uploaded = TRUE
+        FOR SHARE OF fa
+        "#,
+    )
+    .bind(copy.source_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let size = size.ok_or(sqlx::Error::RowNotFound)?;
+
+    check_storage_quota(&mut tx, user_id, size).await?;
+
+    let file_id = insert_file_copy_tx(&mut tx, user_id, target_folder_id, copy).await?;
+    adjust_storage_usage(&mut tx, user_id, size, 0, 1).await?;
+    record_change(
+        &mut tx,
+        user_id,
+        ItemKind::File,
+        file_id,
+        ChangeKind::Created,
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(file_id)
+}
+
+/// Copier un dossier et son contenu vers `target_folder_id` (None = racine).
+/// `folders` et `files` doivent couvrir exactement les sous-dossiers et fichiers finalisés
+/// visibles par l'utilisateur (sinon `COPY_KEYS_MISMATCH`) : le client rechiffre chaque élément
+/// avec de nouvelles clés. Retourne l'id du nouveau dossier.
+pub async fn copy_folder(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    target_folder_id: Option<Uuid>,
+    root: &CopiedItem,
+    folders: &[CopiedItem],
+    files: &[CopiedItem],
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    ensure_copy_target(&mut tx, user_id, target_folder_id).await?;
+
+    // Arborescence source, parents avant enfants
+    let source_folders = sqlx::query_as::<_, (Uuid, Option<Uuid>)>(
+        r#"
+        WITH RECURSIVE folder_tree AS (
+            SELECT f.id, f.parent_folder_id, 0 as depth
+            FROM folders f
+            JOIN folder_access fa ON fa.folder_id = f.id AND fa.user_id = $1
+            WHERE f.id = $2 AND fa.is_deleted = FALSE AND fa.is_accepted = TRUE
+
+            UNION ALL
+
+            SELECT f.id, f.parent_folder_id, ft.depth + 1
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            JOIN folder_access fa ON fa.folder_id = f.id AND fa.user_id = $1
+            WHERE fa.is_deleted = FALSE
+        )
+        SELECT id, parent_folder_id FROM folder_tree ORDER BY depth
+        "#,
+    )
+    .bind(user_id)
+    .bind(root.source_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if source_folders.is_empty() {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    let source_folder_ids: Vec<Uuid> = source_folders.iter().map(|(id, _)| *id).collect();
+    let source_files = sqlx::query_as::<_, (Uuid, Option<Uuid>, i64)>(
+        r#"
+        SELECT fa.file_id, fa.folder_id, f.size
+        FROM file_access fa
+        JOIN files f ON f.id = fa.file_id
+        WHERE fa.user_id = $1
+          AND fa.folder_id = ANY($2)
+          AND fa.is_deleted = FALSE
+          AND f.is_fully_uploaded = TRUE
+        FOR SHARE OF fa
+        "#,
+    )
+    .bind(user_id)
+    .bind(&source_folder_ids)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let provided_folders: Vec<Uuid> = folders.iter().map(|f| f.source_id).collect();
+    let provided_files: Vec<Uuid> = files.iter().map(|f| f.source_id).collect();
+    let source_file_ids: Vec<Uuid> = source_files.iter().map(|(id, _, _)| *id).collect();
+    if !services::copy_covers_sources(&source_folder_ids[1..], &provided_folders)
+        || !services::copy_covers_sources(&source_file_ids, &provided_files)
+    {
+        return Err(sqlx::Error::Protocol(COPY_KEYS_MISMATCH.into()));
+    }
+
+    let total_size: i64 = source_files.iter().map(|(_, _, size)| size).sum();
+    check_storage_quota(&mut tx, user_id, total_size).await?;
+
+    // Correspondance dossier source -> dossier copié
+    let mut copied_folders: HashMap<Uuid, Uuid> = HashMap::new();
+    let new_root_id = insert_folder_tx(
+        &mut tx,
+        user_id,
+        &root.encrypted_metadata,
+        target_folder_id,
+        &root.encrypted_key,
+    )
+    .await?;
+    copied_folders.insert(root.source_id, new_root_id);
+
+    let folder_keys: HashMap<Uuid, &CopiedItem> =
+        folders.iter().map(|f| (f.source_id, f)).collect();
+    for (source_id, parent_id) in source_folders.iter().skip(1) {
+        let keys = folder_keys[source_id];
+        let new_parent_id = parent_id.and_then(|id| copied_folders.get(&id).copied());
+        let new_id = insert_folder_tx(
+            &mut tx,
+            user_id,
+            &keys.encrypted_metadata,
+            new_parent_id,
+            &keys.encrypted_key,
+        )
+        .await?;
+        copied_folders.insert(*source_id, new_id);
+    }
+
+    let file_folders: HashMap<Uuid, Option<Uuid>> = source_files
+        .iter()
+        .map(|(id, folder_id, _)| (*id, *folder_id))
+        .collect();
+    for copy in files {
+        let folder_id =
+            file_folders[&copy.source_id].and_then(|id| copied_folders.get(&id).copied());
+        insert_file_copy_tx(&mut tx, user_id, folder_id, copy).await?;
+    }
+
+    adjust_storage_usage(&mut tx, user_id, total_size, 0, files.len() as i64).await?;
+    // Une entrée pour le dossier racine : son contenu est rechargé par le client
+    record_change(
+        &mut tx,
+        user_id,
+        ItemKind::Folder,
+        new_root_id,
+        ChangeKind::Created,
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(new_root_id)
+}
+
+/// Récupérer les infos d'un fichier avec ses chunks
+pub async fn get_file_info(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    file_id: Uuid,
+) -> Result<serde_json::Value, sqlx::Error> {
+    #[derive(FromRow)]
+    struct FileInfo {
+        file_id: Uuid,
+        encrypted_metadata: Vec<u8>,
+        size: i64,
+        mime_type: String,
+        encrypted_file_key: Vec<u8>,
+        team_id: Option<Uuid>,
+        created_at: Option<String>,
+        updated_at: Option<String>,
+    }
+
+    #[derive(FromRow)]
+    struct ChunkInfo {
+        s3_key: String,
+        index: i32,
+        chunk_id: Uuid,
+    }
+
+    let file_info = sqlx::query_as::<_, FileInfo>(
+        r#"
+        SELECT
+            f.id as file_id,
+            f.encrypted_metadata,
+            f.size,
+            f.mime_type,
+            fa.encrypted_file_key,
+            fa.team_id,
+            f.created_at::text,
+            f.updated_at::text
+        FROM files f
+        JOIN file_access fa ON fa.file_id = f.id
+        WHERE f.id = $1 AND fa.user_id = $2
+        "#,
+    )
+    .bind(file_id)
+    .bind(user_id)
+    .fetch_one(db_pool)
+    .await?;
+
+    let chunks = sqlx::query_as::<_, ChunkInfo>(
+        r#"
+        SELECT id as chunk_id, s3_key, index
+        FROM s3_keys
+        WHERE file_id = $1
+        ORDER BY index ASC
+        "#,
+    )
+    .bind(file_id)
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(json!({
+        "file_id": file_info.file_id,
+        "encrypted_metadata": bytes_to_text_or_b64(&file_info.encrypted_metadata),
+        "encrypted_file_key": bytes_to_text_or_b64(&file_info.encrypted_file_key),
+        "team_id": file_info.team_id,
+        "size": file_info.size,
+        "mime_type": file_info.mime_type,
+        "created_at": file_info.created_at,
+        "updated_at": file_info.updated_at,
+        "chunk_count": chunks.len(),
+        "chunks": chunks.iter().map(|c| json!({
+            "s3_key": c.s3_key,
+            "index": c.index,
+            "chunk_id": c.chunk_id,
+        })).collect::<Vec<_>>(),
+    }))
+}
+
+/// Récupérer tous les IDs et clés chiffrées d'un dossier récursivement
+pub async fn get_folder_contents_recursive(
+    pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Option<Uuid>,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    #[derive(FromRow)]
+    struct FolderItem {
+        folder_id: Uuid,
+        encrypted_folder_key: Vec<u8>,
+        team_id: Option<Uuid>,
+    }
+
+    #[derive(FromRow)]
+    struct FileItem {
+        file_id: Uuid,
+        encrypted_file_key: Vec<u8>,
+        team_id: Option<Uuid>,
+    }
+
+    let folders: Vec<FolderItem> = sqlx::query_as::<_, FolderItem>(
+        r#"
+WITH RECURSIVE folder_tree AS (
+    SELECT f.id as folder_id
+    FROM folders f
+    JOIN folder_access fa ON fa.folder_id = f.id
+    WHERE fa.user_id = $1
+        AND fa.is_deleted = FALSE
+        AND (
+            ($2::uuid IS NULL AND f.parent_folder_id IS NULL)
+            OR f.parent_folder_id = $2::uuid
+        )
+
+    UNION ALL
+
+    SELECT f.id as folder_id
+    FROM folders f
+    JOIN folder_tree ft ON f.parent_folder_id = ft.folder_id
+    JOIN folder_access fa ON fa.folder_id = f.id AND fa.user_id = $1
+    WHERE fa.is_deleted = FALSE
+)
+SELECT
+    ft.folder_id,
+    fa.encrypted_folder_key,
+    fa.team_id
+FROM folder_tree ft
+JOIN folder_access fa ON fa.folder_id = ft.folder_id AND fa.user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(folder_id)
+    .fetch_all(pool)
+    .await?;
+
+    let files: Vec<FileItem> = sqlx::query_as::<_, FileItem>(
+        r#"
+WITH RECURSIVE folder_tree AS (
+    SELECT f.id as folder_id
+    FROM folders f
+    WHERE ($1::uuid IS NULL AND f.parent_folder_id IS NULL)
+       OR f.id = $1::uuid
+
+    UNION ALL
+
+    SELECT f.id as folder_id
+    FROM folders f
+    JOIN folder_tree ft ON f.parent_folder_id = ft.folder_id
+)
+SELECT
+    fa.file_id,
+    fa.encrypted_file_key,
+    fa.team_id
+FROM folder_tree ft
+JOIN file_access fa ON fa.folder_id = ft.folder_id AND fa.user_id = $2
+JOIN files ON files.id = fa.file_id
+WHERE fa.is_deleted = FALSE
+  AND files.is_fully_uploaded = TRUE
+        "#,
+    )
+    .bind(folder_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut results = Vec::new();
+
+    for folder in folders {
+        results.push(json!({
+            "type": "folder",
+            "folder_id": folder.folder_id,
+            "encrypted_folder_key": bytes_to_text_or_b64(&folder.encrypted_folder_key),
+            "team_id": folder.team_id,
+        }));
+    }
+
+    for file in files {
+        results.push(json!({
+            "type": "file",
+            "file_id": file.file_id,
+            "encrypted_file_key": bytes_to_text_or_b64(&file.encrypted_file_key),
+            "team_id": file.team_id,
+        }));
+    }
+
+    Ok(results)
+}
+
+/// Fichier inclus dans l'archive ZIP d'un dossier
+#[derive(Debug, FromRow)]
+pub struct ArchiveFile {
+    pub file_id: Uuid,
+    pub folder_id: Option<Uuid>,
+    pub encrypted_metadata: Vec<u8>,
+    pub mime_type: String,
+    pub size: i64,
+    pub encrypted_file_key: Vec<u8>,
+}
+
+impl ArchiveFile {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "file_id": self.file_id,
+            "folder_id": self.folder_id,
+            "encrypted_metadata": bytes_to_text_or_b64(&self.encrypted_metadata),
+            "mime_type": self.mime_type,
+            "size": self.size,
+            "encrypted_file_key": bytes_to_text_or_b64(&self.encrypted_file_key),
+        })
+    }
+}
+
+/// Arborescence d'un dossier pour son archive ZIP : le dossier et ses sous-dossiers accessibles
+/// (métadonnées et clés chiffrées de l'utilisateur), puis les fichiers finalisés qu'ils contiennent.
+/// `RowNotFound` si l'utilisateur n'a pas accès au dossier.
+pub async fn get_folder_archive_tree(
+    pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<(Vec<serde_json::Value>, Vec<ArchiveFile>), sqlx::Error> {
+    #[derive(FromRow)]
+    struct ArchiveFolder {
+        folder_id: Uuid,
+        parent_folder_id: Option<Uuid>,
+        encrypted_metadata: Vec<u8>,
+        encrypted_folder_key: Vec<u8>,
+    }
+
+    let folders = sqlx::query_as::<_, ArchiveFolder>(
+        r#"
+        WITH RECURSIVE folder_tree AS (
+            SELECT f.id, f.parent_folder_id
+            FROM folders f
+            JOIN folder_access fa ON fa.folder_id = f.id AND fa.user_id = $1
+            WHERE f.id = $2 AND fa.is_deleted = FALSE AND fa.is_accepted = TRUE
+
+            UNION ALL
+
+            SELECT f.id, f.parent_folder_id
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+            JOIN folder_access fa ON fa.folder_id = f.id AND fa.user_id = $1
+            WHERE fa.is_deleted = FALSE
+        )
+        SELECT
+            ft.id as folder_id,
+            ft.parent_folder_id,
+            f.encrypted_metadata,
+            fa.encrypted_folder_key
+        FROM folder_tree ft
+        JOIN folders f ON f.id = ft.id
+        JOIN folder_access fa ON fa.folder_id = ft.id AND fa.user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(folder_id)
+    .fetch_all(pool)
+    .await?;
+
+    if folders.is_empty() {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    let folder_ids: Vec<Uuid> = folders.iter().map(|f| f.folder_id).collect();
+    let files = sqlx::query_as::<_, ArchiveFile>(
+        r#"
+        SELECT
+            f.id as file_id,
+            fa.folder_id,
+            f.encrypted_metadata,
+            f.mime_type,
+            f.size,
+            fa.encrypted_file_key
+        FROM file_access fa
+        JOIN files f ON f.id = fa.file_id
+        WHERE fa.user_id = $1
+          AND fa.folder_id = ANY($2)
+          AND fa.is_deleted = FALSE
+          AND f.is_fully_uploaded = TRUE
+        ORDER BY f.created_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(&folder_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let folders = folders
+        .into_iter()
+        .map(|folder| {
+            json!({
+                "folder_id": folder.folder_id,
+                "parent_folder_id": folder.parent_folder_id,
+                "encrypted_metadata": bytes_to_text_or_b64(&folder.encrypted_metadata),
+                "encrypted_folder_key": bytes_to_text_or_b64(&folder.encrypted_folder_key),
+            })
+        })
+        .collect();
+
+    Ok((folders, files))
+}
+
+/// Chunks d'un fichier (clé S3, index) dans l'ordre
+pub async fn get_file_chunk_keys(
+    pool: &PgPool,
+    file_id: Uuid,
+) -> Result<Vec<(String, i32)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i32)>(
+        "SELECT s3_key, index FROM s3_keys WHERE file_id = $1 ORDER BY index",
+    )
+    .bind(file_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Finaliser un upload de fichier
+pub async fn finalize_file_upload(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    file_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    // La réservation devient de l'espace utilisé : les changements sont atomiques
+    let mut tx = db_pool.begin().await?;
+
+    ensure_permission(
+        &mut tx,
+        user_id,
+        ItemKind::File,
+        file_id,
+        Permission::Upload,
+    )
+    .await?;
+
+    // Verrouille la réservation : aucun chunk ne peut être enregistré pendant la finalisation
+    let has_reservation = sqlx::query_scalar::<_, Uuid>(
+        "SELECT file_id FROM storage_reservations WHERE file_id = $1 FOR UPDATE",
+    )
+    .bind(file_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .is_some();
+
+    let declared_size: Option<i64> = sqlx::query_scalar(
+        "SELECT size FROM files WHERE id = $1 AND is_fully_uploaded = FALSE FOR UPDATE",
+    )
+    .bind(file_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    // Seule la première finalisation compte dans l'espace utilisé (taille déclarée, déjà réservée ;
+    // les chunks reçus en sont bornés à l'enregistrement)
+    let finalized_size = match declared_size {
+        Some(_) if !has_reservation => {
+            return Err(sqlx::Error::Protocol(UPLOAD_NOT_IN_PROGRESS.into()));
+        }
+        size => size,
+    };
+
+    // L'espace est compté au propriétaire (qui n'est pas l'uploader pour un ajout d'éditeur)
+    if let Some(size) = finalized_size {
+        sqlx::query("UPDATE files SET updated_at = NOW(), is_fully_uploaded = TRUE WHERE id = $1")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let owner_id: Uuid = sqlx::query_scalar(
+            "SELECT user_id FROM file_access WHERE file_id = $1 AND access_level = 'owner' LIMIT 1",
+        )
+        .bind(file_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        adjust_storage_usage(&mut tx, owner_id, size, 0, 0).await?;
+    }
+
+    delete_storage_reservation(&mut tx, file_id).await?;
+
+    // Le fichier n'apparaît dans le drive qu'une fois finalisé
+    if finalized_size.is_some() {
+        record_change_for_holders(&mut tx, ItemKind::File, file_id, ChangeKind::Created).await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Récupérer les infos de la corbeille
+pub async fn get_corbeille_info(
+    db_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<serde_json::Value, sqlx::Error> {
+    #[derive(FromRow)]
+    struct CorbeilleStats {
+        deleted_files_count: i64,
+        total_deleted_size: Option<i64>,
+    }
+
+    let stats = sqlx::query_as::<_, CorbeilleStats>(
+        "
+        SELECT COUNT(*) as deleted_files_count, COALESCE(SUM(f.size), 0)::BIGINT as total_deleted_size
+        FROM file_access
+        LEFT JOIN files f ON f.id = file_access.file_id
+        WHERE file_access.user_id = $1 AND file_access.is_deleted = TRUE
+        ",
+    )
+    .bind(user_id)
+    .fetch_one(db_pool)
+    .await?;
+
+    let deleted_folders_count: i64 = sqlx::query_scalar::<_, i64>(
+        "
+        SELECT COUNT(*) FROM folder_access
+        WHERE user_id = $1 AND is_deleted = TRUE
+        ",
+    )
+    .bind(user_id)
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(json!({
+        "deleted_files_count": stats.deleted_files_count,
+        "deleted_folders_count": deleted_folders_count,
+        "total_deleted_size": stats.total_deleted_size.unwrap_or(0),
+    }))
+}
+
+/// Récupérer le contenu de la corbeille
+pub async fn get_corbeille_contents(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<serde_json::Value, sqlx::Error> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct DeletedFileRow {
+        folder_id: Option<Uuid>,
+        file_id: Uuid,
+        encrypted_metadata: Vec<u8>,
+        file_size: i64,
+        mime_type: String,
+        created_at: Option<String>,
+        updated_at: Option<String>,
+        access_level: AccessLevel,
+        encrypted_file_key: Vec<u8>,
+        file_type: String,
+    }
+
+    let files: Vec<DeletedFileRow> = sqlx::query_as::<_, DeletedFileRow>(
+        r#"
+        select
+            fa2.folder_id,
+            fa2.file_id as file_id,
+            f.encrypted_metadata,
+            f.size as file_size,
+            f.mime_type,
+            f.created_at::text as created_at,
+            f.updated_at::text as updated_at,
+            fa2.access_level,
+            fa2.encrypted_file_key,
+            'file'::text as file_type
+        from file_access fa2
+        join files f on f.id = fa2.file_id
+        where fa2.user_id = $1
+            and fa2.is_deleted is true
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(serde_json::json!({
+        "files": files.iter().map(|row| {
+            serde_json::json!({
+                "folder_id": row.folder_id,
+                "file_id": row.file_id,
+                "encrypted_metadata": bytes_to_text_or_b64(&row.encrypted_metadata),
+                "file_size": row.file_size,
+                "mime_type": row.mime_type,
+                "created_at": row.created_at,
+                "updated_at": row.updated_at,
+                "access_level": row.access_level,
+                "encrypted_file_key": bytes_to_text_or_b64(&row.encrypted_file_key),
+                "type": row.file_type,
+            })
+        }).collect::<Vec<_>>(),
+    }))
+}
+
+/// Restaurer un fichier de la corbeille
+pub async fn restore_file_from_corbeille(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    file_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    restore_file_from_corbeille_tx(&mut tx, user_id, file_id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Restaurer un fichier de la corbeille, dans la transaction appelante
+pub async fn restore_file_from_corbeille_tx(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    file_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let has_deleted_access = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND is_deleted = TRUE)",
+    )
+    .bind(file_id)
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if !has_deleted_access {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    let folder_id: Option<Uuid> =
+        sqlx::query_scalar("SELECT folder_id FROM file_access WHERE file_id = $1 AND user_id = $2")
+            .bind(file_id)
+            .bind(user_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+    let mut restored_parents = Vec::new();
+    if let Some(fid) = folder_id {
+        let parent_folders: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
+            "
+            WITH RECURSIVE folder_path AS (
+                SELECT f.id, f.parent_folder_id
+                FROM folders f
+                WHERE f.id = $1
+
+                UNION ALL
+
+                SELECT f.id, f.parent_folder_id
+                FROM folders f
+                JOIN folder_path fp ON f.id = fp.parent_folder_id
+            )
+            SELECT DISTINCT fp.id
+            FROM folder_path fp
+            JOIN folder_access fa ON fa.folder_id = fp.id
+            WHERE fa.user_id = $2 AND fa.is_deleted = TRUE
+            ",
+        )
+        .bind(fid)
+        .bind(user_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for parent_id in parent_folders.iter() {
+            sqlx::query(
+                "
+                UPDATE folder_access
+                SET is_deleted = FALSE, updated_at = NOW()
+                WHERE folder_id = $1 AND user_id = $2
+                ",
+            )
+            .bind(parent_id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        restored_parents = parent_folders;
     }
 
-    tx.commit().await?;
-    Ok(())
-}
-
-/// Renommer un fichier (modifier encrypted_metadata)
-pub async fn rename_file(
-    db_pool: &PgPool,
-    user_id: Uuid,
-    file_id: Uuid,
-    new_encrypted_metadata: &str,
-) -> Result<(), sqlx::Error> {
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND (access_level = 'owner' OR access_level = 'editor'))",
+    sqlx::query(
+        "
+        UPDATE file_access
+        SET is_deleted = FALSE, updated_at = NOW()
+        WHERE file_id = $1 AND user_id = $2
+        ",
     )
     .bind(file_id)
     .bind(user_id)
-    .fetch_one(db_pool)
+    .execute(&mut *conn)
     .await?;
 
-    if !has_access {
-        return Err(sqlx::Error::RowNotFound);
-    }
+    sqlx::query(
+        "
+        UPDATE files
+        SET is_deleted = FALSE, updated_at = NOW()
+        WHERE id = $1
+        ",
+    )
+    .bind(file_id)
+    .execute(&mut *conn)
+    .await?;
 
-    sqlx::query("UPDATE files SET encrypted_metadata = $1, updated_at = NOW() WHERE id = $2")
-        .bind(new_encrypted_metadata.as_bytes())
-        .bind(file_id)
-        .execute(db_pool)
-        .await?;
+    // Les dossiers parents restaurés avec l'élément réapparaissent aussi
+    let mut changes: Vec<ChangeEntry> = restored_parents
+        .into_iter()
+        .map(|parent_id| ChangeEntry {
+            user_id,
+            kind: ItemKind::Folder,
+            item_id: parent_id,
+            change: ChangeKind::Restored,
+        })
+        .collect();
+    changes.push(ChangeEntry {
+        user_id,
+        kind: ItemKind::File,
+        item_id: file_id,
+        change: ChangeKind::Restored,
+    });
+    append_changes(conn, &changes).await
+}
 
+/// Restaurer un dossier de la corbeille
+pub async fn restore_folder_from_corbeille(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    restore_folder_from_corbeille_tx(&mut tx, user_id, folder_id).await?;
+    tx.commit().await?;
     Ok(())
 }
 
-/// Renommer un dossier (modifier encrypted_metadata)
-pub async fn rename_folder(
-    db_pool: &PgPool,
+/// Restaurer un dossier de la corbeille, dans la transaction appelante
+pub async fn restore_folder_from_corbeille_tx(
+    conn: &mut PgConnection,
     user_id: Uuid,
     folder_id: Uuid,
-    new_encrypted_metadata: &str,
 ) -> Result<(), sqlx::Error> {
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND (access_level = 'owner' OR access_level = 'editor'))",
+    let has_deleted_access = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND is_deleted = TRUE)",
     )
     .bind(folder_id)
     .bind(user_id)
-    .fetch_one(db_pool)
+    .fetch_one(&mut *conn)
     .await?;
 
-    if !has_access {
+    if !has_deleted_access {
         return Err(sqlx::Error::RowNotFound);
     }
 
-    sqlx::query("UPDATE folders SET encrypted_metadata = $1, updated_at = NOW() WHERE id = $2")
-        .bind(new_encrypted_metadata.as_bytes())
-        .bind(folder_id)
-        .execute(db_pool)
-        .await?;
+    let parent_folders: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
+        "
+        WITH RECURSIVE folder_path AS (
+            SELECT f.id, f.parent_folder_id
+            FROM folders f
+            WHERE f.id = $1
 
-    Ok(())
-}
+            UNION ALL
 
-/// Déplacer un fichier vers un autre dossier
-pub async fn move_file(
-    db_pool: &PgPool,
-    user_id: Uuid,
-    file_id: Uuid,
-    new_folder_id: Option<Uuid>,
-) -> Result<(), sqlx::Error> {
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND (access_level = 'owner' OR access_level = 'editor'))",
+            SELECT f.id, f.parent_folder_id
+            FROM folders f
+            JOIN folder_path fp ON f.id = fp.parent_folder_id
+        )
+        SELECT DISTINCT fp.id
+        FROM folder_path fp
+        JOIN folder_access fa ON fa.folder_id = fp.id
+        WHERE fa.user_id = $2 AND fa.is_deleted = TRUE
+        ",
     )
-    .bind(file_id)
+    .bind(folder_id)
     .bind(user_id)
-    .fetch_one(db_pool)
+    .fetch_all(&mut *conn)
     .await?;
 
-    if !has_access {
-        return Err(sqlx::Error::RowNotFound);
-    }
-
-    if let Some(folder_id) = new_folder_id {
-        let has_folder_access = sqlx::query_scalar::<_, bool>(
-            "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND is_deleted = FALSE AND is_accepted = TRUE)",
+    for parent_id in parent_folders.iter() {
+        sqlx::query(
+            "
+            UPDATE folder_access
+            SET is_deleted = FALSE, updated_at = NOW()
+            WHERE folder_id = $1 AND user_id = $2
+            ",
         )
-        .bind(folder_id)
+        .bind(parent_id)
         .bind(user_id)
-        .fetch_one(db_pool)
+        .execute(&mut *conn)
         .await?;
-
-        if !has_folder_access {
-            return Err(sqlx::Error::RowNotFound);
-        }
     }
 
-    sqlx::query("UPDATE file_access SET folder_id = $1 WHERE file_id = $2 AND user_id = $3")
-        .bind(new_folder_id)
-        .bind(file_id)
-        .bind(user_id)
-        .execute(db_pool)
-        .await?;
+    sqlx::query(
+        "
+        UPDATE folder_access
+        SET is_deleted = FALSE, updated_at = NOW()
+        WHERE folder_id = $1 AND user_id = $2
+        ",
+    )
+    .bind(folder_id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
 
-    Ok(())
+    sqlx::query(
+        "
+        WITH RECURSIVE folder_tree AS (
+            SELECT id
+            FROM folders
+            WHERE id = $1
+            UNION ALL
+            SELECT f.id
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        )
+        UPDATE folder_access fa
+        SET is_deleted = FALSE, updated_at = NOW()
+        FROM folder_tree ft
+        WHERE fa.folder_id = ft.id AND fa.user_id = $2
+        ",
+    )
+    .bind(folder_id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "
+        WITH RECURSIVE folder_tree AS (
+            SELECT id
+            FROM folders
+            WHERE id = $1
+            UNION ALL
+            SELECT f.id
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        )
+        UPDATE file_access fa
+        SET is_deleted = FALSE, updated_at = NOW()
+        FROM folder_tree ft
+        WHERE fa.folder_id = ft.id AND fa.user_id = $2
+        ",
+    )
+    .bind(folder_id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+
+    // Les dossiers parents restaurés avec l'élément réapparaissent aussi
+    let mut changes: Vec<ChangeEntry> = parent_folders
+        .into_iter()
+        .map(|parent_id| ChangeEntry {
+            user_id,
+            kind: ItemKind::Folder,
+            item_id: parent_id,
+            change: ChangeKind::Restored,
+        })
+        .collect();
+    changes.push(ChangeEntry {
+        user_id,
+        kind: ItemKind::Folder,
+        item_id: folder_id,
+        change: ChangeKind::Restored,
+    });
+    append_changes(conn, &changes).await
 }
 
-/// Déplacer un dossier vers un autre parent
-pub async fn move_folder(
+/// Vider la corbeille (suppression définitive)
+pub async fn empty_corbeille(
     db_pool: &PgPool,
+    storage_client: &crate::storage::StorageClient,
     user_id: Uuid,
-    folder_id: Uuid,
-    new_parent_folder_id: Option<Uuid>,
 ) -> Result<(), sqlx::Error> {
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND (access_level = 'owner' OR access_level = 'editor'))",
+    let mut tx = db_pool.begin().await?;
+
+    // Tout le contenu de la corbeille disparaît du drive de l'utilisateur
+    let purged: Vec<ChangeEntry> = sqlx::query_as::<_, (bool, Uuid)>(
+        "
+        SELECT FALSE, file_id FROM file_access WHERE user_id = $1 AND is_deleted = TRUE
+        UNION ALL
+        SELECT TRUE, folder_id FROM folder_access WHERE user_id = $1 AND is_deleted = TRUE
+        ",
     )
-    .bind(folder_id)
     .bind(user_id)
-    .fetch_one(db_pool)
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|(is_folder, item_id)| ChangeEntry {
+        user_id,
+        kind: if is_folder {
+            ItemKind::Folder
+        } else {
+            ItemKind::File
+        },
+        item_id,
+        change: ChangeKind::Purged,
+    })
+    .collect();
+
+    // Fichiers dont l'utilisateur est owner : suppression physique complète (S3 + DB)
+    let owned_file_ids: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
+        "
+        SELECT file_id FROM file_access
+        WHERE user_id = $1 AND is_deleted = TRUE AND access_level = 'owner'
+        ",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let s3_keys = detach_file_chunks(&mut tx, &owned_file_ids).await?;
+
+    for file_id in owned_file_ids.iter() {
+        release_file_usage(&mut tx, *file_id).await?;
+
+        sqlx::query("DELETE FROM file_access WHERE file_id = $1")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM files WHERE id = $1")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // Fichiers partagés dont l'utilisateur n'est PAS owner :
+    // supprimer uniquement la ligne file_access de cet utilisateur
+    sqlx::query(
+        "
+        DELETE FROM file_access
+        WHERE user_id = $1 AND is_deleted = TRUE AND access_level != 'owner'
+        ",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // Dossiers dont l'utilisateur est owner : suppression physique complète
+    let owned_folder_ids: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
+        "
+        SELECT folder_id FROM folder_access
+        WHERE user_id = $1 AND is_deleted = TRUE AND access_level = 'owner'
+        ",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for folder_id in owned_folder_ids.iter() {
+        sqlx::query("DELETE FROM folder_access WHERE folder_id = $1")
+            .bind(folder_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM folders WHERE id = $1")
+            .bind(folder_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // Dossiers partagés dont l'utilisateur n'est PAS owner :
+    // supprimer uniquement la ligne folder_access de cet utilisateur
+    sqlx::query(
+        "
+        DELETE FROM folder_access
+        WHERE user_id = $1 AND is_deleted = TRUE AND access_level != 'owner'
+        ",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
     .await?;
 
-    if !has_access {
-        return Err(sqlx::Error::RowNotFound);
-    }
+    append_changes(&mut tx, &purged).await?;
 
-    if let Some(parent_folder_id) = new_parent_folder_id {
-        let has_parent_access = sqlx::query_scalar::<_, bool>(
-            "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND is_deleted = FALSE AND is_accepted = TRUE)",
-        )
-        .bind(parent_folder_id)
-        .bind(user_id)
-        .fetch_one(db_pool)
-        .await?;
+    tx.commit().await?;
 
-        if !has_parent_access {
-            return Err(sqlx::Error::RowNotFound);
-        }
+    delete_storage_objects(storage_client, &s3_keys).await;
+    Ok(())
+}
 
-        let would_create_cycle = sqlx::query_scalar::<_, bool>(
-            r#"
-            WITH RECURSIVE descendants AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id FROM folders f
-                JOIN descendants d ON f.parent_folder_id = d.id
-            )
-            SELECT EXISTS(SELECT 1 FROM descendants WHERE id = $2)
-            "#,
-        )
-        .bind(folder_id)
-        .bind(parent_folder_id)
-        .fetch_one(db_pool)
-        .await?;
+// ========== Journal des changements ==========
 
-        if would_create_cycle {
-            return Err(sqlx::Error::Protocol(
-                "Cannot move folder into its own descendant".into(),
-            ));
+/// Type d'élément des journaux (`drive_changes`, `activity_log`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    File,
+    Folder,
+}
+
+impl ItemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::File => "file",
+            ItemKind::Folder => "folder",
         }
     }
+}
 
-    sqlx::query("UPDATE folders SET parent_folder_id = $1, is_root = $2 WHERE id = $3")
-        .bind(new_parent_folder_id)
-        .bind(new_parent_folder_id.is_none())
-        .bind(folder_id)
-        .execute(db_pool)
-        .await?;
+/// Type de changement du journal `drive_changes`.
+/// `Deleted` : mis à la corbeille ; `Purged` : supprimé définitivement ou accès retiré.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Renamed,
+    Moved,
+    Deleted,
+    Restored,
+    Shared,
+    Accepted,
+    Purged,
+    KeyRotated,
+}
 
-    Ok(())
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Renamed => "renamed",
+            ChangeKind::Moved => "moved",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Restored => "restored",
+            ChangeKind::Shared => "shared",
+            ChangeKind::Accepted => "accepted",
+            ChangeKind::Purged => "purged",
+            ChangeKind::KeyRotated => "key_rotated",
+        }
+    }
 }
 
-/// Récupérer les infos d'un fichier avec ses chunks
-pub async fn get_file_info(
-    db_pool: &PgPool,
+/// Entrée à ajouter au journal d'un utilisateur
+#[derive(Debug, Clone, Copy)]
+struct ChangeEntry {
     user_id: Uuid,
-    file_id: Uuid,
-) -> Result<serde_json::Value, sqlx::Error> {
-    #[derive(FromRow)]
-    struct FileInfo {
-        file_id: Uuid,
-        encrypted_metadata: Vec<u8>,
-        size: i64,
-        mime_type: String,
-        encrypted_file_key: Vec<u8>,
-        created_at: Option<String>,
-        updated_at: Option<String>,
-    }
+    kind: ItemKind,
+    item_id: Uuid,
+    change: ChangeKind,
+}
 
-    #[derive(FromRow)]
-    struct ChunkInfo {
-        s3_key: String,
-        index: i32,
-        chunk_id: Uuid,
+/// Ajoute des entrées au journal. Les séquences des utilisateurs concernés sont verrouillées
+/// (dans l'ordre des ids, pour éviter les interblocages) jusqu'à la fin de la transaction
+/// appelante : les entrées d'un utilisateur sont validées dans l'ordre de leur numéro.
+/// Appeler une seule fois par transaction quand c'est possible, après les mises à jour des
+/// compteurs d'usage.
+async fn append_changes(
+    conn: &mut PgConnection,
+    entries: &[ChangeEntry],
+) -> Result<(), sqlx::Error> {
+    if entries.is_empty() {
+        return Ok(());
     }
 
-    let file_info = sqlx::query_as::<_, FileInfo>(
-        r#"
-        SELECT
-            f.id as file_id,
-            f.encrypted_metadata,
-            f.size,
-            f.mime_type,
-            fa.encrypted_file_key,
-            f.created_at::text,
-            f.updated_at::text
-        FROM files f
-        JOIN file_access fa ON fa.file_id = f.id
-        WHERE f.id = $1 AND fa.user_id = $2
-        "#,
-    )
-    .bind(file_id)
-    .bind(user_id)
-    .fetch_one(db_pool)
-    .await?;
+    let user_ids: Vec<Uuid> = entries.iter().map(|e| e.user_id).collect();
+    let item_types: Vec<&str> = entries.iter().map(|e| e.kind.as_str()).collect();
+    let item_ids: Vec<Uuid> = entries.iter().map(|e| e.item_id).collect();
+    let change_types: Vec<&str> = entries.iter().map(|e| e.change.as_str()).collect();
 
-    let chunks = sqlx::query_as::<_, ChunkInfo>(
+    sqlx::query(
         r#"
-        SELECT id as chunk_id, s3_key, index
-        FROM s3_keys
-        WHERE file_id = $1
-        ORDER BY index ASC
+        INSERT INTO drive_change_sequences (user_id)
+        SELECT DISTINCT user_id FROM UNNEST($1::uuid[]) AS u(user_id) ORDER BY user_id
+        ON CONFLICT (user_id) DO NOTHING
         "#,
     )
-    .bind(file_id)
-    .fetch_all(db_pool)
+    .bind(&user_ids)
+    .execute(&mut *conn)
     .await?;
 
-    Ok(json!({
-        "file_id": file_info.file_id,
-        "encrypted_metadata": bytes_to_text_or_b64(&file_info.encrypted_metadata),
-        "encrypted_file_key": bytes_to_text_or_b64(&file_info.encrypted_file_key),
-        "size": file_info.size,
-        "mime_type": file_info.mime_type,
-        "created_at": file_info.created_at,
-        "updated_at": file_info.updated_at,
-        "chunk_count": chunks.len(),
-        "chunks": chunks.iter().map(|c| json!({
-            "s3_key": c.s3_key,
-            "index": c.index,
-            "chunk_id": c.chunk_id,
-        })).collect::<Vec<_>>(),
-    }))
-}
-
-/// Récupérer tous les IDs et clés chiffrées d'un dossier récursivement
-pub async fn get_folder_contents_recursive(
-    pool: &PgPool,
-    user_id: Uuid,
-    folder_id: Option<Uuid>,
-) -> Result<Vec<serde_json::Value>, sqlx::Error> {
-    #[derive(FromRow)]
-    struct FolderItem {
-        folder_id: Uuid,
-        encrypted_folder_key: Vec<u8>,
-    }
-
-    #[derive(FromRow)]
-    struct FileItem {
-        file_id: Uuid,
-        encrypted_file_key: Vec<u8>,
-    }
-
-    let folders: Vec<FolderItem> = sqlx::query_as::<_, FolderItem>(
-        r#"
-WITH RECURSIVE folder_tree AS (
-    SELECT f.id as folder_id
-    FROM folders f
-    JOIN folder_access fa ON fa.folder_id = f.id
-    WHERE fa.user_id = $1
-        AND fa.is_deleted = FALSE
-        AND (
-            ($2::uuid IS NULL AND f.parent_folder_id IS NULL)
-            OR f.parent_folder_id = $2::uuid
-        )
-
-    UNION ALL
-
-    SELECT f.id as folder_id
-    FROM folders f
-    JOIN folder_tree ft ON f.parent_folder_id = ft.folder_id
-    JOIN folder_access fa ON fa.folder_id = f.id AND fa.user_id = $1
-    WHERE fa.is_deleted = FALSE
-)
-SELECT
-    ft.folder_id,
-    fa.encrypted_folder_key
-FROM folder_tree ft
-JOIN folder_access fa ON fa.folder_id = ft.folder_id AND fa.user_id = $1
-        "#,
+    sqlx::query(
+        "SELECT 1 FROM drive_change_sequences WHERE user_id = ANY($1) ORDER BY user_id FOR UPDATE",
     )
-    .bind(user_id)
-    .bind(folder_id)
-    .fetch_all(pool)
+    .bind(&user_ids)
+    .execute(&mut *conn)
     .await?;
 
-    let files: Vec<FileItem> = sqlx::query_as::<_, FileItem>(
+    sqlx::query(
         r#"
-WITH RECURSIVE folder_tree AS (
-    SELECT f.id as folder_id
-    FROM folders f
-    WHERE ($1::uuid IS NULL AND f.parent_folder_id IS NULL)
-       OR f.id = $1::uuid
-
-    UNION ALL
-
-    SELECT f.id as folder_id
-    FROM folders f
-    JOIN folder_tree ft ON f.parent_folder_id = ft.folder_id
-)
-SELECT
-    fa.file_id,
-    fa.encrypted_file_key
-FROM folder_tree ft
-JOIN file_access fa ON fa.folder_id = ft.folder_id AND fa.user_id = $2
-JOIN files ON files.id = fa.file_id
-WHERE fa.is_deleted = FALSE
-  AND files.is_fully_uploaded = TRUE
+        WITH entries AS (
+            SELECT e.user_id, e.item_type, e.item_id, e.change_type,
+                   ROW_NUMBER() OVER (PARTITION BY e.user_id ORDER BY e.ord) AS n
+            FROM UNNEST($1::uuid[], $2::text[], $3::uuid[], $4::text[])
+                 WITH ORDINALITY AS e(user_id, item_type, item_id, change_type, ord)
+        ),
+        counts AS (
+            SELECT user_id, COUNT(*) AS n FROM entries GROUP BY user_id
+        ),
+        bumped AS (
+            UPDATE drive_change_sequences s
+            SET last_seq = s.last_seq + c.n
+            FROM counts c
+            WHERE s.user_id = c.user_id
+            RETURNING s.user_id, s.last_seq - c.n AS base
+        )
+        INSERT INTO drive_changes (user_id, seq, item_type, item_id, change_type)
+        SELECT e.user_id, b.base + e.n, e.item_type, e.item_id, e.change_type
+        FROM entries e
+        JOIN bumped b ON b.user_id = e.user_id
         "#,
     )
-    .bind(folder_id)
-    .bind(user_id)
-    .fetch_all(pool)
+    .bind(&user_ids)
+    .bind(&item_types)
+    .bind(&item_ids)
+    .bind(&change_types)
+    .execute(&mut *conn)
     .await?;
 
-    let mut results = Vec::new();
-
-    for folder in folders {
-        results.push(json!({
-            "type": "folder",
-            "folder_id": folder.folder_id,
-            "encrypted_folder_key": bytes_to_text_or_b64(&folder.encrypted_folder_key),
-        }));
-    }
-
-    for file in files {
-        results.push(json!({
-            "type": "file",
-            "file_id": file.file_id,
-            "encrypted_file_key": bytes_to_text_or_b64(&file.encrypted_file_key),
-        }));
-    }
-
-    Ok(results)
+    Ok(())
 }
 
-/// Finaliser un upload de fichier
-pub async fn finalize_file_upload(
-    db_pool: &PgPool,
+/// Ajoute une entrée au journal de `user_id`
+async fn record_change(
+    conn: &mut PgConnection,
     user_id: Uuid,
-    file_id: Uuid,
+    kind: ItemKind,
+    item_id: Uuid,
+    change: ChangeKind,
 ) -> Result<(), sqlx::Error> {
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND access_level = 'owner')",
+    append_changes(
+        conn,
+        &[ChangeEntry {
+            user_id,
+            kind,
+            item_id,
+            change,
+        }],
     )
-    .bind(file_id)
-    .bind(user_id)
-    .fetch_one(db_pool)
-    .await?;
-
-    if !has_access {
-        return Err(sqlx::Error::RowNotFound);
-    }
+    .await
+}
 
-    sqlx::query("UPDATE files SET updated_at = NOW(), is_fully_uploaded = TRUE WHERE id = $1")
-        .bind(file_id)
-        .execute(db_pool)
-        .await?;
+/// Utilisateurs ayant un accès (quel qu'il soit) à un fichier ou un dossier
+async fn item_holders(
+    conn: &mut PgConnection,
+    kind: ItemKind,
+    item_id: Uuid,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let query = match kind {
+        ItemKind::File => "SELECT user_id FROM file_access WHERE file_id = $1",
+        ItemKind::Folder => "SELECT user_id FROM folder_access WHERE folder_id = $1",
+    };
+    sqlx::query_scalar::<_, Uuid>(query)
+        .bind(item_id)
+        .fetch_all(&mut *conn)
+        .await
+}
 
-    Ok(())
+/// Ajoute une entrée au journal de chaque utilisateur ayant accès à l'élément
+async fn record_change_for_holders(
+    conn: &mut PgConnection,
+    kind: ItemKind,
+    item_id: Uuid,
+    change: ChangeKind,
+) -> Result<(), sqlx::Error> {
+    let entries: Vec<ChangeEntry> = item_holders(&mut *conn, kind, item_id)
+        .await?
+        .into_iter()
+        .map(|user_id| ChangeEntry {
+            user_id,
+            kind,
+            item_id,
+            change,
+        })
+        .collect();
+    append_changes(conn, &entries).await
 }
 
-/// Récupérer les infos de la corbeille
-pub async fn get_corbeille_info(
-    db_pool: &PgPool,
+/// Retraits d'accès des autres utilisateurs à l'arborescence d'un dossier,
+/// à relever avant de supprimer leurs accès
+async fn folder_tree_foreign_removals(
+    conn: &mut PgConnection,
+    folder_id: Uuid,
     user_id: Uuid,
-) -> Result<serde_json::Value, sqlx::Error> {
-    #[derive(FromRow)]
-    struct CorbeilleStats {
-        deleted_files_count: i64,
-        total_deleted_size: Option<i64>,
-    }
-
-    let stats = sqlx::query_as::<_, CorbeilleStats>(
-        "
-        SELECT COUNT(*) as deleted_files_count, COALESCE(SUM(f.size), 0)::BIGINT as total_deleted_size
-        FROM file_access
-        LEFT JOIN files f ON f.id = file_access.file_id
-        WHERE file_access.user_id = $1 AND file_access.is_deleted = TRUE
-        ",
+) -> Result<Vec<ChangeEntry>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (Uuid, bool, Uuid)>(
+        r#"
+        WITH RECURSIVE folder_tree AS (
+            SELECT id FROM folders WHERE id = $1
+            UNION ALL
+            SELECT f.id FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        )
+        SELECT fa.user_id, TRUE, fa.folder_id
+        FROM folder_access fa
+        JOIN folder_tree ft ON fa.folder_id = ft.id
+        WHERE fa.user_id != $2
+        UNION ALL
+        SELECT fa.user_id, FALSE, fa.file_id
+        FROM file_access fa
+        JOIN folder_tree ft ON fa.folder_id = ft.id
+        WHERE fa.user_id != $2
+        "#,
     )
+    .bind(folder_id)
     .bind(user_id)
-    .fetch_one(db_pool)
+    .fetch_all(&mut *conn)
     .await?;
 
-    let deleted_folders_count: i64 = sqlx::query_scalar::<_, i64>(
-        "
-        SELECT COUNT(*) FROM folder_access
-        WHERE user_id = $1 AND is_deleted = TRUE
-        ",
-    )
-    .bind(user_id)
-    .fetch_one(db_pool)
-    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(user_id, is_folder, item_id)| ChangeEntry {
+            user_id,
+            kind: if is_folder {
+                ItemKind::Folder
+            } else {
+                ItemKind::File
+            },
+            item_id,
+            change: ChangeKind::Purged,
+        })
+        .collect())
+}
 
-    Ok(json!({
-        "deleted_files_count": stats.deleted_files_count,
-        "deleted_folders_count": deleted_folders_count,
-        "total_deleted_size": stats.total_deleted_size.unwrap_or(0),
-    }))
+/// Entrée du journal renvoyée par `GET /drive/changes`
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct DriveChange {
+    pub seq: i64,
+    pub item_type: String,
+    pub item_id: Uuid,
+    pub change_type: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
-/// Récupérer le contenu de la corbeille
-pub async fn get_corbeille_contents(
+/// Page du journal à partir d'un curseur
+#[derive(Debug, serde::Serialize)]
+pub struct DriveChangesPage {
+    pub changes: Vec<DriveChange>,
+    /// Curseur à renvoyer à la prochaine requête
+    pub cursor: i64,
+    pub has_more: bool,
+    /// Curseur absent, expiré ou inconnu : le client doit recharger toute l'arborescence
+    /// puis reprendre à partir de `cursor`
+    pub reset_required: bool,
+}
+
+/// Entrées du journal de `user_id` postérieures à `cursor` (au plus `limit`)
+pub async fn get_drive_changes(
     pool: &PgPool,
     user_id: Uuid,
-) -> Result<serde_json::Value, sqlx::Error> {
-    #[derive(Debug, sqlx::FromRow)]
-    struct DeletedFileRow {
-        folder_id: Option<Uuid>,
-        file_id: Uuid,
-        encrypted_metadata: Vec<u8>,
-        file_size: i64,
-        mime_type: String,
-        created_at: Option<String>,
-        updated_at: Option<String>,
-        access_level: String,
-        encrypted_file_key: Vec<u8>,
-        file_type: String,
-    }
+    cursor: Option<i64>,
+    limit: i64,
+) -> Result<DriveChangesPage, sqlx::Error> {
+    let (last_seq, pruned_seq): (i64, i64) = sqlx::query_as(
+        "SELECT last_seq, pruned_seq FROM drive_change_sequences WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or((0, 0));
+
+    let Some(cursor) =
+        cursor.filter(|c| services::change_cursor_is_valid(*c, pruned_seq, last_seq))
+    else {
+        return Ok(DriveChangesPage {
+            changes: Vec::new(),
+            cursor: last_seq,
+            has_more: false,
+            reset_required: true,
+        });
+    };
 
-    let files: Vec<DeletedFileRow> = sqlx::query_as::<_, DeletedFileRow>(
+    let mut changes = sqlx::query_as::<_, DriveChange>(
         r#"
-        select
-            fa2.folder_id,
-            fa2.file_id as file_id,
-            f.encrypted_metadata,
-            f.size as file_size,
-            f.mime_type,
-            f.created_at::text as created_at,
-            f.updated_at::text as updated_at,
-            fa2.access_level,
-            fa2.encrypted_file_key,
-            'file'::text as file_type
-        from file_access fa2
-        join files f on f.id = fa2.file_id
-        where fa2.user_id = $1
-            and fa2.is_deleted is true
+        SELECT seq, item_type, item_id, change_type, created_at
+        FROM drive_changes
+        WHERE user_id = $1 AND seq > $2
+        ORDER BY seq
+        LIMIT $3
         "#,
     )
     .bind(user_id)
+    .bind(cursor)
+    .bind(limit + 1)
     .fetch_all(pool)
     .await?;
 
-    Ok(serde_json::json!({
-        "files": files.iter().map(|row| {
-            serde_json::json!({
-                "folder_id": row.folder_id,
-                "file_id": row.file_id,
-                "encrypted_metadata": bytes_to_text_or_b64(&row.encrypted_metadata),
-                "file_size": row.file_size,
-                "mime_type": row.mime_type,
-                "created_at": row.created_at,
-                "updated_at": row.updated_at,
-                "access_level": row.access_level,
-                "encrypted_file_key": bytes_to_text_or_b64(&row.encrypted_file_key),
-                "type": row.file_type,
-            })
-        }).collect::<Vec<_>>(),
-    }))
+    let has_more = changes.len() as i64 > limit;
+    changes.truncate(limit as usize);
+    let cursor = changes.last().map(|c| c.seq).unwrap_or(cursor);
+
+    Ok(DriveChangesPage {
+        changes,
+        cursor,
+        has_more,
+        reset_required: false,
+    })
 }
 
-/// Restaurer un fichier de la corbeille
-pub async fn restore_file_from_corbeille(
+/// Supprime au plus `limit` entrées du journal plus anciennes que `retention_days` et retient,
+/// par utilisateur, le dernier numéro supprimé (les curseurs antérieurs deviennent expirés).
+/// Retourne le nombre d'entrées supprimées.
+pub async fn prune_drive_changes(
+    pool: &PgPool,
+    retention_days: i64,
+    limit: i64,
+) -> Result<u64, sqlx::Error> {
+    let pruned: i64 = sqlx::query_scalar(
+        r#"
+        WITH pruned AS (
+            DELETE FROM drive_changes
+            WHERE (user_id, seq) IN (
+                SELECT user_id, seq
+                FROM drive_changes
+                WHERE created_at < NOW() - make_interval(days => $1::INT)
+                LIMIT $2
+            )
+            RETURNING user_id, seq
+        ),
+        per_user AS (
+            SELECT user_id, MAX(seq) AS max_seq, COUNT(*) AS n FROM pruned GROUP BY user_id
+        ),
+        updated AS (
+            UPDATE drive_change_sequences s
+            SET pruned_seq = GREATEST(s.pruned_seq, p.max_seq)
+            FROM per_user p
+            WHERE s.user_id = p.user_id
+        )
+        SELECT COALESCE(SUM(n), 0)::BIGINT FROM per_user
+        "#,
+    )
+    .bind(retention_days as i32)
+    .bind(limit)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(pruned as u64)
+}
+
+// ========== Opérations groupées ==========
+
+/// Opération d'un lot (`POST /drive/batch`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    MoveFile {
+        file_id: Uuid,
+        target_folder_id: Option<Uuid>,
+    },
+    MoveFolder {
+        folder_id: Uuid,
+        target_folder_id: Option<Uuid>,
+    },
+    DeleteFile {
+        file_id: Uuid,
+    },
+    DeleteFolder {
+        folder_id: Uuid,
+    },
+    RestoreFile {
+        file_id: Uuid,
+    },
+    RestoreFolder {
+        folder_id: Uuid,
+    },
+}
+
+impl BatchOperation {
+    /// Déplacement et restauration sont refusés sur un drive en lecture seule (suppression autorisée)
+    pub fn requires_writable_drive(&self) -> bool {
+        !matches!(
+            self,
+            BatchOperation::DeleteFile { .. } | BatchOperation::DeleteFolder { .. }
+        )
+    }
+}
+
+/// Exécute un lot d'opérations dans une seule transaction, chacune dans un savepoint.
+/// - `atomic = false` : une opération en échec est annulée seule, les autres sont appliquées
+/// - `atomic = true` : le premier échec annule tout le lot (les résultats s'arrêtent à cet échec)
+///
+/// Les chunks des fichiers supprimés définitivement sont effacés de S3 après validation.
+pub async fn execute_batch(
     db_pool: &PgPool,
+    storage_client: &crate::storage::StorageClient,
     user_id: Uuid,
-    file_id: Uuid,
-) -> Result<(), sqlx::Error> {
+    operations: &[BatchOperation],
+    atomic: bool,
+) -> Result<Vec<Result<(), sqlx::Error>>, sqlx::Error> {
     let mut tx = db_pool.begin().await?;
+    let mut results = Vec::with_capacity(operations.len());
+    let mut s3_keys = Vec::new();
+
+    for operation in operations {
+        let mut savepoint = sqlx::Acquire::begin(&mut tx).await?;
+
+        let result = match *operation {
+            BatchOperation::MoveFile {
+                file_id,
+                target_folder_id,
+            } => move_file_tx(&mut savepoint, user_id, file_id, target_folder_id)
+                .await
+                .map(|()| Vec::new()),
+            BatchOperation::MoveFolder {
+                folder_id,
+                target_folder_id,
+            } => move_folder_tx(&mut savepoint, user_id, folder_id, target_folder_id)
+                .await
+                .map(|()| Vec::new()),
+            BatchOperation::DeleteFile { file_id } => {
+                delete_file_tx(&mut savepoint, user_id, file_id).await
+            }
+            BatchOperation::DeleteFolder { folder_id } => {
+                delete_folder_tx(&mut savepoint, user_id, folder_id)
+                    .await
+                    .map(|()| Vec::new())
+            }
+            BatchOperation::RestoreFile { file_id } => {
+                restore_file_from_corbeille_tx(&mut savepoint, user_id, file_id)
+                    .await
+                    .map(|()| Vec::new())
+            }
+            BatchOperation::RestoreFolder { folder_id } => {
+                restore_folder_from_corbeille_tx(&mut savepoint, user_id, folder_id)
+                    .await
+                    .map(|()| Vec::new())
+            }
+        };
 
-    let has_deleted_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND is_deleted = TRUE)",
-    )
-    .bind(file_id)
-    .bind(user_id)
-    .fetch_one(&mut *tx)
-    .await?;
+        match result {
+            Ok(keys) => {
+                savepoint.commit().await?;
+                s3_keys.extend(keys);
+                results.push(Ok(()));
+            }
+            Err(e) => {
+                savepoint.rollback().await?;
+                results.push(Err(e));
+                if atomic {
+                    tx.rollback().await?;
+                    return Ok(results);
+                }
+            }
+        }
+    }
 
-    if !has_deleted_access {
-        return Err(sqlx::Error::RowNotFound);
+    tx.commit().await?;
+    delete_storage_objects(storage_client, &s3_keys).await;
+    Ok(results)
+}
+
+/// Partager un dossier avec un contact (avec clés rechiffrées)
+pub async fn share_folder_batch(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+    contact_user_id: Uuid,
+    grant: ShareGrant,
+    folder_keys: Vec<(Uuid, String)>,
+    file_keys: Vec<(Uuid, String)>,
+) -> Result<(), sqlx::Error> {
+    if contact_user_id == user_id {
+        return Err(sqlx::Error::Protocol(
+            "Cannot share folder with oneself".into(),
+        ));
     }
 
-    let folder_id: Option<Uuid> =
-        sqlx::query_scalar("SELECT folder_id FROM file_access WHERE file_id = $1 AND user_id = $2")
-            .bind(file_id)
-            .bind(user_id)
+    let mut tx = db_pool.begin().await?;
+
+    let contact_exists =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+            .bind(contact_user_id)
             .fetch_one(&mut *tx)
             .await?;
 
-    if let Some(fid) = folder_id {
-        let parent_folders: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
-            "
-            WITH RECURSIVE folder_path AS (
-                SELECT f.id, f.parent_folder_id
-                FROM folders f
-                WHERE f.id = $1
+    if !contact_exists {
+        return Err(sqlx::Error::RowNotFound);
+    }
 
-                UNION ALL
+    ensure_can_grant(
+        &mut tx,
+        user_id,
+        ItemKind::Folder,
+        folder_id,
+        grant.access_level,
+    )
+    .await?;
 
-                SELECT f.id, f.parent_folder_id
-                FROM folders f
-                JOIN folder_path fp ON f.id = fp.parent_folder_id
-            )
-            SELECT DISTINCT fp.id
-            FROM folder_path fp
-            JOIN folder_access fa ON fa.folder_id = fp.id
-            WHERE fa.user_id = $2 AND fa.is_deleted = TRUE
+    for (fid, encrypted_key) in folder_keys {
+        sqlx::query(
+            "
+            INSERT INTO folder_access (id, folder_id, user_id, encrypted_folder_key, access_level, expires_at, shared_by, key_version, created_at, updated_at, is_deleted, is_accepted, is_root_anchor)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, (SELECT key_version FROM folders WHERE id = $2), NOW(), NOW(), FALSE, FALSE, FALSE)
+            ON CONFLICT (folder_id, user_id) DO UPDATE
+            SET encrypted_folder_key = EXCLUDED.encrypted_folder_key,
+                team_id = NULL,
+                shared_by = EXCLUDED.shared_by,
+                key_version = EXCLUDED.key_version,
+                access_level = EXCLUDED.access_level,
+                expires_at = EXCLUDED.expires_at,
+                updated_at = NOW(),
+                is_deleted = FALSE,
+                is_accepted = FALSE,
+                is_root_anchor = FALSE
+            WHERE folder_access.access_level <> 'owner'
             ",
         )
+        .bind(Uuid::new_v4())
         .bind(fid)
+        .bind(contact_user_id)
+        .bind(encrypted_key.as_bytes())
+        .bind(grant.access_level)
+        .bind(grant.expires_at)
         .bind(user_id)
-        .fetch_all(&mut *tx)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (file_id, encrypted_key) in file_keys {
+        let folder_id_for_file: Option<Uuid> = sqlx::query_scalar(
+            "
+            SELECT folder_id
+            FROM file_access
+            WHERE file_id = $1 AND user_id = $2
+            ",
+        )
+        .bind(file_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
         .await?;
 
-        for parent_id in parent_folders {
+        if let Some(folder_id_val) = folder_id_for_file {
             sqlx::query(
                 "
-                UPDATE folder_access
-                SET is_deleted = FALSE, updated_at = NOW()
-                WHERE folder_id = $1 AND user_id = $2
+                INSERT INTO file_access (id, file_id, user_id, folder_id, encrypted_file_key, access_level, expires_at, shared_by, created_at, updated_at, is_deleted, is_accepted)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW(), NOW(), FALSE, FALSE)
+                ON CONFLICT (file_id, user_id) DO UPDATE
+                SET encrypted_file_key = EXCLUDED.encrypted_file_key,
+                    team_id = NULL,
+                    shared_by = EXCLUDED.shared_by,
+                    access_level = EXCLUDED.access_level,
+                    expires_at = EXCLUDED.expires_at,
+                    updated_at = NOW(),
+                    is_deleted = FALSE,
+                    is_accepted = FALSE
+                WHERE file_access.access_level <> 'owner'
                 ",
             )
-            .bind(parent_id)
+            .bind(Uuid::new_v4())
+            .bind(file_id)
+            .bind(contact_user_id)
+            .bind(folder_id_val)
+            .bind(encrypted_key.as_bytes())
+            .bind(grant.access_level)
+            .bind(grant.expires_at)
             .bind(user_id)
             .execute(&mut *tx)
             .await?;
         }
     }
 
-    sqlx::query(
-        "
-        UPDATE file_access
-        SET is_deleted = FALSE, updated_at = NOW()
-        WHERE file_id = $1 AND user_id = $2
-        ",
+    // Une entrée pour le dossier partagé : son contenu est chargé à l'acceptation
+    record_change(
+        &mut tx,
+        contact_user_id,
+        ItemKind::Folder,
+        folder_id,
+        ChangeKind::Shared,
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Partager un dossier avec un contact (version simple, sans propagation aux enfants)
+pub async fn share_folder_with_contact(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+    contact_user_id: Uuid,
+    encrypted_folder_key: &str,
+    grant: ShareGrant,
+) -> Result<(), sqlx::Error> {
+    if contact_user_id == user_id {
+        return Err(sqlx::Error::Protocol(
+            "Cannot share folder with oneself".into(),
+        ));
+    }
+
+    let mut tx = db_pool.begin().await?;
+
+    let contact_exists =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+            .bind(contact_user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    if !contact_exists {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    ensure_can_grant(
+        &mut tx,
+        user_id,
+        ItemKind::Folder,
+        folder_id,
+        grant.access_level,
     )
-    .bind(file_id)
-    .bind(user_id)
-    .execute(&mut *tx)
     .await?;
 
     sqlx::query(
         "
-        UPDATE files
-        SET is_deleted = FALSE, updated_at = NOW()
-        WHERE id = $1
+        INSERT INTO folder_access (id, folder_id, user_id, encrypted_folder_key, access_level, expires_at, shared_by, key_version, created_at, updated_at, is_deleted, is_accepted, is_root_anchor)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, (SELECT key_version FROM folders WHERE id = $2), NOW(), NOW(), FALSE, FALSE, FALSE)
+        ON CONFLICT (folder_id, user_id) DO UPDATE
+        SET encrypted_folder_key = EXCLUDED.encrypted_folder_key,
+            team_id = NULL,
+            shared_by = EXCLUDED.shared_by,
+            key_version = EXCLUDED.key_version,
+            access_level = EXCLUDED.access_level,
+            expires_at = EXCLUDED.expires_at,
+            updated_at = NOW(),
+            is_deleted = FALSE,
+            is_accepted = FALSE,
+            is_root_anchor = FALSE
+        WHERE folder_access.access_level <> 'owner'
         ",
     )
-    .bind(file_id)
+    .bind(Uuid::new_v4())
+    .bind(folder_id)
+    .bind(contact_user_id)
+    .bind(encrypted_folder_key.as_bytes())
+    .bind(grant.access_level)
+    .bind(grant.expires_at)
+    .bind(user_id)
     .execute(&mut *tx)
     .await?;
 
+    record_change(
+        &mut tx,
+        contact_user_id,
+        ItemKind::Folder,
+        folder_id,
+        ChangeKind::Shared,
+    )
+    .await?;
+
     tx.commit().await?;
     Ok(())
 }
 
-/// Restaurer un dossier de la corbeille
-pub async fn restore_folder_from_corbeille(
+/// Partager un fichier avec un contact
+pub async fn share_file_with_contact(
     db_pool: &PgPool,
     user_id: Uuid,
-    folder_id: Uuid,
+    file_id: Uuid,
+    contact_user_id: Uuid,
+    encrypted_file_key: &str,
+    grant: ShareGrant,
 ) -> Result<(), sqlx::Error> {
+    if contact_user_id == user_id {
+        return Err(sqlx::Error::Protocol(
+            "Cannot share file with oneself".into(),
+        ));
+    }
+
     let mut tx = db_pool.begin().await?;
 
-    let has_deleted_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND is_deleted = TRUE)",
-    )
-    .bind(folder_id)
-    .bind(user_id)
-    .fetch_one(&mut *tx)
-    .await?;
+    let contact_exists =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+            .bind(contact_user_id)
+            .fetch_one(&mut *tx)
+            .await?;
 
-    if !has_deleted_access {
+    if !contact_exists {
         return Err(sqlx::Error::RowNotFound);
     }
 
-    let parent_folders: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
-        "
-        WITH RECURSIVE folder_path AS (
-            SELECT f.id, f.parent_folder_id
-            FROM folders f
-            WHERE f.id = $1
-
-            UNION ALL
-
-            SELECT f.id, f.parent_folder_id
-            FROM folders f
-            JOIN folder_path fp ON f.id = fp.parent_folder_id
-        )
-        SELECT DISTINCT fp.id
-        FROM folder_path fp
-        JOIN folder_access fa ON fa.folder_id = fp.id
-        WHERE fa.user_id = $2 AND fa.is_deleted = TRUE
-        ",
+    ensure_can_grant(
+        &mut tx,
+        user_id,
+        ItemKind::File,
+        file_id,
+        grant.access_level,
     )
-    .bind(folder_id)
-    .bind(user_id)
-    .fetch_all(&mut *tx)
     .await?;
 
-    for parent_id in parent_folders {
-        sqlx::query(
-            "
-            UPDATE folder_access
-            SET is_deleted = FALSE, updated_at = NOW()
-            WHERE folder_id = $1 AND user_id = $2
-            ",
-        )
-        .bind(parent_id)
-        .bind(user_id)
-        .execute(&mut *tx)
-        .await?;
-    }
-
     sqlx::query(
         "
-        UPDATE folder_access
-        SET is_deleted = FALSE, updated_at = NOW()
-        WHERE folder_id = $1 AND user_id = $2
+        INSERT INTO file_access (id, file_id, user_id, folder_id, encrypted_file_key, access_level, expires_at, shared_by, created_at, updated_at, is_deleted, is_accepted)
+        VALUES ($1, $2, $3, NULL, $4, $5, $6, $7, NOW(), NOW(), FALSE, FALSE)
+        ON CONFLICT (file_id, user_id) DO UPDATE
+        SET encrypted_file_key = EXCLUDED.encrypted_file_key,
+            team_id = NULL,
+            shared_by = EXCLUDED.shared_by,
+            access_level = EXCLUDED.access_level,
+            expires_at = EXCLUDED.expires_at,
+            updated_at = NOW(),
+            is_deleted = FALSE,
+            is_accepted = FALSE
+        WHERE file_access.access_level <> 'owner'
         ",
     )
-    .bind(folder_id)
+    .bind(Uuid::new_v4())
+    .bind(file_id)
+    .bind(contact_user_id)
+    .bind(encrypted_file_key.as_bytes())
+    .bind(grant.access_level)
+    .bind(grant.expires_at)
     .bind(user_id)
     .execute(&mut *tx)
     .await?;
 
-    sqlx::query(
-        "
-        WITH RECURSIVE folder_tree AS (
-            SELECT id
-            FROM folders
-            WHERE id = $1
-            UNION ALL
-            SELECT f.id
-            FROM folders f
-            JOIN folder_tree ft ON f.parent_folder_id = ft.id
-        )
-        UPDATE folder_access fa
-        SET is_deleted = FALSE, updated_at = NOW()
-        FROM folder_tree ft
-        WHERE fa.folder_id = ft.id AND fa.user_id = $2
-        ",
+    record_change(
+        &mut tx,
+        contact_user_id,
+        ItemKind::File,
+        file_id,
+        ChangeKind::Shared,
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Utilisateur ayant accès à un élément partagé
+#[derive(Debug, FromRow)]
+pub struct SharedUser {
+    pub user_id: Uuid,
+    pub access_level: AccessLevel,
+    /// Révocation automatique prévue (partage temporaire)
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Accès accordé par une équipe (None : partage direct)
+    pub team_id: Option<Uuid>,
+}
+
+/// Récupérer la liste des utilisateurs ayant accès à un dossier
+pub async fn get_folder_shared_users(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<Vec<SharedUser>, sqlx::Error> {
+    let has_access = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2)",
     )
     .bind(folder_id)
     .bind(user_id)
-    .execute(&mut *tx)
+    .fetch_one(db_pool)
     .await?;
 
-    sqlx::query(
+    if !has_access {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    let shared_users = sqlx::query_as::<_, SharedUser>(
         "
-        WITH RECURSIVE folder_tree AS (
-            SELECT id
-            FROM folders
-            WHERE id = $1
-            UNION ALL
-            SELECT f.id
-            FROM folders f
-            JOIN folder_tree ft ON f.parent_folder_id = ft.id
-        )
-        UPDATE file_access fa
-        SET is_deleted = FALSE, updated_at = NOW()
-        FROM folder_tree ft
-        WHERE fa.folder_id = ft.id AND fa.user_id = $2
+        SELECT user_id, access_level, expires_at, team_id
+        FROM folder_access
+        WHERE folder_id = $1 AND user_id != $2 AND is_deleted = FALSE
         ",
     )
     .bind(folder_id)
     .bind(user_id)
-    .execute(&mut *tx)
+    .fetch_all(db_pool)
     .await?;
 
-    tx.commit().await?;
-    Ok(())
+    Ok(shared_users)
 }
 
-/// Vider la corbeille (suppression définitive)
-pub async fn empty_corbeille(
+/// Récupérer la liste des utilisateurs ayant accès à un fichier
+pub async fn get_file_shared_users(
     db_pool: &PgPool,
-    storage_client: &crate::storage::StorageClient,
     user_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    let mut tx = db_pool.begin().await?;
-
-    // Fichiers dont l'utilisateur est owner : suppression physique complète (S3 + DB)
-    let owned_file_ids: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
-        "
-        SELECT file_id FROM file_access
-        WHERE user_id = $1 AND is_deleted = TRUE AND access_level = 'owner'
-        ",
+    file_id: Uuid,
+) -> Result<Vec<SharedUser>, sqlx::Error> {
+    let has_access = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND is_deleted = FALSE)"
     )
+    .bind(file_id)
     .bind(user_id)
-    .fetch_all(&mut *tx)
+    .fetch_one(db_pool)
     .await?;
 
-    for file_id in owned_file_ids.iter() {
-        let s3_keys: Vec<String> =
-            sqlx::query_scalar::<_, String>("SELECT s3_key FROM s3_keys WHERE file_id = $1")
-                .bind(file_id)
-                .fetch_all(&mut *tx)
-                .await?;
-
-        for s3_key in s3_keys.iter() {
-            storage_client.delete_line(s3_key).await.map_err(|e| {
-                sqlx::Error::Protocol(format!("Failed to delete from storage: {}", e))
-            })?;
-        }
-
-        sqlx::query("DELETE FROM s3_keys WHERE file_id = $1")
-            .bind(file_id)
-            .execute(&mut *tx)
-            .await?;
-
-        sqlx::query("DELETE FROM file_access WHERE file_id = $1")
-            .bind(file_id)
-            .execute(&mut *tx)
-            .await?;
-
-        sqlx::query("DELETE FROM files WHERE id = $1")
-            .bind(file_id)
-            .execute(&mut *tx)
-            .await?;
+    if !has_access {
+        return Err(sqlx::Error::RowNotFound);
     }
-
-    // Fichiers partagés dont l'utilisateur n'est PAS owner :
-    // supprimer uniquement la ligne file_access de cet utilisateur
-    sqlx::query(
+    let shared_users = sqlx::query_as::<_, SharedUser>(
         "
-        DELETE FROM file_access
-        WHERE user_id = $1 AND is_deleted = TRUE AND access_level != 'owner'
+        SELECT user_id, access_level, expires_at, team_id
+        FROM file_access
+        WHERE file_id = $1 AND user_id != $2 AND is_deleted = FALSE
         ",
     )
+    .bind(file_id)
     .bind(user_id)
-    .execute(&mut *tx)
+    .fetch_all(db_pool)
     .await?;
 
-    // Dossiers dont l'utilisateur est owner : suppression physique complète
-    let owned_folder_ids: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
-        "
-        SELECT folder_id FROM folder_access
-        WHERE user_id = $1 AND is_deleted = TRUE AND access_level = 'owner'
-        ",
+    Ok(shared_users)
+}
+
+/// Propager les permissions d'un fichier nouvellement créé
+pub async fn propagate_file_access(
+    db_pool: &PgPool,
+    owner_id: Uuid,
+    file_id: Uuid,
+    user_keys: Vec<(Uuid, String, AccessLevel)>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let owner_level = ensure_permission(
+        &mut tx,
+        owner_id,
+        ItemKind::File,
+        file_id,
+        Permission::Share,
     )
-    .bind(user_id)
-    .fetch_all(&mut *tx)
     .await?;
+    if user_keys
+        .iter()
+        .any(|(_, _, access_level)| !owner_level.can_grant(*access_level))
+    {
+        return Err(sqlx::Error::Protocol(ACCESS_LEVEL_NOT_GRANTABLE.into()));
+    }
 
-    for folder_id in owned_folder_ids.iter() {
-        sqlx::query("DELETE FROM folder_access WHERE folder_id = $1")
-            .bind(folder_id)
-            .execute(&mut *tx)
+    let folder_id: Option<Uuid> =
+        sqlx::query_scalar("SELECT folder_id FROM file_access WHERE file_id = $1 AND user_id = $2")
+            .bind(file_id)
+            .bind(owner_id)
+            .fetch_optional(&mut *tx)
             .await?;
 
-        sqlx::query("DELETE FROM folders WHERE id = $1")
-            .bind(folder_id)
-            .execute(&mut *tx)
-            .await?;
+    let mut changes = Vec::new();
+    for (user_id, encrypted_key, access_level) in user_keys {
+        sqlx::query(
+            "
+            INSERT INTO file_access (id, file_id, user_id, folder_id, encrypted_file_key, access_level, created_at, updated_at, is_deleted, is_accepted)
+            VALUES ($1, $2, $3, $4, $5, $6, NOW(), NOW(), FALSE, TRUE)
+            ON CONFLICT (file_id, user_id) DO UPDATE
+            SET encrypted_file_key = EXCLUDED.encrypted_file_key,
+                team_id = NULL,
+                access_level = EXCLUDED.access_level,
+                updated_at = NOW(),
+                is_deleted = FALSE,
+                is_accepted = TRUE
+            WHERE file_access.access_level <> 'owner'
+            "
+        )
+        .bind(Uuid::new_v4())
+        .bind(file_id)
+        .bind(user_id)
+        .bind(folder_id)
+        .bind(encrypted_key.as_bytes())
+        .bind(access_level)
+        .execute(&mut *tx)
+        .await?;
+
+        changes.push(ChangeEntry {
+            user_id,
+            kind: ItemKind::File,
+            item_id: file_id,
+            change: ChangeKind::Shared,
+        });
     }
 
-    // Dossiers partagés dont l'utilisateur n'est PAS owner :
-    // supprimer uniquement la ligne folder_access de cet utilisateur
-    sqlx::query(
-        "
-        DELETE FROM folder_access
-        WHERE user_id = $1 AND is_deleted = TRUE AND access_level != 'owner'
-        ",
-    )
-    .bind(user_id)
-    .execute(&mut *tx)
-    .await?;
+    append_changes(&mut tx, &changes).await?;
 
     tx.commit().await?;
     Ok(())
 }
 
-/// Partager un dossier avec un contact (avec clés rechiffrées)
-pub async fn share_folder_batch(
+/// Propager les permissions d'un dossier nouvellement créé
+pub async fn propagate_folder_access(
     db_pool: &PgPool,
-    user_id: Uuid,
+    owner_id: Uuid,
     folder_id: Uuid,
-    contact_user_id: Uuid,
-    access_level: &str,
-    folder_keys: Vec<(Uuid, String)>,
-    file_keys: Vec<(Uuid, String)>,
+    user_keys: Vec<(Uuid, String, AccessLevel)>,
 ) -> Result<(), sqlx::Error> {
-    let access_level = match access_level {
-        "owner" => "owner",
-        "editor" => "editor",
-        "viewer" => "viewer",
-        _ => return Err(sqlx::Error::Protocol("Invalid access level".into())),
-    };
-
-    if contact_user_id == user_id {
-        return Err(sqlx::Error::Protocol(
-            "Cannot share folder with oneself".into(),
-        ));
-    }
-
     let mut tx = db_pool.begin().await?;
 
-    let contact_exists =
-        sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
-            .bind(contact_user_id)
-            .fetch_one(&mut *tx)
-            .await?;
-
-    if !contact_exists {
-        return Err(sqlx::Error::RowNotFound);
-    }
-
-    let has_owner_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND access_level = 'owner')"
+    let owner_level = ensure_permission(
+        &mut tx,
+        owner_id,
+        ItemKind::Folder,
+        folder_id,
+        Permission::Share,
     )
-    .bind(folder_id)
-    .bind(user_id)
-    .fetch_one(&mut *tx)
     .await?;
-
-    if !has_owner_access {
-        return Err(sqlx::Error::RowNotFound);
+    if user_keys
+        .iter()
+        .any(|(_, _, access_level)| !owner_level.can_grant(*access_level))
+    {
+        return Err(sqlx::Error::Protocol(ACCESS_LEVEL_NOT_GRANTABLE.into()));
     }
 
-    for (fid, encrypted_key) in folder_keys {
+    let mut changes = Vec::new();
+    for (user_id, encrypted_key, access_level) in user_keys {
         sqlx::query(
             "
-            INSERT INTO folder_access (id, folder_id, user_id, encrypted_folder_key, access_level, created_at, updated_at, is_deleted, is_accepted, is_root_anchor)
-            VALUES ($1, $2, $3, $4, $5, NOW(), NOW(), FALSE, FALSE, FALSE)
+            INSERT INTO folder_access (id, folder_id, user_id, encrypted_folder_key, access_level, key_version, created_at, updated_at, is_deleted, is_accepted, is_root_anchor)
+            VALUES ($1, $2, $3, $4, $5, (SELECT key_version FROM folders WHERE id = $2), NOW(), NOW(), FALSE, TRUE, FALSE)
             ON CONFLICT (folder_id, user_id) DO UPDATE
             SET encrypted_folder_key = EXCLUDED.encrypted_folder_key,
+                team_id = NULL,
+                key_version = EXCLUDED.key_version,
                 access_level = EXCLUDED.access_level,
                 updated_at = NOW(),
                 is_deleted = FALSE,
-                is_accepted = FALSE,
-                is_root_anchor = FALSE
-            ",
+                is_accepted = TRUE
+            WHERE folder_access.access_level <> 'owner'
+            "
         )
         .bind(Uuid::new_v4())
-        .bind(fid)
-        .bind(contact_user_id)
+        .bind(folder_id)
+        .bind(user_id)
         .bind(encrypted_key.as_bytes())
         .bind(access_level)
         .execute(&mut *tx)
         .await?;
-    }
-
-    for (file_id, encrypted_key) in file_keys {
-        let folder_id_for_file: Option<Uuid> = sqlx::query_scalar(
-            "
-            SELECT folder_id
-            FROM file_access
-            WHERE file_id = $1 AND user_id = $2 AND access_level = 'owner'
-            ",
-        )
-        .bind(file_id)
-        .bind(user_id)
-        .fetch_optional(&mut *tx)
-        .await?;
 
-        if let Some(folder_id_val) = folder_id_for_file {
-            sqlx::query(
-                "
-                INSERT INTO file_access (id, file_id, user_id, folder_id, encrypted_file_key, access_level, created_at, updated_at, is_deleted, is_accepted)
-                VALUES ($1, $2, $3, $4, $5, $6, NOW(), NOW(), FALSE, FALSE)
-                ON CONFLICT (file_id, user_id) DO UPDATE
-                SET encrypted_file_key = EXCLUDED.encrypted_file_key,
-                    access_level = EXCLUDED.access_level,
-                    updated_at = NOW(),
-                    is_deleted = FALSE,
-                    is_accepted = FALSE
-                ",
-            )
-            .bind(Uuid::new_v4())
-            .bind(file_id)
-            .bind(contact_user_id)
-            .bind(folder_id_val)
-            .bind(encrypted_key.as_bytes())
-            .bind(access_level)
-            .execute(&mut *tx)
-            .await?;
-        }
+        changes.push(ChangeEntry {
+            user_id,
+            kind: ItemKind::Folder,
+            item_id: folder_id,
+            change: ChangeKind::Shared,
+        });
     }
 
+    append_changes(&mut tx, &changes).await?;
+
     tx.commit().await?;
     Ok(())
 }
 
-/// Partager un dossier avec un contact (version simple, sans propagation aux enfants)
-pub async fn share_folder_with_contact(
+/// Révoquer l'accès à un fichier pour un utilisateur
+pub async fn revoke_file_access(
     db_pool: &PgPool,
-    user_id: Uuid,
-    folder_id: Uuid,
-    contact_user_id: Uuid,
-    encrypted_folder_key: &str,
-    access_level: &str,
+    owner_id: Uuid,
+    file_id: Uuid,
+    target_user_id: Uuid,
 ) -> Result<(), sqlx::Error> {
-    let access_level = match access_level {
-        "owner" => "owner",
-        "editor" => "editor",
-        "viewer" => "viewer",
-        _ => return Err(sqlx::Error::Protocol("Invalid access level".into())),
-    };
-
-    if contact_user_id == user_id {
-        return Err(sqlx::Error::Protocol(
-            "Cannot share folder with oneself".into(),
-        ));
-    }
-
     let mut tx = db_pool.begin().await?;
 
-    let contact_exists =
-        sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
-            .bind(contact_user_id)
-            .fetch_one(&mut *tx)
-            .await?;
-
-    if !contact_exists {
-        return Err(sqlx::Error::RowNotFound);
-    }
-
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND access_level = 'owner')"
+    ensure_permission(
+        &mut tx,
+        owner_id,
+        ItemKind::File,
+        file_id,
+        Permission::Share,
     )
-    .bind(folder_id)
-    .bind(user_id)
-    .fetch_one(&mut *tx)
     .await?;
 
-    if !has_access {
-        return Err(sqlx::Error::RowNotFound);
-    }
-
-    sqlx::query(
+    let revoked = sqlx::query(
         "
-        INSERT INTO folder_access (id, folder_id, user_id, encrypted_folder_key, access_level, created_at, updated_at, is_deleted, is_accepted, is_root_anchor)
-        VALUES ($1, $2, $3, $4, $5, NOW(), NOW(), FALSE, FALSE, FALSE)
-        ON CONFLICT (folder_id, user_id) DO UPDATE
-        SET encrypted_folder_key = EXCLUDED.encrypted_folder_key,
-            access_level = EXCLUDED.access_level,
-            updated_at = NOW(),
-            is_deleted = FALSE,
-            is_accepted = FALSE,
-            is_root_anchor = FALSE
+        DELETE FROM file_access
+        WHERE file_id = $1 AND user_id = $2 AND team_id IS NULL
         ",
     )
-    .bind(Uuid::new_v4())
-    .bind(folder_id)
-    .bind(contact_user_id)
-    .bind(encrypted_folder_key.as_bytes())
-    .bind(access_level)
+    .bind(file_id)
+    .bind(target_user_id)
     .execute(&mut *tx)
-    .await?;
+    .await?
+    .rows_affected();
+
+    // L'accès accordé par une équipe de l'utilisateur prend le relais du partage direct
+    materialize_team_access(&mut tx, None, Some(target_user_id), Some(file_id)).await?;
+
+    if revoked > 0 {
+        record_change(
+            &mut tx,
+            target_user_id,
+            ItemKind::File,
+            file_id,
+            ChangeKind::Purged,
+        )
+        .await?;
+    }
 
     tx.commit().await?;
     Ok(())
 }
 
-/// Partager un fichier avec un contact
-pub async fn share_file_with_contact(
+/// Révoquer l'accès à un dossier pour un utilisateur (incluant enfants)
+/// Avec `rotate`, le dossier et ses sous-dossiers passent en attente de rotation de clé (voir `rotate_folder_key`).
+pub async fn revoke_folder_access(
     db_pool: &PgPool,
-    user_id: Uuid,
-    file_id: Uuid,
-    contact_user_id: Uuid,
-    encrypted_file_key: &str,
-    access_level: &str,
+    owner_id: Uuid,
+    folder_id: Uuid,
+    target_user_id: Uuid,
+    rotate: bool,
 ) -> Result<(), sqlx::Error> {
-    let access_level = match access_level {
-        "owner" => "owner",
-        "editor" => "editor",
-        "viewer" => "viewer",
-        _ => return Err(sqlx::Error::Protocol("Invalid access level".into())),
-    };
-
-    if contact_user_id == user_id {
-        return Err(sqlx::Error::Protocol(
-            "Cannot share file with oneself".into(),
-        ));
-    }
-
     let mut tx = db_pool.begin().await?;
 
-    let contact_exists =
-        sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
-            .bind(contact_user_id)
-            .fetch_one(&mut *tx)
-            .await?;
-
-    if !contact_exists {
-        return Err(sqlx::Error::RowNotFound);
-    }
-
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND access_level = 'owner')"
+    ensure_permission(
+        &mut tx,
+        owner_id,
+        ItemKind::Folder,
+        folder_id,
+        Permission::Share,
     )
-    .bind(file_id)
-    .bind(user_id)
-    .fetch_one(&mut *tx)
     .await?;
 
-    if !has_access {
-        return Err(sqlx::Error::RowNotFound);
-    }
+    let revoked = sqlx::query(
+        "
+        WITH RECURSIVE folder_tree AS (
+            SELECT id
+            FROM folders
+            WHERE id = $1
+
+            UNION ALL
+
+            SELECT f.id
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        )
+        DELETE FROM folder_access
+        WHERE folder_id IN (SELECT id FROM folder_tree) AND user_id = $2 AND team_id IS NULL
+        ",
+    )
+    .bind(folder_id)
+    .bind(target_user_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
 
     sqlx::query(
         "
-        INSERT INTO file_access (id, file_id, user_id, folder_id, encrypted_file_key, access_level, created_at, updated_at, is_deleted, is_accepted)
-        VALUES ($1, $2, $3, NULL, $4, $5, NOW(), NOW(), FALSE, FALSE)
-        ON CONFLICT (file_id, user_id) DO UPDATE
-        SET encrypted_file_key = EXCLUDED.encrypted_file_key,
-            access_level = EXCLUDED.access_level,
-            updated_at = NOW(),
-            is_deleted = FALSE,
-            is_accepted = FALSE
+        WITH RECURSIVE folder_tree AS (
+            SELECT id
+            FROM folders
+            WHERE id = $1
+
+            UNION ALL
+
+            SELECT f.id
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        )
+        DELETE FROM file_access
+        WHERE folder_id IN (SELECT id FROM folder_tree) AND user_id = $2 AND team_id IS NULL
         ",
     )
-    .bind(Uuid::new_v4())
-    .bind(file_id)
-    .bind(contact_user_id)
-    .bind(encrypted_file_key.as_bytes())
-    .bind(access_level)
+    .bind(folder_id)
+    .bind(target_user_id)
     .execute(&mut *tx)
     .await?;
 
+    // L'accès accordé par une équipe de l'utilisateur prend le relais du partage direct
+    materialize_team_access(&mut tx, None, Some(target_user_id), None).await?;
+
+    // Les anciennes clés ne sont conservées que pour les membres restants
+    prune_folder_key_history(&mut tx, &[target_user_id]).await?;
+
+    if rotate && revoked > 0 {
+        mark_subtree_rotation_pending(&mut tx, folder_id).await?;
+    }
+
+    if revoked > 0 {
+        record_change(
+            &mut tx,
+            target_user_id,
+            ItemKind::Folder,
+            folder_id,
+            ChangeKind::Purged,
+        )
+        .await?;
+    }
+
     tx.commit().await?;
     Ok(())
 }
 
-/// Récupérer la liste des utilisateurs ayant accès à un dossier
-pub async fn get_folder_shared_users(
-    db_pool: &PgPool,
-    user_id: Uuid,
-    folder_id: Uuid,
-) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
-    #[derive(FromRow)]
-    struct SharedUser {
-        user_id: Uuid,
-        access_level: String,
-    }
-
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2)",
+// ========== Partages d'équipe ==========
+
+/// Matérialise les accès d'équipe : chaque membre reçoit un accès (accepté) à chaque élément
+/// partagé à l'équipe, avec la clé chiffrée pour l'équipe et `team_id` renseigné. Filtres
+/// optionnels par équipe, membre et élément. Un accès existant (partage direct, autre équipe)
+/// est conservé ; entre deux équipes, le niveau le plus élevé l'emporte. Les dossiers partagés
+/// à l'équipe sont ancrés à la racine des membres (entrée `accepted` au journal).
+pub async fn materialize_team_access(
+    conn: &mut PgConnection,
+    team_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    item_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let anchored: Vec<(Uuid, Uuid)> = sqlx::query_as(
+        r#"
+        WITH inserted AS (
+            INSERT INTO folder_access (id, folder_id, user_id, team_id, encrypted_folder_key, access_level, key_version, created_at, updated_at, is_deleted, is_accepted, is_root_anchor)
+            SELECT gen_random_uuid(), t.folder_id, m.user_id, t.team_id, t.encrypted_folder_key, t.access_level, t.key_version, NOW(), NOW(), FALSE, TRUE, t.is_root
+            FROM team_folder_access t
+            JOIN team_members m ON m.team_id = t.team_id
+            WHERE ($1::uuid IS NULL OR t.team_id = $1)
+              AND ($2::uuid IS NULL OR m.user_id = $2)
+              AND ($3::uuid IS NULL OR t.folder_id = $3)
+            ORDER BY (t.access_level = 'editor') DESC
+            ON CONFLICT (folder_id, user_id) DO NOTHING
+            RETURNING user_id, folder_id, is_root_anchor
+        )
+        SELECT user_id, folder_id FROM inserted WHERE is_root_anchor
+        "#,
     )
-    .bind(folder_id)
+    .bind(team_id)
     .bind(user_id)
-    .fetch_one(db_pool)
+    .bind(item_id)
+    .fetch_all(&mut *conn)
     .await?;
 
-    if !has_access {
-        return Err(sqlx::Error::RowNotFound);
-    }
-
-    let shared_users = sqlx::query_as::<_, SharedUser>(
-        "
-        SELECT user_id, access_level
-        FROM folder_access
-        WHERE folder_id = $1 AND user_id != $2 AND is_deleted = FALSE
-        ",
+    sqlx::query(
+        r#"
+        INSERT INTO file_access (id, file_id, user_id, folder_id, team_id, encrypted_file_key, access_level, created_at, updated_at, is_deleted, is_accepted)
+        SELECT gen_random_uuid(), t.file_id, m.user_id, t.folder_id, t.team_id, t.encrypted_file_key, t.access_level, NOW(), NOW(), FALSE, TRUE
+        FROM team_file_access t
+        JOIN team_members m ON m.team_id = t.team_id
+        WHERE ($1::uuid IS NULL OR t.team_id = $1)
+          AND ($2::uuid IS NULL OR m.user_id = $2)
+          AND ($3::uuid IS NULL OR t.file_id = $3)
+        ORDER BY (t.access_level = 'editor') DESC
+        ON CONFLICT (file_id, user_id) DO NOTHING
+        "#,
     )
-    .bind(folder_id)
+    .bind(team_id)
     .bind(user_id)
-    .fetch_all(db_pool)
+    .bind(item_id)
+    .execute(&mut *conn)
     .await?;
 
-    Ok(shared_users
+    let changes: Vec<ChangeEntry> = anchored
         .into_iter()
-        .map(|u| (u.user_id, u.access_level))
-        .collect())
+        .map(|(user_id, folder_id)| ChangeEntry {
+            user_id,
+            kind: ItemKind::Folder,
+            item_id: folder_id,
+            change: ChangeKind::Accepted,
+        })
+        .collect();
+    append_changes(conn, &changes).await
 }
 
-/// Récupérer la liste des utilisateurs ayant accès à un fichier
-pub async fn get_file_shared_users(
-    db_pool: &PgPool,
-    user_id: Uuid,
-    file_id: Uuid,
-) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
-    #[derive(FromRow)]
-    struct SharedUser {
-        user_id: Uuid,
-        access_level: String,
-    }
+/// Supprime les anciennes clés de dossier des utilisateurs qui n'ont plus accès au dossier
+async fn prune_folder_key_history(
+    conn: &mut PgConnection,
+    user_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        DELETE FROM folder_key_history h
+        WHERE h.user_id = ANY($1)
+          AND NOT EXISTS (
+              SELECT 1 FROM folder_access fa
+              WHERE fa.folder_id = h.folder_id AND fa.user_id = h.user_id
+          )
+        "#,
+    )
+    .bind(user_ids)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
 
-    let has_access = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND is_deleted = FALSE)"
+/// Retire les accès matérialisés d'une équipe qui ne correspondent plus à un membre et à un
+/// élément partagé (membre retiré, partage révoqué, équipe supprimée), puis rematérialise ceux
+/// que les autres équipes des utilisateurs concernés accordent encore.
+pub async fn prune_team_access(conn: &mut PgConnection, team_id: Uuid) -> Result<(), sqlx::Error> {
+    let removed_folders: Vec<(Uuid, Uuid, bool)> = sqlx::query_as(
+        r#"
+        DELETE FROM folder_access fa
+        WHERE fa.team_id = $1
+          AND NOT EXISTS (
+              SELECT 1
+              FROM team_folder_access t
+              JOIN team_members m ON m.team_id = t.team_id
+              WHERE t.team_id = $1 AND t.folder_id = fa.folder_id AND m.user_id = fa.user_id
+          )
+        RETURNING fa.user_id, fa.folder_id, fa.is_root_anchor
+        "#,
     )
-    .bind(file_id)
-    .bind(user_id)
-    .fetch_one(db_pool)
+    .bind(team_id)
+    .fetch_all(&mut *conn)
     .await?;
 
-    if !has_access {
-        return Err(sqlx::Error::RowNotFound);
-    }
-    let shared_users = sqlx::query_as::<_, SharedUser>(
-        "
-        SELECT user_id, access_level
-        FROM file_access
-        WHERE file_id = $1 AND user_id != $2 AND is_deleted = FALSE
-        ",
+    let removed_file_users: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        DELETE FROM file_access fa
+        WHERE fa.team_id = $1
+          AND NOT EXISTS (
+              SELECT 1
+              FROM team_file_access t
+              JOIN team_members m ON m.team_id = t.team_id
+              WHERE t.team_id = $1 AND t.file_id = fa.file_id AND m.user_id = fa.user_id
+          )
+        RETURNING fa.user_id
+        "#,
     )
-    .bind(file_id)
-    .bind(user_id)
-    .fetch_all(db_pool)
+    .bind(team_id)
+    .fetch_all(&mut *conn)
     .await?;
 
-    Ok(shared_users
+    let mut user_ids: Vec<Uuid> = removed_folders
+        .iter()
+        .map(|(user_id, _, _)| *user_id)
+        .chain(removed_file_users)
+        .collect();
+    user_ids.sort();
+    user_ids.dedup();
+
+    let changes: Vec<ChangeEntry> = removed_folders
         .into_iter()
-        .map(|u| (u.user_id, u.access_level))
-        .collect())
+        .filter(|(_, _, is_root_anchor)| *is_root_anchor)
+        .map(|(user_id, folder_id, _)| ChangeEntry {
+            user_id,
+            kind: ItemKind::Folder,
+            item_id: folder_id,
+            change: ChangeKind::Purged,
+        })
+        .collect();
+    append_changes(&mut *conn, &changes).await?;
+
+    for user_id in &user_ids {
+        materialize_team_access(&mut *conn, None, Some(*user_id), None).await?;
+    }
+    prune_folder_key_history(conn, &user_ids).await
 }
 
-/// Propager les permissions d'un fichier nouvellement créé
-pub async fn propagate_file_access(
+/// Erreur (`sqlx::Error::Protocol`) quand la clé du dossier partagé à l'équipe manque
+pub const TEAM_FOLDER_KEY_REQUIRED: &str = "Encrypted key of the shared folder is required";
+
+/// Équipe ayant accès à un dossier partagé
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct SharedTeam {
+    pub team_id: Uuid,
+    pub name: String,
+    pub public_key: String,
+    pub access_level: AccessLevel,
+    pub shared_by: Option<Uuid>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Partager l'arborescence d'un dossier avec une équipe dont `user_id` est membre. Les clés
+/// (`folder_keys`, `file_keys`) sont chiffrées avec la clé publique de l'équipe ; celles qui
+/// désignent un élément hors de l'arborescence sont ignorées. Un nouveau partage remplace
+/// le niveau et les clés du précédent.
+pub async fn share_folder_with_team(
     db_pool: &PgPool,
-    owner_id: Uuid,
-    file_id: Uuid,
-    user_keys: Vec<(Uuid, String, String)>,
+    user_id: Uuid,
+    folder_id: Uuid,
+    team_id: Uuid,
+    access_level: AccessLevel,
+    folder_keys: Vec<(Uuid, String)>,
+    file_keys: Vec<(Uuid, String)>,
 ) -> Result<(), sqlx::Error> {
+    if !folder_keys.iter().any(|(fid, _)| *fid == folder_id) {
+        return Err(sqlx::Error::Protocol(TEAM_FOLDER_KEY_REQUIRED.into()));
+    }
+
     let mut tx = db_pool.begin().await?;
 
-    let is_owner = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND access_level = 'owner')"
+    let is_member: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM team_members WHERE team_id = $1 AND user_id = $2)",
     )
-    .bind(file_id)
-    .bind(owner_id)
+    .bind(team_id)
+    .bind(user_id)
     .fetch_one(&mut *tx)
     .await?;
-
-    if !is_owner {
+    if !is_member {
         return Err(sqlx::Error::RowNotFound);
     }
 
-    let folder_id: Option<Uuid> =
-        sqlx::query_scalar("SELECT folder_id FROM file_access WHERE file_id = $1 AND user_id = $2")
-            .bind(file_id)
-            .bind(owner_id)
-            .fetch_optional(&mut *tx)
-            .await?;
+    ensure_can_grant(&mut tx, user_id, ItemKind::Folder, folder_id, access_level).await?;
 
-    for (user_id, encrypted_key, access_level) in user_keys {
-        let access_level = match access_level.as_str() {
-            "owner" => "owner",
-            "editor" => "editor",
-            "viewer" => "viewer",
-            _ => continue,
-        };
+    let tree: Vec<Uuid> = sqlx::query_scalar(
+        "
+        WITH RECURSIVE folder_tree AS (
+            SELECT id
+            FROM folders
+            WHERE id = $1
+
+            UNION ALL
+
+            SELECT f.id
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        )
+        SELECT id FROM folder_tree
+        ",
+    )
+    .bind(folder_id)
+    .fetch_all(&mut *tx)
+    .await?;
 
+    for (fid, encrypted_key) in folder_keys.iter().filter(|(fid, _)| tree.contains(fid)) {
         sqlx::query(
             "
-            INSERT INTO file_access (id, file_id, user_id, folder_id, encrypted_file_key, access_level, created_at, updated_at, is_deleted, is_accepted)
-            VALUES ($1, $2, $3, $4, $5, $6, NOW(), NOW(), FALSE, TRUE)
-            ON CONFLICT (file_id, user_id) DO UPDATE
-            SET encrypted_file_key = EXCLUDED.encrypted_file_key,
+            INSERT INTO team_folder_access (team_id, folder_id, encrypted_folder_key, access_level, key_version, is_root, shared_by)
+            VALUES ($1, $2, $3, $4, (SELECT key_version FROM folders WHERE id = $2), $5, $6)
+            ON CONFLICT (team_id, folder_id) DO UPDATE
+            SET encrypted_folder_key = EXCLUDED.encrypted_folder_key,
                 access_level = EXCLUDED.access_level,
-                updated_at = NOW(),
-                is_deleted = FALSE,
-                is_accepted = TRUE
-            "
+                key_version = EXCLUDED.key_version,
+                is_root = EXCLUDED.is_root,
+                shared_by = EXCLUDED.shared_by
+            ",
         )
-        .bind(Uuid::new_v4())
-        .bind(file_id)
-        .bind(user_id)
-        .bind(folder_id)
+        .bind(team_id)
+        .bind(fid)
         .bind(encrypted_key.as_bytes())
         .bind(access_level)
+        .bind(*fid == folder_id)
+        .bind(user_id)
         .execute(&mut *tx)
         .await?;
     }
 
+    for (file_id, encrypted_key) in &file_keys {
+        let folder_id_for_file: Option<Option<Uuid>> = sqlx::query_scalar(
+            "SELECT folder_id FROM file_access WHERE file_id = $1 AND user_id = $2",
+        )
+        .bind(file_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(Some(file_folder_id)) = folder_id_for_file
+            && tree.contains(&file_folder_id)
+        {
+            sqlx::query(
+                "
+                INSERT INTO team_file_access (team_id, file_id, folder_id, encrypted_file_key, access_level)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (team_id, file_id) DO UPDATE
+                SET encrypted_file_key = EXCLUDED.encrypted_file_key,
+                    access_level = EXCLUDED.access_level,
+                    folder_id = EXCLUDED.folder_id
+                ",
+            )
+            .bind(team_id)
+            .bind(file_id)
+            .bind(file_folder_id)
+            .bind(encrypted_key.as_bytes())
+            .bind(access_level)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    // Accès déjà matérialisés (nouveau partage à l'équipe) : mêmes clés et niveau que l'équipe
+    sqlx::query(
+        r#"
+        UPDATE folder_access fa
+        SET encrypted_folder_key = t.encrypted_folder_key,
+            access_level = t.access_level,
+            key_version = t.key_version,
+            is_root_anchor = t.is_root,
+            updated_at = NOW()
+        FROM team_folder_access t
+        WHERE t.team_id = $1 AND fa.team_id = t.team_id AND fa.folder_id = t.folder_id
+        "#,
+    )
+    .bind(team_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE file_access fa
+        SET encrypted_file_key = t.encrypted_file_key,
+            access_level = t.access_level,
+            updated_at = NOW()
+        FROM team_file_access t
+        WHERE t.team_id = $1 AND fa.team_id = t.team_id AND fa.file_id = t.file_id
+        "#,
+    )
+    .bind(team_id)
+    .execute(&mut *tx)
+    .await?;
+
+    materialize_team_access(&mut tx, Some(team_id), None, None).await?;
+
     tx.commit().await?;
     Ok(())
 }
 
-/// Propager les permissions d'un dossier nouvellement créé
-pub async fn propagate_folder_access(
+/// Révoquer l'accès d'une équipe à un dossier (incluant enfants). Les membres gardent les
+/// accès accordés directement ou par une autre équipe.
+/// Avec `rotate`, le dossier et ses sous-dossiers passent en attente de rotation de clé (voir `rotate_folder_key`).
+pub async fn revoke_team_folder_access(
     db_pool: &PgPool,
     owner_id: Uuid,
     folder_id: Uuid,
-    user_keys: Vec<(Uuid, String, String)>,
+    team_id: Uuid,
+    rotate: bool,
 ) -> Result<(), sqlx::Error> {
     let mut tx = db_pool.begin().await?;
 
-    let is_owner = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND access_level = 'owner')"
+    ensure_permission(
+        &mut tx,
+        owner_id,
+        ItemKind::Folder,
+        folder_id,
+        Permission::Share,
     )
-    .bind(folder_id)
-    .bind(owner_id)
-    .fetch_one(&mut *tx)
     .await?;
 
-    if !is_owner {
-        return Err(sqlx::Error::RowNotFound);
-    }
+    let revoked = sqlx::query(
+        "
+        WITH RECURSIVE folder_tree AS (
+            SELECT id
+            FROM folders
+            WHERE id = $1
 
-    for (user_id, encrypted_key, access_level) in user_keys {
-        let access_level = match access_level.as_str() {
-            "owner" => "owner",
-            "editor" => "editor",
-            "viewer" => "viewer",
-            _ => continue,
-        };
+            UNION ALL
 
-        sqlx::query(
-            "
-            INSERT INTO folder_access (id, folder_id, user_id, encrypted_folder_key, access_level, created_at, updated_at, is_deleted, is_accepted, is_root_anchor)
-            VALUES ($1, $2, $3, $4, $5, NOW(), NOW(), FALSE, TRUE, FALSE)
-            ON CONFLICT (folder_id, user_id) DO UPDATE
-            SET encrypted_folder_key = EXCLUDED.encrypted_folder_key,
-                access_level = EXCLUDED.access_level,
-                updated_at = NOW(),
-                is_deleted = FALSE,
-                is_accepted = TRUE
-            "
+            SELECT f.id
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
         )
-        .bind(Uuid::new_v4())
-        .bind(folder_id)
-        .bind(user_id)
-        .bind(encrypted_key.as_bytes())
-        .bind(access_level)
-        .execute(&mut *tx)
-        .await?;
+        DELETE FROM team_folder_access
+        WHERE folder_id IN (SELECT id FROM folder_tree) AND team_id = $2
+        ",
+    )
+    .bind(folder_id)
+    .bind(team_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query(
+        "
+        WITH RECURSIVE folder_tree AS (
+            SELECT id
+            FROM folders
+            WHERE id = $1
+
+            UNION ALL
+
+            SELECT f.id
+            FROM folders f
+            JOIN folder_tree ft ON f.parent_folder_id = ft.id
+        )
+        DELETE FROM team_file_access
+        WHERE folder_id IN (SELECT id FROM folder_tree) AND team_id = $2
+        ",
+    )
+    .bind(folder_id)
+    .bind(team_id)
+    .execute(&mut *tx)
+    .await?;
+
+    prune_team_access(&mut tx, team_id).await?;
+
+    if rotate && revoked > 0 {
+        mark_subtree_rotation_pending(&mut tx, folder_id).await?;
     }
 
     tx.commit().await?;
     Ok(())
 }
 
-/// Révoquer l'accès à un fichier pour un utilisateur
-pub async fn revoke_file_access(
-    db_pool: &PgPool,
-    owner_id: Uuid,
-    file_id: Uuid,
-    target_user_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    let mut tx = db_pool.begin().await?;
+/// Récupérer la liste des équipes ayant accès à un dossier
+pub async fn get_folder_shared_teams(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+) -> Result<Vec<SharedTeam>, sqlx::Error> {
+    let has_access = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2)",
+    )
+    .bind(folder_id)
+    .bind(user_id)
+    .fetch_one(db_pool)
+    .await?;
+
+    if !has_access {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    sqlx::query_as::<_, SharedTeam>(
+        "
+        SELECT t.id AS team_id, t.name, t.public_key, ta.access_level, ta.shared_by, ta.created_at
+        FROM team_folder_access ta
+        JOIN teams t ON t.id = ta.team_id
+        WHERE ta.folder_id = $1
+        ORDER BY t.name
+        ",
+    )
+    .bind(folder_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+// ========== Partages temporaires ==========
+
+/// Partage arrivé à expiration, révoqué au nom du propriétaire de l'élément
+#[derive(Debug, Clone, Copy, FromRow)]
+pub struct ExpiredShare {
+    pub item_id: Uuid,
+    pub user_id: Uuid,
+    pub owner_id: Uuid,
+}
+
+/// Partages de dossiers expirés. Seul le dossier le plus haut d'une arborescence partagée est
+/// retourné : sa révocation retire aussi les accès aux sous-dossiers et fichiers.
+pub async fn get_expired_folder_shares(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<ExpiredShare>, sqlx::Error> {
+    sqlx::query_as::<_, ExpiredShare>(
+        r#"
+        SELECT fa.folder_id AS item_id, fa.user_id, o.user_id AS owner_id
+        FROM folder_access fa
+        JOIN folders f ON f.id = fa.folder_id
+        JOIN folder_access o ON o.folder_id = fa.folder_id AND o.access_level = 'owner'
+        WHERE fa.expires_at <= NOW()
+          AND fa.access_level <> 'owner'
+          AND NOT EXISTS (
+              SELECT 1 FROM folder_access p
+              WHERE p.folder_id = f.parent_folder_id
+                AND p.user_id = fa.user_id
+                AND p.expires_at <= NOW()
+          )
+        ORDER BY fa.expires_at
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Partages de fichiers expirés (hors fichiers d'un dossier dont le partage a aussi expiré)
+pub async fn get_expired_file_shares(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<ExpiredShare>, sqlx::Error> {
+    sqlx::query_as::<_, ExpiredShare>(
+        r#"
+        SELECT fa.file_id AS item_id, fa.user_id, o.user_id AS owner_id
+        FROM file_access fa
+        JOIN file_access o ON o.file_id = fa.file_id AND o.access_level = 'owner'
+        WHERE fa.expires_at <= NOW()
+          AND fa.access_level <> 'owner'
+          AND NOT EXISTS (
+              SELECT 1 FROM folder_access p
+              WHERE p.folder_id = fa.folder_id
+                AND p.user_id = fa.user_id
+                AND p.expires_at <= NOW()
+          )
+        ORDER BY fa.expires_at
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+// ========== Rotation des clés de dossier ==========
 
-    let is_owner = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM file_access WHERE file_id = $1 AND user_id = $2 AND access_level = 'owner')"
-    )
-    .bind(file_id)
-    .bind(owner_id)
-    .fetch_one(&mut *tx)
-    .await?;
+/// Erreur (`sqlx::Error::Protocol`) : ajout refusé tant que la rotation de clé du dossier n'est pas terminée
+pub const KEY_ROTATION_PENDING: &str = "Folder key rotation is pending";
 
-    if !is_owner {
-        return Err(sqlx::Error::RowNotFound);
-    }
+/// Erreur (`sqlx::Error::Protocol`) quand les nouvelles clés ne couvrent pas exactement les membres du dossier
+pub const ROTATION_KEYS_MISMATCH: &str = "New keys must be provided for every folder member";
 
-    sqlx::query(
-        "
-        DELETE FROM file_access
-        WHERE file_id = $1 AND user_id = $2
-        ",
-    )
-    .bind(file_id)
-    .bind(target_user_id)
-    .execute(&mut *tx)
-    .await?;
+/// Erreur (`sqlx::Error::Protocol`) quand la version de clé connue du client n'est plus la version courante
+pub const KEY_VERSION_CONFLICT: &str = "Folder key version has changed";
 
-    tx.commit().await?;
-    Ok(())
+/// Erreur (`sqlx::Error::Protocol`) quand une rotation omet un sous-dossier en attente ou vise un
+/// dossier hors de l'arborescence
+pub const ROTATION_FOLDERS_MISMATCH: &str =
+    "New keys must be provided for the folder and every subfolder pending rotation";
+
+/// Clé d'un dossier dans une version donnée, chiffrée pour l'utilisateur
+#[derive(Debug, serde::Serialize)]
+pub struct FolderKeyVersion {
+    pub key_version: i32,
+    pub encrypted_folder_key: String,
 }
 
-/// Révoquer l'accès à un dossier pour un utilisateur (incluant enfants)
-pub async fn revoke_folder_access(
-    db_pool: &PgPool,
-    owner_id: Uuid,
+/// Clés d'un dossier détenues par un utilisateur : version courante et anciennes versions
+#[derive(Debug, serde::Serialize)]
+pub struct FolderKeys {
+    pub key_version: i32,
+    pub rotation_pending: bool,
+    /// Toutes les versions détenues, de la plus récente à la plus ancienne
+    pub keys: Vec<FolderKeyVersion>,
+}
+
+/// Clés d'un dossier accessibles à `user_id` (RowNotFound sans accès)
+pub async fn get_folder_keys(
+    pool: &PgPool,
+    user_id: Uuid,
     folder_id: Uuid,
-    target_user_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    let mut tx = db_pool.begin().await?;
+) -> Result<FolderKeys, sqlx::Error> {
+    let (key_version, rotation_pending, current_version, current_key): (i32, bool, i32, Vec<u8>) =
+        sqlx::query_as(
+            r#"
+            SELECT f.key_version, f.key_rotation_pending_since IS NOT NULL, fa.key_version, fa.encrypted_folder_key
+            FROM folder_access fa
+            JOIN folders f ON f.id = fa.folder_id
+            WHERE fa.folder_id = $1 AND fa.user_id = $2 AND fa.is_deleted = FALSE
+            "#,
+        )
+        .bind(folder_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
 
-    let is_owner = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM folder_access WHERE folder_id = $1 AND user_id = $2 AND access_level = 'owner')"
+    let history: Vec<(i32, Vec<u8>)> = sqlx::query_as(
+        r#"
+        SELECT key_version, encrypted_folder_key
+        FROM folder_key_history
+        WHERE folder_id = $1 AND user_id = $2 AND key_version < $3
+        ORDER BY key_version DESC
+        "#,
     )
     .bind(folder_id)
-    .bind(owner_id)
-    .fetch_one(&mut *tx)
+    .bind(user_id)
+    .bind(current_version)
+    .fetch_all(pool)
     .await?;
 
-    if !is_owner {
-        return Err(sqlx::Error::RowNotFound);
-    }
+    let keys = std::iter::once((current_version, current_key))
+        .chain(history)
+        .map(|(key_version, key)| FolderKeyVersion {
+            key_version,
+            encrypted_folder_key: bytes_to_text_or_b64(&key),
+        })
+        .collect();
 
+    Ok(FolderKeys {
+        key_version,
+        rotation_pending,
+        keys,
+    })
+}
+
+/// Marque un dossier et tous ses sous-dossiers en attente de rotation de clé : l'utilisateur
+/// révoqué détenait aussi les clés des sous-dossiers.
+async fn mark_subtree_rotation_pending(
+    conn: &mut PgConnection,
+    folder_id: Uuid,
+) -> Result<(), sqlx::Error> {
     sqlx::query(
         "
         WITH RECURSIVE folder_tree AS (
@@ -2123,16 +5399,60 @@ pub async fn revoke_folder_access(
             FROM folders f
             JOIN folder_tree ft ON f.parent_folder_id = ft.id
         )
-        DELETE FROM folder_access
-        WHERE folder_id IN (SELECT id FROM folder_tree) AND user_id = $2
+        UPDATE folders
+        SET key_rotation_pending_since = COALESCE(key_rotation_pending_since, NOW())
+        WHERE id IN (SELECT id FROM folder_tree)
         ",
     )
     .bind(folder_id)
-    .bind(target_user_id)
-    .execute(&mut *tx)
+    .execute(&mut *conn)
     .await?;
+    Ok(())
+}
 
-    sqlx::query(
+/// Nouvelle clé d'un dossier : version remplacée et clés chiffrées pour chaque membre et équipe
+#[derive(Debug, Clone)]
+pub struct FolderKeyRotation {
+    pub folder_id: Uuid,
+    /// Version sur laquelle le client s'est basé (rotations concurrentes)
+    pub expected_version: i32,
+    pub new_keys: Vec<(Uuid, String)>,
+    pub team_keys: Vec<(Uuid, String)>,
+}
+
+/// Nouvelle version de la clé d'un dossier après rotation
+#[derive(Debug, serde::Serialize)]
+pub struct RotatedFolderKey {
+    pub folder_id: Uuid,
+    pub key_version: i32,
+}
+
+/// Remplace en une seule transaction la clé de `folder_id` et celles de sous-dossiers par une
+/// nouvelle version, chiffrée par le client pour chaque membre (les clés doivent couvrir exactement
+/// les accès directs de chaque dossier) et pour chaque équipe (recopiées dans les accès
+/// matérialisés des membres). `rotations` doit contenir `folder_id` et chaque sous-dossier en
+/// attente de rotation, et uniquement des dossiers de son arborescence. Les clés courantes sont
+/// archivées dans `folder_key_history` : les éléments existants restent lisibles.
+/// Retourne les nouvelles versions, dans l'ordre de `rotations`.
+pub async fn rotate_folder_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    folder_id: Uuid,
+    rotations: &[FolderKeyRotation],
+) -> Result<Vec<RotatedFolderKey>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    ensure_permission(
+        &mut tx,
+        user_id,
+        ItemKind::Folder,
+        folder_id,
+        Permission::Share,
+    )
+    .await?;
+
+    // Verrou de l'arborescence (ordre stable) : sérialise les rotations et les partages concurrents
+    let subtree: Vec<(Uuid, i32, bool)> = sqlx::query_as(
         "
         WITH RECURSIVE folder_tree AS (
             SELECT id
@@ -2145,17 +5465,175 @@ pub async fn revoke_folder_access(
             FROM folders f
             JOIN folder_tree ft ON f.parent_folder_id = ft.id
         )
-        DELETE FROM file_access
-        WHERE folder_id IN (SELECT id FROM folder_tree) AND user_id = $2
+        SELECT id, key_version, key_rotation_pending_since IS NOT NULL
+        FROM folders
+        WHERE id IN (SELECT id FROM folder_tree)
+        ORDER BY id
+        FOR UPDATE
         ",
     )
     .bind(folder_id)
-    .bind(target_user_id)
-    .execute(&mut *tx)
+    .fetch_all(&mut *tx)
     .await?;
 
+    let required: Vec<Uuid> = subtree
+        .iter()
+        .filter(|(id, _, pending)| *id == folder_id || *pending)
+        .map(|(id, _, _)| *id)
+        .collect();
+    let folders: Vec<Uuid> = subtree.iter().map(|(id, _, _)| *id).collect();
+    let provided: Vec<Uuid> = rotations.iter().map(|r| r.folder_id).collect();
+    if !services::rotation_covers_subtree(&required, &folders, &provided) {
+        return Err(sqlx::Error::Protocol(ROTATION_FOLDERS_MISMATCH.into()));
+    }
+
+    let mut rotated = Vec::with_capacity(rotations.len());
+    for rotation in rotations {
+        if rotation.folder_id != folder_id {
+            ensure_permission(
+                &mut tx,
+                user_id,
+                ItemKind::Folder,
+                rotation.folder_id,
+                Permission::Share,
+            )
+            .await?;
+        }
+        let current_version = subtree
+            .iter()
+            .find(|(id, _, _)| *id == rotation.folder_id)
+            .map(|(_, version, _)| *version)
+            .ok_or(sqlx::Error::RowNotFound)?;
+        if current_version != rotation.expected_version {
+            return Err(sqlx::Error::Protocol(KEY_VERSION_CONFLICT.into()));
+        }
+        let key_version = apply_folder_key_rotation(&mut tx, rotation, current_version + 1).await?;
+        rotated.push(RotatedFolderKey {
+            folder_id: rotation.folder_id,
+            key_version,
+        });
+    }
+
     tx.commit().await?;
-    Ok(())
+    Ok(rotated)
+}
+
+/// Vérifie la couverture des membres et équipes d'un dossier puis installe sa nouvelle version de clé
+async fn apply_folder_key_rotation(
+    conn: &mut PgConnection,
+    rotation: &FolderKeyRotation,
+    new_version: i32,
+) -> Result<i32, sqlx::Error> {
+    let folder_id = rotation.folder_id;
+
+    let members: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT user_id FROM folder_access WHERE folder_id = $1 AND team_id IS NULL",
+    )
+    .bind(folder_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let provided: Vec<Uuid> = rotation.new_keys.iter().map(|(id, _)| *id).collect();
+    if !services::keys_cover_members(&members, &provided) {
+        return Err(sqlx::Error::Protocol(ROTATION_KEYS_MISMATCH.into()));
+    }
+
+    let teams: Vec<Uuid> =
+        sqlx::query_scalar("SELECT team_id FROM team_folder_access WHERE folder_id = $1")
+            .bind(folder_id)
+            .fetch_all(&mut *conn)
+            .await?;
+    let provided_teams: Vec<Uuid> = rotation.team_keys.iter().map(|(id, _)| *id).collect();
+    if !services::keys_cover_members(&teams, &provided_teams) {
+        return Err(sqlx::Error::Protocol(TEAM_KEYS_MISMATCH.into()));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO folder_key_history (folder_id, user_id, key_version, encrypted_folder_key)
+        SELECT folder_id, user_id, key_version, encrypted_folder_key
+        FROM folder_access
+        WHERE folder_id = $1
+        ON CONFLICT (folder_id, user_id, key_version) DO NOTHING
+        "#,
+    )
+    .bind(folder_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "UPDATE folders SET key_version = $2, key_rotation_pending_since = NULL, updated_at = NOW() WHERE id = $1",
+    )
+    .bind(folder_id)
+    .bind(new_version)
+    .execute(&mut *conn)
+    .await?;
+
+    let keys: Vec<&[u8]> = rotation
+        .new_keys
+        .iter()
+        .map(|(_, key)| key.as_bytes())
+        .collect();
+    sqlx::query(
+        r#"
+        UPDATE folder_access fa
+        SET encrypted_folder_key = k.encrypted_folder_key,
+            key_version = $2,
+            updated_at = NOW()
+        FROM UNNEST($3::uuid[], $4::bytea[]) AS k(user_id, encrypted_folder_key)
+        WHERE fa.folder_id = $1 AND fa.user_id = k.user_id AND fa.team_id IS NULL
+        "#,
+    )
+    .bind(folder_id)
+    .bind(new_version)
+    .bind(&provided)
+    .bind(&keys)
+    .execute(&mut *conn)
+    .await?;
+
+    let team_keys: Vec<&[u8]> = rotation
+        .team_keys
+        .iter()
+        .map(|(_, key)| key.as_bytes())
+        .collect();
+    sqlx::query(
+        r#"
+        UPDATE team_folder_access t
+        SET encrypted_folder_key = k.encrypted_folder_key,
+            key_version = $2
+        FROM UNNEST($3::uuid[], $4::bytea[]) AS k(team_id, encrypted_folder_key)
+        WHERE t.folder_id = $1 AND t.team_id = k.team_id
+        "#,
+    )
+    .bind(folder_id)
+    .bind(new_version)
+    .bind(&provided_teams)
+    .bind(&team_keys)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE folder_access fa
+        SET encrypted_folder_key = t.encrypted_folder_key,
+            key_version = t.key_version,
+            updated_at = NOW()
+        FROM team_folder_access t
+        WHERE fa.folder_id = $1 AND t.folder_id = fa.folder_id AND t.team_id = fa.team_id
+        "#,
+    )
+    .bind(folder_id)
+    .execute(&mut *conn)
+    .await?;
+
+    record_change_for_holders(
+        &mut *conn,
+        ItemKind::Folder,
+        folder_id,
+        ChangeKind::KeyRotated,
+    )
+    .await?;
+
+    Ok(new_version)
 }
 
 /// Récupérer tous les fichiers accessibles par un utilisateur (tous dossiers confondus)
@@ -2172,7 +5650,8 @@ pub async fn get_files_list(
         created_at: Option<String>,
         updated_at: Option<String>,
         encrypted_file_key: Vec<u8>,
-        access_level: String,
+        team_id: Option<Uuid>,
+        access_level: AccessLevel,
         folder_id: Option<Uuid>,
     }
 
@@ -2186,6 +5665,7 @@ pub async fn get_files_list(
             f.created_at::text as created_at,
             f.updated_at::text as updated_at,
             fa.encrypted_file_key,
+            fa.team_id,
             fa.access_level,
             fa.folder_id
         FROM file_access fa
@@ -2214,6 +5694,7 @@ pub async fn get_files_list(
                     "created_at": row.created_at,
                     "updated_at": row.updated_at,
                     "encrypted_file_key": bytes_to_text_or_b64(&row.encrypted_file_key),
+                    "team_id": row.team_id,
                     "access_level": row.access_level,
                     "folder_id": row.folder_id,
                     "type": "file",
@@ -2250,7 +5731,7 @@ pub async fn get_shared_with_me_contents(
         mime_type: String,
         created_at: Option<String>,
         updated_at: Option<String>,
-        access_level: String,
+        access_level: AccessLevel,
         encrypted_file_key: Vec<u8>,
         file_type: String,
     }
@@ -2345,12 +5826,120 @@ pub async fn get_shared_with_me_contents(
     }))
 }
 
+/// Partage direct en attente d'acceptation
+#[derive(Debug, FromRow)]
+pub struct PendingShare {
+    pub item_type: String,
+    pub item_id: Uuid,
+    pub access_level: AccessLevel,
+    /// Auteur du partage (None pour un partage antérieur au suivi ou un compte supprimé)
+    pub shared_by: Option<Uuid>,
+    pub shared_by_username: Option<String>,
+    pub shared_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub encrypted_metadata: Vec<u8>,
+    pub encrypted_key: Vec<u8>,
+}
+
+impl PendingShare {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "item_type": self.item_type,
+            "item_id": self.item_id,
+            "access_level": self.access_level,
+            "shared_by": self.shared_by,
+            "shared_by_username": self.shared_by_username,
+            "shared_at": self.shared_at,
+            "expires_at": self.expires_at,
+            "encrypted_metadata": bytes_to_text_or_b64(&self.encrypted_metadata),
+            "encrypted_key": bytes_to_text_or_b64(&self.encrypted_key),
+        })
+    }
+}
+
+/// Partages en attente de l'utilisateur, du plus récent au plus ancien : racines des dossiers
+/// partagés et fichiers partagés seuls (le contenu d'un dossier suit son acceptation).
+/// `received_between` restreint aux partages reçus ou renouvelés dans l'intervalle `]début, fin]`
+/// (période d'un résumé quotidien).
+pub async fn get_pending_shares(
+    pool: &PgPool,
+    user_id: Uuid,
+    received_between: Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>,
+) -> Result<Vec<PendingShare>, sqlx::Error> {
+    let (since, until) = received_between.unzip();
+    sqlx::query_as::<_, PendingShare>(
+        r#"
+        SELECT * FROM (
+            SELECT
+                'folder'::text AS item_type,
+                fa.folder_id AS item_id,
+                fa.access_level,
+                fa.shared_by,
+                u.username AS shared_by_username,
+                COALESCE(fa.updated_at, fa.created_at) AS shared_at,
+                fa.expires_at,
+                f.encrypted_metadata,
+                fa.encrypted_folder_key AS encrypted_key
+            FROM folder_access fa
+            JOIN folders f ON f.id = fa.folder_id
+            LEFT JOIN users u ON u.id = fa.shared_by
+            WHERE fa.user_id = $1
+                AND fa.is_accepted = FALSE
+                AND fa.is_deleted = FALSE
+                AND fa.access_level != 'owner'
+                AND f.is_deleted = FALSE
+                AND NOT EXISTS (
+                    SELECT 1 FROM folder_access fa2
+                    WHERE fa2.folder_id = f.parent_folder_id
+                      AND fa2.user_id = $1
+                      AND fa2.is_deleted = FALSE
+                )
+            UNION ALL
+            SELECT
+                'file'::text,
+                fa.file_id,
+                fa.access_level,
+                fa.shared_by,
+                u.username,
+                COALESCE(fa.updated_at, fa.created_at),
+                fa.expires_at,
+                f.encrypted_metadata,
+                fa.encrypted_file_key
+            FROM file_access fa
+            JOIN files f ON f.id = fa.file_id
+            LEFT JOIN users u ON u.id = fa.shared_by
+            WHERE fa.user_id = $1
+                AND fa.is_accepted = FALSE
+                AND fa.is_deleted = FALSE
+                AND fa.access_level != 'owner'
+                AND f.is_fully_uploaded = TRUE
+                AND f.is_deleted = FALSE
+                AND NOT EXISTS (
+                    SELECT 1 FROM folder_access fa2
+                    WHERE fa2.folder_id = fa.folder_id
+                      AND fa2.user_id = $1
+                      AND fa2.is_deleted = FALSE
+                )
+        ) pending
+        WHERE $2::timestamptz IS NULL OR (shared_at > $2 AND shared_at <= $3)
+        ORDER BY shared_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(since)
+    .bind(until)
+    .fetch_all(pool)
+    .await
+}
+
 /// Accepter un fichier partagé (l'ancre à la racine du drive de l'utilisateur)
 pub async fn accept_shared_file(
     pool: &PgPool,
     user_id: Uuid,
     file_id: Uuid,
 ) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
     let rows_affected = sqlx::query(
         "
         UPDATE file_access
@@ -2375,13 +5964,24 @@ pub async fn accept_shared_file(
     )
     .bind(file_id)
     .bind(user_id)
-    .execute(pool)
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
     if rows_affected == 0 {
         return Err(sqlx::Error::RowNotFound);
     }
+
+    record_change(
+        &mut tx,
+        user_id,
+        ItemKind::File,
+        file_id,
+        ChangeKind::Accepted,
+    )
+    .await?;
+
+    tx.commit().await?;
     Ok(())
 }
 
@@ -2456,6 +6056,15 @@ pub async fn accept_shared_folder(
     .execute(&mut *tx)
     .await?;
 
+    record_change(
+        &mut tx,
+        user_id,
+        ItemKind::Folder,
+        folder_id,
+        ChangeKind::Accepted,
+    )
+    .await?;
+
     tx.commit().await?;
     Ok(())
 }
@@ -2530,6 +6139,15 @@ pub async fn reject_shared_folder(
     .execute(&mut *tx)
     .await?;
 
+    record_change(
+        &mut tx,
+        user_id,
+        ItemKind::Folder,
+        folder_id,
+        ChangeKind::Deleted,
+    )
+    .await?;
+
     tx.commit().await?;
     Ok(())
 }
@@ -2539,6 +6157,8 @@ pub async fn reject_shared_file(
     user_id: Uuid,
     file_id: Uuid,
 ) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
     let rows_affected = sqlx::query(
         "
         UPDATE file_access
@@ -2551,12 +6171,136 @@ pub async fn reject_shared_file(
     )
     .bind(file_id)
     .bind(user_id)
-    .execute(pool)
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
     if rows_affected == 0 {
         return Err(sqlx::Error::RowNotFound);
     }
+
+    record_change(
+        &mut tx,
+        user_id,
+        ItemKind::File,
+        file_id,
+        ChangeKind::Deleted,
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Purger toutes les données drive d'un utilisateur (suppression de compte).
+/// - fichiers dont il est owner : chunks S3, s3_keys, accès (y compris ceux donnés à d'autres), fichiers
+/// - dossiers dont il est owner : accès (y compris ceux donnés à d'autres), dossiers
+/// - partages reçus : ses lignes file_access / folder_access restantes
+///
+/// Retourne RowNotFound (rien n'est supprimé) si la suppression du compte a été annulée entre-temps.
+pub async fn purge_user_drive(
+    db_pool: &PgPool,
+    storage_client: &crate::storage::StorageClient,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    if !crate::auth::repo::claim_account_purge(&mut tx, user_id).await? {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    let owned_file_ids: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
+        "SELECT file_id FROM file_access WHERE user_id = $1 AND access_level = 'owner'",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // Les éléments de l'utilisateur disparaissent du drive des utilisateurs avec qui ils étaient partagés
+    let mut removed_shares: Vec<ChangeEntry> = sqlx::query_as::<_, (Uuid, Uuid)>(
+        "SELECT user_id, file_id FROM file_access WHERE file_id = ANY($1) AND user_id != $2",
+    )
+    .bind(&owned_file_ids)
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|(holder, file_id)| ChangeEntry {
+        user_id: holder,
+        kind: ItemKind::File,
+        item_id: file_id,
+        change: ChangeKind::Purged,
+    })
+    .collect();
+
+    // Les chunks encore référencés par des copies d'autres utilisateurs sont conservés
+    let s3_keys = detach_file_chunks(&mut tx, &owned_file_ids).await?;
+
+    sqlx::query("DELETE FROM file_access WHERE file_id = ANY($1)")
+        .bind(&owned_file_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM files WHERE id = ANY($1)")
+        .bind(&owned_file_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    let owned_folder_ids: Vec<Uuid> = sqlx::query_scalar::<_, Uuid>(
+        "SELECT folder_id FROM folder_access WHERE user_id = $1 AND access_level = 'owner'",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    removed_shares.extend(
+        sqlx::query_as::<_, (Uuid, Uuid)>(
+            "SELECT user_id, folder_id FROM folder_access WHERE folder_id = ANY($1) AND user_id != $2",
+        )
+        .bind(&owned_folder_ids)
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|(holder, folder_id)| ChangeEntry {
+            user_id: holder,
+            kind: ItemKind::Folder,
+            item_id: folder_id,
+            change: ChangeKind::Purged,
+        }),
+    );
+
+    sqlx::query("DELETE FROM folder_access WHERE folder_id = ANY($1)")
+        .bind(&owned_folder_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM folders WHERE id = ANY($1)")
+        .bind(&owned_folder_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    // Partages reçus : on détache l'utilisateur sans toucher aux éléments des autres
+    sqlx::query("DELETE FROM file_access WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM folder_access WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Plus aucun fichier ni réservation : compteurs remis à zéro
+    sqlx::query("DELETE FROM user_storage_usage WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    append_changes(&mut tx, &removed_shares).await?;
+
+    tx.commit().await?;
+
+    delete_storage_objects(storage_client, &s3_keys).await;
     Ok(())
 }
@@ -5,25 +5,47 @@ use crate::state::AppState;
 use axum::{
     Router,
     extract::DefaultBodyLimit,
-    routing::{get, patch, post},
+    routing::{get, patch, post, put},
 };
 
-use super::handlers;
+use super::{handlers, services};
+
+/// Marge accordée aux champs multipart autres que le chunk (index, iv, en-têtes)
+const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Marge pour l'enveloppe JSON d'une mise à jour de l'index de recherche
+const SEARCH_INDEX_OVERHEAD_BYTES: usize = 64 * 1024;
 
 /// Toutes les routes liées au drive (fichiers/dossiers)
 /// Retourne un Router<AppState> qui sera composé dans routes.rs principal
 pub fn drive_routes() -> Router<AppState> {
-    // Routes d'upload avec limite de 6MB par chunk
+    // Routes d'upload : chunk limité à MAX_CHUNK_SIZE_BYTES (+ marge pour les autres champs multipart)
     let upload_routes = Router::new()
         .route(
             "/files/{file_id}/upload-chunk",
             post(handlers::upload_chunk_restful_handler),
         )
-        .layer(DefaultBodyLimit::max(6 * 1024 * 1024)); // 6 MB max par chunk
+        .layer(DefaultBodyLimit::max(
+            services::max_chunk_size_bytes() as usize + MULTIPART_OVERHEAD_BYTES,
+        ));
+
+    // Index de recherche : tous les chunks peuvent être envoyés dans une seule mise à jour
+    let search_index_routes = Router::new()
+        .route(
+            "/search-index",
+            get(handlers::get_search_index_handler)
+                .put(handlers::update_search_index_handler)
+                .delete(handlers::delete_search_index_handler),
+        )
+        .layer(DefaultBodyLimit::max(
+            services::MAX_SEARCH_INDEX_CHUNKS as usize * services::MAX_SEARCH_INDEX_CHUNK_BYTES
+                + SEARCH_INDEX_OVERHEAD_BYTES,
+        ));
 
     Router::new()
         // ========== Gestion des fichiers (endpoints conservés pour compatibilité) ==========
         .route("/initialize_file", post(handlers::initialize_file_handler))
+        .route("/batch", post(handlers::batch_handler))
         .merge(upload_routes)
         .route(
             "/finalize_upload/{file_id}/{etat}",
@@ -45,6 +67,7 @@ pub fn drive_routes() -> Router<AppState> {
             "/files/{file_id}/move",
             patch(handlers::move_file_restful_handler),
         )
+        .route("/files/{file_id}/copy", post(handlers::copy_file_handler))
         .route(
             "/files/{file_id}/share",
             post(handlers::share_file_restful_handler),
@@ -57,6 +80,22 @@ pub fn drive_routes() -> Router<AppState> {
             "/files/{file_id}/reject",
             post(handlers::reject_shared_file_handler),
         )
+        .route(
+            "/files/{file_id}/favorite",
+            put(handlers::favorite_file_handler).delete(handlers::unfavorite_file_handler),
+        )
+        .route(
+            "/files/{file_id}/color",
+            put(handlers::set_file_color_handler),
+        )
+        .route(
+            "/files/{file_id}/search-token",
+            put(handlers::set_file_search_token_handler),
+        )
+        .route(
+            "/files/{file_id}/tags/{token}",
+            put(handlers::tag_file_handler).delete(handlers::untag_file_handler),
+        )
         .route("/files/{file_id}", get(handlers::get_file_info_handler))
         .route(
             "/files/{file_id}",
@@ -91,6 +130,10 @@ pub fn drive_routes() -> Router<AppState> {
             "/folders/{folder_id}/move",
             patch(handlers::move_folder_restful_handler),
         )
+        .route(
+            "/folders/{folder_id}/copy",
+            post(handlers::copy_folder_handler),
+        )
         .route(
             "/folders/{folder_id}/accept",
             post(handlers::accept_shared_folder_handler),
@@ -99,6 +142,39 @@ pub fn drive_routes() -> Router<AppState> {
             "/folders/{folder_id}/reject",
             post(handlers::reject_shared_folder_handler),
         )
+        .route(
+            "/folders/{folder_id}/keys",
+            get(handlers::get_folder_keys_handler),
+        )
+        .route(
+            "/folders/{folder_id}/rotate-key",
+            post(handlers::rotate_folder_key_handler),
+        )
+        .route(
+            "/folders/{folder_id}/teams/{team_id}",
+            post(handlers::share_folder_with_team_handler)
+                .delete(handlers::revoke_team_folder_access_handler),
+        )
+        .route(
+            "/folders/{folder_id}/favorite",
+            put(handlers::favorite_folder_handler).delete(handlers::unfavorite_folder_handler),
+        )
+        .route(
+            "/folders/{folder_id}/color",
+            put(handlers::set_folder_color_handler),
+        )
+        .route(
+            "/folders/{folder_id}/search-token",
+            put(handlers::set_folder_search_token_handler),
+        )
+        .route(
+            "/folders/{folder_id}/tags/{token}",
+            put(handlers::tag_folder_handler).delete(handlers::untag_folder_handler),
+        )
+        .route(
+            "/folders/{folder_id}/archive",
+            get(handlers::download_folder_archive_handler),
+        )
         .route("/folders/{folder_id}", get(handlers::get_folder_handler))
         .route(
             "/folders/{folder_id}",
@@ -109,6 +185,7 @@ pub fn drive_routes() -> Router<AppState> {
             patch(handlers::rename_folder_restful_handler),
         )
         // ========== Gestion des accès ==========
+        .route("/shares/pending", get(handlers::get_pending_shares_handler))
         .route(
             "/propagate_file_access",
             post(handlers::propagate_file_access_handler),
@@ -124,5 +201,19 @@ pub fn drive_routes() -> Router<AppState> {
             get(handlers::get_file_folder_handler),
         )
         .route("/empty_trash", post(handlers::empty_trash_handler))
+        .route("/search", get(handlers::search_handler))
+        .merge(search_index_routes)
+        .route(
+            "/search-index/chunks/{chunk_index}",
+            get(handlers::get_search_index_chunk_handler),
+        )
+        .route("/tags", get(handlers::list_tags_handler))
+        .route(
+            "/tags/{token}",
+            put(handlers::upsert_tag_handler).delete(handlers::delete_tag_handler),
+        )
+        .route("/tags/{token}/items", get(handlers::get_tag_items_handler))
         .route("/get_drive_info", get(handlers::get_drive_info_handler))
+        // ========== Synchronisation ==========
+        .route("/changes", get(handlers::get_drive_changes_handler))
 }
@@ -1,8 +1,18 @@
 // Services - Logique métier du drive
 // Fonctions utilitaires et helpers
 
+use std::collections::HashSet;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Postgres;
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef};
 use uuid::Uuid;
 
+use super::repo::StorageUsage;
+
 // ========== Services ==========
 
 /// Convertir une string en UUID ou None pour les valeurs "null", "root", etc.
@@ -38,3 +48,398 @@ pub fn parse_uuid_or_error(s: &str) -> Result<Option<Uuid>, String> {
         }
     }
 }
+
+// ========== Niveaux d'accès ==========
+
+/// Niveau d'accès d'un utilisateur sur un fichier ou un dossier (colonne `access_level`,
+/// contrainte CHECK sur `owner`, `editor`, `viewer`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLevel {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+/// Action soumise à la matrice des permissions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Lire et télécharger
+    Download,
+    /// Ajouter des fichiers ou des sous-dossiers dans un dossier
+    Upload,
+    Rename,
+    Move,
+    /// Mettre à la corbeille pour tous les détenteurs (sinon : retrait de son propre drive)
+    Delete,
+    /// Partager (éditeur ou lecteur), propager et révoquer les accès
+    Share,
+    /// Repartager en lecture seule un élément reçu
+    Reshare,
+}
+
+impl AccessLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccessLevel::Owner => "owner",
+            AccessLevel::Editor => "editor",
+            AccessLevel::Viewer => "viewer",
+        }
+    }
+
+    /// Matrice des permissions : source unique des contrôles d'accès du drive
+    ///
+    /// | Action   | owner | editor | viewer |
+    /// |----------|-------|--------|--------|
+    /// | Download |   ✓   |   ✓    |   ✓    |
+    /// | Upload   |   ✓   |   ✓    |        |
+    /// | Rename   |   ✓   |   ✓    |        |
+    /// | Move     |   ✓   |   ✓    |        |
+    /// | Delete   |   ✓   |        |        |
+    /// | Share    |   ✓   |        |        |
+    /// | Reshare  |   ✓   |   ✓    |        |
+    pub fn can(self, permission: Permission) -> bool {
+        match self {
+            AccessLevel::Owner => true,
+            AccessLevel::Editor => matches!(
+                permission,
+                Permission::Download
+                    | Permission::Upload
+                    | Permission::Rename
+                    | Permission::Move
+                    | Permission::Reshare
+            ),
+            AccessLevel::Viewer => permission == Permission::Download,
+        }
+    }
+
+    /// Niveau qu'un détenteur de `self` peut accorder en partageant.
+    /// La propriété ne se partage jamais : elle porte le quota et la suppression définitive.
+    pub fn can_grant(self, granted: AccessLevel) -> bool {
+        match granted {
+            AccessLevel::Owner => false,
+            AccessLevel::Editor => self.can(Permission::Share),
+            AccessLevel::Viewer => self.can(Permission::Share) || self.can(Permission::Reshare),
+        }
+    }
+}
+
+impl std::str::FromStr for AccessLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "owner" => Ok(AccessLevel::Owner),
+            "editor" => Ok(AccessLevel::Editor),
+            "viewer" => Ok(AccessLevel::Viewer),
+            other => Err(format!("Invalid access level: {}", other)),
+        }
+    }
+}
+
+// Stocké en TEXT : mêmes types SQL que &str, valeur validée au décodage
+impl sqlx::Type<Postgres> for AccessLevel {
+    fn type_info() -> PgTypeInfo {
+        <&str as sqlx::Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl sqlx::Encode<'_, Postgres> for AccessLevel {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        <&str as sqlx::Encode<Postgres>>::encode(self.as_str(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Postgres> for AccessLevel {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let level = <&str as sqlx::Decode<Postgres>>::decode(value)?;
+        Ok(level.parse()?)
+    }
+}
+
+/// Erreur renvoyée quand la date d'expiration d'un partage n'est pas dans le futur
+pub const SHARE_EXPIRY_NOT_IN_FUTURE: &str = "expires_at must be in the future";
+
+/// Droits accordés par un partage : niveau d'accès et expiration optionnelle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShareGrant {
+    pub access_level: AccessLevel,
+    /// Date de révocation automatique (job `revoke_expired_shares`), None : partage permanent
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ShareGrant {
+    /// Un partage temporaire doit expirer dans le futur
+    pub fn validate(&self, now: DateTime<Utc>) -> Result<(), &'static str> {
+        match self.expires_at {
+            Some(expires_at) if expires_at <= now => Err(SHARE_EXPIRY_NOT_IN_FUTURE),
+            _ => Ok(()),
+        }
+    }
+}
+
+// ========== Ajouts dans un dossier partagé ==========
+
+/// Propriétaire (et titulaire du quota) d'un élément ajouté par un éditeur dans un dossier partagé
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedUploadOwner {
+    /// Le propriétaire du dossier (défaut) : le quota reste celui de l'espace partagé
+    FolderOwner,
+    /// L'éditeur qui ajoute l'élément
+    Uploader,
+}
+
+/// Attribution des éléments ajoutés par un éditeur (SHARED_UPLOAD_OWNER : `folder_owner` par défaut, ou `uploader`)
+pub fn shared_upload_owner() -> SharedUploadOwner {
+    parse_shared_upload_owner(std::env::var("SHARED_UPLOAD_OWNER").ok().as_deref())
+}
+
+/// Valeur de SHARED_UPLOAD_OWNER : `folder_owner` si absente ou inconnue
+pub fn parse_shared_upload_owner(value: Option<&str>) -> SharedUploadOwner {
+    match value {
+        Some("uploader") => SharedUploadOwner::Uploader,
+        _ => SharedUploadOwner::FolderOwner,
+    }
+}
+
+/// Niveau d'accès d'un membre du dossier sur un élément ajouté : le propriétaire de l'élément est
+/// owner, les autres membres gardent leur niveau sur le dossier (owner du dossier rétrogradé en editor)
+pub fn added_item_access_level(folder_level: AccessLevel, is_item_owner: bool) -> AccessLevel {
+    match (is_item_owner, folder_level) {
+        (true, _) => AccessLevel::Owner,
+        (false, AccessLevel::Owner) => AccessLevel::Editor,
+        (false, level) => level,
+    }
+}
+
+/// Vérifie que les clés fournies par le client couvrent exactement les membres du dossier
+/// (ni manquant, ni en trop, ni en double)
+pub fn keys_cover_members(members: &[Uuid], provided: &[Uuid]) -> bool {
+    copy_covers_sources(members, provided)
+}
+
+/// Vérifie qu'une rotation couvre chaque dossier `required` (dossier visé et sous-dossiers en
+/// attente), ne vise que des dossiers de l'arborescence `subtree` et ne cite aucun dossier deux fois
+pub fn rotation_covers_subtree(required: &[Uuid], subtree: &[Uuid], provided: &[Uuid]) -> bool {
+    let unique: HashSet<&Uuid> = provided.iter().collect();
+    unique.len() == provided.len()
+        && provided.iter().all(|id| subtree.contains(id))
+        && required.iter().all(|id| unique.contains(id))
+}
+
+// ========== Dépassement de quota ==========
+
+/// Délai de grâce avant le passage en lecture seule d'un drive au-dessus du quota
+/// (OVER_QUOTA_GRACE_DAYS, 14 jours par défaut)
+pub fn over_quota_grace_days() -> i64 {
+    std::env::var("OVER_QUOTA_GRACE_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days >= 0)
+        .unwrap_or(14)
+}
+
+/// État du drive vis-à-vis du quota de l'offre
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "quota_state", rename_all = "snake_case")]
+pub enum QuotaState {
+    /// Usage sous le quota
+    WithinQuota,
+    /// Quota dépassé (ex: après un downgrade) : uploads refusés, le reste reste possible jusqu'à `read_only_at`
+    OverQuota { read_only_at: DateTime<Utc> },
+    /// Délai de grâce écoulé : drive en lecture seule, seules la suppression et la lecture restent possibles
+    ReadOnly { read_only_at: DateTime<Utc> },
+}
+
+/// Calcule l'état du quota à partir de la date du premier dépassement
+pub fn quota_state(
+    over_quota_since: Option<DateTime<Utc>>,
+    grace_days: i64,
+    now: DateTime<Utc>,
+) -> QuotaState {
+    let Some(since) = over_quota_since else {
+        return QuotaState::WithinQuota;
+    };
+
+    let read_only_at = since + Duration::days(grace_days);
+    if now >= read_only_at {
+        QuotaState::ReadOnly { read_only_at }
+    } else {
+        QuotaState::OverQuota { read_only_at }
+    }
+}
+
+// ========== Réservations de quota ==========
+
+/// Inactivité (heures) au-delà de laquelle un upload non finalisé est considéré abandonné
+/// et sa réservation libérée par le GC (UPLOAD_RESERVATION_TTL_HOURS, 24h par défaut)
+pub fn upload_reservation_ttl_hours() -> i64 {
+    parse_upload_reservation_ttl_hours(
+        std::env::var("UPLOAD_RESERVATION_TTL_HOURS")
+            .ok()
+            .as_deref(),
+    )
+}
+
+/// Valeur de UPLOAD_RESERVATION_TTL_HOURS : 24h si absente, invalide ou nulle
+pub fn parse_upload_reservation_ttl_hours(value: Option<&str>) -> i64 {
+    value
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|hours| *hours > 0)
+        .unwrap_or(24)
+}
+
+// ========== Upload de chunks ==========
+
+/// Taille maximale d'un chunk en bytes (MAX_CHUNK_SIZE_BYTES, 6 Mo par défaut).
+/// Les chunks sont reçus en streaming : la mémoire utilisée ne dépend pas de cette valeur.
+pub fn max_chunk_size_bytes() -> u64 {
+    std::env::var("MAX_CHUNK_SIZE_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(6 * 1024 * 1024)
+}
+
+/// Tag d'authentification AES-256-GCM ajouté par le client à chaque chunk chiffré
+pub const CHUNK_AUTH_TAG_BYTES: i64 = 16;
+
+/// Plus petite taille de chunk couverte par la marge de `upload_size_allowance`
+pub const UPLOAD_ALLOWANCE_MIN_CHUNK_BYTES: i64 = 64 * 1024;
+
+/// Octets de chunks acceptés pour un upload déclaré à `declared_size`.
+/// La taille déclarée peut être celle du clair : une marge couvre le tag AES-GCM de chaque chunk,
+/// pour des chunks d'au moins `UPLOAD_ALLOWANCE_MIN_CHUNK_BYTES` (plus un chunk final partiel).
+pub fn upload_size_allowance(declared_size: i64) -> i64 {
+    let max_chunks = declared_size / UPLOAD_ALLOWANCE_MIN_CHUNK_BYTES + 1;
+    declared_size.saturating_add(max_chunks * CHUNK_AUTH_TAG_BYTES)
+}
+
+// ========== Compteurs d'usage ==========
+
+/// Écart entre les compteurs stockés et les valeurs recalculées (`actual - stored`)
+pub fn storage_usage_drift(stored: &StorageUsage, actual: &StorageUsage) -> StorageUsage {
+    StorageUsage {
+        used_bytes: actual.used_bytes - stored.used_bytes,
+        reserved_bytes: actual.reserved_bytes - stored.reserved_bytes,
+        file_count: actual.file_count - stored.file_count,
+    }
+}
+
+// ========== Copie ==========
+
+/// Vérifie que les éléments fournis par le client pour une copie correspondent exactement
+/// aux éléments source (ni manquant, ni en trop, ni en double)
+pub fn copy_covers_sources(sources: &[Uuid], provided: &[Uuid]) -> bool {
+    let provided_set: HashSet<&Uuid> = provided.iter().collect();
+    provided_set.len() == provided.len()
+        && provided.len() == sources.len()
+        && sources.iter().all(|id| provided_set.contains(id))
+}
+
+// ========== Journal des changements ==========
+
+/// Durée de conservation du journal des changements (DRIVE_CHANGES_RETENTION_DAYS, 30 jours par défaut).
+/// Un client dont le curseur est plus ancien doit se resynchroniser entièrement.
+pub fn drive_changes_retention_days() -> i64 {
+    std::env::var("DRIVE_CHANGES_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(30)
+}
+
+/// Un curseur est utilisable s'il n'est ni antérieur à la rétention (`pruned_seq`),
+/// ni postérieur au dernier changement connu (curseur forgé ou journal réinitialisé)
+pub fn change_cursor_is_valid(cursor: i64, pruned_seq: i64, last_seq: i64) -> bool {
+    cursor >= pruned_seq && cursor <= last_seq
+}
+
+// ========== Favoris, couleurs et tags ==========
+
+/// Couleurs proposées pour les éléments (colonne `color_label`)
+pub const COLOR_LABELS: &[&str] = &["red", "orange", "yellow", "green", "blue", "purple", "grey"];
+
+/// Longueur d'un jeton de tag (HMAC encodé en hex ou base64url par le client)
+pub const MIN_TAG_TOKEN_LENGTH: usize = 16;
+pub const MAX_TAG_TOKEN_LENGTH: usize = 128;
+
+/// Longueur maximale du nom chiffré d'un tag
+pub const MAX_ENCRYPTED_TAG_NAME_LENGTH: usize = 1024;
+
+/// Erreurs de validation renvoyées en 400
+pub const INVALID_COLOR_LABEL: &str =
+    "color_label must be one of red, orange, yellow, green, blue, purple, grey";
+pub const INVALID_TAG_TOKEN: &str = "Tag token must be 16 to 128 URL-safe characters";
+pub const INVALID_TAG_NAME: &str = "Encrypted tag name must be 1 to 1024 characters";
+
+pub fn is_valid_color_label(label: &str) -> bool {
+    COLOR_LABELS.contains(&label)
+}
+
+/// Jeton opaque calculé par le client, utilisable tel quel dans une URL
+pub fn is_valid_tag_token(token: &str) -> bool {
+    (MIN_TAG_TOKEN_LENGTH..=MAX_TAG_TOKEN_LENGTH).contains(&token.len())
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+pub fn is_valid_encrypted_tag_name(name: &str) -> bool {
+    !name.trim().is_empty() && name.len() <= MAX_ENCRYPTED_TAG_NAME_LENGTH
+}
+
+// ========== Index de recherche ==========
+
+/// Nombre maximal de chunks de l'index de recherche d'un utilisateur
+pub const MAX_SEARCH_INDEX_CHUNKS: i32 = 32;
+
+/// Taille maximale d'un chunk chiffré (base64) de l'index de recherche
+pub const MAX_SEARCH_INDEX_CHUNK_BYTES: usize = 512 * 1024;
+
+/// Erreurs de validation renvoyées en 400
+pub const INVALID_SEARCH_TOKEN: &str = "Search token must be 16 to 128 URL-safe characters";
+pub const INVALID_SEARCH_INDEX_CHUNK_COUNT: &str = "chunk_count must be between 0 and 32";
+pub const INVALID_SEARCH_INDEX_CHUNK_INDEX: &str =
+    "chunk_index must be unique and lower than chunk_count";
+pub const INVALID_SEARCH_INDEX_CHUNK_DATA: &str = "encrypted_data must be 1 to 524288 characters";
+
+/// Chunk de l'index de recherche envoyé par le client
+#[derive(Debug, Deserialize)]
+pub struct SearchIndexChunkUpload {
+    pub chunk_index: i32,
+    pub encrypted_data: String,
+}
+
+/// Jeton aveugle du nom : même format que les jetons de tag
+pub fn is_valid_search_token(token: &str) -> bool {
+    is_valid_tag_token(token)
+}
+
+/// Vérifie une mise à jour de l'index avant la transaction. La présence des chunks non
+/// envoyés est vérifiée en base.
+pub fn validate_search_index_update(
+    chunk_count: i32,
+    chunks: &[SearchIndexChunkUpload],
+) -> Result<(), &'static str> {
+    if !(0..=MAX_SEARCH_INDEX_CHUNKS).contains(&chunk_count) {
+        return Err(INVALID_SEARCH_INDEX_CHUNK_COUNT);
+    }
+
+    let mut seen = HashSet::new();
+    for chunk in chunks {
+        if !(0..chunk_count).contains(&chunk.chunk_index) || !seen.insert(chunk.chunk_index) {
+            return Err(INVALID_SEARCH_INDEX_CHUNK_INDEX);
+        }
+        if chunk.encrypted_data.is_empty()
+            || chunk.encrypted_data.len() > MAX_SEARCH_INDEX_CHUNK_BYTES
+        {
+            return Err(INVALID_SEARCH_INDEX_CHUNK_DATA);
+        }
+    }
+    Ok(())
+}
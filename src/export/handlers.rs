@@ -0,0 +1,104 @@
+// Handlers HTTP pour l'export RGPD des données utilisateur
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{auth::Claims, response::ApiResponse, state::AppState};
+
+use super::{repo, services};
+
+#[derive(Deserialize)]
+pub struct DownloadExportQuery {
+    pub token: String,
+}
+
+/// POST /account/export - Demande la génération d'une archive de toutes ses données
+pub async fn request_export_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::create_export(&state.db_pool, claims.id).await {
+        Ok(export) => ApiResponse::ok(export).into_response(),
+        Err(sqlx::Error::Protocol(msg)) => ApiResponse::conflict(msg).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to create data export: {}", e);
+            ApiResponse::internal_error("Failed to create data export").into_response()
+        }
+    }
+}
+
+/// GET /account/export - Liste les exports de l'utilisateur et leur état
+pub async fn list_exports_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::list_exports(&state.db_pool, claims.id).await {
+        Ok(exports) => ApiResponse::ok(exports).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to list data exports: {}", e);
+            ApiResponse::internal_error("Failed to list data exports").into_response()
+        }
+    }
+}
+
+/// GET /account/export/{export_id}/download?token=... - Télécharge l'archive
+/// Lien reçu par email : authentifié par le token, pas par le JWT.
+pub async fn download_export_handler(
+    State(state): State<AppState>,
+    Path(export_id): Path<Uuid>,
+    Query(query): Query<DownloadExportQuery>,
+) -> Response {
+    let export = match repo::get_ready_export(&state.db_pool, export_id).await {
+        Ok(export) => export,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::not_found("Export not found or expired").into_response();
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch data export: {}", e);
+            return ApiResponse::internal_error("Failed to fetch data export").into_response();
+        }
+    };
+
+    let provided_hash = services::hash_download_token(&query.token);
+    if !crate::routes::constant_time_eq(&provided_hash, &export.download_token_hash) {
+        // Même réponse qu'un export inexistant : ne rien révéler
+        return ApiResponse::not_found("Export not found or expired").into_response();
+    }
+
+    let (content_length, stream) = match state.storage_client.download_stream(&export.s3_key).await
+    {
+        Ok(result) => result,
+        Err(crate::storage::StorageError::NotFound) => {
+            return ApiResponse::not_found("Export not found or expired").into_response();
+        }
+        Err(e) => {
+            tracing::error!("Failed to download data export {}: {}", export_id, e);
+            return ApiResponse::internal_error("Failed to download data export").into_response();
+        }
+    };
+
+    let body = Body::from_stream(futures::stream::unfold(stream, |mut stream| async move {
+        stream.next().await.map(|chunk| (chunk, stream))
+    }));
+
+    let mut response = (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"gauzian-export.zip\"".to_string(),
+            ),
+        ],
+        body,
+    )
+        .into_response();
+
+    if let Some(length) = content_length.or(export.size_bytes)
+        && let Ok(value) = length.to_string().parse()
+    {
+        response.headers_mut().insert(header::CONTENT_LENGTH, value);
+    }
+
+    response
+}
@@ -0,0 +1,250 @@
+// Tâches de fond de l'export RGPD
+// Lancées périodiquement depuis main.rs
+
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::{
+    archive::{self, ArchiveSink, ZipStreamWriter},
+    state::AppState,
+    storage::MultipartUpload,
+};
+
+use super::{repo, services};
+
+/// Version du format de l'archive (à incrémenter si la structure change)
+const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// Traite les exports en attente, un par un
+pub async fn process_pending_exports(state: &AppState) {
+    match repo::reset_stale_exports(&state.db_pool).await {
+        Ok(0) => {}
+        Ok(count) => tracing::warn!("{} stale data export(s) requeued", count),
+        Err(e) => tracing::error!("Failed to requeue stale data exports: {}", e),
+    }
+
+    loop {
+        let export = match repo::claim_next_pending_export(&state.db_pool).await {
+            Ok(Some(export)) => export,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::error!("Failed to claim pending data export: {}", e);
+                break;
+            }
+        };
+
+        tracing::info!(
+            "Building data export {} for user {}",
+            export.id,
+            export.user_id
+        );
+        let s3_key = services::export_s3_key(export.id);
+
+        let size = match build_export_archive(state, export.user_id, &s3_key).await {
+            Ok(size) => size,
+            Err(e) => {
+                tracing::error!("Data export {} failed: {}", export.id, e);
+                if let Err(e) =
+                    repo::mark_export_failed(&state.db_pool, export.id, "Archive generation failed")
+                        .await
+                {
+                    tracing::error!("Failed to mark data export {} as failed: {}", export.id, e);
+                }
+                continue;
+            }
+        };
+
+        let token = services::generate_download_token();
+        let ttl_hours = services::export_link_ttl_hours();
+        let expires_at = Utc::now() + Duration::hours(ttl_hours);
+
+        if let Err(e) = repo::mark_export_ready(
+            &state.db_pool,
+            export.id,
+            &s3_key,
+            size as i64,
+            &services::hash_download_token(&token),
+            expires_at,
+        )
+        .await
+        {
+            tracing::error!("Failed to mark data export {} as ready: {}", export.id, e);
+            let _ = state.storage_client.delete_line(&s3_key).await;
+            continue;
+        }
+
+        let url = services::download_url(export.id, &token);
+        if let Err(e) =
+            services::send_export_ready_mail(&state.mailer, &export.email, &url, ttl_hours)
+        {
+            // Sans l'email, le lien (et donc l'archive) est inaccessible : on libère le stockage
+            tracing::error!("Failed to send data export {} email: {}", export.id, e);
+            let _ = state.storage_client.delete_line(&s3_key).await;
+            let _ =
+                repo::mark_export_failed(&state.db_pool, export.id, "Email delivery failed").await;
+        }
+    }
+}
+
+/// Supprime les archives dont le lien a expiré
+pub async fn cleanup_expired_exports(state: &AppState) {
+    let expired = match repo::get_expired_exports(&state.db_pool).await {
+        Ok(expired) => expired,
+        Err(e) => {
+            tracing::error!("Failed to list expired data exports: {}", e);
+            return;
+        }
+    };
+
+    for (export_id, s3_key) in expired {
+        if let Err(e) = state.storage_client.delete_line(&s3_key).await {
+            tracing::error!("Failed to delete expired data export {}: {}", export_id, e);
+            continue;
+        }
+        if let Err(e) = repo::mark_export_expired(&state.db_pool, export_id).await {
+            tracing::error!("Failed to mark data export {} as expired: {}", export_id, e);
+        }
+    }
+}
+
+/// Supprime les archives d'un utilisateur du stockage (suppression de compte).
+/// Les lignes `data_exports` suivent la suppression de l'utilisateur (ON DELETE CASCADE).
+pub async fn delete_user_exports(state: &AppState, user_id: Uuid) -> Result<(), String> {
+    let keys = repo::get_user_export_keys(&state.db_pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for s3_key in keys {
+        state
+            .storage_client
+            .delete_line(&s3_key)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Construit l'archive et l'envoie sur S3 au fil de l'eau. Retourne sa taille.
+async fn build_export_archive(
+    state: &AppState,
+    user_id: Uuid,
+    s3_key: &str,
+) -> Result<u64, String> {
+    let mut upload = state
+        .storage_client
+        .start_multipart_upload(s3_key, "application/zip")
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match write_export_archive(state, user_id, &mut upload).await {
+        Ok(()) => upload.complete().await.map_err(|e| e.to_string()),
+        Err(e) => {
+            upload.abort().await;
+            Err(e)
+        }
+    }
+}
+
+async fn write_export_archive(
+    state: &AppState,
+    user_id: Uuid,
+    upload: &mut MultipartUpload,
+) -> Result<(), String> {
+    let pool = &state.db_pool;
+    let mut zip = ZipStreamWriter::new();
+
+    let profile = repo::get_profile(pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    archive::write_json_entry(&mut zip, upload, "profile.json", &profile).await?;
+
+    let folders = repo::get_folders(pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let folder_count = folders.len();
+    archive::write_json_entry(
+        &mut zip,
+        upload,
+        "folders.json",
+        &serde_json::json!(folders),
+    )
+    .await?;
+
+    let shares = repo::get_shares(pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    archive::write_json_entry(&mut zip, upload, "shares.json", &shares).await?;
+
+    let agenda = repo::get_agenda(pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    archive::write_json_entry(&mut zip, upload, "agenda.json", &agenda).await?;
+
+    let contacts = repo::get_contacts(pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    archive::write_json_entry(&mut zip, upload, "contacts.json", &contacts).await?;
+
+    let files = repo::get_owned_files(pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut files_json = Vec::with_capacity(files.len());
+    let mut chunk_count = 0usize;
+
+    for file in &files {
+        let chunks = repo::get_file_chunks(pool, file.id)
+            .await
+            .map_err(|e| e.to_string())?;
+        let chunks_json =
+            archive::write_file_chunks(&mut zip, upload, &state.storage_client, file.id, chunks)
+                .await?;
+        chunk_count += chunks_json
+            .iter()
+            .filter(|chunk| chunk.get("missing").is_none())
+            .count();
+
+        let mut file_json = file.to_json();
+        file_json["chunks"] = serde_json::Value::Array(chunks_json);
+        files_json.push(file_json);
+    }
+
+    archive::write_json_entry(
+        &mut zip,
+        upload,
+        "files.json",
+        &serde_json::json!(files_json),
+    )
+    .await?;
+
+    let manifest = serde_json::json!({
+        "format": "gauzian-data-export",
+        "version": EXPORT_FORMAT_VERSION,
+        "generated_at": Utc::now(),
+        "user_id": user_id,
+        "counts": {
+            "folders": folder_count,
+            "files": files_json.len(),
+            "chunks": chunk_count,
+        },
+        "contents": {
+            "profile.json": "Account row (without password hash), including the password-encrypted private key",
+            "folders.json": "Folders you own or that were shared with you, with your encrypted copy of each folder key",
+            "files.json": "Files you own, with your encrypted file key and the ordered list of their chunks",
+            "files/<file_id>/<index>.chunk": "Raw ciphertext of each chunk, exactly as stored",
+            "shares.json": "Shares you granted on your items and shares you received",
+            "agenda.json": "Agenda categories, events you own and events you participate in",
+            "contacts.json": "Your contacts with the key fingerprints you verified, your contact groups and pending invitations",
+        },
+        "encoding": "Binary columns are exported as the text the client originally sent (base64), or base64-encoded if not valid UTF-8",
+        "decryption": [
+            "Derive an AES-256-GCM key from your password and profile.private_key_salt with PBKDF2, then decrypt profile.encrypted_private_key using profile.iv: this yields your RSA private key",
+            "Decrypt each encrypted_file_key / encrypted_folder_key with your RSA private key (RSA-OAEP)",
+            "Decrypt encrypted_metadata (\"iv:ciphertext\", AES-256-GCM) with the matching file or folder key to recover names",
+            "For each file, decrypt its chunks in index order with the file key (AES-256-GCM, iv from files.json) and concatenate the plaintexts",
+            "Agenda events are decrypted the same way using encrypted_data_key / encrypted_event_key",
+        ],
+    });
+    archive::write_json_entry(&mut zip, upload, "manifest.json", &manifest).await?;
+
+    upload.write_archive_bytes(zip.finish()).await
+}
@@ -0,0 +1,10 @@
+// Module export - Export RGPD de toutes les données d'un utilisateur
+
+pub mod handlers;
+pub mod jobs;
+pub mod repo;
+pub mod routes;
+pub mod services;
+
+// Re-exports
+pub use routes::export_routes;
@@ -0,0 +1,506 @@
+// Repository - Export RGPD des données utilisateur
+// Table `data_exports` + lectures des données à inclure dans l'archive
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// Convertit un tableau de bytes en String (UTF-8) ou en Base64 si nécessaire
+fn bytes_to_text_or_b64(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) if !s.trim().is_empty() => s.to_string(),
+        _ => base64::engine::general_purpose::STANDARD.encode(bytes),
+    }
+}
+
+/// Convertit une ligne sérialisée par `to_jsonb(...)::text` en JSON
+fn parse_json_row(row: &str) -> Result<serde_json::Value, sqlx::Error> {
+    serde_json::from_str(row).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+// ========== Table data_exports ==========
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DataExport {
+    pub id: Uuid,
+    pub status: String,
+    pub size_bytes: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Export prêt à être téléchargé
+#[derive(Debug, FromRow)]
+pub struct ReadyExport {
+    pub s3_key: String,
+    pub download_token_hash: String,
+    pub size_bytes: Option<i64>,
+}
+
+/// Export réclamé par un worker
+#[derive(Debug, FromRow)]
+pub struct ClaimedExport {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+}
+
+/// Crée une demande d'export. Retourne Protocol si un export est déjà en cours.
+pub async fn create_export(pool: &PgPool, user_id: Uuid) -> Result<DataExport, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // Verrou sur l'utilisateur pour sérialiser les demandes concurrentes
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let in_progress: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM data_exports WHERE user_id = $1 AND status IN ('pending', 'processing'))",
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if in_progress {
+        return Err(sqlx::Error::Protocol(
+            "An export is already in progress".to_string(),
+        ));
+    }
+
+    let export = sqlx::query_as::<_, DataExport>(
+        r#"
+        INSERT INTO data_exports (id, user_id)
+        VALUES ($1, $2)
+        RETURNING id, status, size_bytes, error, created_at, completed_at, expires_at
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(export)
+}
+
+/// Liste les exports d'un utilisateur (plus récents en premier)
+pub async fn list_exports(pool: &PgPool, user_id: Uuid) -> Result<Vec<DataExport>, sqlx::Error> {
+    sqlx::query_as::<_, DataExport>(
+        r#"
+        SELECT id, status, size_bytes, error, created_at, completed_at, expires_at
+        FROM data_exports
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        LIMIT 20
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Réclame le plus ancien export en attente (SKIP LOCKED : plusieurs replicas possibles)
+pub async fn claim_next_pending_export(
+    pool: &PgPool,
+) -> Result<Option<ClaimedExport>, sqlx::Error> {
+    sqlx::query_as::<_, ClaimedExport>(
+        r#"
+        WITH next AS (
+            SELECT id FROM data_exports
+            WHERE status = 'pending'
+            ORDER BY created_at
+            FOR UPDATE SKIP LOCKED
+            LIMIT 1
+        )
+        UPDATE data_exports de
+        SET status = 'processing', started_at = NOW()
+        FROM next, users u
+        WHERE de.id = next.id AND u.id = de.user_id
+        RETURNING de.id, de.user_id, u.email
+        "#,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn mark_export_ready(
+    pool: &PgPool,
+    export_id: Uuid,
+    s3_key: &str,
+    size_bytes: i64,
+    download_token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE data_exports
+        SET status = 'ready', s3_key = $2, size_bytes = $3, download_token_hash = $4,
+            expires_at = $5, completed_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(export_id)
+    .bind(s3_key)
+    .bind(size_bytes)
+    .bind(download_token_hash)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn mark_export_failed(
+    pool: &PgPool,
+    export_id: Uuid,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE data_exports SET status = 'failed', error = $2, completed_at = NOW() WHERE id = $1",
+    )
+    .bind(export_id)
+    .bind(error)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Récupère un export téléchargeable (prêt et non expiré)
+pub async fn get_ready_export(pool: &PgPool, export_id: Uuid) -> Result<ReadyExport, sqlx::Error> {
+    sqlx::query_as::<_, ReadyExport>(
+        r#"
+        SELECT s3_key, download_token_hash, size_bytes
+        FROM data_exports
+        WHERE id = $1 AND status = 'ready' AND expires_at > NOW()
+          AND s3_key IS NOT NULL AND download_token_hash IS NOT NULL
+        "#,
+    )
+    .bind(export_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Exports prêts dont le lien a expiré : (id, s3_key)
+pub async fn get_expired_exports(pool: &PgPool) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+        SELECT id, s3_key
+        FROM data_exports
+        WHERE status = 'ready' AND expires_at <= NOW() AND s3_key IS NOT NULL
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn mark_export_expired(pool: &PgPool, export_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE data_exports
+        SET status = 'expired', s3_key = NULL, download_token_hash = NULL
+        WHERE id = $1
+        "#,
+    )
+    .bind(export_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Remet en attente les exports bloqués en 'processing' (worker arrêté en cours de route)
+pub async fn reset_stale_exports(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE data_exports
+        SET status = 'pending', started_at = NULL
+        WHERE status = 'processing' AND started_at < NOW() - INTERVAL '6 hours'
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Clés S3 des archives d'un utilisateur (purge à la suppression du compte)
+pub async fn get_user_export_keys(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT s3_key FROM data_exports WHERE user_id = $1 AND s3_key IS NOT NULL",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+// ========== Données exportées ==========
+
+/// Profil utilisateur (sans hash de mot de passe ni salt d'authentification)
+pub async fn get_profile(pool: &PgPool, user_id: Uuid) -> Result<serde_json::Value, sqlx::Error> {
+    let row: String = sqlx::query_scalar(
+        r#"
+        SELECT (to_jsonb(u) - 'password_hash' - 'auth_salt'
+                || jsonb_build_object('account_tier', t.name))::text
+        FROM users u
+        JOIN account_tiers t ON t.id = u.account_tier_id
+        WHERE u.id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    parse_json_row(&row)
+}
+
+#[derive(Debug, FromRow)]
+struct ExportFolderRow {
+    id: Uuid,
+    parent_folder_id: Option<Uuid>,
+    encrypted_metadata: Vec<u8>,
+    encrypted_folder_key: Vec<u8>,
+    access_level: String,
+    is_root: bool,
+    is_deleted: bool,
+    created_at: Option<DateTime<Utc>>,
+}
+
+/// Dossiers accessibles par l'utilisateur (possédés et partagés avec lui)
+pub async fn get_folders(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ExportFolderRow>(
+        r#"
+        SELECT f.id, f.parent_folder_id, f.encrypted_metadata, fa.encrypted_folder_key,
+               fa.access_level, f.is_root, f.is_deleted, f.created_at
+        FROM folders f
+        JOIN folder_access fa ON fa.folder_id = f.id AND fa.user_id = $1
+        ORDER BY f.created_at
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "id": row.id,
+                "parent_folder_id": row.parent_folder_id,
+                "encrypted_metadata": bytes_to_text_or_b64(&row.encrypted_metadata),
+                "encrypted_folder_key": bytes_to_text_or_b64(&row.encrypted_folder_key),
+                "access_level": row.access_level,
+                "is_root": row.is_root,
+                "is_deleted": row.is_deleted,
+                "created_at": row.created_at,
+            })
+        })
+        .collect())
+}
+
+#[derive(Debug, FromRow)]
+pub struct ExportFileRow {
+    pub id: Uuid,
+    pub folder_id: Option<Uuid>,
+    pub encrypted_metadata: Vec<u8>,
+    pub encrypted_file_key: Vec<u8>,
+    pub size: i64,
+    pub mime_type: String,
+    pub is_deleted: bool,
+    pub is_fully_uploaded: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl ExportFileRow {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "id": self.id,
+            "folder_id": self.folder_id,
+            "encrypted_metadata": bytes_to_text_or_b64(&self.encrypted_metadata),
+            "encrypted_file_key": bytes_to_text_or_b64(&self.encrypted_file_key),
+            "size": self.size,
+            "mime_type": self.mime_type,
+            "is_deleted": self.is_deleted,
+            "is_fully_uploaded": self.is_fully_uploaded,
+            "created_at": self.created_at,
+        })
+    }
+}
+
+/// Fichiers dont l'utilisateur est owner
+pub async fn get_owned_files(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ExportFileRow>, sqlx::Error> {
+    sqlx::query_as::<_, ExportFileRow>(
+        r#"
+        SELECT f.id, fa.folder_id, f.encrypted_metadata, fa.encrypted_file_key, f.size,
+               f.mime_type, f.is_deleted, f.is_fully_uploaded, f.created_at
+        FROM files f
+        JOIN file_access fa ON fa.file_id = f.id AND fa.user_id = $1
+        WHERE fa.access_level = 'owner'
+        ORDER BY f.created_at
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Chunks d'un fichier, dans l'ordre : (s3_key, index)
+pub async fn get_file_chunks(
+    pool: &PgPool,
+    file_id: Uuid,
+) -> Result<Vec<(String, i32)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i32)>(
+        "SELECT s3_key, index FROM s3_keys WHERE file_id = $1 ORDER BY index",
+    )
+    .bind(file_id)
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Debug, FromRow)]
+struct ShareRow {
+    item_type: String,
+    item_id: Uuid,
+    user_id: Uuid,
+    email: String,
+    access_level: String,
+    is_accepted: bool,
+}
+
+/// Partages accordés (sur les éléments possédés) et reçus (éléments d'autres utilisateurs)
+pub async fn get_shares(pool: &PgPool, user_id: Uuid) -> Result<serde_json::Value, sqlx::Error> {
+    let given = sqlx::query_as::<_, ShareRow>(
+        r#"
+        SELECT 'file' AS item_type, fa.file_id AS item_id, u.id AS user_id, u.email,
+               fa.access_level, fa.is_accepted
+        FROM file_access fa
+        JOIN users u ON u.id = fa.user_id
+        WHERE fa.user_id != $1 AND fa.file_id IN (
+            SELECT file_id FROM file_access WHERE user_id = $1 AND access_level = 'owner'
+        )
+        UNION ALL
+        SELECT 'folder', fa.folder_id, u.id, u.email, fa.access_level, fa.is_accepted
+        FROM folder_access fa
+        JOIN users u ON u.id = fa.user_id
+        WHERE fa.user_id != $1 AND fa.folder_id IN (
+            SELECT folder_id FROM folder_access WHERE user_id = $1 AND access_level = 'owner'
+        )
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let received = sqlx::query_as::<_, ShareRow>(
+        r#"
+        SELECT 'file' AS item_type, fa.file_id AS item_id, o.id AS user_id, o.email,
+               fa.access_level, fa.is_accepted
+        FROM file_access fa
+        JOIN file_access oa ON oa.file_id = fa.file_id AND oa.access_level = 'owner'
+        JOIN users o ON o.id = oa.user_id
+        WHERE fa.user_id = $1 AND fa.access_level != 'owner'
+        UNION ALL
+        SELECT 'folder', fa.folder_id, o.id, o.email, fa.access_level, fa.is_accepted
+        FROM folder_access fa
+        JOIN folder_access oa ON oa.folder_id = fa.folder_id AND oa.access_level = 'owner'
+        JOIN users o ON o.id = oa.user_id
+        WHERE fa.user_id = $1 AND fa.access_level != 'owner'
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let to_json = |rows: Vec<ShareRow>, user_key: &str| -> Vec<serde_json::Value> {
+        rows.into_iter()
+            .map(|row| {
+                serde_json::json!({
+                    "item_type": row.item_type,
+                    "item_id": row.item_id,
+                    user_key: { "id": row.user_id, "email": row.email },
+                    "access_level": row.access_level,
+                    "is_accepted": row.is_accepted,
+                })
+            })
+            .collect()
+    };
+
+    Ok(serde_json::json!({
+        "given": to_json(given, "shared_with"),
+        "received": to_json(received, "owner"),
+    }))
+}
+
+/// Données agenda : catégories, événements possédés et participations
+pub async fn get_agenda(pool: &PgPool, user_id: Uuid) -> Result<serde_json::Value, sqlx::Error> {
+    let categories: Vec<String> =
+        sqlx::query_scalar("SELECT to_jsonb(c)::text FROM agenda_categories c WHERE owner_id = $1")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+    let events: Vec<(String, Vec<u8>)> = sqlx::query_as(
+        r#"
+        SELECT (to_jsonb(e) - 'encrypted_data_key')::text, e.encrypted_data_key
+        FROM agenda_events e
+        WHERE owner_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let participations: Vec<(String, Vec<u8>)> = sqlx::query_as(
+        r#"
+        SELECT (to_jsonb(p) - 'encrypted_event_key')::text, p.encrypted_event_key
+        FROM agenda_event_participants p
+        WHERE participant_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let with_key =
+        |rows: Vec<(String, Vec<u8>)>, key: &str| -> Result<Vec<serde_json::Value>, sqlx::Error> {
+            rows.into_iter()
+                .map(|(row, bytes)| {
+                    let mut value = parse_json_row(&row)?;
+                    value[key] = serde_json::Value::String(bytes_to_text_or_b64(&bytes));
+                    Ok(value)
+                })
+                .collect()
+        };
+
+    Ok(serde_json::json!({
+        "categories": categories
+            .iter()
+            .map(|row| parse_json_row(row))
+            .collect::<Result<Vec<_>, _>>()?,
+        "events": with_key(events, "encrypted_data_key")?,
+        "participations": with_key(participations, "encrypted_event_key")?,
+    }))
+}
+
+/// Carnet de contacts : contacts acceptés (avec empreintes vérifiées), groupes et invitations
+pub async fn get_contacts(pool: &PgPool, user_id: Uuid) -> Result<serde_json::Value, sqlx::Error> {
+    let contacts = crate::contacts::repo::list_contacts(pool, user_id, None, None).await?;
+    let groups = crate::contacts::repo::list_groups(pool, user_id).await?;
+    let invitations = crate::contacts::repo::list_invitations(pool, user_id).await?;
+
+    Ok(serde_json::json!({
+        "contacts": contacts,
+        "groups": groups,
+        "invitations": invitations,
+    }))
+}
@@ -0,0 +1,19 @@
+// Routes du module export (RGPD)
+
+use crate::state::AppState;
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use super::handlers;
+
+pub fn export_routes() -> Router<AppState> {
+    Router::new()
+        .route("/account/export", post(handlers::request_export_handler))
+        .route("/account/export", get(handlers::list_exports_handler))
+        .route(
+            "/account/export/{export_id}/download",
+            get(handlers::download_export_handler),
+        )
+}
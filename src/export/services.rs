@@ -0,0 +1,85 @@
+// Services - Logique métier de l'export RGPD (tokens de téléchargement, configuration, email)
+
+use base64::{Engine as _, engine::general_purpose};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// Durée de validité du lien de téléchargement (EXPORT_LINK_TTL_HOURS, 48h par défaut)
+pub fn export_link_ttl_hours() -> i64 {
+    std::env::var("EXPORT_LINK_TTL_HOURS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|hours| *hours > 0)
+        .unwrap_or(48)
+}
+
+/// URL publique de l'API, utilisée pour construire les liens envoyés par email
+pub fn public_api_url() -> String {
+    std::env::var("PUBLIC_API_URL")
+        .unwrap_or_else(|_| "http://localhost:8080".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Clé S3 d'une archive d'export (préfixe distinct des chunks, qui sont des UUID)
+pub fn export_s3_key(export_id: Uuid) -> String {
+    format!("exports/{export_id}.zip")
+}
+
+/// Génère un token de téléchargement aléatoire (256 bits, URL-safe)
+pub fn generate_download_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Seul le hash du token est stocké en base
+pub fn hash_download_token(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn download_url(export_id: Uuid, token: &str) -> String {
+    format!(
+        "{}/account/export/{}/download?token={}",
+        public_api_url(),
+        export_id,
+        token
+    )
+}
+
+/// Envoie le lien de téléchargement de l'archive à l'utilisateur
+pub fn send_export_ready_mail(
+    mailer: &lettre::SmtpTransport,
+    email: &str,
+    url: &str,
+    ttl_hours: i64,
+) -> Result<(), String> {
+    let plain_body = format!(
+        "Bonjour,\n\nL'export de vos données Gauzian est prêt. Vous pouvez le télécharger ici :\n\n{}\n\nCe lien est valide pendant {} heures. Les fichiers restent chiffrés : le manifest.json inclus dans l'archive explique comment les déchiffrer avec votre mot de passe.\n\nMerci,\nL'équipe Gauzian",
+        url, ttl_hours
+    );
+
+    let html_body = crate::mail::html_layout(
+        "Votre export de données Gauzian",
+        &format!(
+            "<p style=\"margin:0 0 12px 0;font-size:15px;line-height:1.6;\">Bonjour,</p>
+            <p style=\"margin:0 0 20px 0;font-size:15px;line-height:1.6;\">L'export de vos données est prêt.</p>
+            <p style=\"margin:0 0 20px 0;text-align:center;\">
+                <a href=\"{}\" style=\"display:inline-block;padding:12px 20px;border-radius:10px;background:#111827;color:#ffffff;font-size:15px;font-weight:700;text-decoration:none;\">Télécharger mon archive</a>
+            </p>
+            <p style=\"margin:0 0 20px 0;font-size:14px;line-height:1.6;color:#4b5563;\">Ce lien est valide pendant <strong>{} heures</strong>. Les fichiers restent chiffrés : le <code>manifest.json</code> inclus dans l'archive explique comment les déchiffrer avec votre mot de passe.</p>",
+            url, ttl_hours
+        ),
+    );
+
+    crate::mail::send_mail(
+        mailer,
+        email,
+        "Votre export de données Gauzian est prêt",
+        plain_body,
+        html_body,
+    )
+}
@@ -1,14 +1,24 @@
 // Déclaration des modules
 
+pub mod archive; // Archives ZIP en streaming
+pub mod mail; // Envoi d'emails
 pub mod metrics; // Métriques Prometheus
 pub mod response; // Types de réponse HTTP
 pub mod routes; // Composition des routes
 pub mod state; // AppState partagé
 pub mod storage; // Client S3/MinIO
+pub mod upload_limiter; // Limitation des uploads concurrents
 
+pub mod activity; // Journal d'activité (audit)
+pub mod admin; // API d'administration des comptes
 pub mod agenda;
 pub mod auth; // Authentification, gestion des utilisateurs
+pub mod billing; // Offres de stockage et paiement
+pub mod contacts; // Carnet de contacts (invitations, empreintes de clés, groupes)
 pub mod drive; // Gestion des fichiers, dossiers, permissions, upload/download // Gestion des événements d'agenda
+pub mod export; // Export RGPD des données utilisateur
+pub mod notifications; // Notifications temps réel (SSE) et emails de partage
+pub mod teams; // Équipes (paire de clés partagée, dossiers partagés à l'équipe)
 
 #[cfg(test)]
 mod tests;
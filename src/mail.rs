@@ -0,0 +1,92 @@
+// Envoi d'emails transactionnels (OTP, export de données, notifications...)
+// Le transport SMTP est construit une seule fois dans AppState::from_env
+
+use lettre::message::Mailbox;
+use lettre::message::{MultiPart, SinglePart, header::ContentType};
+use lettre::{Message, SmtpTransport, Transport};
+
+const SENDER: &str = "GAUZIAN <gauzian@pupin.fr>";
+
+/// Envoie un email multipart (texte brut + HTML) à un destinataire
+pub fn send_mail(
+    mailer: &SmtpTransport,
+    to: &str,
+    subject: &str,
+    plain_body: String,
+    html_body: String,
+) -> Result<(), String> {
+    let message = Message::builder()
+        .from(SENDER.parse::<Mailbox>().map_err(|e| e.to_string())?)
+        .to(format!("Destinataire <{}>", to)
+            .parse::<Mailbox>()
+            .map_err(|e| e.to_string())?)
+        .subject(subject)
+        .multipart(
+            MultiPart::alternative()
+                .singlepart(
+                    SinglePart::builder()
+                        .header(ContentType::TEXT_PLAIN)
+                        .body(plain_body),
+                )
+                .singlepart(
+                    SinglePart::builder()
+                        .header(ContentType::TEXT_HTML)
+                        .body(html_body),
+                ),
+        )
+        .map_err(|e| e.to_string())?;
+
+    match mailer.send(&message) {
+        Ok(_) => {
+            tracing::info!("Email sent successfully to {}", to);
+            Ok(())
+        }
+        Err(e) => {
+            tracing::error!("Failed to send email to {}: {}", to, e);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// Gabarit HTML commun : titre + paragraphes déjà formatés en HTML
+pub fn html_layout(title: &str, content: &str) -> String {
+    format!(
+        "<!doctype html>
+        <html lang=\"fr\">
+            <body style=\"margin:0;padding:0;background:#f6f8fb;font-family:Arial,sans-serif;color:#1f2937;\">
+                <table role=\"presentation\" width=\"100%\" cellspacing=\"0\" cellpadding=\"0\" style=\"padding:24px 12px;\">
+                    <tr>
+                        <td align=\"center\">
+                            <table role=\"presentation\" width=\"100%\" cellspacing=\"0\" cellpadding=\"0\" style=\"max-width:560px;background:#ffffff;border-radius:12px;border:1px solid #e5e7eb;padding:24px;\">
+                                <tr>
+                                    <td>
+                                        <h1 style=\"margin:0 0 16px 0;font-size:20px;line-height:1.3;color:#111827;\">{}</h1>
+                                        {}
+                                        <p style=\"margin:0;font-size:14px;line-height:1.6;color:#4b5563;\">Merci,<br>L'équipe Gauzian</p>
+                                    </td>
+                                </tr>
+                            </table>
+                        </td>
+                    </tr>
+                </table>
+            </body>
+        </html>",
+        title, content
+    )
+}
+
+/// Échappe un texte libre avant de l'insérer dans un corps HTML
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -40,6 +40,90 @@ async fn main() {
         }
     });
 
+    // Purge des comptes dont le délai de grâce de suppression est écoulé
+    let purge_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            gauzian_back::auth::jobs::purge_scheduled_accounts(&purge_state).await;
+        }
+    });
+
+    // Génération des exports RGPD en attente + nettoyage des archives expirées
+    let export_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            gauzian_back::export::jobs::process_pending_exports(&export_state).await;
+            gauzian_back::export::jobs::cleanup_expired_exports(&export_state).await;
+        }
+    });
+
+    // Libération des réservations de quota des uploads abandonnés
+    let reservation_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(15 * 60));
+        loop {
+            interval.tick().await;
+            gauzian_back::drive::jobs::release_expired_reservations(&reservation_state).await;
+        }
+    });
+
+    // Réconciliation des compteurs d'usage avec les données réelles
+    let usage_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(6 * 3600));
+        loop {
+            interval.tick().await;
+            gauzian_back::drive::jobs::reconcile_storage_usage(&usage_state).await;
+        }
+    });
+
+    // Rétention du journal des changements (synchronisation incrémentale)
+    let changes_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            gauzian_back::drive::jobs::prune_drive_changes(&changes_state).await;
+        }
+    });
+
+    // Révocation des partages temporaires expirés
+    let shares_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5 * 60));
+        loop {
+            interval.tick().await;
+            gauzian_back::drive::jobs::revoke_expired_shares(&shares_state).await;
+        }
+    });
+
+    // Rétention du journal d'activité
+    let activity_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(6 * 3600));
+        loop {
+            interval.tick().await;
+            gauzian_back::activity::jobs::prune_activity_log(&activity_state).await;
+        }
+    });
+
+    // Résumés quotidiens des partages reçus (emails)
+    let digest_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            gauzian_back::notifications::jobs::send_share_digests(&digest_state).await;
+        }
+    });
+
+    // Relais Redis pub/sub -> flux de notifications ouverts sur cette réplica
+    tokio::spawn(state.notifications.clone().run());
+
     // Initialiser le bucket S3 au démarrage (avec timeout plus long)
     match tokio::time::timeout(
         std::time::Duration::from_secs(30),
@@ -1,8 +1,8 @@
 use axum::{extract::Request, middleware::Next, response::Response};
 use lazy_static::lazy_static;
 use prometheus::{
-    CounterVec, Encoder, HistogramOpts, HistogramVec, IntGauge, TextEncoder, opts,
-    register_counter_vec, register_histogram_vec, register_int_gauge,
+    CounterVec, Encoder, HistogramOpts, HistogramVec, IntCounter, IntGauge, TextEncoder, opts,
+    register_counter_vec, register_histogram_vec, register_int_counter, register_int_gauge,
 };
 use std::time::Instant;
 
@@ -104,6 +104,50 @@ lazy_static! {
     )
     .unwrap();
 
+    /// Slots d'upload actuellement occupés
+    pub static ref UPLOAD_PERMITS_IN_USE: IntGauge =
+        register_int_gauge!("upload_permits_in_use", "Number of upload permits currently in use").unwrap();
+
+    /// Nombre maximum de slots d'upload (MAX_CONCURRENT_UPLOADS)
+    pub static ref UPLOAD_PERMITS_MAX: IntGauge =
+        register_int_gauge!("upload_permits_max", "Maximum number of concurrent upload permits").unwrap();
+
+    /// Requêtes d'upload en attente d'un slot
+    pub static ref UPLOAD_QUEUE_WAITING: IntGauge =
+        register_int_gauge!("upload_queue_waiting", "Number of upload requests waiting for a permit").unwrap();
+
+    /// Uploads refusés faute de slot dans le délai d'attente
+    pub static ref UPLOAD_REJECTIONS_TOTAL: CounterVec = register_counter_vec!(
+        opts!("upload_rejections_total", "Total number of uploads rejected for lack of a permit"),
+        &["reason"] // "user_limit", "server_busy"
+    )
+    .unwrap();
+
+    /// Utilisateurs dont les compteurs d'usage étaient faux lors de la dernière réconciliation
+    pub static ref STORAGE_USAGE_DRIFTED_USERS: IntGauge = register_int_gauge!(
+        "storage_usage_drifted_users",
+        "Number of users with drifted storage usage counters in the last reconciliation"
+    )
+    .unwrap();
+
+    /// Écart absolu (en bytes, utilisé + réservé) corrigé lors de la dernière réconciliation
+    pub static ref STORAGE_USAGE_DRIFT_BYTES: IntGauge = register_int_gauge!(
+        "storage_usage_drift_bytes",
+        "Absolute storage usage drift in bytes corrected in the last reconciliation"
+    )
+    .unwrap();
+
+    /// Nombre total de corrections de compteurs d'usage
+    pub static ref STORAGE_USAGE_CORRECTIONS_TOTAL: IntCounter = register_int_counter!(
+        "storage_usage_corrections_total",
+        "Total number of storage usage counter corrections"
+    )
+    .unwrap();
+
+    /// Flux de notifications (SSE) ouverts sur cette réplica
+    pub static ref NOTIFICATION_STREAMS_OPEN: IntGauge =
+        register_int_gauge!("notification_streams_open", "Number of open notification streams").unwrap();
+
     // ==================== Métriques DB Pool ====================
 
     /// Nombre de connexions actives dans le pool
@@ -265,6 +309,13 @@ pub fn track_db_query(query_type: &str, duration_secs: f64, success: bool) {
         .observe(duration_secs);
 }
 
+/// Enregistre le résultat d'une réconciliation des compteurs d'usage
+pub fn track_storage_usage_reconciliation(drifted_users: i64, drift_bytes: i64) {
+    STORAGE_USAGE_DRIFTED_USERS.set(drifted_users);
+    STORAGE_USAGE_DRIFT_BYTES.set(drift_bytes);
+    STORAGE_USAGE_CORRECTIONS_TOTAL.inc_by(drifted_users as u64);
+}
+
 /// Met à jour les métriques du pool de connexions DB
 pub fn update_db_pool_metrics(pool: &sqlx::PgPool) {
     // SQLx expose ces stats via pool.size() et pool.num_idle()
@@ -0,0 +1,101 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::Json;
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures::Stream;
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use super::{repo, services::ShareEmailMode};
+use crate::{
+    auth::{
+        Claims,
+        services::{AuthError, validate_session},
+    },
+    response::ApiResponse,
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct NotificationSettingsRequest {
+    pub share_email_mode: ShareEmailMode,
+}
+
+/// Intervalle de revérification de la session d'un flux ouvert (déconnexion forcée, suspension)
+const SESSION_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// GET /notifications/stream - Flux Server-Sent Events des notifications de l'utilisateur.
+/// Chaque message est un `NotificationEvent` en JSON ; un commentaire keep-alive maintient
+/// la connexion ouverte derrière les proxies. La session est revérifiée périodiquement : le flux
+/// se ferme dès qu'elle est révoquée, expirée ou que le compte est suspendu.
+pub async fn stream_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let subscription = state.notifications.subscribe(claims.id);
+    tracing::debug!("Notification stream opened for user {}", claims.id);
+
+    let recheck = tokio::time::interval_at(
+        Instant::now() + SESSION_RECHECK_INTERVAL,
+        SESSION_RECHECK_INTERVAL,
+    );
+    let stream = futures::stream::unfold(
+        (subscription, recheck, state, claims),
+        |(mut subscription, mut recheck, state, claims)| async move {
+            loop {
+                tokio::select! {
+                    payload = subscription.recv() => {
+                        let payload = payload?;
+                        let event = Event::default().data(&*payload);
+                        return Some((Ok(event), (subscription, recheck, state, claims)));
+                    }
+                    _ = recheck.tick() => {
+                        if let Err(AuthError(_, reason)) = validate_session(&state, &claims).await {
+                            tracing::debug!(
+                                "Notification stream closed for user {}: {}",
+                                claims.id,
+                                reason
+                            );
+                            return None;
+                        }
+                    }
+                }
+            }
+        },
+    );
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// GET /notifications/settings - Préférences d'email des partages reçus
+pub async fn get_settings_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::get_share_email_mode(&state.db_pool, claims.id).await {
+        Ok(mode) => {
+            ApiResponse::ok(serde_json::json!({ "share_email_mode": mode })).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve notification settings: {:?}", e);
+            ApiResponse::internal_error("Failed to retrieve notification settings").into_response()
+        }
+    }
+}
+
+/// PUT /notifications/settings - Email à chaque partage (`instant`), résumé quotidien (`daily`)
+/// ou aucun email (`off`)
+pub async fn update_settings_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(body): Json<NotificationSettingsRequest>,
+) -> Response {
+    match repo::set_share_email_mode(&state.db_pool, claims.id, body.share_email_mode).await {
+        Ok(()) => ApiResponse::ok(serde_json::json!({ "share_email_mode": body.share_email_mode }))
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to update notification settings: {:?}", e);
+            ApiResponse::internal_error("Failed to update notification settings").into_response()
+        }
+    }
+}
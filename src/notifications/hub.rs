@@ -0,0 +1,146 @@
+// Hub de notifications - Flux SSE ouverts sur cette réplica
+// Un abonnement Redis par réplica (PSUBSCRIBE notifications:*) alimente les flux locaux
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use super::services::{CHANNEL_PREFIX, user_from_channel};
+use crate::metrics;
+
+/// Messages en attente par utilisateur avant qu'un flux lent ne décroche
+const USER_BUFFER_SIZE: usize = 64;
+
+/// Délai avant reconnexion à Redis après une coupure
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+type Senders = Arc<Mutex<HashMap<Uuid, broadcast::Sender<Arc<str>>>>>;
+
+/// Flux de notifications d'un utilisateur sur cette réplica
+pub struct NotificationHub {
+    redis_client: redis::Client,
+    senders: Senders,
+}
+
+/// Abonnement d'un flux SSE ; se désinscrit (métrique comprise) quand il est abandonné
+pub struct NotificationSubscription {
+    user_id: Uuid,
+    receiver: broadcast::Receiver<Arc<str>>,
+    senders: Senders,
+}
+
+impl NotificationSubscription {
+    /// Prochain message (JSON) ; None quand le hub ferme le canal.
+    /// Les messages perdus par un flux trop lent sont ignorés : le client se resynchronise.
+    pub async fn recv(&mut self) -> Option<Arc<str>> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(payload) => return Some(payload),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Notification stream lagged, {} message(s) dropped", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for NotificationSubscription {
+    fn drop(&mut self) {
+        metrics::NOTIFICATION_STREAMS_OPEN.dec();
+
+        // Dernier flux de l'utilisateur : son canal est retiré. Sous le verrou, aucun nouveau flux
+        // ne peut s'abonner entre la vérification et le retrait.
+        let mut senders = self.senders.lock().unwrap();
+        if senders
+            .get(&self.user_id)
+            .is_some_and(|sender| sender.receiver_count() <= 1)
+        {
+            senders.remove(&self.user_id);
+        }
+    }
+}
+
+impl NotificationHub {
+    pub fn new(redis_client: redis::Client) -> Self {
+        Self {
+            redis_client,
+            senders: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Ouvre un flux pour `user_id` (plusieurs flux par utilisateur possibles : onglets, appareils)
+    pub fn subscribe(&self, user_id: Uuid) -> NotificationSubscription {
+        let mut senders = self.senders.lock().unwrap();
+        let sender = senders
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(USER_BUFFER_SIZE).0);
+        metrics::NOTIFICATION_STREAMS_OPEN.inc();
+        NotificationSubscription {
+            user_id,
+            receiver: sender.subscribe(),
+            senders: Arc::clone(&self.senders),
+        }
+    }
+
+    /// Transmet un message aux flux ouverts localement par `user_id`.
+    /// Retourne le nombre de flux atteints.
+    pub fn dispatch(&self, user_id: Uuid, payload: Arc<str>) -> usize {
+        let mut senders = self.senders.lock().unwrap();
+        let Some(sender) = senders.get(&user_id) else {
+            return 0;
+        };
+
+        match sender.send(payload) {
+            Ok(reached) => reached,
+            Err(_) => {
+                senders.remove(&user_id);
+                0
+            }
+        }
+    }
+
+    /// Nombre d'utilisateurs ayant un canal sur cette réplica
+    pub fn connected_users(&self) -> usize {
+        self.senders.lock().unwrap().len()
+    }
+
+    /// Écoute les canaux de notification sur Redis et les distribue aux flux locaux.
+    /// Tourne indéfiniment (tâche de fond) et se reconnecte après une coupure.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            if let Err(e) = self.listen().await {
+                tracing::error!("Notification listener disconnected from Redis: {}", e);
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn listen(&self) -> redis::RedisResult<()> {
+        let mut pubsub = self.redis_client.get_async_pubsub().await?;
+        pubsub.psubscribe(format!("{}*", CHANNEL_PREFIX)).await?;
+        tracing::info!("Notification listener subscribed to Redis");
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let Some(user_id) = user_from_channel(message.get_channel_name()) else {
+                continue;
+            };
+            match message.get_payload::<String>() {
+                Ok(payload) => {
+                    self.dispatch(user_id, payload.into());
+                }
+                Err(e) => tracing::warn!("Invalid notification payload: {}", e),
+            }
+        }
+
+        Err(redis::RedisError::from((
+            redis::ErrorKind::Io,
+            "Redis pub/sub stream closed",
+        )))
+    }
+}
@@ -0,0 +1,69 @@
+// Tâches de fond des notifications
+// Lancées périodiquement depuis main.rs
+
+use crate::{drive, state::AppState};
+
+use super::{repo, services};
+
+/// Nombre maximum de résumés quotidiens envoyés par passage
+const DIGEST_BATCH_SIZE: i64 = 200;
+
+/// Envoie le résumé quotidien des partages en attente reçus depuis le précédent. Les résumés
+/// dus sont réservés avant l'envoi : plusieurs instances peuvent exécuter le job sans doublon.
+/// Sans nouveau partage, la période est close sans email ; en cas d'échec d'envoi, la
+/// réservation est rendue et le résumé retenté au passage suivant.
+pub async fn send_share_digests(state: &AppState) {
+    let recipients = match repo::claim_due_digests(&state.db_pool, DIGEST_BATCH_SIZE).await {
+        Ok(recipients) => recipients,
+        Err(e) => {
+            tracing::error!("Failed to claim due share digests: {}", e);
+            return;
+        }
+    };
+
+    for recipient in recipients {
+        // Bornée par la réservation : un partage reçu après `until` ira dans le résumé suivant
+        let shares = match drive::repo::get_pending_shares(
+            &state.db_pool,
+            recipient.user_id,
+            Some((recipient.since, recipient.until)),
+        )
+        .await
+        {
+            Ok(shares) => shares,
+            Err(e) => {
+                tracing::error!(
+                    "Failed to list pending shares of user {}: {}",
+                    recipient.user_id,
+                    e
+                );
+                release_claim(state, &recipient).await;
+                continue;
+            }
+        };
+
+        if shares.is_empty() {
+            continue;
+        }
+
+        let lines = services::summarize_pending_shares(&shares);
+        if let Err(e) = services::send_share_digest_mail(&state.mailer, &recipient.email, &lines) {
+            tracing::error!(
+                "Failed to send share digest to user {}: {}",
+                recipient.user_id,
+                e
+            );
+            release_claim(state, &recipient).await;
+        }
+    }
+}
+
+async fn release_claim(state: &AppState, recipient: &repo::DigestRecipient) {
+    if let Err(e) = repo::release_digest_claim(&state.db_pool, recipient).await {
+        tracing::error!(
+            "Failed to release share digest of user {}: {}",
+            recipient.user_id,
+            e
+        );
+    }
+}
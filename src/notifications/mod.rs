@@ -0,0 +1,13 @@
+// Module notifications - Notifications temps réel (Server-Sent Events)
+// Diffusion entre réplicas via Redis pub/sub, emails de partage (immédiats ou résumé quotidien)
+
+pub mod handlers;
+pub mod hub;
+pub mod jobs;
+pub mod repo;
+pub mod routes;
+pub mod services;
+
+// Re-exports
+pub use hub::NotificationHub;
+pub use routes::notifications_routes;
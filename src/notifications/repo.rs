@@ -0,0 +1,141 @@
+// Repository - Préférences d'email des notifications de partage
+
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use super::services::ShareEmailMode;
+
+/// Destinataire d'un email de partage, avec l'auteur du partage
+#[derive(Debug, FromRow)]
+pub struct ShareEmailRecipient {
+    pub email: String,
+    pub share_email_mode: ShareEmailMode,
+    pub sender_username: Option<String>,
+}
+
+/// Résumé quotidien réservé par un passage du job : il couvre les partages reçus dans
+/// `]since, until]`
+#[derive(Debug, FromRow)]
+pub struct DigestRecipient {
+    pub user_id: Uuid,
+    pub email: String,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+/// Mode d'email de l'utilisateur (immédiat tant qu'il ne l'a pas modifié)
+pub async fn get_share_email_mode(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<ShareEmailMode, sqlx::Error> {
+    let mode = sqlx::query_scalar::<_, ShareEmailMode>(
+        "SELECT share_email_mode FROM notification_settings WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(mode.unwrap_or(ShareEmailMode::Instant))
+}
+
+/// Change le mode d'email. Le passage en résumé quotidien ouvre sa première période.
+pub async fn set_share_email_mode(
+    pool: &PgPool,
+    user_id: Uuid,
+    mode: ShareEmailMode,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "
+        INSERT INTO notification_settings (user_id, share_email_mode, last_digest_at, updated_at)
+        VALUES ($1, $2, NOW(), NOW())
+        ON CONFLICT (user_id) DO UPDATE
+        SET share_email_mode = EXCLUDED.share_email_mode,
+            last_digest_at = CASE
+                WHEN notification_settings.share_email_mode = 'daily' THEN notification_settings.last_digest_at
+                ELSE NOW()
+            END,
+            updated_at = NOW()
+        ",
+    )
+    .bind(user_id)
+    .bind(mode)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Email et préférence du destinataire d'un partage (None si son compte est suspendu ou
+/// en cours de suppression)
+pub async fn get_share_email_recipient(
+    pool: &PgPool,
+    recipient_id: Uuid,
+    from_user_id: Uuid,
+) -> Result<Option<ShareEmailRecipient>, sqlx::Error> {
+    sqlx::query_as::<_, ShareEmailRecipient>(
+        "
+        SELECT
+            u.email,
+            COALESCE(ns.share_email_mode, 'instant') AS share_email_mode,
+            sender.username AS sender_username
+        FROM users u
+        LEFT JOIN notification_settings ns ON ns.user_id = u.id
+        LEFT JOIN users sender ON sender.id = $2
+        WHERE u.id = $1 AND u.is_active = TRUE AND u.deletion_scheduled_at IS NULL
+        ",
+    )
+    .bind(recipient_id)
+    .bind(from_user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Réserve les résumés quotidiens dus (dernier résumé d'au moins un jour) en avançant
+/// `last_digest_at` dans la même requête. Les lignes verrouillées par une autre instance
+/// sont ignorées : chaque résumé n'est réservé que par une instance.
+pub async fn claim_due_digests(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<DigestRecipient>, sqlx::Error> {
+    sqlx::query_as::<_, DigestRecipient>(
+        "
+        WITH due AS (
+            SELECT ns.user_id, ns.last_digest_at
+            FROM notification_settings ns
+            JOIN users u ON u.id = ns.user_id
+            WHERE ns.share_email_mode = 'daily'
+              AND ns.last_digest_at <= NOW() - INTERVAL '1 day'
+              AND u.is_active = TRUE
+              AND u.deletion_scheduled_at IS NULL
+            ORDER BY ns.last_digest_at
+            LIMIT $1
+            FOR UPDATE OF ns SKIP LOCKED
+        )
+        UPDATE notification_settings ns
+        SET last_digest_at = NOW()
+        FROM due
+        JOIN users u ON u.id = due.user_id
+        WHERE ns.user_id = due.user_id
+        RETURNING ns.user_id, u.email, due.last_digest_at AS since, ns.last_digest_at AS until
+        ",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Rend un résumé réservé dont l'envoi a échoué : il sera retenté au passage suivant,
+/// sauf si la période a changé entre-temps (changement de mode)
+pub async fn release_digest_claim(
+    pool: &PgPool,
+    recipient: &DigestRecipient,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE notification_settings SET last_digest_at = $2 WHERE user_id = $1 AND last_digest_at = $3",
+    )
+    .bind(recipient.user_id)
+    .bind(recipient.since)
+    .bind(recipient.until)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,15 @@
+// Routes du module notifications
+
+use crate::state::AppState;
+use axum::{Router, routing::get};
+
+use super::handlers;
+
+pub fn notifications_routes() -> Router<AppState> {
+    Router::new()
+        .route("/stream", get(handlers::stream_handler))
+        .route(
+            "/settings",
+            get(handlers::get_settings_handler).put(handlers::update_settings_handler),
+        )
+}
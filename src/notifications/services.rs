@@ -0,0 +1,335 @@
+// Services - Événements de notification, publication sur Redis et emails de partage
+
+use lettre::SmtpTransport;
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Postgres,
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+};
+use uuid::Uuid;
+
+use crate::{drive::repo::PendingShare, state::AppState};
+
+use super::repo;
+
+/// Préfixe des canaux Redis : un canal par utilisateur (`notifications:{user_id}`)
+pub const CHANNEL_PREFIX: &str = "notifications:";
+
+/// Événement poussé aux clients connectés (sérialisé en JSON, champ `type`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// Fichier ou dossier partagé avec l'utilisateur, en attente d'acceptation
+    ShareReceived {
+        item_type: String,
+        item_id: Uuid,
+        from_user_id: Uuid,
+    },
+    /// Fichier ajouté par un collaborateur dans un dossier partagé (accès déjà accepté)
+    FileAdded { file_id: Uuid, added_by: Uuid },
+    /// Dossier ajouté par un collaborateur dans un dossier partagé (accès déjà accepté)
+    FolderAdded { folder_id: Uuid, added_by: Uuid },
+    /// Partage temporaire arrivé à expiration et révoqué (envoyé au propriétaire)
+    ShareExpired {
+        item_type: String,
+        item_id: Uuid,
+        user_id: Uuid,
+    },
+    /// Invitation à rejoindre les contacts d'un autre utilisateur
+    ContactInvitation {
+        invitation_id: Uuid,
+        from_user_id: Uuid,
+    },
+    /// Invitation acceptée (envoyé à son auteur) : la clé publique du contact est disponible
+    ContactAccepted { user_id: Uuid },
+    /// Un contact a changé de clé publique : l'empreinte vérifiée ne correspond plus
+    ContactKeyChanged {
+        user_id: Uuid,
+        key_version: i32,
+        fingerprint: String,
+    },
+    /// Dossier partagé à une équipe de l'utilisateur (accès déjà accepté)
+    TeamFolderShared {
+        team_id: Uuid,
+        folder_id: Uuid,
+        from_user_id: Uuid,
+    },
+    /// Ajout à une équipe : ses dossiers sont accessibles
+    TeamJoined { team_id: Uuid, added_by: Uuid },
+}
+
+/// Canal Redis d'un utilisateur
+pub fn channel_for(user_id: Uuid) -> String {
+    format!("{}{}", CHANNEL_PREFIX, user_id)
+}
+
+/// Utilisateur destinataire d'un canal Redis (None si le canal n'est pas un canal de notification)
+pub fn user_from_channel(channel: &str) -> Option<Uuid> {
+    channel
+        .strip_prefix(CHANNEL_PREFIX)
+        .and_then(|id| Uuid::parse_str(id).ok())
+}
+
+/// Publie `event` sur le canal de chaque destinataire. Toutes les réplicas le reçoivent et le
+/// transmettent aux flux ouverts localement. Best effort : appelé après le commit de la mutation,
+/// un échec est journalisé sans remettre en cause la requête (les clients se resynchronisent
+/// via `GET /drive/changes`).
+pub async fn publish(redis: &ConnectionManager, user_ids: &[Uuid], event: &NotificationEvent) {
+    if user_ids.is_empty() {
+        return;
+    }
+
+    let payload = match serde_json::to_string(event) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::error!("Failed to serialize notification {:?}: {}", event, e);
+            return;
+        }
+    };
+
+    let mut redis_conn = redis.clone();
+    for user_id in user_ids {
+        let result: redis::RedisResult<usize> =
+            redis_conn.publish(channel_for(*user_id), &payload).await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to publish notification to user {}: {}", user_id, e);
+        }
+    }
+}
+
+// ========== Emails de partage ==========
+
+/// Envoi d'un email à la réception d'un partage (colonne `notification_settings.share_email_mode`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareEmailMode {
+    /// Un email par partage (par défaut)
+    Instant,
+    /// Un résumé des partages en attente reçus dans la journée
+    Daily,
+    Off,
+}
+
+impl ShareEmailMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ShareEmailMode::Instant => "instant",
+            ShareEmailMode::Daily => "daily",
+            ShareEmailMode::Off => "off",
+        }
+    }
+}
+
+impl std::str::FromStr for ShareEmailMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "instant" => Ok(ShareEmailMode::Instant),
+            "daily" => Ok(ShareEmailMode::Daily),
+            "off" => Ok(ShareEmailMode::Off),
+            other => Err(format!("Invalid share email mode: {}", other)),
+        }
+    }
+}
+
+// Stocké en TEXT, comme AccessLevel
+impl sqlx::Type<Postgres> for ShareEmailMode {
+    fn type_info() -> PgTypeInfo {
+        <&str as sqlx::Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl sqlx::Encode<'_, Postgres> for ShareEmailMode {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        <&str as sqlx::Encode<Postgres>>::encode(self.as_str(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Postgres> for ShareEmailMode {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let mode = <&str as sqlx::Decode<Postgres>>::decode(value)?;
+        Ok(mode.parse()?)
+    }
+}
+
+/// Nom affiché pour l'auteur d'un partage (inconnu si son compte a été supprimé)
+fn sender_name(username: Option<&str>) -> &str {
+    username.unwrap_or("Un utilisateur")
+}
+
+/// Libellé d'éléments partagés ("un dossier", "3 fichiers"...)
+fn items_label(item_type: &str, count: usize) -> String {
+    let (one, many) = if item_type == "folder" {
+        ("un dossier", "dossiers")
+    } else {
+        ("un fichier", "fichiers")
+    };
+    if count == 1 {
+        one.to_string()
+    } else {
+        format!("{} {}", count, many)
+    }
+}
+
+/// Lignes du résumé quotidien : une par auteur, dans l'ordre des partages, avec le nombre de
+/// dossiers et de fichiers partagés. Les noms des éléments sont chiffrés et n'y figurent pas.
+pub fn summarize_pending_shares(shares: &[PendingShare]) -> Vec<String> {
+    let mut senders: Vec<(Option<&str>, usize, usize)> = Vec::new();
+    for share in shares {
+        let username = share.shared_by_username.as_deref();
+        let index = match senders.iter().position(|(name, _, _)| *name == username) {
+            Some(index) => index,
+            None => {
+                senders.push((username, 0, 0));
+                senders.len() - 1
+            }
+        };
+        if share.item_type == "folder" {
+            senders[index].1 += 1;
+        } else {
+            senders[index].2 += 1;
+        }
+    }
+
+    senders
+        .into_iter()
+        .map(|(username, folders, files)| {
+            let items = match (folders, files) {
+                (0, files) => items_label("file", files),
+                (folders, 0) => items_label("folder", folders),
+                (folders, files) => format!(
+                    "{} et {}",
+                    items_label("folder", folders),
+                    items_label("file", files)
+                ),
+            };
+            format!("{} a partagé {} avec vous", sender_name(username), items)
+        })
+        .collect()
+}
+
+/// Prévient le destinataire d'un partage par email, selon ses préférences (mode immédiat).
+/// Best effort et sans bloquer la requête : l'envoi SMTP est fait sur un thread dédié.
+pub async fn email_share_received(
+    state: &AppState,
+    recipient_id: Uuid,
+    from_user_id: Uuid,
+    item_type: &str,
+) {
+    let recipient =
+        match repo::get_share_email_recipient(&state.db_pool, recipient_id, from_user_id).await {
+            Ok(Some(recipient)) => recipient,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!(
+                    "Failed to load share email settings of user {}: {}",
+                    recipient_id,
+                    e
+                );
+                return;
+            }
+        };
+    if recipient.share_email_mode != ShareEmailMode::Instant {
+        return;
+    }
+
+    let mailer = state.mailer.clone();
+    let item_type = item_type.to_string();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = send_share_received_mail(
+            &mailer,
+            &recipient.email,
+            recipient.sender_username.as_deref(),
+            &item_type,
+        ) {
+            tracing::error!(
+                "Failed to send share received email to user {}: {}",
+                recipient_id,
+                e
+            );
+        }
+    });
+}
+
+/// Email envoyé à la réception d'un partage
+pub fn send_share_received_mail(
+    mailer: &SmtpTransport,
+    email: &str,
+    sender_username: Option<&str>,
+    item_type: &str,
+) -> Result<(), String> {
+    let line = format!(
+        "{} a partagé {} avec vous",
+        sender_name(sender_username),
+        items_label(item_type, 1)
+    );
+    let plain_body = format!(
+        "Bonjour,\n\n{}. Connectez-vous à Gauzian pour l'accepter ou le refuser depuis vos partages en attente.\n\nVous pouvez désactiver ces emails ou recevoir un résumé quotidien dans vos paramètres de notification.\n\nL'équipe Gauzian",
+        line
+    );
+
+    let html_body = crate::mail::html_layout(
+        "Nouveau partage sur Gauzian",
+        &format!(
+            "<p style=\"margin:0 0 12px 0;font-size:15px;line-height:1.6;\">Bonjour,</p>
+            <p style=\"margin:0 0 20px 0;font-size:15px;line-height:1.6;\">{}. Connectez-vous à Gauzian pour l'accepter ou le refuser depuis vos partages en attente.</p>
+            <p style=\"margin:0 0 20px 0;font-size:14px;line-height:1.6;color:#4b5563;\">Vous pouvez désactiver ces emails ou recevoir un résumé quotidien dans vos paramètres de notification.</p>",
+            crate::mail::escape_html(&line)
+        ),
+    );
+
+    crate::mail::send_mail(
+        mailer,
+        email,
+        "Nouveau partage sur Gauzian",
+        plain_body,
+        html_body,
+    )
+}
+
+/// Résumé quotidien des partages en attente reçus depuis le dernier résumé
+pub fn send_share_digest_mail(
+    mailer: &SmtpTransport,
+    email: &str,
+    lines: &[String],
+) -> Result<(), String> {
+    let plain_body = format!(
+        "Bonjour,\n\nVoici les partages reçus depuis votre dernier résumé :\n\n{}\n\nConnectez-vous à Gauzian pour les accepter ou les refuser depuis vos partages en attente.\n\nL'équipe Gauzian",
+        lines
+            .iter()
+            .map(|line| format!("- {}", line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+
+    let html_body = crate::mail::html_layout(
+        "Vos partages du jour sur Gauzian",
+        &format!(
+            "<p style=\"margin:0 0 12px 0;font-size:15px;line-height:1.6;\">Bonjour,</p>
+            <p style=\"margin:0 0 12px 0;font-size:15px;line-height:1.6;\">Voici les partages reçus depuis votre dernier résumé :</p>
+            <ul style=\"margin:0 0 20px 0;padding-left:20px;font-size:15px;line-height:1.6;\">{}</ul>
+            <p style=\"margin:0 0 20px 0;font-size:14px;line-height:1.6;color:#4b5563;\">Connectez-vous à Gauzian pour les accepter ou les refuser depuis vos partages en attente.</p>",
+            lines
+                .iter()
+                .map(|line| format!("<li>{}</li>", crate::mail::escape_html(line)))
+                .collect::<String>()
+        ),
+    );
+
+    crate::mail::send_mail(
+        mailer,
+        email,
+        "Vos partages du jour sur Gauzian",
+        plain_body,
+        html_body,
+    )
+}
@@ -94,6 +94,36 @@ impl ApiResponse<ErrorResponse> {
         }
     }
 
+    pub fn payment_required(message: impl Into<String>) -> Self {
+        Self {
+            data: ErrorResponse {
+                error: message.into(),
+            },
+            token: None,
+            status: StatusCode::PAYMENT_REQUIRED,
+        }
+    }
+
+    pub fn service_unavailable(message: impl Into<String>) -> Self {
+        Self {
+            data: ErrorResponse {
+                error: message.into(),
+            },
+            token: None,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    pub fn payload_too_large(message: impl Into<String>) -> Self {
+        Self {
+            data: ErrorResponse {
+                error: message.into(),
+            },
+            token: None,
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+
     pub fn insufficient_storage(message: impl Into<String>) -> Self {
         Self {
             data: ErrorResponse {
@@ -1,12 +1,15 @@
 use axum::{Router, middleware, routing::get};
 use tower_http::trace::TraceLayer;
 
-use crate::{agenda, auth, drive, metrics, state::AppState};
+use crate::{
+    activity, admin, agenda, auth, billing, contacts, drive, export, metrics, notifications,
+    state::AppState, teams,
+};
 
 /// Comparaison en temps constant pour éviter les timing attacks.
 /// Retourne false immédiatement si les longueurs diffèrent (pas d'information
 /// sur le contenu), puis compare octet par octet en accumulant les différences.
-fn constant_time_eq(a: &str, b: &str) -> bool {
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
     if a.len() != b.len() {
         return false;
     }
@@ -24,8 +27,15 @@ pub fn app(state: AppState) -> Router {
         .route("/metrics", get(metrics_handler))
         // Composition des modules
         .merge(auth::auth_routes())
+        .merge(export::export_routes())
+        .merge(billing::billing_routes())
+        .merge(activity::activity_routes())
+        .merge(contacts::contacts_routes())
+        .merge(teams::teams_routes())
         .nest("/drive", drive::drive_routes())
         .nest("/agenda", agenda::agenda_routes())
+        .nest("/admin", admin::admin_routes())
+        .nest("/notifications", notifications::notifications_routes())
         // Middlewares globaux
         .layer(middleware::from_fn(metrics::track_metrics))
         .layer(TraceLayer::new_for_http())
@@ -1,8 +1,10 @@
+use crate::billing::services::{PaymentProvider, payment_provider_from_env};
+use crate::notifications::NotificationHub;
 use crate::storage::StorageClient;
+use crate::upload_limiter::UploadLimiter;
 use redis::aio::ConnectionManager;
 use sqlx::PgPool;
 use std::sync::Arc;
-use tokio::sync::Semaphore;
 
 use lettre::SmtpTransport;
 use lettre::transport::smtp::authentication::Credentials;
@@ -13,9 +15,13 @@ pub struct AppState {
     pub redis_manager: ConnectionManager, // ConnectionManager au lieu de Client
     pub db_pool: PgPool,
     pub storage_client: StorageClient,
-    // Limite le nombre d'uploads concurrents pour éviter la saturation RAM
-    pub upload_semaphore: Arc<Semaphore>,
+    // Limite le nombre d'uploads concurrents (global et par utilisateur) pour éviter la saturation RAM
+    pub upload_limiter: Arc<UploadLimiter>,
     pub mailer: SmtpTransport,
+    // Prestataire de paiement des changements d'offre (PAYMENT_PROVIDER)
+    pub payment_provider: Arc<dyn PaymentProvider>,
+    // Flux de notifications temps réel ouverts sur cette réplica (alimentés par Redis pub/sub)
+    pub notifications: Arc<NotificationHub>,
 }
 
 impl AppState {
@@ -27,23 +33,22 @@ impl AppState {
         let redis_client = redis::Client::open(redis_url).expect("Invalid REDIS_URL");
 
         // Utiliser ConnectionManager pour pooler les connexions Redis automatiquement
-        let redis_manager = ConnectionManager::new(redis_client)
+        let redis_manager = ConnectionManager::new(redis_client.clone())
             .await
             .expect("Failed to create Redis ConnectionManager");
 
         tracing::info!("Redis ConnectionManager initialized");
 
+        // Le pub/sub nécessite une connexion dédiée, ouverte par le hub
+        let notifications = Arc::new(NotificationHub::new(redis_client));
+
         let s3_bucket = std::env::var("S3_BUCKET").unwrap_or_else(|_| "gauzian".to_string());
         let storage_client = StorageClient::new(s3_bucket)
             .await
             .expect("Failed to initialize S3 client");
 
-        // Limite à 50 uploads concurrents (ajustable via env var)
-        let max_concurrent_uploads = std::env::var("MAX_CONCURRENT_UPLOADS")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(50);
-        tracing::info!("Max concurrent uploads: {}", max_concurrent_uploads);
+        // Limite à 50 uploads concurrents, 10 par utilisateur (ajustable via env var)
+        let upload_limiter = Arc::new(UploadLimiter::from_env());
 
         // mail
         let creds = Credentials::new(
@@ -56,13 +61,18 @@ impl AppState {
             .build();
         tracing::info!("SMTP transport initialized");
 
+        let payment_provider = payment_provider_from_env();
+        tracing::info!("Payment provider: {}", payment_provider.name());
+
         Self {
             jwt_secret,
             redis_manager,
             db_pool,
             storage_client,
-            upload_semaphore: Arc::new(Semaphore::new(max_concurrent_uploads)),
+            upload_limiter,
             mailer,
+            payment_provider,
+            notifications,
         }
     }
 }
@@ -1,9 +1,15 @@
 use aws_credential_types::Credentials;
-use aws_sdk_s3::{Client, config::Region};
-use bytes::Bytes;
+use aws_sdk_s3::{
+    Client,
+    config::Region,
+    types::{CompletedMultipartUpload, CompletedPart},
+};
+use bytes::{Bytes, BytesMut};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
 /// Métadonnées pour une ligne de données stockée
@@ -23,6 +29,9 @@ pub enum StorageError {
     JsonError(String),
     NotFound,
     DataValidationError(String),
+    /// Données plus grandes que la taille maximale acceptée
+    TooLarge,
+    IoError(String),
 }
 
 impl std::fmt::Display for StorageError {
@@ -32,6 +41,8 @@ impl std::fmt::Display for StorageError {
             StorageError::JsonError(e) => write!(f, "JSON Error: {}", e),
             StorageError::NotFound => write!(f, "Data not found in S3"),
             StorageError::DataValidationError(e) => write!(f, "Validation Error: {}", e),
+            StorageError::TooLarge => write!(f, "Data too large"),
+            StorageError::IoError(e) => write!(f, "IO Error: {}", e),
         }
     }
 }
@@ -91,6 +102,18 @@ impl StorageClient {
         data_encrypted: Bytes,
         index: String,
         iv: String,
+    ) -> Result<StorageMetadata, StorageError> {
+        self.upload_chunk(&SpooledChunk::from_bytes(data_encrypted), index, iv)
+            .await
+    }
+
+    /// Stocke un chunk reçu en streaming (voir `ChunkSpooler`).
+    /// Chaque tentative relit le chunk depuis son spool : rien n'est dupliqué en mémoire.
+    pub async fn upload_chunk(
+        &self,
+        chunk: &SpooledChunk,
+        index: String,
+        iv: String,
     ) -> Result<StorageMetadata, StorageError> {
         let start_time = std::time::Instant::now();
         const MAX_RETRIES: u32 = 5; // Augmenté pour Cellar Clever Cloud
@@ -99,30 +122,26 @@ impl StorageClient {
         let s3_id = Uuid::new_v4().to_string();
         let date_upload = Utc::now().to_rfc3339();
 
-        // Créer les métadonnées
-        let mut hasher = Sha256::new();
-        hasher.update(&data_encrypted);
-        let data_hash = format!("{:x}", hasher.finalize());
-
+        // Créer les métadonnées (hash calculé pendant la réception)
         let metadata = StorageMetadata {
             s3_id: s3_id.clone(),
             index: index.clone(),
             iv: Some(iv.clone()),
             date_upload: date_upload.clone(),
-            data_hash: data_hash.clone(),
+            data_hash: chunk.data_hash().to_string(),
         };
 
         // Stocker les données chiffrées avec retry
         let mut last_error = None;
         for attempt in 1..=MAX_RETRIES {
+            let body = chunk.body().await?;
             match self
                 .client
                 .put_object()
                 .bucket(&self.bucket)
                 .key(&s3_id)
-                .body(aws_sdk_s3::primitives::ByteStream::from(
-                    data_encrypted.clone(),
-                ))
+                .body(body)
+                .content_length(chunk.len() as i64)
                 .metadata("index", &index)
                 .metadata("date-upload", &date_upload)
                 .metadata("data-hash", &metadata.data_hash)
@@ -146,7 +165,7 @@ impl StorageClient {
                             "Data uploaded to S3: s3_id={}, index={}, size={}",
                             s3_id,
                             index,
-                            data_encrypted.len()
+                            chunk.len()
                         );
                     }
                     return Ok(metadata);
@@ -424,6 +443,74 @@ impl StorageClient {
         Ok(())
     }
 
+    /// Démarre un upload multipart vers `key` (archives volumineuses produites en streaming)
+    pub async fn start_multipart_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> Result<MultipartUpload, StorageError> {
+        let resp = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .send()
+            .await
+            .map_err(|e| {
+                StorageError::S3Error(format!("Failed to start multipart upload: {}", e))
+            })?;
+
+        let upload_id = resp
+            .upload_id()
+            .ok_or_else(|| StorageError::S3Error("Missing multipart upload id".to_string()))?
+            .to_string();
+
+        Ok(MultipartUpload {
+            client: self.client.clone(),
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            upload_id,
+            parts: Vec::new(),
+            buffer: BytesMut::with_capacity(MULTIPART_PART_SIZE),
+            total_size: 0,
+        })
+    }
+
+    /// Télécharge un objet en streaming (sans le charger entièrement en mémoire).
+    /// Retourne la taille annoncée par S3 et le flux d'octets.
+    pub async fn download_stream(
+        &self,
+        key: &str,
+    ) -> Result<(Option<i64>, aws_sdk_s3::primitives::ByteStream), StorageError> {
+        let start_time = std::time::Instant::now();
+
+        let result = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await;
+
+        let duration = start_time.elapsed().as_secs_f64();
+        crate::metrics::track_s3_operation("get", duration);
+
+        match result {
+            Ok(resp) => Ok((resp.content_length(), resp.body)),
+            Err(e) => {
+                let err_str = e.to_string();
+                if err_str.contains("NoSuchKey") {
+                    return Err(StorageError::NotFound);
+                }
+                Err(StorageError::S3Error(format!(
+                    "Failed to download stream: {}",
+                    err_str
+                )))
+            }
+        }
+    }
+
     /// Health check for readiness probe - verifies S3 connectivity
     pub async fn health_check(&self) -> Result<(), StorageError> {
         self.client
@@ -436,6 +523,291 @@ impl StorageClient {
     }
 }
 
+/// Taille des parts d'un upload multipart (minimum S3 : 5 Mo, maximum 10 000 parts)
+const MULTIPART_PART_SIZE: usize = 32 * 1024 * 1024;
+
+/// Upload multipart en cours : les données sont bufferisées puis envoyées par parts
+pub struct MultipartUpload {
+    client: Client,
+    bucket: String,
+    key: String,
+    upload_id: String,
+    parts: Vec<CompletedPart>,
+    buffer: BytesMut,
+    total_size: u64,
+}
+
+impl MultipartUpload {
+    /// Ajoute des données à l'upload (envoie une part dès que le buffer est plein)
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), StorageError> {
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            let available = MULTIPART_PART_SIZE - self.buffer.len();
+            let take = available.min(remaining.len());
+            self.buffer.extend_from_slice(&remaining[..take]);
+            remaining = &remaining[take..];
+
+            if self.buffer.len() == MULTIPART_PART_SIZE {
+                self.flush_part().await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush_part(&mut self) -> Result<(), StorageError> {
+        const MAX_RETRIES: u32 = 3;
+        const RETRY_DELAY_MS: u64 = 1000;
+
+        let start_time = std::time::Instant::now();
+        let part_number = self.parts.len() as i32 + 1;
+        let body = self.buffer.split().freeze();
+
+        let mut last_error = None;
+        for attempt in 1..=MAX_RETRIES {
+            match self
+                .client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .upload_id(&self.upload_id)
+                .part_number(part_number)
+                .body(aws_sdk_s3::primitives::ByteStream::from(body.clone()))
+                .send()
+                .await
+            {
+                Ok(resp) => {
+                    crate::metrics::track_s3_operation("put", start_time.elapsed().as_secs_f64());
+                    self.total_size += body.len() as u64;
+                    self.parts.push(
+                        CompletedPart::builder()
+                            .part_number(part_number)
+                            .set_e_tag(resp.e_tag().map(|t| t.to_string()))
+                            .build(),
+                    );
+                    return Ok(());
+                }
+                Err(e) => {
+                    last_error = Some(e.to_string());
+                    if attempt < MAX_RETRIES {
+                        let delay = RETRY_DELAY_MS * (1 << (attempt - 1));
+                        tracing::warn!(
+                            "S3 part upload failed (attempt {}/{}), retrying in {}ms: {}",
+                            attempt,
+                            MAX_RETRIES,
+                            delay,
+                            last_error.as_deref().unwrap_or_default()
+                        );
+                        tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                    }
+                }
+            }
+        }
+
+        Err(StorageError::S3Error(format!(
+            "Failed to upload part {} after {} retries: {}",
+            part_number,
+            MAX_RETRIES,
+            last_error.unwrap_or_default()
+        )))
+    }
+
+    /// Envoie la dernière part et finalise l'objet. Retourne sa taille totale.
+    pub async fn complete(mut self) -> Result<u64, StorageError> {
+        if !self.buffer.is_empty() || self.parts.is_empty() {
+            self.flush_part().await?;
+        }
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(std::mem::take(&mut self.parts)))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| {
+                StorageError::S3Error(format!("Failed to complete multipart upload: {}", e))
+            })?;
+
+        tracing::info!(
+            "Multipart upload completed: key={}, size={}",
+            self.key,
+            self.total_size
+        );
+        Ok(self.total_size)
+    }
+
+    /// Abandonne l'upload (libère les parts déjà envoyées côté S3)
+    pub async fn abort(self) {
+        if let Err(e) = self
+            .client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .send()
+            .await
+        {
+            tracing::warn!("Failed to abort multipart upload {}: {}", self.key, e);
+        }
+    }
+}
+
+// ==================== Chunks reçus en streaming ====================
+
+/// Taille au-delà de laquelle un chunk en cours de réception est écrit sur disque
+const SPOOL_MEMORY_THRESHOLD: usize = 256 * 1024;
+
+/// Fichier temporaire supprimé quand il n'est plus utilisé (upload terminé ou abandonné)
+struct SpoolFile(PathBuf);
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!("Failed to remove spool file {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+enum SpoolBuffer {
+    Memory(BytesMut),
+    File(SpoolFile, tokio::fs::File),
+}
+
+/// Reçoit un chunk par morceaux : le hash SHA-256 est calculé au fil de l'eau et les données
+/// restent en mémoire jusqu'à SPOOL_MEMORY_THRESHOLD, au-delà elles sont écrites dans un
+/// fichier temporaire. La mémoire consommée par upload reste bornée quelle que soit la taille du chunk.
+pub struct ChunkSpooler {
+    buffer: SpoolBuffer,
+    hasher: Sha256,
+    len: u64,
+    max_len: u64,
+}
+
+impl ChunkSpooler {
+    /// `max_len` : taille maximale acceptée (au-delà, `write` renvoie `StorageError::TooLarge`)
+    pub fn new(max_len: u64) -> Self {
+        Self {
+            buffer: SpoolBuffer::Memory(BytesMut::new()),
+            hasher: Sha256::new(),
+            len: 0,
+            max_len,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Ajoute un morceau reçu
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), StorageError> {
+        if self.len + data.len() as u64 > self.max_len {
+            return Err(StorageError::TooLarge);
+        }
+        self.hasher.update(data);
+        self.len += data.len() as u64;
+
+        match &mut self.buffer {
+            SpoolBuffer::Memory(buf) if buf.len() + data.len() <= SPOOL_MEMORY_THRESHOLD => {
+                buf.extend_from_slice(data);
+            }
+            SpoolBuffer::Memory(buf) => {
+                // Seuil dépassé : bascule sur disque avec ce qui a déjà été reçu
+                let path = std::env::temp_dir().join(format!("gauzian-chunk-{}", Uuid::new_v4()));
+                let spool = SpoolFile(path);
+                let mut file = tokio::fs::File::create(&spool.0).await.map_err(io_error)?;
+                file.write_all(buf).await.map_err(io_error)?;
+                file.write_all(data).await.map_err(io_error)?;
+                self.buffer = SpoolBuffer::File(spool, file);
+            }
+            SpoolBuffer::File(_, file) => {
+                file.write_all(data).await.map_err(io_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Termine la réception : le chunk est prêt à être envoyé (autant de fois que nécessaire)
+    pub async fn finish(self) -> Result<SpooledChunk, StorageError> {
+        let data_hash = format!("{:x}", self.hasher.finalize());
+        let body = match self.buffer {
+            SpoolBuffer::Memory(buf) => SpooledBody::Memory(buf.freeze()),
+            SpoolBuffer::File(spool, mut file) => {
+                file.flush().await.map_err(io_error)?;
+                SpooledBody::File(spool)
+            }
+        };
+
+        Ok(SpooledChunk {
+            body,
+            data_hash,
+            len: self.len,
+        })
+    }
+}
+
+enum SpooledBody {
+    Memory(Bytes),
+    File(SpoolFile),
+}
+
+/// Chunk entièrement reçu, avec son hash SHA-256
+pub struct SpooledChunk {
+    body: SpooledBody,
+    data_hash: String,
+    len: u64,
+}
+
+impl SpooledChunk {
+    /// Chunk déjà en mémoire (endpoint JSON base64)
+    pub fn from_bytes(data: Bytes) -> Self {
+        let data_hash = format!("{:x}", Sha256::digest(&data));
+        Self {
+            len: data.len() as u64,
+            body: SpooledBody::Memory(data),
+            data_hash,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn data_hash(&self) -> &str {
+        &self.data_hash
+    }
+
+    /// Corps de requête S3 : relu depuis le disque si le chunk y a été écrit
+    pub async fn body(&self) -> Result<aws_sdk_s3::primitives::ByteStream, StorageError> {
+        match &self.body {
+            SpooledBody::Memory(bytes) => {
+                Ok(aws_sdk_s3::primitives::ByteStream::from(bytes.clone()))
+            }
+            SpooledBody::File(spool) => aws_sdk_s3::primitives::ByteStream::from_path(&spool.0)
+                .await
+                .map_err(|e| StorageError::IoError(format!("Failed to read spool file: {}", e))),
+        }
+    }
+}
+
+fn io_error(e: std::io::Error) -> StorageError {
+    StorageError::IoError(e.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -453,4 +825,57 @@ mod tests {
         assert_eq!(metadata.s3_id, "test-id");
         assert_eq!(metadata.index, "file-1");
     }
+
+    #[tokio::test]
+    async fn test_chunk_spooler_keeps_small_chunks_in_memory() {
+        let mut spooler = ChunkSpooler::new(1024);
+        spooler.write(b"hello ").await.unwrap();
+        spooler.write(b"world").await.unwrap();
+        let chunk = spooler.finish().await.unwrap();
+
+        assert_eq!(chunk.len(), 11);
+        assert!(matches!(chunk.body, SpooledBody::Memory(_)));
+        assert_eq!(
+            chunk.data_hash(),
+            format!("{:x}", Sha256::digest(b"hello world"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chunk_spooler_spills_large_chunks_to_disk() {
+        let data = vec![7u8; SPOOL_MEMORY_THRESHOLD + 1];
+        let mut spooler = ChunkSpooler::new(data.len() as u64);
+        for part in data.chunks(4096) {
+            spooler.write(part).await.unwrap();
+        }
+        let chunk = spooler.finish().await.unwrap();
+
+        let path = match &chunk.body {
+            SpooledBody::File(spool) => spool.0.clone(),
+            SpooledBody::Memory(_) => panic!("chunk should be spooled to disk"),
+        };
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert_eq!(chunk.data_hash(), format!("{:x}", Sha256::digest(&data)));
+
+        // Le fichier temporaire disparaît avec le chunk
+        drop(chunk);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_chunk_spooler_rejects_data_over_max_len() {
+        let mut spooler = ChunkSpooler::new(4);
+        spooler.write(b"abc").await.unwrap();
+        assert!(matches!(
+            spooler.write(b"de").await,
+            Err(StorageError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_spooled_chunk_from_bytes_hashes_data() {
+        let chunk = SpooledChunk::from_bytes(Bytes::from_static(b"data"));
+        assert_eq!(chunk.len(), 4);
+        assert_eq!(chunk.data_hash(), format!("{:x}", Sha256::digest(b"data")));
+    }
 }
@@ -0,0 +1,248 @@
+// Handlers HTTP des équipes
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{
+    repo::{self, NewTeam, NewTeamMember},
+    services::{self, TeamRole},
+};
+use crate::{
+    auth::Claims,
+    notifications::services::{self as notifications, NotificationEvent},
+    response::ApiResponse,
+    state::AppState,
+};
+
+const TEAM_NOT_FOUND: &str = "Team not found";
+const ALREADY_A_MEMBER: &str = "User is already a team member";
+
+#[derive(Deserialize)]
+pub struct CreateTeamRequest {
+    pub name: String,
+    pub public_key: String,
+    /// Clé privée de l'équipe chiffrée avec la clé publique du créateur
+    pub encrypted_private_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct RenameTeamRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct AddMemberRequest {
+    pub user_id: Uuid,
+    #[serde(default = "default_role")]
+    pub role: TeamRole,
+    /// Clé privée de l'équipe chiffrée avec la clé publique du nouveau membre
+    pub encrypted_private_key: String,
+}
+
+fn default_role() -> TeamRole {
+    TeamRole::Member
+}
+
+#[derive(Deserialize)]
+pub struct SetRoleRequest {
+    pub role: TeamRole,
+}
+
+#[derive(Deserialize)]
+pub struct TeamKeyRequest {
+    pub encrypted_private_key: String,
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505"))
+}
+
+/// Erreurs communes : 404 pour un non-membre, 403 pour une action réservée aux admins,
+/// 409 pour le retrait du dernier admin
+fn team_error(e: sqlx::Error, context: &str) -> Response {
+    match e {
+        sqlx::Error::RowNotFound => ApiResponse::not_found(TEAM_NOT_FOUND).into_response(),
+        sqlx::Error::Protocol(msg) if msg == repo::TEAM_ADMIN_REQUIRED => {
+            ApiResponse::forbidden(msg).into_response()
+        }
+        sqlx::Error::Protocol(msg) if msg == repo::LAST_TEAM_ADMIN => {
+            ApiResponse::conflict(msg).into_response()
+        }
+        sqlx::Error::Protocol(msg) => ApiResponse::bad_request(msg).into_response(),
+        e => {
+            tracing::error!("{}: {:?}", context, e);
+            ApiResponse::internal_error(context).into_response()
+        }
+    }
+}
+
+// ========== Équipes ==========
+
+/// GET /teams - Équipes de l'utilisateur, avec la clé privée de chacune chiffrée pour lui
+pub async fn list_teams_handler(State(state): State<AppState>, claims: Claims) -> Response {
+    match repo::list_teams(&state.db_pool, claims.id).await {
+        Ok(teams) => ApiResponse::ok(teams).into_response(),
+        Err(e) => team_error(e, "Failed to retrieve teams"),
+    }
+}
+
+/// POST /teams - Crée une équipe (paire de clés générée par le client)
+pub async fn create_team_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(body): Json<CreateTeamRequest>,
+) -> Response {
+    let name = match services::normalize_team_name(&body.name) {
+        Ok(name) => name,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+    if body.public_key.trim().is_empty() || body.encrypted_private_key.trim().is_empty() {
+        return ApiResponse::bad_request(services::TEAM_KEY_REQUIRED).into_response();
+    }
+
+    let team = NewTeam {
+        name: &name,
+        public_key: &body.public_key,
+        encrypted_private_key: &body.encrypted_private_key,
+    };
+    match repo::create_team(&state.db_pool, claims.id, team).await {
+        Ok(team_id) => ApiResponse::ok(serde_json::json!({ "team_id": team_id })).into_response(),
+        Err(e) => team_error(e, "Failed to create team"),
+    }
+}
+
+/// GET /teams/{team_id} - Équipe et membres
+pub async fn get_team_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(team_id): Path<Uuid>,
+) -> Response {
+    let team = match repo::get_team(&state.db_pool, claims.id, team_id).await {
+        Ok(team) => team,
+        Err(e) => return team_error(e, "Failed to retrieve team"),
+    };
+    match repo::list_members(&state.db_pool, claims.id, team_id).await {
+        Ok(members) => {
+            ApiResponse::ok(serde_json::json!({ "team": team, "members": members })).into_response()
+        }
+        Err(e) => team_error(e, "Failed to retrieve team"),
+    }
+}
+
+/// PATCH /teams/{team_id} - Renomme l'équipe (admins)
+pub async fn rename_team_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(team_id): Path<Uuid>,
+    Json(body): Json<RenameTeamRequest>,
+) -> Response {
+    let name = match services::normalize_team_name(&body.name) {
+        Ok(name) => name,
+        Err(msg) => return ApiResponse::bad_request(msg).into_response(),
+    };
+
+    match repo::rename_team(&state.db_pool, claims.id, team_id, &name).await {
+        Ok(()) => ApiResponse::ok("Team renamed").into_response(),
+        Err(e) => team_error(e, "Failed to rename team"),
+    }
+}
+
+/// DELETE /teams/{team_id} - Supprime l'équipe et ses partages (admins)
+pub async fn delete_team_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(team_id): Path<Uuid>,
+) -> Response {
+    match repo::delete_team(&state.db_pool, claims.id, team_id).await {
+        Ok(()) => ApiResponse::ok("Team deleted").into_response(),
+        Err(e) => team_error(e, "Failed to delete team"),
+    }
+}
+
+/// PUT /teams/{team_id}/key - Rechiffre la clé de l'équipe pour soi (après un changement de clé publique)
+pub async fn update_own_team_key_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(team_id): Path<Uuid>,
+    Json(body): Json<TeamKeyRequest>,
+) -> Response {
+    if body.encrypted_private_key.trim().is_empty() {
+        return ApiResponse::bad_request(services::TEAM_KEY_REQUIRED).into_response();
+    }
+
+    match repo::update_own_team_key(
+        &state.db_pool,
+        claims.id,
+        team_id,
+        &body.encrypted_private_key,
+    )
+    .await
+    {
+        Ok(()) => ApiResponse::ok("Team key updated").into_response(),
+        Err(e) => team_error(e, "Failed to update team key"),
+    }
+}
+
+// ========== Membres ==========
+
+/// POST /teams/{team_id}/members - Ajoute un contact à l'équipe (admins)
+pub async fn add_member_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(team_id): Path<Uuid>,
+    Json(body): Json<AddMemberRequest>,
+) -> Response {
+    if body.encrypted_private_key.trim().is_empty() {
+        return ApiResponse::bad_request(services::TEAM_KEY_REQUIRED).into_response();
+    }
+
+    let member = NewTeamMember {
+        user_id: body.user_id,
+        role: body.role,
+        encrypted_private_key: &body.encrypted_private_key,
+    };
+    match repo::add_member(&state.db_pool, claims.id, team_id, member).await {
+        Ok(()) => {
+            let event = NotificationEvent::TeamJoined {
+                team_id,
+                added_by: claims.id,
+            };
+            notifications::publish(&state.redis_manager, &[body.user_id], &event).await;
+            ApiResponse::ok("Member added").into_response()
+        }
+        Err(e) if is_unique_violation(&e) => {
+            ApiResponse::conflict(ALREADY_A_MEMBER).into_response()
+        }
+        Err(e) => team_error(e, "Failed to add team member"),
+    }
+}
+
+/// PATCH /teams/{team_id}/members/{user_id} - Change le rôle d'un membre (admins)
+pub async fn set_member_role_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((team_id, user_id)): Path<(Uuid, Uuid)>,
+    Json(body): Json<SetRoleRequest>,
+) -> Response {
+    match repo::set_member_role(&state.db_pool, claims.id, team_id, user_id, body.role).await {
+        Ok(()) => ApiResponse::ok("Member role updated").into_response(),
+        Err(e) => team_error(e, "Failed to update team member"),
+    }
+}
+
+/// DELETE /teams/{team_id}/members/{user_id} - Retire un membre (admins) ou quitte l'équipe
+pub async fn remove_member_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((team_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+    match repo::remove_member(&state.db_pool, claims.id, team_id, user_id).await {
+        Ok(()) => ApiResponse::ok("Member removed").into_response(),
+        Err(e) => team_error(e, "Failed to remove team member"),
+    }
+}
@@ -0,0 +1,11 @@
+// Module teams - Équipes avec paire de clés partagée et propriété partagée
+// Un dossier partagé à l'équipe est accessible à tous ses membres : ajouter ou retirer un
+// membre ne rechiffre que la clé privée de l'équipe
+
+pub mod handlers;
+pub mod repo;
+pub mod routes;
+pub mod services;
+
+// Re-exports
+pub use routes::teams_routes;
@@ -0,0 +1,414 @@
+// Repository - Équipes (tables `teams`, `team_members`)
+// Les accès aux dossiers partagés à une équipe sont gérés par drive::repo
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::services::TeamRole;
+use crate::drive::repo as drive_repo;
+
+/// Erreur (`sqlx::Error::Protocol`) quand l'action est réservée aux admins de l'équipe
+pub const TEAM_ADMIN_REQUIRED: &str = "Only team admins can perform this action";
+
+/// Erreur (`sqlx::Error::Protocol`) quand le changement laisserait l'équipe sans admin
+pub const LAST_TEAM_ADMIN: &str = "A team must keep at least one admin";
+
+/// Erreur (`sqlx::Error::Protocol`) quand l'utilisateur ajouté n'est pas un contact de l'admin
+pub const MEMBER_NOT_A_CONTACT: &str = "Team members must be added from your contacts";
+
+/// Équipe vue par l'un de ses membres, avec la clé privée de l'équipe chiffrée pour lui
+#[derive(Debug, FromRow, Serialize)]
+pub struct TeamRecord {
+    pub id: Uuid,
+    pub name: String,
+    pub public_key: String,
+    pub role: TeamRole,
+    pub encrypted_private_key: String,
+    pub member_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct TeamMemberRecord {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub public_key: String,
+    pub role: TeamRole,
+    pub added_at: DateTime<Utc>,
+}
+
+/// Équipe à créer : la paire de clés est générée par le client
+#[derive(Debug, Clone, Copy)]
+pub struct NewTeam<'a> {
+    pub name: &'a str,
+    pub public_key: &'a str,
+    /// Clé privée de l'équipe chiffrée avec la clé publique du créateur
+    pub encrypted_private_key: &'a str,
+}
+
+/// Membre à ajouter, avec la clé privée de l'équipe chiffrée pour lui
+#[derive(Debug, Clone, Copy)]
+pub struct NewTeamMember<'a> {
+    pub user_id: Uuid,
+    pub role: TeamRole,
+    pub encrypted_private_key: &'a str,
+}
+
+// ========== Contrôles ==========
+
+/// Rôle de `user_id` dans l'équipe (RowNotFound s'il n'en est pas membre)
+async fn member_role(
+    conn: &mut PgConnection,
+    team_id: Uuid,
+    user_id: Uuid,
+) -> Result<TeamRole, sqlx::Error> {
+    sqlx::query_scalar("SELECT role FROM team_members WHERE team_id = $1 AND user_id = $2")
+        .bind(team_id)
+        .bind(user_id)
+        .fetch_one(&mut *conn)
+        .await
+}
+
+/// Vérifie que `user_id` est admin de l'équipe. L'équipe est verrouillée jusqu'à la fin de la
+/// transaction : les changements de membres concurrents ne peuvent pas retirer le dernier admin.
+async fn ensure_admin(
+    conn: &mut PgConnection,
+    team_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    lock_team(conn, team_id).await?;
+    match member_role(conn, team_id, user_id).await? {
+        TeamRole::Admin => Ok(()),
+        TeamRole::Member => Err(sqlx::Error::Protocol(TEAM_ADMIN_REQUIRED.into())),
+    }
+}
+
+async fn lock_team(conn: &mut PgConnection, team_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT 1 FROM teams WHERE id = $1 FOR UPDATE")
+        .bind(team_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Nombre de membres et d'admins restants
+async fn count_members(conn: &mut PgConnection, team_id: Uuid) -> Result<(i64, i64), sqlx::Error> {
+    sqlx::query_as(
+        "SELECT COUNT(*), COUNT(*) FILTER (WHERE role = 'admin') FROM team_members WHERE team_id = $1",
+    )
+    .bind(team_id)
+    .fetch_one(&mut *conn)
+    .await
+}
+
+// ========== Équipes ==========
+
+/// Crée une équipe dont `user_id` est le premier admin
+pub async fn create_team(
+    pool: &PgPool,
+    user_id: Uuid,
+    team: NewTeam<'_>,
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let team_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO teams (name, public_key, created_by) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(team.name)
+    .bind(team.public_key)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO team_members (team_id, user_id, role, encrypted_private_key) VALUES ($1, $2, 'admin', $3)",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .bind(team.encrypted_private_key)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(team_id)
+}
+
+const TEAM_SELECT: &str = r#"
+    SELECT t.id, t.name, t.public_key, m.role, m.encrypted_private_key,
+           (SELECT COUNT(*) FROM team_members c WHERE c.team_id = t.id) AS member_count,
+           t.created_at
+    FROM team_members m
+    JOIN teams t ON t.id = m.team_id
+    WHERE m.user_id = $1
+"#;
+
+/// Équipes dont `user_id` est membre
+pub async fn list_teams(pool: &PgPool, user_id: Uuid) -> Result<Vec<TeamRecord>, sqlx::Error> {
+    sqlx::query_as::<_, TeamRecord>(&format!("{} ORDER BY t.name", TEAM_SELECT))
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// Équipe vue par `user_id` (RowNotFound s'il n'en est pas membre)
+pub async fn get_team(
+    pool: &PgPool,
+    user_id: Uuid,
+    team_id: Uuid,
+) -> Result<TeamRecord, sqlx::Error> {
+    sqlx::query_as::<_, TeamRecord>(&format!("{} AND t.id = $2", TEAM_SELECT))
+        .bind(user_id)
+        .bind(team_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Membres de l'équipe, avec leur clé publique pour rechiffrer la clé de l'équipe
+pub async fn list_members(
+    pool: &PgPool,
+    user_id: Uuid,
+    team_id: Uuid,
+) -> Result<Vec<TeamMemberRecord>, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    member_role(&mut conn, team_id, user_id).await?;
+
+    sqlx::query_as::<_, TeamMemberRecord>(
+        r#"
+        SELECT u.id AS user_id, u.username, u.email, u.public_key, m.role, m.added_at
+        FROM team_members m
+        JOIN users u ON u.id = m.user_id
+        WHERE m.team_id = $1
+        ORDER BY m.added_at
+        "#,
+    )
+    .bind(team_id)
+    .fetch_all(&mut *conn)
+    .await
+}
+
+/// Identifiants des membres de l'équipe (destinataires des notifications)
+pub async fn get_member_ids(pool: &PgPool, team_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar("SELECT user_id FROM team_members WHERE team_id = $1")
+        .bind(team_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn rename_team(
+    pool: &PgPool,
+    user_id: Uuid,
+    team_id: Uuid,
+    name: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    ensure_admin(&mut tx, team_id, user_id).await?;
+
+    sqlx::query("UPDATE teams SET name = $2 WHERE id = $1")
+        .bind(team_id)
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Supprime l'équipe et ses partages. Les membres gardent les accès accordés directement
+/// ou par une autre équipe.
+pub async fn delete_team(pool: &PgPool, user_id: Uuid, team_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    ensure_admin(&mut tx, team_id, user_id).await?;
+    dissolve_team(&mut tx, team_id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn dissolve_team(conn: &mut PgConnection, team_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM team_members WHERE team_id = $1")
+        .bind(team_id)
+        .execute(&mut *conn)
+        .await?;
+    drive_repo::prune_team_access(&mut *conn, team_id).await?;
+
+    sqlx::query("DELETE FROM teams WHERE id = $1")
+        .bind(team_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+// ========== Membres ==========
+
+/// Ajoute un contact de l'admin à l'équipe : il accède aussitôt aux dossiers de l'équipe
+pub async fn add_member(
+    pool: &PgPool,
+    admin_id: Uuid,
+    team_id: Uuid,
+    member: NewTeamMember<'_>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    ensure_admin(&mut tx, team_id, admin_id).await?;
+
+    let is_contact: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM contacts WHERE user_id = $1 AND contact_user_id = $2)",
+    )
+    .bind(admin_id)
+    .bind(member.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if !is_contact {
+        return Err(sqlx::Error::Protocol(MEMBER_NOT_A_CONTACT.into()));
+    }
+
+    sqlx::query(
+        "INSERT INTO team_members (team_id, user_id, role, encrypted_private_key) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(team_id)
+    .bind(member.user_id)
+    .bind(member.role)
+    .bind(member.encrypted_private_key)
+    .execute(&mut *tx)
+    .await?;
+
+    drive_repo::materialize_team_access(&mut tx, Some(team_id), Some(member.user_id), None).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Change le rôle d'un membre ; l'équipe garde au moins un admin
+pub async fn set_member_role(
+    pool: &PgPool,
+    admin_id: Uuid,
+    team_id: Uuid,
+    member_id: Uuid,
+    role: TeamRole,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    ensure_admin(&mut tx, team_id, admin_id).await?;
+
+    let updated =
+        sqlx::query("UPDATE team_members SET role = $3 WHERE team_id = $1 AND user_id = $2")
+            .bind(team_id)
+            .bind(member_id)
+            .bind(role)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    if updated == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    let (_, admins) = count_members(&mut tx, team_id).await?;
+    if admins == 0 {
+        return Err(sqlx::Error::Protocol(LAST_TEAM_ADMIN.into()));
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Remplace la clé privée de l'équipe chiffrée pour `user_id` (après un changement de sa
+/// clé publique)
+pub async fn update_own_team_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    team_id: Uuid,
+    encrypted_private_key: &str,
+) -> Result<(), sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE team_members SET encrypted_private_key = $3 WHERE team_id = $1 AND user_id = $2",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .bind(encrypted_private_key)
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Retire un membre (par un admin) ou quitte l'équipe (par le membre lui-même). Ses accès
+/// d'équipe sont supprimés ; le dernier admin ne peut partir qu'en dernier, l'équipe
+/// étant alors supprimée.
+pub async fn remove_member(
+    pool: &PgPool,
+    actor_id: Uuid,
+    team_id: Uuid,
+    member_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    lock_team(&mut tx, team_id).await?;
+
+    let actor_role = member_role(&mut tx, team_id, actor_id).await?;
+    if !super::services::can_remove_member(actor_role, actor_id == member_id) {
+        return Err(sqlx::Error::Protocol(TEAM_ADMIN_REQUIRED.into()));
+    }
+
+    let removed = sqlx::query("DELETE FROM team_members WHERE team_id = $1 AND user_id = $2")
+        .bind(team_id)
+        .bind(member_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if removed == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    match count_members(&mut tx, team_id).await? {
+        (0, _) => dissolve_team(&mut tx, team_id).await?,
+        (_, 0) => return Err(sqlx::Error::Protocol(LAST_TEAM_ADMIN.into())),
+        _ => drive_repo::prune_team_access(&mut tx, team_id).await?,
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Retire un compte supprimé de toutes ses équipes. Le membre le plus ancien devient admin
+/// d'une équipe dont il était le seul admin ; une équipe vide est supprimée.
+pub async fn remove_user_from_teams(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let team_ids: Vec<Uuid> =
+        sqlx::query_scalar("DELETE FROM team_members WHERE user_id = $1 RETURNING team_id")
+            .bind(user_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+    for team_id in team_ids {
+        lock_team(&mut tx, team_id).await?;
+        match count_members(&mut tx, team_id).await? {
+            (0, _) => dissolve_team(&mut tx, team_id).await?,
+            (_, 0) => {
+                sqlx::query(
+                    r#"
+                    UPDATE team_members SET role = 'admin'
+                    WHERE team_id = $1
+                      AND user_id = (
+                          SELECT user_id FROM team_members
+                          WHERE team_id = $1
+                          ORDER BY added_at, user_id
+                          LIMIT 1
+                      )
+                    "#,
+                )
+                .bind(team_id)
+                .execute(&mut *tx)
+                .await?;
+                drive_repo::prune_team_access(&mut tx, team_id).await?;
+            }
+            _ => drive_repo::prune_team_access(&mut tx, team_id).await?,
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
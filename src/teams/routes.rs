@@ -0,0 +1,35 @@
+// Routes du module teams
+
+use crate::state::AppState;
+use axum::{
+    Router,
+    routing::{get, patch, post, put},
+};
+
+use super::handlers;
+
+pub fn teams_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/teams",
+            get(handlers::list_teams_handler).post(handlers::create_team_handler),
+        )
+        .route(
+            "/teams/{team_id}",
+            get(handlers::get_team_handler)
+                .patch(handlers::rename_team_handler)
+                .delete(handlers::delete_team_handler),
+        )
+        .route(
+            "/teams/{team_id}/key",
+            put(handlers::update_own_team_key_handler),
+        )
+        .route(
+            "/teams/{team_id}/members",
+            post(handlers::add_member_handler),
+        )
+        .route(
+            "/teams/{team_id}/members/{user_id}",
+            patch(handlers::set_member_role_handler).delete(handlers::remove_member_handler),
+        )
+}
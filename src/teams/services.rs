@@ -0,0 +1,84 @@
+// Services - Rôles et validation des entrées des équipes
+
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Postgres,
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+};
+
+/// Longueur maximale d'un nom d'équipe (en caractères)
+pub const MAX_TEAM_NAME_LENGTH: usize = 100;
+
+/// Erreurs de validation renvoyées en 400
+pub const INVALID_TEAM_NAME: &str = "Team name must be between 1 and 100 characters";
+pub const TEAM_KEY_REQUIRED: &str = "Team public key and encrypted private key are required";
+
+/// Rôle d'un membre (colonne `team_members.role`). Les admins partagent la propriété de
+/// l'équipe : gestion des membres, renommage, suppression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TeamRole {
+    Admin,
+    Member,
+}
+
+impl TeamRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TeamRole::Admin => "admin",
+            TeamRole::Member => "member",
+        }
+    }
+}
+
+impl std::str::FromStr for TeamRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(TeamRole::Admin),
+            "member" => Ok(TeamRole::Member),
+            other => Err(format!("Invalid team role: {}", other)),
+        }
+    }
+}
+
+// Stocké en TEXT, comme AccessLevel
+impl sqlx::Type<Postgres> for TeamRole {
+    fn type_info() -> PgTypeInfo {
+        <&str as sqlx::Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl sqlx::Encode<'_, Postgres> for TeamRole {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        <&str as sqlx::Encode<Postgres>>::encode(self.as_str(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Postgres> for TeamRole {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let role = <&str as sqlx::Decode<Postgres>>::decode(value)?;
+        Ok(role.parse()?)
+    }
+}
+
+/// Un admin peut retirer n'importe quel membre, un membre peut seulement quitter l'équipe
+pub fn can_remove_member(actor_role: TeamRole, removing_self: bool) -> bool {
+    actor_role == TeamRole::Admin || removing_self
+}
+
+/// Nom d'équipe sans espaces superflus, refusé s'il est vide ou trop long
+pub fn normalize_team_name(name: &str) -> Result<String, &'static str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TEAM_NAME_LENGTH {
+        return Err(INVALID_TEAM_NAME);
+    }
+    Ok(name.to_string())
+}
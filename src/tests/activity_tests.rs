@@ -0,0 +1,69 @@
+// Tests unitaires pour le module activity
+// Teste: noms des actions stockés en base, construction des entrées, configuration de la rétention
+
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::activity::services::{ActivityAction, ActivityEntry, parse_activity_retention_days};
+use crate::drive::repo::ItemKind;
+
+#[test]
+fn test_action_names_are_unique() {
+    let actions = [
+        ActivityAction::Login,
+        ActivityAction::LoginFailed,
+        ActivityAction::Logout,
+        ActivityAction::Uploaded,
+        ActivityAction::Downloaded,
+        ActivityAction::Renamed,
+        ActivityAction::Moved,
+        ActivityAction::Deleted,
+        ActivityAction::Restored,
+        ActivityAction::Shared,
+        ActivityAction::Revoked,
+        ActivityAction::Accepted,
+        ActivityAction::Rejected,
+        ActivityAction::KeyRotated,
+        ActivityAction::ShareExpired,
+        ActivityAction::PublicKeyChanged,
+        ActivityAction::SharedWithTeam,
+        ActivityAction::RevokedFromTeam,
+    ];
+    let names: HashSet<&str> = actions.iter().map(|action| action.as_str()).collect();
+
+    assert_eq!(names.len(), actions.len());
+    assert_eq!(ActivityAction::LoginFailed.as_str(), "login_failed");
+    assert_eq!(ActivityAction::KeyRotated.as_str(), "key_rotated");
+}
+
+#[test]
+fn test_entry_builder() {
+    let user_id = Uuid::new_v4();
+    let file_id = Uuid::new_v4();
+    let recipient_id = Uuid::new_v4();
+
+    let entry = ActivityEntry::new(user_id, ActivityAction::Shared)
+        .on_item(ItemKind::File, file_id)
+        .target_user(recipient_id);
+
+    assert_eq!(entry.user_id, user_id);
+    assert_eq!(entry.item, Some((ItemKind::File, file_id)));
+    assert_eq!(entry.target_user_id, Some(recipient_id));
+    assert_eq!(entry.ip_address, None);
+}
+
+#[test]
+fn test_login_entry_has_no_item() {
+    let entry =
+        ActivityEntry::new(Uuid::new_v4(), ActivityAction::Login).from_ip(Some("203.0.113.7"));
+
+    assert_eq!(entry.item, None);
+    assert_eq!(entry.ip_address.as_deref(), Some("203.0.113.7"));
+}
+
+#[test]
+fn test_activity_retention_days_config() {
+    assert_eq!(parse_activity_retention_days(None), 365);
+    assert_eq!(parse_activity_retention_days(Some("0")), 365);
+    assert_eq!(parse_activity_retention_days(Some("90")), 90);
+}
@@ -0,0 +1,208 @@
+// Tests unitaires pour archive.rs
+// Teste: CRC32, structure des archives ZIP64 produites en streaming
+
+use bytes::Bytes;
+use futures::StreamExt;
+
+use crate::archive::{ArchiveSink, Crc32, ZipStreamWriter, crc32, write_json_entry};
+
+fn read_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn read_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+/// Parcourt le répertoire central et retourne (nom, crc, taille, offset) pour chaque entrée
+fn parse_central_directory(archive: &[u8]) -> Vec<(String, u32, u64, u64)> {
+    // EOCD classique (22 octets) précédé du locator ZIP64 (20 octets)
+    let eocd = archive.len() - 22;
+    assert_eq!(read_u32(archive, eocd), 0x06054b50);
+    let locator = eocd - 20;
+    assert_eq!(read_u32(archive, locator), 0x07064b50);
+    let zip64_eocd = read_u64(archive, locator + 8) as usize;
+    assert_eq!(read_u32(archive, zip64_eocd), 0x06064b50);
+
+    let entry_count = read_u64(archive, zip64_eocd + 32);
+    let mut cursor = read_u64(archive, zip64_eocd + 48) as usize;
+    let mut entries = Vec::new();
+
+    for _ in 0..entry_count {
+        assert_eq!(read_u32(archive, cursor), 0x02014b50);
+        let crc = read_u32(archive, cursor + 16);
+        let name_len = read_u16(archive, cursor + 28) as usize;
+        let extra_len = read_u16(archive, cursor + 30) as usize;
+        let name =
+            String::from_utf8(archive[cursor + 46..cursor + 46 + name_len].to_vec()).unwrap();
+        let extra = cursor + 46 + name_len;
+        assert_eq!(
+            read_u16(archive, extra),
+            0x0001,
+            "ZIP64 extra field expected"
+        );
+        let size = read_u64(archive, extra + 4);
+        let offset = read_u64(archive, extra + 20);
+        entries.push((name, crc, size, offset));
+        cursor = extra + extra_len;
+    }
+
+    entries
+}
+
+// ========== Tests CRC32 ==========
+
+#[test]
+fn test_crc32_check_value() {
+    // Valeur de contrôle standard du CRC-32/ISO-HDLC
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+}
+
+#[test]
+fn test_crc32_empty_input() {
+    assert_eq!(crc32(b""), 0);
+}
+
+#[test]
+fn test_crc32_incremental_matches_one_shot() {
+    let data = b"The quick brown fox jumps over the lazy dog";
+    let mut crc = Crc32::new();
+    for chunk in data.chunks(7) {
+        crc.update(chunk);
+    }
+    assert_eq!(crc.finalize(), crc32(data));
+    assert_eq!(crc.finalize(), 0x414FA339);
+}
+
+// ========== Tests ZipStreamWriter ==========
+
+#[test]
+fn test_empty_archive_is_valid() {
+    let writer = ZipStreamWriter::new();
+    let archive = writer.finish();
+
+    assert!(parse_central_directory(&archive).is_empty());
+}
+
+#[test]
+fn test_single_entry_layout() {
+    let mut writer = ZipStreamWriter::new();
+    let archive = [writer.entry("manifest.json", b"{}"), writer.finish()].concat();
+
+    assert_eq!(read_u32(&archive, 0), 0x04034b50, "local header first");
+    assert_eq!(
+        read_u16(&archive, 6) & 0x0008,
+        0x0008,
+        "data descriptor flag"
+    );
+
+    let entries = parse_central_directory(&archive);
+    assert_eq!(entries.len(), 1);
+    let (name, crc, size, offset) = &entries[0];
+    assert_eq!(name, "manifest.json");
+    assert_eq!(*crc, crc32(b"{}"));
+    assert_eq!(*size, 2);
+    assert_eq!(*offset, 0);
+}
+
+#[test]
+fn test_streamed_entry_data_descriptor() {
+    let mut writer = ZipStreamWriter::new();
+    let mut archive = Vec::new();
+
+    archive.extend_from_slice(&writer.start_entry("files/a/0.chunk"));
+    let data_start = archive.len();
+    for block in [&b"hello "[..], &b"streaming "[..], &b"world"[..]] {
+        writer.record_data(block);
+        archive.extend_from_slice(block);
+    }
+    archive.extend_from_slice(&writer.finish_entry());
+
+    // Data descriptor ZIP64 juste après les données
+    let descriptor = data_start + b"hello streaming world".len();
+    assert_eq!(read_u32(&archive, descriptor), 0x08074b50);
+    assert_eq!(
+        read_u32(&archive, descriptor + 4),
+        crc32(b"hello streaming world")
+    );
+    assert_eq!(read_u64(&archive, descriptor + 8), 21);
+    assert_eq!(read_u64(&archive, descriptor + 16), 21);
+}
+
+#[test]
+fn test_multiple_entries_offsets_match_local_headers() {
+    let mut writer = ZipStreamWriter::new();
+    let mut archive = Vec::new();
+    archive.extend_from_slice(&writer.entry("profile.json", b"{\"id\":1}"));
+    archive.extend_from_slice(&writer.entry("files.json", b"[]"));
+    archive.extend_from_slice(&writer.entry("agenda.json", b""));
+    assert_eq!(writer.bytes_written(), archive.len() as u64);
+    archive.extend_from_slice(&writer.finish());
+
+    let entries = parse_central_directory(&archive);
+    let names: Vec<&str> = entries.iter().map(|e| e.0.as_str()).collect();
+    assert_eq!(names, vec!["profile.json", "files.json", "agenda.json"]);
+
+    for (name, _, _, offset) in entries {
+        let offset = offset as usize;
+        assert_eq!(read_u32(&archive, offset), 0x04034b50);
+        let name_len = read_u16(&archive, offset + 26) as usize;
+        assert_eq!(
+            &archive[offset + 30..offset + 30 + name_len],
+            name.as_bytes()
+        );
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_start_entry_twice_panics() {
+    let mut writer = ZipStreamWriter::new();
+    writer.start_entry("a");
+    writer.start_entry("b");
+}
+
+// ========== Tests des destinations ==========
+
+#[tokio::test]
+async fn test_json_entry_streamed_to_channel() {
+    let (mut sender, receiver) =
+        futures::channel::mpsc::channel::<Result<Bytes, std::io::Error>>(8);
+    let mut writer = ZipStreamWriter::new();
+
+    write_json_entry(
+        &mut writer,
+        &mut sender,
+        "manifest.json",
+        &serde_json::json!({ "a": 1 }),
+    )
+    .await
+    .unwrap();
+    sender.write_archive_bytes(writer.finish()).await.unwrap();
+    drop(sender);
+
+    let blocks: Vec<_> = receiver.map(|block| block.unwrap()).collect().await;
+    let archive = blocks.concat();
+    let entries = parse_central_directory(&archive);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].0, "manifest.json");
+    let expected = serde_json::to_vec_pretty(&serde_json::json!({ "a": 1 })).unwrap();
+    assert_eq!(entries[0].1, crc32(&expected));
+}
+
+#[tokio::test]
+async fn test_channel_sink_reports_disconnected_client() {
+    let (mut sender, receiver) =
+        futures::channel::mpsc::channel::<Result<Bytes, std::io::Error>>(8);
+    drop(receiver);
+
+    let err = sender
+        .write_archive_bytes(Bytes::from_static(b"data"))
+        .await
+        .unwrap_err();
+    assert_eq!(err, "client disconnected");
+}
@@ -1,5 +1,5 @@
 // Tests unitaires pour auth/services.rs
-// Teste: JWT (create_jwt, decode_jwt), password hashing (hash_password, verify_password)
+// Teste: JWT (create_jwt, decode_jwt), révocation des sessions, password hashing (hash_password, verify_password)
 
 use base64::{Engine, engine::general_purpose};
 use chrono::{Duration, Utc};
@@ -43,6 +43,42 @@ fn test_decode_jwt_valid_token() {
     assert_eq!(claims.jti.len(), 36); // UUID string length
 }
 
+#[test]
+fn test_create_jwt_sets_issued_at() {
+    let secret = b"test-secret-key-for-testing-only";
+    let before = Utc::now().timestamp() as usize;
+
+    let token =
+        services::create_jwt(Uuid::new_v4(), "user", secret).expect("JWT creation should succeed");
+    let claims = services::decode_jwt(&token, secret).expect("JWT decoding should succeed");
+
+    assert!(claims.iat >= before, "iat should be set to the issue time");
+    assert!(claims.iat < claims.exp, "iat should be before exp");
+}
+
+#[test]
+fn test_decode_jwt_without_iat_defaults_to_zero() {
+    // Tokens émis avant l'ajout de `iat` : ils doivent rester décodables
+    // (et seront considérés comme révoqués par toute révocation globale)
+    let secret = b"test-secret-key-for-testing-only";
+    let legacy_claims = serde_json::json!({
+        "id": Uuid::new_v4(),
+        "role": "user",
+        "exp": (Utc::now() + Duration::hours(1)).timestamp() as usize,
+        "jti": Uuid::new_v4().to_string(),
+    });
+
+    let token = encode(
+        &Header::default(),
+        &legacy_claims,
+        &EncodingKey::from_secret(secret),
+    )
+    .expect("JWT creation should succeed");
+
+    let claims = services::decode_jwt(&token, secret).expect("Legacy JWT should still decode");
+    assert_eq!(claims.iat, 0);
+}
+
 #[test]
 fn test_decode_jwt_wrong_secret_fails() {
     let user_id = Uuid::new_v4();
@@ -69,6 +105,8 @@ fn test_decode_jwt_expired_token_fails() {
         role: "user".to_string(),
         exp: (Utc::now() - Duration::hours(1)).timestamp() as usize,
         jti: Uuid::new_v4().to_string(),
+        iat: (Utc::now() - Duration::hours(2)).timestamp() as usize,
+        iat_ms: (Utc::now() - Duration::hours(2)).timestamp_millis(),
     };
 
     let key = EncodingKey::from_secret(secret);
@@ -136,6 +174,8 @@ fn test_decode_jwt_wrong_algorithm_fails() {
         role: "user".to_string(),
         exp: (Utc::now() + Duration::hours(1)).timestamp() as usize,
         jti: Uuid::new_v4().to_string(),
+        iat: Utc::now().timestamp() as usize,
+        iat_ms: Utc::now().timestamp_millis(),
     };
 
     let token = encode(
@@ -324,3 +364,158 @@ fn test_generate_salt_uniqueness_over_128_samples() {
         "Generated salts should be unique over sample set"
     );
 }
+
+// ========== Tests rôle admin ==========
+
+#[test]
+fn test_claims_is_admin_only_for_admin_role() {
+    let secret = b"test-secret-key-for-testing-only";
+
+    let token = services::create_jwt(Uuid::new_v4(), services::ADMIN_ROLE, secret)
+        .expect("JWT creation should succeed");
+    let admin = services::decode_jwt(&token, secret).expect("JWT decoding should succeed");
+    assert!(admin.is_admin());
+
+    let token =
+        services::create_jwt(Uuid::new_v4(), "user", secret).expect("JWT creation should succeed");
+    let user = services::decode_jwt(&token, secret).expect("JWT decoding should succeed");
+    assert!(!user.is_admin());
+}
+
+#[test]
+fn test_claims_is_admin_is_case_sensitive() {
+    let claims = services::Claims {
+        id: Uuid::new_v4(),
+        role: "Admin".to_string(),
+        exp: 0,
+        jti: Uuid::new_v4().to_string(),
+        iat: 0,
+        iat_ms: 0,
+    };
+    assert!(!claims.is_admin());
+}
+
+// ========== Tests révocation des sessions ==========
+
+fn claims_issued_at(iat: usize, iat_ms: i64) -> services::Claims {
+    services::Claims {
+        id: Uuid::new_v4(),
+        role: "user".to_string(),
+        exp: 0,
+        jti: Uuid::new_v4().to_string(),
+        iat,
+        iat_ms,
+    }
+}
+
+#[test]
+fn test_login_in_same_second_after_revocation_is_valid() {
+    let revoked_at_ms = 1_775_000_000_250;
+    let before = claims_issued_at(1_775_000_000, 1_775_000_000_100);
+    let after = claims_issued_at(1_775_000_000, 1_775_000_000_400);
+
+    assert!(services::issued_before_revocation(&before, revoked_at_ms));
+    assert!(services::issued_before_revocation(
+        &claims_issued_at(1_775_000_000, revoked_at_ms),
+        revoked_at_ms
+    ));
+    assert!(!services::issued_before_revocation(&after, revoked_at_ms));
+}
+
+#[test]
+fn test_revocation_legacy_values_compare_by_second() {
+    // Token émis avant `iat_ms` : révoqué par un marqueur de la même seconde
+    let legacy_token = claims_issued_at(1_775_000_000, 0);
+    assert!(services::issued_before_revocation(
+        &legacy_token,
+        1_775_000_000_250
+    ));
+    assert!(!services::issued_before_revocation(
+        &legacy_token,
+        1_774_999_999_999
+    ));
+
+    // Marqueur en secondes : couvre toute la seconde de la révocation
+    let same_second = claims_issued_at(1_775_000_000, 1_775_000_000_900);
+    let next_second = claims_issued_at(1_775_000_001, 1_775_000_001_000);
+    assert!(services::issued_before_revocation(
+        &same_second,
+        1_775_000_000
+    ));
+    assert!(!services::issued_before_revocation(
+        &next_second,
+        1_775_000_000
+    ));
+}
+
+// ========== Tests statut du compte ==========
+
+#[test]
+fn test_user_status_round_trip() {
+    for status in [
+        services::UserStatus::Active,
+        services::UserStatus::Suspended,
+    ] {
+        assert_eq!(services::UserStatus::parse(status.as_str()), Some(status));
+    }
+    assert_eq!(services::UserStatus::parse("deleted"), None);
+}
+
+#[test]
+fn test_user_status_from_is_active() {
+    assert_eq!(
+        services::UserStatus::from_is_active(true),
+        services::UserStatus::Active
+    );
+    assert_eq!(
+        services::UserStatus::from_is_active(false),
+        services::UserStatus::Suspended
+    );
+}
+
+#[test]
+fn test_suspension_reason_is_escaped_in_html() {
+    assert_eq!(
+        crate::mail::escape_html("<b>spam</b> & \"abuse\""),
+        "&lt;b&gt;spam&lt;/b&gt; &amp; &quot;abuse&quot;"
+    );
+}
+
+// ========== Tests transparence des clés ==========
+
+#[test]
+fn test_public_key_fingerprint_is_sha256_hex() {
+    assert_eq!(
+        services::public_key_fingerprint("abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn test_key_statement_chains_previous_fingerprint() {
+    let user_id = Uuid::new_v4();
+    let first = services::key_statement(user_id, 1, "aa", None);
+    let second = services::key_statement(user_id, 2, "bb", Some("aa"));
+
+    assert_eq!(
+        first,
+        format!(
+            "gauzian-key-statement/v1\nuser_id:{}\nversion:1\nfingerprint:aa\nprevious_fingerprint:none",
+            user_id
+        )
+    );
+    assert!(second.starts_with(services::KEY_STATEMENT_HEADER));
+    assert!(second.ends_with("\nversion:2\nfingerprint:bb\nprevious_fingerprint:aa"));
+}
+
+#[test]
+fn test_key_signature_must_be_non_empty_base64() {
+    let signature = general_purpose::STANDARD.encode([7u8; 256]);
+
+    assert!(services::validate_key_signature(&signature).is_ok());
+    assert_eq!(
+        services::validate_key_signature(""),
+        Err(services::INVALID_KEY_SIGNATURE)
+    );
+    assert!(services::validate_key_signature("not base64!").is_err());
+}
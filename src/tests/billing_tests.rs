@@ -0,0 +1,96 @@
+// Tests unitaires pour billing/services.rs
+// Teste: prestataires de paiement (fake, désactivé, remboursements), montant d'un changement d'offre
+
+use uuid::Uuid;
+
+use crate::billing::{
+    repo::AccountTier,
+    services::{
+        self, DisabledPaymentProvider, FakePaymentProvider, PaymentOutcome, PaymentProvider,
+        PaymentRequest,
+    },
+};
+
+fn tier(name: &str, price_cents: i64) -> AccountTier {
+    AccountTier {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        description: None,
+        storage_limit_bytes: 0,
+        price_cents,
+    }
+}
+
+fn payment_request() -> PaymentRequest {
+    PaymentRequest {
+        user_id: Uuid::new_v4(),
+        tier_name: "pro".to_string(),
+        amount_cents: 999,
+    }
+}
+
+#[tokio::test]
+async fn test_fake_provider_approving_returns_reference() {
+    let provider = FakePaymentProvider::approving();
+
+    match provider.charge(&payment_request()).await {
+        Ok(PaymentOutcome::Paid { reference }) => assert!(reference.starts_with("fake_")),
+        other => panic!("expected Paid, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_fake_provider_declining_returns_reason() {
+    let provider = FakePaymentProvider::declining("card declined");
+
+    let outcome = provider.charge(&payment_request()).await.unwrap();
+    assert_eq!(
+        outcome,
+        PaymentOutcome::Declined {
+            reason: "card declined".to_string()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_disabled_provider_returns_error() {
+    assert!(
+        DisabledPaymentProvider
+            .charge(&payment_request())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_fake_provider_refunds() {
+    let provider = FakePaymentProvider::approving();
+
+    assert!(provider.refund("fake_reference", 999).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_disabled_provider_refund_returns_error() {
+    assert!(
+        DisabledPaymentProvider
+            .refund("fake_reference", 999)
+            .await
+            .is_err()
+    );
+}
+
+#[test]
+fn test_tier_change_amount_charges_target_price_on_upgrade() {
+    assert_eq!(
+        services::tier_change_amount(&tier("free", 0), &tier("pro", 999)),
+        999
+    );
+}
+
+#[test]
+fn test_tier_change_amount_is_free_on_downgrade() {
+    assert_eq!(
+        services::tier_change_amount(&tier("premium", 2999), &tier("pro", 999)),
+        0
+    );
+}
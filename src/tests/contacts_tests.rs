@@ -0,0 +1,52 @@
+// Tests unitaires pour le module contacts
+// Teste: validation des noms de groupe, normalisation des empreintes, motif de recherche
+
+use crate::contacts::services::{
+    INVALID_FINGERPRINT, INVALID_GROUP_NAME, MAX_GROUP_NAME_LENGTH, normalize_email,
+    normalize_fingerprint, normalize_group_name, search_pattern,
+};
+
+#[test]
+fn test_group_name_is_trimmed_and_bounded() {
+    assert_eq!(normalize_group_name("  Famille ").unwrap(), "Famille");
+    assert_eq!(normalize_group_name("   "), Err(INVALID_GROUP_NAME));
+
+    let longest = "é".repeat(MAX_GROUP_NAME_LENGTH);
+    assert!(normalize_group_name(&longest).is_ok());
+    assert!(normalize_group_name(&format!("{}x", longest)).is_err());
+}
+
+#[test]
+fn test_fingerprint_is_normalized_to_lowercase_hex() {
+    let hex = "AB".repeat(32);
+    let with_separators = hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| std::str::from_utf8(pair).unwrap())
+        .collect::<Vec<_>>()
+        .join(":");
+
+    assert_eq!(normalize_fingerprint(&hex).unwrap(), "ab".repeat(32));
+    assert_eq!(
+        normalize_fingerprint(&with_separators).unwrap(),
+        "ab".repeat(32)
+    );
+}
+
+#[test]
+fn test_fingerprint_rejects_wrong_length_or_non_hex() {
+    assert_eq!(normalize_fingerprint(""), Err(INVALID_FINGERPRINT));
+    assert!(normalize_fingerprint(&"a".repeat(40)).is_err());
+    assert!(normalize_fingerprint(&"g".repeat(64)).is_err());
+}
+
+#[test]
+fn test_search_pattern_escapes_wildcards() {
+    assert_eq!(search_pattern(" alice "), "%alice%");
+    assert_eq!(search_pattern("100%_a\\b"), "%100\\%\\_a\\\\b%");
+}
+
+#[test]
+fn test_email_is_normalized_like_registration() {
+    assert_eq!(normalize_email("  Alice@Example.COM "), "alice@example.com");
+}
@@ -1,9 +1,13 @@
 // Tests unitaires pour drive/services.rs
-// Teste: format_string_to_uuid_or_root, parse_uuid_or_error
+// Teste: format_string_to_uuid_or_root, parse_uuid_or_error, quota_state, upload_reservation_ttl_hours, upload_size_allowance, storage_usage_drift, BatchOperation, copy_covers_sources, change_cursor_is_valid, AccessLevel, added_item_access_level, keys_cover_members, rotation_covers_subtree, shared_upload_owner, ChangeKind, ShareGrant, is_valid_color_label, is_valid_tag_token, validate_search_index_update
 
+use chrono::{Duration, Utc};
 use uuid::Uuid;
 
-use crate::drive::services;
+use crate::drive::{
+    repo::{BatchOperation, ChangeKind, StorageUsage},
+    services::{self, AccessLevel, Permission, ShareGrant, SharedUploadOwner},
+};
 
 // ========== Tests UUID parsing ==========
 
@@ -204,3 +208,431 @@ fn test_parse_rejects_obviously_invalid_uuid_patterns() {
         );
     }
 }
+
+// ========== Tests quota_state ==========
+
+#[test]
+fn test_quota_state_within_quota_without_over_quota_date() {
+    assert_eq!(
+        services::quota_state(None, 14, Utc::now()),
+        services::QuotaState::WithinQuota
+    );
+}
+
+#[test]
+fn test_quota_state_over_quota_during_grace_period() {
+    let since = Utc::now() - Duration::days(3);
+    let state = services::quota_state(Some(since), 14, Utc::now());
+
+    assert_eq!(
+        state,
+        services::QuotaState::OverQuota {
+            read_only_at: since + Duration::days(14)
+        }
+    );
+}
+
+#[test]
+fn test_quota_state_read_only_after_grace_period() {
+    let since = Utc::now() - Duration::days(15);
+    let state = services::quota_state(Some(since), 14, Utc::now());
+
+    assert!(matches!(state, services::QuotaState::ReadOnly { .. }));
+}
+
+#[test]
+fn test_quota_state_serializes_with_tag() {
+    let value = serde_json::to_value(services::QuotaState::WithinQuota).unwrap();
+    assert_eq!(value, serde_json::json!({ "quota_state": "within_quota" }));
+}
+
+// ========== Tests upload_reservation_ttl_hours ==========
+
+#[test]
+fn test_upload_reservation_ttl_hours_ignores_invalid_values() {
+    assert_eq!(services::parse_upload_reservation_ttl_hours(None), 24);
+    assert_eq!(services::parse_upload_reservation_ttl_hours(Some("0")), 24);
+    assert_eq!(
+        services::parse_upload_reservation_ttl_hours(Some("abc")),
+        24
+    );
+    assert_eq!(services::parse_upload_reservation_ttl_hours(Some("6")), 6);
+}
+
+// ========== Tests upload_size_allowance ==========
+
+#[test]
+fn test_upload_size_allowance_covers_auth_tags_of_plaintext_sized_uploads() {
+    // 10 Mo de clair en chunks de 1 Mo : 10 tags AES-GCM
+    let declared = 10 * 1024 * 1024;
+    let ciphertext = declared + 10 * services::CHUNK_AUTH_TAG_BYTES;
+    assert!(ciphertext <= services::upload_size_allowance(declared));
+
+    // Chunks de la taille minimale couverte, avec un dernier chunk partiel
+    let declared = 3 * services::UPLOAD_ALLOWANCE_MIN_CHUNK_BYTES + 10;
+    let ciphertext = declared + 4 * services::CHUNK_AUTH_TAG_BYTES;
+    assert!(ciphertext <= services::upload_size_allowance(declared));
+}
+
+#[test]
+fn test_upload_size_allowance_stays_bounded() {
+    // Une taille déclarée nulle n'autorise qu'un tag, pas des chunks illimités
+    assert_eq!(
+        services::upload_size_allowance(0),
+        services::CHUNK_AUTH_TAG_BYTES
+    );
+    let declared = 1024 * 1024 * 1024;
+    assert!(services::upload_size_allowance(declared) - declared < declared / 1000);
+    assert_eq!(services::upload_size_allowance(i64::MAX), i64::MAX);
+}
+
+// ========== Tests storage_usage_drift ==========
+
+#[test]
+fn test_storage_usage_drift_none_when_counters_match() {
+    let usage = StorageUsage {
+        used_bytes: 1024,
+        reserved_bytes: 512,
+        file_count: 3,
+    };
+    assert_eq!(
+        services::storage_usage_drift(&usage, &usage),
+        StorageUsage::default()
+    );
+}
+
+#[test]
+fn test_storage_usage_drift_is_actual_minus_stored() {
+    let stored = StorageUsage {
+        used_bytes: 2048,
+        reserved_bytes: 0,
+        file_count: 5,
+    };
+    let actual = StorageUsage {
+        used_bytes: 1024,
+        reserved_bytes: 256,
+        file_count: 4,
+    };
+    let drift = services::storage_usage_drift(&stored, &actual);
+    assert_eq!(drift.used_bytes, -1024);
+    assert_eq!(drift.reserved_bytes, 256);
+    assert_eq!(drift.file_count, -1);
+}
+
+// ========== Tests BatchOperation ==========
+
+#[test]
+fn test_batch_operation_move_file_without_target_goes_to_root() {
+    let file_id = Uuid::new_v4();
+    let op: BatchOperation =
+        serde_json::from_value(serde_json::json!({ "op": "move_file", "file_id": file_id }))
+            .unwrap();
+    assert_eq!(
+        op,
+        BatchOperation::MoveFile {
+            file_id,
+            target_folder_id: None
+        }
+    );
+}
+
+#[test]
+fn test_batch_operation_rejects_unknown_op() {
+    let result: Result<BatchOperation, _> = serde_json::from_value(
+        serde_json::json!({ "op": "share_file", "file_id": Uuid::new_v4() }),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_batch_operation_deletes_allowed_on_read_only_drive() {
+    let id = Uuid::new_v4();
+    assert!(!BatchOperation::DeleteFile { file_id: id }.requires_writable_drive());
+    assert!(!BatchOperation::DeleteFolder { folder_id: id }.requires_writable_drive());
+    assert!(BatchOperation::RestoreFile { file_id: id }.requires_writable_drive());
+    assert!(
+        BatchOperation::MoveFolder {
+            folder_id: id,
+            target_folder_id: Some(Uuid::new_v4())
+        }
+        .requires_writable_drive()
+    );
+}
+
+// ========== Tests copy_covers_sources ==========
+
+#[test]
+fn test_copy_covers_sources_same_items_any_order() {
+    let a = Uuid::new_v4();
+    let b = Uuid::new_v4();
+    assert!(services::copy_covers_sources(&[a, b], &[b, a]));
+    assert!(services::copy_covers_sources(&[], &[]));
+}
+
+#[test]
+fn test_copy_covers_sources_missing_or_extra_item() {
+    let a = Uuid::new_v4();
+    let b = Uuid::new_v4();
+    assert!(!services::copy_covers_sources(&[a, b], &[a]));
+    assert!(!services::copy_covers_sources(&[a], &[a, b]));
+}
+
+#[test]
+fn test_copy_covers_sources_rejects_duplicates() {
+    let a = Uuid::new_v4();
+    let b = Uuid::new_v4();
+    assert!(!services::copy_covers_sources(&[a, b], &[a, a]));
+}
+
+// ========== Tests change_cursor_is_valid ==========
+
+#[test]
+fn test_change_cursor_valid_between_pruned_and_last() {
+    assert!(services::change_cursor_is_valid(10, 5, 20));
+    assert!(services::change_cursor_is_valid(5, 5, 20));
+    assert!(services::change_cursor_is_valid(20, 5, 20));
+    // Journal vide, client à jour
+    assert!(services::change_cursor_is_valid(0, 0, 0));
+}
+
+#[test]
+fn test_change_cursor_expired_by_retention() {
+    assert!(!services::change_cursor_is_valid(4, 5, 20));
+}
+
+#[test]
+fn test_change_kind_names() {
+    // Valeurs stockées dans drive_changes.change_type et exposées par GET /drive/changes
+    assert_eq!(ChangeKind::Purged.as_str(), "purged");
+    assert_eq!(ChangeKind::KeyRotated.as_str(), "key_rotated");
+}
+
+#[test]
+fn test_change_cursor_ahead_of_journal_is_invalid() {
+    assert!(!services::change_cursor_is_valid(21, 5, 20));
+    assert!(!services::change_cursor_is_valid(-1, 0, 0));
+}
+
+// ========== Tests AccessLevel ==========
+
+#[test]
+fn test_access_level_parse_and_serialize() {
+    assert_eq!("owner".parse::<AccessLevel>(), Ok(AccessLevel::Owner));
+    assert_eq!("editor".parse::<AccessLevel>(), Ok(AccessLevel::Editor));
+    assert_eq!("viewer".parse::<AccessLevel>(), Ok(AccessLevel::Viewer));
+    // L'ancien défaut 'read' n'est plus accepté
+    assert!("read".parse::<AccessLevel>().is_err());
+    assert!("write".parse::<AccessLevel>().is_err());
+
+    assert_eq!(
+        serde_json::to_string(&AccessLevel::Editor).unwrap(),
+        "\"editor\""
+    );
+    assert!(serde_json::from_str::<AccessLevel>("\"admin\"").is_err());
+}
+
+#[test]
+fn test_access_level_permission_matrix() {
+    for perm in [
+        Permission::Download,
+        Permission::Upload,
+        Permission::Rename,
+        Permission::Move,
+        Permission::Delete,
+        Permission::Share,
+        Permission::Reshare,
+    ] {
+        assert!(AccessLevel::Owner.can(perm));
+    }
+
+    assert!(AccessLevel::Editor.can(Permission::Upload));
+    assert!(AccessLevel::Editor.can(Permission::Rename));
+    assert!(AccessLevel::Editor.can(Permission::Reshare));
+    assert!(!AccessLevel::Editor.can(Permission::Delete));
+    assert!(!AccessLevel::Editor.can(Permission::Share));
+
+    assert!(AccessLevel::Viewer.can(Permission::Download));
+    assert!(!AccessLevel::Viewer.can(Permission::Upload));
+    assert!(!AccessLevel::Viewer.can(Permission::Move));
+    assert!(!AccessLevel::Viewer.can(Permission::Reshare));
+}
+
+#[test]
+fn test_access_level_grant_rules() {
+    assert!(AccessLevel::Owner.can_grant(AccessLevel::Editor));
+    assert!(AccessLevel::Owner.can_grant(AccessLevel::Viewer));
+    assert!(!AccessLevel::Owner.can_grant(AccessLevel::Owner));
+
+    // Un éditeur ne peut re-partager qu'en lecture seule
+    assert!(AccessLevel::Editor.can_grant(AccessLevel::Viewer));
+    assert!(!AccessLevel::Editor.can_grant(AccessLevel::Editor));
+
+    assert!(!AccessLevel::Viewer.can_grant(AccessLevel::Viewer));
+}
+
+// ========== Tests ajouts dans un dossier partagé ==========
+
+#[test]
+fn test_added_item_access_level() {
+    // Le propriétaire de l'élément est owner quel que soit son niveau sur le dossier
+    assert_eq!(
+        services::added_item_access_level(AccessLevel::Editor, true),
+        AccessLevel::Owner
+    );
+    // Le propriétaire du dossier reste editor d'un élément attribué à l'uploader
+    assert_eq!(
+        services::added_item_access_level(AccessLevel::Owner, false),
+        AccessLevel::Editor
+    );
+    assert_eq!(
+        services::added_item_access_level(AccessLevel::Editor, false),
+        AccessLevel::Editor
+    );
+    assert_eq!(
+        services::added_item_access_level(AccessLevel::Viewer, false),
+        AccessLevel::Viewer
+    );
+}
+
+#[test]
+fn test_keys_cover_members() {
+    let a = Uuid::new_v4();
+    let b = Uuid::new_v4();
+    assert!(services::keys_cover_members(&[a, b], &[b, a]));
+    assert!(services::keys_cover_members(&[], &[]));
+    assert!(!services::keys_cover_members(&[a, b], &[a]));
+    assert!(!services::keys_cover_members(&[a], &[a, b]));
+    assert!(!services::keys_cover_members(&[a, b], &[a, a]));
+}
+
+#[test]
+fn test_rotation_covers_subtree() {
+    let root = Uuid::new_v4();
+    let pending = Uuid::new_v4();
+    let rotated = Uuid::new_v4();
+    let outside = Uuid::new_v4();
+    let subtree = [root, pending, rotated];
+    let required = [root, pending];
+    assert!(services::rotation_covers_subtree(
+        &required,
+        &subtree,
+        &[pending, root]
+    ));
+    // Un sous-dossier non marqué peut être tourné dans le même lot
+    assert!(services::rotation_covers_subtree(
+        &required,
+        &subtree,
+        &[root, pending, rotated]
+    ));
+    // Sous-dossier en attente oublié
+    assert!(!services::rotation_covers_subtree(
+        &required,
+        &subtree,
+        &[root]
+    ));
+    assert!(!services::rotation_covers_subtree(
+        &required,
+        &subtree,
+        &[root, pending, outside]
+    ));
+    assert!(!services::rotation_covers_subtree(
+        &required,
+        &subtree,
+        &[root, pending, pending]
+    ));
+}
+
+#[test]
+fn test_shared_upload_owner_from_env() {
+    assert_eq!(
+        services::parse_shared_upload_owner(None),
+        SharedUploadOwner::FolderOwner
+    );
+    assert_eq!(
+        services::parse_shared_upload_owner(Some("uploader")),
+        SharedUploadOwner::Uploader
+    );
+    assert_eq!(
+        services::parse_shared_upload_owner(Some("nobody")),
+        SharedUploadOwner::FolderOwner
+    );
+}
+
+// ========== Tests partages temporaires ==========
+
+#[test]
+fn test_share_grant_expiry_must_be_in_future() {
+    let now = Utc::now();
+    let grant = |expires_at| ShareGrant {
+        access_level: AccessLevel::Viewer,
+        expires_at,
+    };
+
+    assert!(grant(None).validate(now).is_ok());
+    assert!(grant(Some(now + Duration::days(30))).validate(now).is_ok());
+    assert_eq!(
+        grant(Some(now)).validate(now),
+        Err(services::SHARE_EXPIRY_NOT_IN_FUTURE)
+    );
+    assert!(grant(Some(now - Duration::hours(1))).validate(now).is_err());
+}
+
+// ========== Tests favoris, couleurs et tags ==========
+
+#[test]
+fn test_color_label_validation() {
+    for label in services::COLOR_LABELS {
+        assert!(services::is_valid_color_label(label));
+    }
+    assert!(!services::is_valid_color_label("pink"));
+    assert!(!services::is_valid_color_label("Red"));
+    assert!(!services::is_valid_color_label(""));
+}
+
+#[test]
+fn test_tag_token_validation() {
+    assert!(services::is_valid_tag_token("a1B2-c3D4_e5F6g7H8"));
+    assert!(services::is_valid_tag_token(&"f".repeat(64)));
+    assert!(!services::is_valid_tag_token("short"));
+    assert!(!services::is_valid_tag_token(&"f".repeat(129)));
+    assert!(!services::is_valid_tag_token("abcdefgh/ijklmnop"));
+    assert!(!services::is_valid_tag_token("abcdefgh ijklmnop"));
+}
+
+// ========== Tests index de recherche ==========
+
+#[test]
+fn test_search_index_update_validation() {
+    let chunk = |chunk_index, encrypted_data: &str| services::SearchIndexChunkUpload {
+        chunk_index,
+        encrypted_data: encrypted_data.to_string(),
+    };
+
+    assert!(services::validate_search_index_update(0, &[]).is_ok());
+    assert!(services::validate_search_index_update(3, &[chunk(0, "a"), chunk(2, "c")]).is_ok());
+    assert_eq!(
+        services::validate_search_index_update(services::MAX_SEARCH_INDEX_CHUNKS + 1, &[]),
+        Err(services::INVALID_SEARCH_INDEX_CHUNK_COUNT)
+    );
+    assert_eq!(
+        services::validate_search_index_update(-1, &[]),
+        Err(services::INVALID_SEARCH_INDEX_CHUNK_COUNT)
+    );
+    assert_eq!(
+        services::validate_search_index_update(2, &[chunk(2, "a")]),
+        Err(services::INVALID_SEARCH_INDEX_CHUNK_INDEX)
+    );
+    assert_eq!(
+        services::validate_search_index_update(2, &[chunk(1, "a"), chunk(1, "b")]),
+        Err(services::INVALID_SEARCH_INDEX_CHUNK_INDEX)
+    );
+    assert_eq!(
+        services::validate_search_index_update(1, &[chunk(0, "")]),
+        Err(services::INVALID_SEARCH_INDEX_CHUNK_DATA)
+    );
+    let oversized = "a".repeat(services::MAX_SEARCH_INDEX_CHUNK_BYTES + 1);
+    assert_eq!(
+        services::validate_search_index_update(1, &[chunk(0, &oversized)]),
+        Err(services::INVALID_SEARCH_INDEX_CHUNK_DATA)
+    );
+}
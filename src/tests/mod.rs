@@ -12,3 +12,24 @@ mod response_tests;
 
 #[cfg(test)]
 mod metrics_tests;
+
+#[cfg(test)]
+mod archive_tests;
+
+#[cfg(test)]
+mod billing_tests;
+
+#[cfg(test)]
+mod upload_limiter_tests;
+
+#[cfg(test)]
+mod notifications_tests;
+
+#[cfg(test)]
+mod activity_tests;
+
+#[cfg(test)]
+mod contacts_tests;
+
+#[cfg(test)]
+mod teams_tests;
@@ -0,0 +1,191 @@
+// Tests unitaires pour le module notifications
+// Teste: format JSON des événements, canaux Redis, distribution aux flux locaux, résumé des partages
+
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::drive::{repo::PendingShare, services::AccessLevel};
+use crate::notifications::NotificationHub;
+use crate::notifications::services::{
+    NotificationEvent, ShareEmailMode, channel_for, summarize_pending_shares, user_from_channel,
+};
+
+fn pending_share(item_type: &str, username: Option<&str>) -> PendingShare {
+    PendingShare {
+        item_type: item_type.to_string(),
+        item_id: Uuid::new_v4(),
+        access_level: AccessLevel::Viewer,
+        shared_by: username.map(|_| Uuid::new_v4()),
+        shared_by_username: username.map(str::to_string),
+        shared_at: chrono::Utc::now(),
+        expires_at: None,
+        encrypted_metadata: b"meta".to_vec(),
+        encrypted_key: b"key".to_vec(),
+    }
+}
+
+fn test_hub() -> NotificationHub {
+    // Client::open ne se connecte pas : aucun serveur Redis n'est nécessaire
+    NotificationHub::new(redis::Client::open("redis://127.0.0.1").unwrap())
+}
+
+#[test]
+fn test_event_serialization_is_tagged() {
+    let item_id = Uuid::new_v4();
+    let from_user_id = Uuid::new_v4();
+    let event = NotificationEvent::ShareReceived {
+        item_type: "folder".to_string(),
+        item_id,
+        from_user_id,
+    };
+
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["type"], "share_received");
+    assert_eq!(json["item_type"], "folder");
+    assert_eq!(json["item_id"], item_id.to_string());
+
+    let parsed: NotificationEvent = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed, event);
+}
+
+#[test]
+fn test_share_expired_event_names_revoked_user() {
+    let item_id = Uuid::new_v4();
+    let user_id = Uuid::new_v4();
+    let event = NotificationEvent::ShareExpired {
+        item_type: "file".to_string(),
+        item_id,
+        user_id,
+    };
+
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["type"], "share_expired");
+    assert_eq!(json["user_id"], user_id.to_string());
+}
+
+#[test]
+fn test_contact_invitation_event_is_tagged() {
+    let invitation_id = Uuid::new_v4();
+    let event = NotificationEvent::ContactInvitation {
+        invitation_id,
+        from_user_id: Uuid::new_v4(),
+    };
+
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["type"], "contact_invitation");
+    assert_eq!(json["invitation_id"], invitation_id.to_string());
+}
+
+#[test]
+fn test_contact_key_changed_event_carries_new_fingerprint() {
+    let event = NotificationEvent::ContactKeyChanged {
+        user_id: Uuid::new_v4(),
+        key_version: 2,
+        fingerprint: "ab".repeat(32),
+    };
+
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["type"], "contact_key_changed");
+    assert_eq!(json["key_version"], 2);
+    assert_eq!(json["fingerprint"], "ab".repeat(32));
+}
+
+#[test]
+fn test_team_folder_shared_event_names_team() {
+    let team_id = Uuid::new_v4();
+    let folder_id = Uuid::new_v4();
+    let event = NotificationEvent::TeamFolderShared {
+        team_id,
+        folder_id,
+        from_user_id: Uuid::new_v4(),
+    };
+
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["type"], "team_folder_shared");
+    assert_eq!(json["team_id"], team_id.to_string());
+    assert_eq!(json["folder_id"], folder_id.to_string());
+}
+
+#[test]
+fn test_channel_roundtrip() {
+    let user_id = Uuid::new_v4();
+    let channel = channel_for(user_id);
+
+    assert_eq!(channel, format!("notifications:{}", user_id));
+    assert_eq!(user_from_channel(&channel), Some(user_id));
+    assert_eq!(user_from_channel("notifications:not-a-uuid"), None);
+    assert_eq!(user_from_channel(&format!("other:{}", user_id)), None);
+}
+
+#[tokio::test]
+async fn test_dispatch_reaches_all_streams_of_user() {
+    let hub = test_hub();
+    let user_id = Uuid::new_v4();
+    let mut first = hub.subscribe(user_id);
+    let mut second = hub.subscribe(user_id);
+    let _other = hub.subscribe(Uuid::new_v4());
+
+    assert_eq!(hub.dispatch(user_id, Arc::from("{\"type\":\"test\"}")), 2);
+    assert_eq!(first.recv().await.as_deref(), Some("{\"type\":\"test\"}"));
+    assert_eq!(second.recv().await.as_deref(), Some("{\"type\":\"test\"}"));
+}
+
+#[tokio::test]
+async fn test_dispatch_without_stream_is_dropped() {
+    let hub = test_hub();
+    let user_id = Uuid::new_v4();
+
+    assert_eq!(hub.dispatch(user_id, Arc::from("{}")), 0);
+
+    // Le canal d'un utilisateur est retiré à la fermeture de son dernier flux
+    drop(hub.subscribe(user_id));
+    assert_eq!(hub.connected_users(), 0);
+    assert_eq!(hub.dispatch(user_id, Arc::from("{}")), 0);
+}
+
+#[tokio::test]
+async fn test_channel_kept_while_a_stream_remains() {
+    let hub = test_hub();
+    let user_id = Uuid::new_v4();
+    let first = hub.subscribe(user_id);
+    let mut second = hub.subscribe(user_id);
+
+    drop(first);
+    assert_eq!(hub.connected_users(), 1);
+    assert_eq!(hub.dispatch(user_id, Arc::from("{}")), 1);
+    assert_eq!(second.recv().await.as_deref(), Some("{}"));
+
+    drop(second);
+    assert_eq!(hub.connected_users(), 0);
+}
+
+#[test]
+fn test_share_digest_groups_by_sender() {
+    let shares = vec![
+        pending_share("folder", Some("alice")),
+        pending_share("file", Some("bob")),
+        pending_share("file", Some("alice")),
+        pending_share("file", Some("alice")),
+        pending_share("file", None),
+    ];
+
+    assert_eq!(
+        summarize_pending_shares(&shares),
+        vec![
+            "alice a partagé un dossier et 2 fichiers avec vous",
+            "bob a partagé un fichier avec vous",
+            "Un utilisateur a partagé un fichier avec vous",
+        ]
+    );
+}
+
+#[test]
+fn test_share_email_mode_serialization() {
+    assert_eq!(
+        serde_json::to_value(ShareEmailMode::Daily).unwrap(),
+        "daily"
+    );
+    let mode: ShareEmailMode = serde_json::from_value(serde_json::json!("off")).unwrap();
+    assert_eq!(mode, ShareEmailMode::Off);
+    assert!(serde_json::from_value::<ShareEmailMode>(serde_json::json!("weekly")).is_err());
+}
@@ -123,3 +123,12 @@ async fn test_api_response_cookie_secure_flag_true_by_default_and_when_true() {
 
     unsafe { std::env::remove_var("COOKIE_SECURE") };
 }
+
+#[tokio::test]
+async fn test_api_response_payment_required_returns_402_and_error_body() {
+    let response = ApiResponse::<ErrorResponse>::payment_required("card declined").into_response();
+
+    assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+    let body = response_body_json(response).await;
+    assert_eq!(body, json!({ "error": "card declined" }));
+}
@@ -0,0 +1,33 @@
+// Tests unitaires pour le module teams
+// Teste: validation des noms d'équipe, rôles, droits de retrait des membres
+
+use crate::teams::services::{
+    INVALID_TEAM_NAME, MAX_TEAM_NAME_LENGTH, TeamRole, can_remove_member, normalize_team_name,
+};
+
+#[test]
+fn test_team_name_is_trimmed_and_bounded() {
+    assert_eq!(normalize_team_name("  Compta ").unwrap(), "Compta");
+    assert_eq!(normalize_team_name(""), Err(INVALID_TEAM_NAME));
+
+    let longest = "é".repeat(MAX_TEAM_NAME_LENGTH);
+    assert!(normalize_team_name(&longest).is_ok());
+    assert!(normalize_team_name(&format!("{}x", longest)).is_err());
+}
+
+#[test]
+fn test_team_role_round_trips() {
+    for role in [TeamRole::Admin, TeamRole::Member] {
+        assert_eq!(role.as_str().parse::<TeamRole>().unwrap(), role);
+        assert_eq!(serde_json::to_value(role).unwrap(), role.as_str());
+    }
+    assert!("owner".parse::<TeamRole>().is_err());
+}
+
+#[test]
+fn test_only_admins_remove_other_members() {
+    assert!(can_remove_member(TeamRole::Admin, false));
+    assert!(can_remove_member(TeamRole::Admin, true));
+    assert!(can_remove_member(TeamRole::Member, true));
+    assert!(!can_remove_member(TeamRole::Member, false));
+}
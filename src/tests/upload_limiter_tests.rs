@@ -0,0 +1,83 @@
+// Tests unitaires pour upload_limiter.rs
+// Teste: plafond par utilisateur, plafond global, délai d'attente, libération des slots
+
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::upload_limiter::{UploadLimitError, UploadLimiter};
+
+const SHORT_WAIT: Duration = Duration::from_millis(50);
+
+#[tokio::test]
+async fn test_user_cannot_exceed_per_user_limit() {
+    let limiter = UploadLimiter::new(10, 2, SHORT_WAIT);
+    let user = Uuid::new_v4();
+
+    let _first = limiter.acquire(user).await.unwrap();
+    let _second = limiter.acquire(user).await.unwrap();
+
+    assert_eq!(
+        limiter.acquire(user).await.err(),
+        Some(UploadLimitError::UserLimit)
+    );
+    // Les autres utilisateurs ne sont pas bloqués
+    assert!(limiter.acquire(Uuid::new_v4()).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_server_busy_when_global_limit_reached() {
+    let limiter = UploadLimiter::new(2, 2, SHORT_WAIT);
+
+    let _first = limiter.acquire(Uuid::new_v4()).await.unwrap();
+    let _second = limiter.acquire(Uuid::new_v4()).await.unwrap();
+
+    assert_eq!(
+        limiter.acquire(Uuid::new_v4()).await.err(),
+        Some(UploadLimitError::ServerBusy)
+    );
+}
+
+#[tokio::test]
+async fn test_waiting_request_gets_released_permit() {
+    let limiter = std::sync::Arc::new(UploadLimiter::new(1, 1, Duration::from_secs(5)));
+    let user = Uuid::new_v4();
+
+    let permit = limiter.acquire(user).await.unwrap();
+    let waiter = {
+        let limiter = limiter.clone();
+        tokio::spawn(async move { limiter.acquire(user).await.map(|_| ()) })
+    };
+
+    tokio::time::sleep(SHORT_WAIT).await;
+    drop(permit);
+
+    assert_eq!(waiter.await.unwrap(), Ok(()));
+}
+
+#[tokio::test]
+async fn test_permits_are_released_on_drop() {
+    let limiter = UploadLimiter::new(3, 3, SHORT_WAIT);
+    let user = Uuid::new_v4();
+
+    {
+        let _permit = limiter.acquire(user).await.unwrap();
+        assert_eq!(limiter.available_permits(), 2);
+        assert_eq!(limiter.active_users(), 1);
+    }
+
+    assert_eq!(limiter.available_permits(), 3);
+    assert_eq!(limiter.active_users(), 0);
+}
+
+#[tokio::test]
+async fn test_per_user_limit_is_capped_by_global_limit() {
+    let limiter = UploadLimiter::new(1, 10, SHORT_WAIT);
+    let user = Uuid::new_v4();
+
+    let _permit = limiter.acquire(user).await.unwrap();
+    assert_eq!(
+        limiter.acquire(user).await.err(),
+        Some(UploadLimitError::UserLimit)
+    );
+    assert_eq!(limiter.active_users(), 1);
+}
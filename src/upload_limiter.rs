@@ -0,0 +1,165 @@
+// Limitation des uploads concurrents
+// Plafond global (protection RAM/S3) + plafond par utilisateur (un utilisateur ne peut pas
+// monopoliser tous les slots). Une requête attend un slot au plus UPLOAD_QUEUE_TIMEOUT_SECS.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Instant, timeout_at};
+use uuid::Uuid;
+
+use crate::metrics;
+
+/// Délai conseillé au client (header Retry-After) quand aucun slot n'est disponible
+pub const UPLOAD_RETRY_AFTER_SECS: u64 = 5;
+
+type UserSemaphores = Arc<Mutex<HashMap<Uuid, Arc<Semaphore>>>>;
+
+/// Aucun slot obtenu avant l'expiration du délai d'attente
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadLimitError {
+    /// L'utilisateur a déjà atteint son nombre maximal d'uploads simultanés
+    UserLimit,
+    /// Tous les slots du serveur sont occupés
+    ServerBusy,
+}
+
+pub struct UploadLimiter {
+    global: Arc<Semaphore>,
+    users: UserSemaphores,
+    max_per_user: usize,
+    wait_timeout: Duration,
+}
+
+impl UploadLimiter {
+    pub fn new(max_concurrent: usize, max_per_user: usize, wait_timeout: Duration) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        metrics::UPLOAD_PERMITS_MAX.set(max_concurrent as i64);
+
+        Self {
+            global: Arc::new(Semaphore::new(max_concurrent)),
+            users: Arc::new(Mutex::new(HashMap::new())),
+            max_per_user: max_per_user.clamp(1, max_concurrent),
+            wait_timeout,
+        }
+    }
+
+    /// MAX_CONCURRENT_UPLOADS (50), MAX_UPLOADS_PER_USER (10), UPLOAD_QUEUE_TIMEOUT_SECS (10)
+    pub fn from_env() -> Self {
+        fn env_or(name: &str, default: u64) -> u64 {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        }
+
+        let max_concurrent = env_or("MAX_CONCURRENT_UPLOADS", 50) as usize;
+        let max_per_user = env_or("MAX_UPLOADS_PER_USER", 10) as usize;
+        let wait_timeout = Duration::from_secs(env_or("UPLOAD_QUEUE_TIMEOUT_SECS", 10));
+        tracing::info!(
+            "Max concurrent uploads: {} ({} per user, queue timeout {:?})",
+            max_concurrent,
+            max_per_user,
+            wait_timeout
+        );
+
+        Self::new(max_concurrent, max_per_user, wait_timeout)
+    }
+
+    /// Slots actuellement libres sur le serveur
+    pub fn available_permits(&self) -> usize {
+        self.global.available_permits()
+    }
+
+    /// Nombre d'utilisateurs ayant un upload en cours ou en attente
+    pub fn active_users(&self) -> usize {
+        lock_users(&self.users).len()
+    }
+
+    /// Attend un slot d'upload pour `user_id` (slot utilisateur puis slot global, dans l'ordre
+    /// d'arrivée). Le slot est libéré quand le `UploadPermit` est relâché.
+    pub async fn acquire(&self, user_id: Uuid) -> Result<UploadPermit, UploadLimitError> {
+        let user_semaphore = {
+            let mut users = lock_users(&self.users);
+            users
+                .entry(user_id)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_user)))
+                .clone()
+        };
+        let deadline = Instant::now() + self.wait_timeout;
+
+        metrics::UPLOAD_QUEUE_WAITING.inc();
+        let result = async {
+            let user = timeout_at(deadline, user_semaphore.acquire_owned())
+                .await
+                .map_err(|_| UploadLimitError::UserLimit)?
+                .map_err(|_| UploadLimitError::ServerBusy)?;
+            let global = timeout_at(deadline, self.global.clone().acquire_owned())
+                .await
+                .map_err(|_| UploadLimitError::ServerBusy)?
+                .map_err(|_| UploadLimitError::ServerBusy)?;
+            Ok((user, global))
+        }
+        .await;
+        metrics::UPLOAD_QUEUE_WAITING.dec();
+
+        match result {
+            Ok((user, global)) => {
+                metrics::UPLOAD_PERMITS_IN_USE.inc();
+                Ok(UploadPermit {
+                    user: Some(user),
+                    global: Some(global),
+                    users: self.users.clone(),
+                    user_id,
+                })
+            }
+            Err(e) => {
+                let reason = match e {
+                    UploadLimitError::UserLimit => "user_limit",
+                    UploadLimitError::ServerBusy => "server_busy",
+                };
+                metrics::UPLOAD_REJECTIONS_TOTAL
+                    .with_label_values(&[reason])
+                    .inc();
+                // Le sémaphore utilisateur a été libéré avec la future d'attente
+                release_user_entry(&self.users, user_id);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Slot d'upload obtenu ; libéré au drop
+pub struct UploadPermit {
+    user: Option<OwnedSemaphorePermit>,
+    global: Option<OwnedSemaphorePermit>,
+    users: UserSemaphores,
+    user_id: Uuid,
+}
+
+impl Drop for UploadPermit {
+    fn drop(&mut self) {
+        self.global.take();
+        self.user.take();
+        metrics::UPLOAD_PERMITS_IN_USE.dec();
+        release_user_entry(&self.users, self.user_id);
+    }
+}
+
+fn lock_users(users: &UserSemaphores) -> std::sync::MutexGuard<'_, HashMap<Uuid, Arc<Semaphore>>> {
+    // Le verrou ne protège qu'une HashMap : un panic ne peut pas la laisser incohérente
+    users.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Retire le sémaphore d'un utilisateur qui n'a plus ni upload en cours ni requête en attente
+fn release_user_entry(users: &UserSemaphores, user_id: Uuid) {
+    let mut users = lock_users(users);
+    if users
+        .get(&user_id)
+        .is_some_and(|semaphore| Arc::strong_count(semaphore) == 1)
+    {
+        users.remove(&user_id);
+    }
+}
@@ -190,6 +190,7 @@ TEST_FILES=(
     "${SCRIPT_DIR}/api/drive/03_idor_security.hurl"
     "${SCRIPT_DIR}/api/drive/04_sharing_security.hurl"
     "${SCRIPT_DIR}/api/drive/05_trash.hurl"
+    "${SCRIPT_DIR}/api/drive/06_key_rotation.hurl"
 )
 
 echo -e "${BLUE}========================================${NC}"